@@ -0,0 +1,136 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::io;
+use whitespace_rs::ender::{self, EndOfLine, EolOptions};
+use whitespace_rs::spacer::{self, BeginningOfLine, BolOptions};
+
+/// Size of each generated corpus in bytes
+const CORPUS_SIZE: usize = 1 << 20;
+
+/// Repeat text with mixed line endings until it is at least `CORPUS_SIZE` bytes long.
+fn corpus(lines: &[&str]) -> String {
+  let endings = ["\n", "\r\n", "\n", "\r"];
+  let mut s = String::new();
+  let mut i = 0;
+
+  while s.len() < CORPUS_SIZE {
+    s += lines[i % lines.len()];
+    s += endings[i % endings.len()];
+    i += 1;
+  }
+
+  s
+}
+
+/// Representative inputs: indented ASCII source, deeply indented source, CJK text and long lines
+fn corpora() -> Vec<(&'static str, String)> {
+  vec![
+    (
+      "ascii_source",
+      corpus(&[
+        "fn main() {",
+        "    let x = 1;",
+        "\tif x > 0 {",
+        "\t    println!(\"{}\", x);",
+        "        }",
+        "",
+        "}",
+      ]),
+    ),
+    (
+      "deep_indent",
+      corpus(&[
+        &format!("{}x = [", "\t".repeat(12)),
+        &format!("{}  1, 2,", " ".repeat(50)),
+        &format!("{}]", "\t".repeat(6)),
+        &"    ".repeat(16),
+      ]),
+    ),
+    (
+      "cjk_text",
+      corpus(&[
+        "空白文字の問題を修正するためのツールです。",
+        "  行末と行頭を正規化します。",
+        "\t中文文本，包含多字节字符。",
+      ]),
+    ),
+    (
+      "long_lines",
+      corpus(&[&"lorem ipsum dolor sit amet ".repeat(400), &"\t".repeat(8)]),
+    ),
+  ]
+}
+
+fn bench_read_eol_info(c: &mut Criterion) {
+  let mut group = c.benchmark_group("read_eol_info");
+
+  for (name, text) in corpora() {
+    group.throughput(Throughput::Bytes(text.len() as u64));
+    group.bench_with_input(BenchmarkId::new("dyn", name), &text, |b, text| {
+      b.iter(|| ender::read_eol_info(&mut text.as_bytes()).unwrap())
+    });
+    group.bench_with_input(BenchmarkId::new("generic", name), &text, |b, text| {
+      b.iter(|| {
+        ender::read_eol_info_generic(&mut text.as_bytes(), &ender::EolOptions::default()).unwrap()
+      })
+    });
+  }
+
+  group.finish();
+}
+
+fn bench_write_new_eols(c: &mut Criterion) {
+  let mut group = c.benchmark_group("write_new_eols");
+
+  for (name, text) in corpora() {
+    group.throughput(Throughput::Bytes(text.len() as u64));
+    group.bench_with_input(BenchmarkId::new("dyn", name), &text, |b, text| {
+      b.iter(|| {
+        ender::write_new_eols(&mut text.as_bytes(), &mut io::sink(), EndOfLine::CrLf).unwrap()
+      })
+    });
+    group.bench_with_input(BenchmarkId::new("generic", name), &text, |b, text| {
+      b.iter(|| {
+        ender::write_new_eols_generic(
+          &mut text.as_bytes(),
+          &mut io::sink(),
+          EndOfLine::CrLf,
+          &EolOptions::default(),
+        )
+        .unwrap()
+      })
+    });
+  }
+
+  group.finish();
+}
+
+fn bench_write_new_bols(c: &mut Criterion) {
+  let mut group = c.benchmark_group("write_new_bols");
+  let options = BolOptions::default();
+
+  for (name, text) in corpora() {
+    group.throughput(Throughput::Bytes(text.len() as u64));
+
+    for (bol_name, new_bol) in [
+      ("tabs", BeginningOfLine::Tabs(4, false)),
+      ("spaces", BeginningOfLine::Spaces(4)),
+    ] {
+      group.bench_with_input(BenchmarkId::new(bol_name, name), &text, |b, text| {
+        b.iter(|| {
+          spacer::write_new_bols_generic(&mut text.as_bytes(), &mut io::sink(), new_bol, &options)
+            .unwrap()
+        })
+      });
+    }
+  }
+
+  group.finish();
+}
+
+criterion_group!(
+  benches,
+  bench_read_eol_info,
+  bench_write_new_eols,
+  bench_write_new_bols
+);
+criterion_main!(benches);
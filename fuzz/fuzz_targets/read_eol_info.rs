@@ -0,0 +1,20 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use whitespace_rs::ender;
+
+fuzz_target!(|data: &[u8]| {
+    let result = ender::read_eol_info(&mut &data[..]);
+
+    if let Ok(s) = std::str::from_utf8(data) {
+        let eol_info = result.expect("valid UTF-8 must always scan");
+        let num_lfs = s.matches('\n').count();
+        let num_crs = s.matches('\r').count();
+
+        assert_eq!(eol_info.lf + eol_info.crlf, num_lfs);
+        assert_eq!(eol_info.cr + eol_info.crlf, num_crs);
+        assert_eq!(
+            eol_info.num_lines,
+            eol_info.cr + eol_info.lf + eol_info.crlf + 1
+        );
+    }
+});
@@ -0,0 +1,31 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use whitespace_rs::spacer::{self, BeginningOfLine};
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+
+    let tab_size = (data[0] % 17) as usize;
+    let new_bol = match data[1] % 3 {
+        0 => BeginningOfLine::Spaces(tab_size),
+        1 => BeginningOfLine::Tabs(tab_size, false),
+        _ => BeginningOfLine::Tabs(tab_size, true),
+    };
+    let input = &data[2..];
+    let mut output = Vec::new();
+    let result = spacer::write_new_bols(&mut &input[..], &mut output, new_bol);
+
+    if let Ok(s) = std::str::from_utf8(input) {
+        result.expect("valid UTF-8 must always convert");
+
+        let output = String::from_utf8(output).expect("output must be valid UTF-8");
+        let is_indent = |c: &char| *c == ' ' || *c == '\t';
+
+        assert!(s
+            .chars()
+            .filter(|c| !is_indent(c))
+            .eq(output.chars().filter(|c| !is_indent(c))));
+    }
+});
@@ -0,0 +1,46 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use whitespace_rs::ender::{self, EndOfLine, EolOptions};
+
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+
+    let new_eol = match data[0] % 3 {
+        0 => EndOfLine::Cr,
+        1 => EndOfLine::Lf,
+        _ => EndOfLine::CrLf,
+    };
+    // Lines between ws:disable and ws:enable comments keep their endings, so only without the
+    // directives is the output certain to have one kind of line ending
+    let options = EolOptions::default().directives(data[0] / 3 % 2 == 1);
+    let input = &data[1..];
+    let mut output = Vec::new();
+    let result =
+        ender::write_new_eols_with_options(&mut &input[..], &mut output, new_eol, &options);
+
+    if let Ok(s) = std::str::from_utf8(input) {
+        let num_lines = result.expect("valid UTF-8 must always convert");
+        let output = String::from_utf8(output).expect("output must be valid UTF-8");
+        let is_eol = |c: &char| *c == '\r' || *c == '\n';
+
+        assert!(s.chars().filter(|c| !is_eol(c)).eq(output.chars().filter(|c| !is_eol(c))));
+        assert_eq!(
+            num_lines,
+            ender::read_eol_info(&mut s.as_bytes()).unwrap().num_lines
+        );
+
+        let eol_info = ender::read_eol_info(&mut output.as_bytes()).unwrap();
+
+        assert!(options.directives || eol_info.num_endings() <= 1);
+        assert_eq!(eol_info.num_lines, num_lines);
+        assert!(ender::verify_new_eols_with_options(
+            &mut s.as_bytes(),
+            &mut output.as_bytes(),
+            new_eol,
+            &options
+        )
+        .unwrap());
+    }
+});
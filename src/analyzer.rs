@@ -0,0 +1,309 @@
+//! Report on line endings, line beginnings and other whitespace in one pass.
+//!
+//! To get everything that [`read_eol_info()`](crate::ender::read_eol_info) and
+//! [`read_bol_info()`](crate::spacer::read_bol_info) find, along with trailing whitespace, blank
+//! lines and whether there is a byte order mark, without reading the data more than once use
+//! [`analyze()`]:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::analyze;
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let report = analyze(&mut "abc \r\n\txyz".as_bytes())?;
+//!
+//!   assert_eq!(report.eol_info.crlf, 1);
+//!   assert_eq!(report.bol_info.tabs, 1);
+//!   assert_eq!(report.trailing_whitespace_lines, 1);
+//!   assert!(!report.final_newline);
+//!   assert!(!report.bom);
+//!   Ok(())
+//! }
+//! ```
+//!
+//! Use [`analyze_str()`] for text that is already in memory.
+
+use crate::decode::{DecodePolicy, Decoder};
+use crate::ender::EolInfo;
+use crate::spacer::{self, BolInfo};
+use std::error::Error;
+use std::io::{BufReader, Read};
+
+/// Whitespace information for a file
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WhitespaceReport {
+  /// Line ending information, as from [`read_eol_info()`](crate::ender::read_eol_info)
+  pub eol_info: EolInfo,
+  /// Line beginning information, as from [`read_bol_info()`](crate::spacer::read_bol_info)
+  pub bol_info: BolInfo,
+  /// Number of lines that end in spaces or tabs
+  pub trailing_whitespace_lines: usize,
+  /// Total number of spaces and tabs at the ends of lines
+  pub trailing_whitespace_chars: usize,
+  /// Number of lines that are empty or only contain spaces and tabs, not counting the empty last
+  /// line after the final line ending
+  pub blank_lines: usize,
+  /// `true` if the file is empty or ends in a line ending
+  pub final_newline: bool,
+  /// `true` if the file starts with a UTF-8 byte order mark.  The mark is counted as a character
+  /// of the first line, as it is by [`read_eol_info()`](crate::ender::read_eol_info).
+  pub bom: bool,
+}
+
+/// Line beginning counts in progress
+struct BolCounter {
+  at_bol: bool,
+  num_spaces: usize,
+  num_tabs: usize,
+  is_smart_tab: bool,
+  is_unusual: bool,
+}
+
+impl BolCounter {
+  /// Count a character, with the same rules as [`read_bol_info()`](crate::spacer::read_bol_info)
+  fn push(&mut self, c: char, bol_info: &mut BolInfo) {
+    if self.at_bol {
+      if c == ' ' {
+        self.num_spaces += 1;
+      } else if c == '\t' {
+        self.num_tabs += 1;
+        self.is_smart_tab = self.is_smart_tab && self.num_spaces == 0;
+      } else if spacer::is_unusual_space(c) {
+        self.is_unusual = true;
+      } else {
+        self.finish(bol_info);
+
+        if self.num_spaces == 0 && self.num_tabs == 0 {
+          bol_info.none += 1;
+        } else if self.num_spaces > 0 && self.num_tabs > 0 {
+          bol_info.mixed += 1;
+          if self.is_smart_tab {
+            bol_info.smart_tabs += 1;
+          }
+        } else if self.num_spaces > 0 {
+          bol_info.spaces += 1;
+        } else {
+          bol_info.tabs += 1;
+        }
+
+        if c == '\n' {
+          self.start_line();
+        } else {
+          self.at_bol = false;
+        }
+      }
+    } else if c == '\n' {
+      self.start_line();
+      self.at_bol = true;
+    }
+  }
+
+  /// Count a line beginning with unusual spaces that has ended
+  fn finish(&mut self, bol_info: &mut BolInfo) {
+    if self.is_unusual {
+      bol_info.unusual += 1;
+      self.is_unusual = false;
+    }
+  }
+
+  fn start_line(&mut self) {
+    self.num_spaces = 0;
+    self.num_tabs = 0;
+    self.is_smart_tab = true;
+  }
+}
+
+/// Get line ending, line beginning, trailing whitespace, blank line and byte order mark
+/// information
+pub fn analyze(reader: &mut dyn Read) -> Result<WhitespaceReport, Box<dyn Error>> {
+  analyze_generic(reader)
+}
+
+/// Generic version of [`analyze()`] that can be inlined for a concrete reader type
+pub fn analyze_generic<R: Read + ?Sized>(
+  reader: &mut R,
+) -> Result<WhitespaceReport, Box<dyn Error>> {
+  let mut report = WhitespaceReport {
+    eol_info: EolInfo::new(),
+    bol_info: BolInfo::default(),
+    trailing_whitespace_lines: 0,
+    trailing_whitespace_chars: 0,
+    blank_lines: 0,
+    final_newline: true,
+    bom: false,
+  };
+  let mut bol_counter = BolCounter {
+    at_bol: true,
+    num_spaces: 0,
+    num_tabs: 0,
+    is_smart_tab: true,
+    is_unusual: false,
+  };
+  let mut decoder = Decoder::new(BufReader::new(reader).bytes(), DecodePolicy::Strict).peekable();
+  let mut in_word = false;
+  let mut line_len = 0;
+  let mut trailing_len = 0;
+  let mut is_whitespace_line = true;
+
+  while let Some(value) = decoder.next() {
+    let c = value?;
+    let eol_info = &mut report.eol_info;
+
+    if eol_info.num_chars == 0 && c == '\u{feff}' {
+      report.bom = true;
+    }
+
+    eol_info.num_chars += 1;
+    eol_info.num_bytes += c.len_utf8();
+
+    if c.is_whitespace() {
+      in_word = false;
+    } else {
+      is_whitespace_line = false;
+
+      if !in_word {
+        eol_info.num_words += 1;
+        in_word = true;
+      }
+    }
+
+    bol_counter.push(c, &mut report.bol_info);
+
+    if c == '\r' {
+      if matches!(decoder.peek(), Some(Ok(c)) if *c == '\n') {
+        eol_info.crlf += 1;
+        eol_info.num_chars += 1;
+        eol_info.num_bytes += 1;
+        bol_counter.push('\n', &mut report.bol_info);
+        decoder.next();
+      } else {
+        eol_info.cr += 1;
+      }
+    } else if c == '\n' {
+      eol_info.lf += 1;
+    } else {
+      line_len += 1;
+      trailing_len = if c == ' ' || c == '\t' {
+        trailing_len + 1
+      } else {
+        0
+      };
+      report.final_newline = false;
+      continue;
+    }
+
+    eol_info.num_lines += 1;
+    eol_info.trailing_blank_lines = if line_len == 0 {
+      eol_info.trailing_blank_lines + 1
+    } else {
+      0
+    };
+    eol_info.count_line(line_len, is_whitespace_line);
+    report.count_line(line_len, trailing_len);
+    report.final_newline = true;
+    line_len = 0;
+    trailing_len = 0;
+    is_whitespace_line = true;
+  }
+
+  if line_len > 0 {
+    report.eol_info.trailing_blank_lines = 0;
+    report.eol_info.missing_final_newline = 1;
+    report.eol_info.count_line(line_len, is_whitespace_line);
+    report.count_line(line_len, trailing_len);
+  }
+
+  bol_counter.finish(&mut report.bol_info);
+  report.bol_info.num_chars = report.eol_info.num_chars;
+  report.bol_info.num_words = report.eol_info.num_words;
+  report.bol_info.num_bytes = report.eol_info.num_bytes;
+
+  Ok(report)
+}
+
+/// Get the [`analyze()`] information for text that is already in memory
+pub fn analyze_str(s: &str) -> WhitespaceReport {
+  // A string is always valid UTF-8 and reading it can't fail
+  analyze_generic(&mut s.as_bytes()).unwrap()
+}
+
+impl WhitespaceReport {
+  /// Count the trailing whitespace and blank lines for a line of `line_len` characters ending in
+  /// `trailing_len` spaces and tabs
+  fn count_line(&mut self, line_len: usize, trailing_len: usize) {
+    if trailing_len > 0 {
+      self.trailing_whitespace_lines += 1;
+      self.trailing_whitespace_chars += trailing_len;
+    }
+
+    if line_len == trailing_len {
+      self.blank_lines += 1;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ender::read_eol_info;
+  use crate::spacer::read_bol_info;
+
+  #[test]
+  fn test_analyze() {
+    let report = analyze(&mut "abc  \n\n \t\r\n\t  x\rend".as_bytes()).unwrap();
+
+    assert_eq!(report.trailing_whitespace_lines, 2);
+    assert_eq!(report.trailing_whitespace_chars, 4);
+    assert_eq!(report.blank_lines, 2);
+    assert!(!report.final_newline);
+    assert!(analyze(&mut "".as_bytes()).unwrap().final_newline);
+    assert!(analyze(&mut "a\r".as_bytes()).unwrap().final_newline);
+    assert!(analyze(&mut &[0xffu8][..]).is_err());
+  }
+
+  #[test]
+  fn test_analyze_str() {
+    let input = "\tabc \r\n";
+
+    assert_eq!(analyze_str(input), analyze(&mut input.as_bytes()).unwrap());
+  }
+
+  #[test]
+  fn test_analyze_bom() {
+    let report = analyze(&mut "\u{feff}abc\n".as_bytes()).unwrap();
+
+    assert!(report.bom);
+    assert_eq!(report.eol_info.num_bytes, 7);
+    assert!(!analyze(&mut "abc\u{feff}\n".as_bytes()).unwrap().bom);
+    assert!(!analyze(&mut "".as_bytes()).unwrap().bom);
+  }
+
+  #[test]
+  fn test_analyze_matches_separate_reads() {
+    for input in [
+      "",
+      "abc\n\txyz\r\n  \t mixed\r\n\n\n",
+      "\t  smart\n\tabc\r\rlast  ",
+      " \r\n\r\n  x\n\t\n",
+      "ünïcödé words  \n\t\tend\n",
+      "\u{feff}\tbom\r\n",
+      "\u{a0}\u{a0}nbsp\n \u{200b}\n\t\u{3000}",
+    ] {
+      let report = analyze(&mut input.as_bytes()).unwrap();
+
+      assert_eq!(
+        report.eol_info,
+        read_eol_info(&mut input.as_bytes()).unwrap(),
+        "{:?}",
+        input
+      );
+      assert_eq!(
+        report.bol_info,
+        read_bol_info(&mut input.as_bytes()).unwrap(),
+        "{:?}",
+        input
+      );
+    }
+  }
+}
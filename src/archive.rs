@@ -0,0 +1,356 @@
+//! Read and rewrite the files inside `.tar`, `.tar.gz` and `.zip` archives.
+//!
+//! To look at each file in an archive use [`read_archive()`]:
+//!
+//! ```no_run
+//! use std::error::Error;
+//! use std::path::Path;
+//! use whitespace_rs::{archive, ender};
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   archive::read_archive(Path::new("release.tar.gz"), |name, data| {
+//!     println!("{} {:?}", name, ender::read_eol_info(&mut &data[..])?);
+//!     Ok(())
+//!   })
+//! }
+//! ```
+//!
+//! To write a copy of an archive with some of the files changed use [`rewrite_archive()`].
+//! Requires the `archive` feature.
+
+use crate::error::{self, ErrorCode};
+use crate::paths;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Types of archives
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ArchiveFormat {
+  /// Uncompressed tar archive
+  Tar,
+  /// Gzip compressed tar archive
+  TarGz,
+  /// Zip archive
+  Zip,
+}
+
+impl ArchiveFormat {
+  /// Get the type of an archive from its file name, or `None` if it isn't an archive.
+  pub fn from_path(path: &Path) -> Option<ArchiveFormat> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+      Some(ArchiveFormat::TarGz)
+    } else if name.ends_with(".tar") {
+      Some(ArchiveFormat::Tar)
+    } else if name.ends_with(".zip") {
+      Some(ArchiveFormat::Zip)
+    } else {
+      None
+    }
+  }
+}
+
+/// Get the type of an archive from its file name, or an error if it isn't an archive
+fn archive_format(path: &Path) -> Result<ArchiveFormat, Box<dyn Error>> {
+  ArchiveFormat::from_path(path).ok_or_else(|| {
+    error::Error::new(
+      ErrorCode::Archive,
+      format!(
+        "'{}' is not a .tar, .tar.gz or .zip archive",
+        path.to_string_lossy()
+      ),
+    )
+    .into()
+  })
+}
+
+/// Call `f` with the name and contents of each file in an archive.
+pub fn read_archive<F>(path: &Path, mut f: F) -> Result<(), Box<dyn Error>>
+where
+  F: FnMut(&str, &[u8]) -> Result<(), Box<dyn Error>>,
+{
+  let format = archive_format(path)?;
+  let reader = BufReader::new(File::open(paths::extended_length_path(path))?);
+
+  match format {
+    ArchiveFormat::Tar => read_tar(tar::Archive::new(reader), &mut f),
+    ArchiveFormat::TarGz => read_tar(tar::Archive::new(GzDecoder::new(reader)), &mut f),
+    ArchiveFormat::Zip => {
+      let mut archive = zip::ZipArchive::new(reader)?;
+
+      for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+
+        if entry.is_file() {
+          let mut data = Vec::new();
+
+          entry.read_to_end(&mut data)?;
+          f(entry.name(), &data)?;
+        }
+      }
+
+      Ok(())
+    }
+  }
+}
+
+/// Call `f` with the name and contents of each file in a tar archive
+fn read_tar<R, F>(mut archive: tar::Archive<R>, f: &mut F) -> Result<(), Box<dyn Error>>
+where
+  R: Read,
+  F: FnMut(&str, &[u8]) -> Result<(), Box<dyn Error>>,
+{
+  for entry in archive.entries()? {
+    let mut entry = entry?;
+
+    if entry.header().entry_type().is_file() {
+      let name = entry.path()?.to_string_lossy().to_string();
+      let mut data = Vec::new();
+
+      entry.read_to_end(&mut data)?;
+      f(&name, &data)?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Write a copy of an archive, replacing the contents of each file for which `f` returns new
+/// contents.
+///
+/// The output archive must be the same type as the input archive.  Entries keep their order,
+/// permissions and modification times.  Returns the number of files that were changed.
+pub fn rewrite_archive<F>(input: &Path, output: &Path, mut f: F) -> Result<usize, Box<dyn Error>>
+where
+  F: FnMut(&str, &[u8]) -> Result<Option<Vec<u8>>, Box<dyn Error>>,
+{
+  let format = archive_format(input)?;
+
+  if archive_format(output)? != format {
+    return Err(
+      error::Error::new(
+        ErrorCode::Archive,
+        format!(
+          "output archive '{}' is not the same type as the input archive",
+          output.to_string_lossy()
+        ),
+      )
+      .into(),
+    );
+  }
+
+  if paths::is_same_file(input, output) {
+    return Err(
+      error::Error::new(
+        ErrorCode::SamePath,
+        format!(
+          "output archive '{}' is the same as the input archive",
+          output.to_string_lossy()
+        ),
+      )
+      .into(),
+    );
+  }
+
+  let reader = BufReader::new(File::open(paths::extended_length_path(input))?);
+  let writer = BufWriter::new(File::create(paths::extended_length_path(output))?);
+
+  match format {
+    ArchiveFormat::Tar => {
+      let (mut writer, num_changed) = rewrite_tar(tar::Archive::new(reader), writer, &mut f)?;
+
+      writer.flush()?;
+      Ok(num_changed)
+    }
+    ArchiveFormat::TarGz => {
+      let (encoder, num_changed) = rewrite_tar(
+        tar::Archive::new(GzDecoder::new(reader)),
+        GzEncoder::new(writer, Compression::default()),
+        &mut f,
+      )?;
+
+      encoder.finish()?.flush()?;
+      Ok(num_changed)
+    }
+    ArchiveFormat::Zip => {
+      let mut archive = zip::ZipArchive::new(reader)?;
+      let mut zip_writer = zip::ZipWriter::new(writer);
+      let mut num_changed = 0;
+
+      for i in 0..archive.len() {
+        let changed = {
+          let mut entry = archive.by_index(i)?;
+          let new_data = if entry.is_file() {
+            let mut data = Vec::new();
+
+            entry.read_to_end(&mut data)?;
+            f(entry.name(), &data)?
+          } else {
+            None
+          };
+
+          new_data.map(|data| {
+            let mut options = zip::write::FileOptions::default()
+              .compression_method(entry.compression())
+              .last_modified_time(entry.last_modified());
+
+            if let Some(mode) = entry.unix_mode() {
+              options = options.unix_permissions(mode);
+            }
+
+            (entry.name().to_string(), options, data)
+          })
+        };
+
+        match changed {
+          Some((name, options, data)) => {
+            zip_writer.start_file(name, options)?;
+            zip_writer.write_all(&data)?;
+            num_changed += 1;
+          }
+          None => zip_writer.raw_copy_file(archive.by_index_raw(i)?)?,
+        }
+      }
+
+      zip_writer.finish()?.flush()?;
+      Ok(num_changed)
+    }
+  }
+}
+
+/// Copy the entries of a tar archive to a writer, replacing the contents of each file for which
+/// `f` returns new contents
+fn rewrite_tar<R, W, F>(
+  mut archive: tar::Archive<R>,
+  writer: W,
+  f: &mut F,
+) -> Result<(W, usize), Box<dyn Error>>
+where
+  R: Read,
+  W: Write,
+  F: FnMut(&str, &[u8]) -> Result<Option<Vec<u8>>, Box<dyn Error>>,
+{
+  let mut builder = tar::Builder::new(writer);
+  let mut num_changed = 0;
+
+  for entry in archive.entries()? {
+    let mut entry = entry?;
+    let path = entry.path()?.into_owned();
+    let mut header = entry.header().clone();
+    let mut data = Vec::new();
+
+    entry.read_to_end(&mut data)?;
+
+    if header.entry_type().is_file() {
+      if let Some(new_data) = f(&path.to_string_lossy(), &data)? {
+        data = new_data;
+        num_changed += 1;
+      }
+    }
+
+    header.set_size(data.len() as u64);
+    builder.append_data(&mut header, &path, data.as_slice())?;
+  }
+
+  Ok((builder.into_inner()?, num_changed))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn write_test_archive(path: &Path) {
+    let entries: [(&str, &[u8]); 2] = [("a.txt", b"a\r\nb\r\n"), ("dir/b.bin", &[0xff, 0xfe])];
+    let file = File::create(path).unwrap();
+
+    if ArchiveFormat::from_path(path) == Some(ArchiveFormat::Zip) {
+      let mut writer = zip::ZipWriter::new(file);
+
+      for (name, data) in entries.iter() {
+        writer
+          .start_file(*name, zip::write::FileOptions::default())
+          .unwrap();
+        writer.write_all(data).unwrap();
+      }
+
+      writer.finish().unwrap();
+    } else {
+      let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+      for (name, data) in entries.iter() {
+        let mut header = tar::Header::new_gnu();
+
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, name, *data).unwrap();
+      }
+
+      builder.into_inner().unwrap().finish().unwrap();
+    }
+  }
+
+  fn read_test_archive(path: &Path) -> Vec<(String, Vec<u8>)> {
+    let mut entries = Vec::new();
+
+    read_archive(path, |name, data| {
+      entries.push((name.to_string(), data.to_vec()));
+      Ok(())
+    })
+    .unwrap();
+
+    entries
+  }
+
+  #[test]
+  fn test_archive_format() {
+    assert_eq!(
+      ArchiveFormat::from_path(Path::new("a/b.TGZ")),
+      Some(ArchiveFormat::TarGz)
+    );
+    assert_eq!(
+      ArchiveFormat::from_path(Path::new("b.tar")),
+      Some(ArchiveFormat::Tar)
+    );
+    assert_eq!(ArchiveFormat::from_path(Path::new("b.txt")), None);
+  }
+
+  #[test]
+  fn test_rewrite_archive() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    for (input, output) in [("in.tar.gz", "out.tar.gz"), ("in.zip", "out.zip")].iter() {
+      let input = temp_dir.path().join(input);
+      let output = temp_dir.path().join(output);
+
+      write_test_archive(&input);
+
+      let num_changed = rewrite_archive(&input, &output, |_, data| {
+        Ok(
+          std::str::from_utf8(data)
+            .ok()
+            .map(|s| s.replace("\r\n", "\n").into_bytes()),
+        )
+      })
+      .unwrap();
+
+      assert_eq!(num_changed, 1);
+      assert_eq!(
+        read_test_archive(&output),
+        vec![
+          ("a.txt".to_string(), b"a\nb\n".to_vec()),
+          ("dir/b.bin".to_string(), vec![0xff, 0xfe]),
+        ]
+      );
+      assert!(rewrite_archive(&input, &input, |_, _| Ok(None)).is_err());
+      assert!(rewrite_archive(&input, &temp_dir.path().join("out.tar"), |_, _| Ok(None)).is_err());
+    }
+
+    temp_dir.close().unwrap();
+  }
+}
@@ -0,0 +1,47 @@
+//! Make text safe for logs that only handle ASCII.
+//!
+//! Use [`escape_non_ascii()`] to replace the characters that aren't ASCII with `\uXXXX` escapes,
+//! which are also valid inside JSON strings:
+//!
+//! ```
+//! use whitespace_rs::ascii;
+//!
+//! assert_eq!(ascii::escape_non_ascii("'müll.txt', lf"), "'m\\u00fcll.txt', lf");
+//! ```
+
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// Replace the characters in `s` that aren't ASCII with `\uXXXX` escapes, using UTF-16 surrogate
+/// pairs for characters outside the Basic Multilingual Plane as JSON does.
+pub fn escape_non_ascii(s: &str) -> Cow<'_, str> {
+  if s.is_ascii() {
+    return Cow::Borrowed(s);
+  }
+
+  let mut escaped = String::with_capacity(s.len() + 16);
+
+  for c in s.chars() {
+    if c.is_ascii() {
+      escaped.push(c);
+    } else {
+      for unit in c.encode_utf16(&mut [0; 2]) {
+        write!(escaped, "\\u{:04x}", unit).unwrap();
+      }
+    }
+  }
+
+  Cow::Owned(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_escape_non_ascii() {
+    assert!(matches!(escape_non_ascii("abc"), Cow::Borrowed("abc")));
+    assert_eq!(escape_non_ascii("ü→"), "\\u00fc\\u2192");
+    assert_eq!(escape_non_ascii("a😀b"), "a\\ud83d\\ude00b");
+  }
+}
@@ -0,0 +1,230 @@
+//! Keep an audit trail of the files changed in place.
+//!
+//! An audit log is a file of JSON lines, one for each change, that is only ever appended to.  Open
+//! one with [`AuditLog::open()`] and add each changed file with [`AuditLog::record()`]:
+//!
+//! ```
+//! use std::error::Error;
+//! use std::path::Path;
+//! use whitespace_rs::audit::{self, AuditLog, Change};
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let temp_dir = tempfile::tempdir()?;
+//!   let audit_log = AuditLog::open(&temp_dir.path().join("audit.log"))?;
+//!
+//!   audit_log.record(&Change {
+//!     path: Path::new("a.txt"),
+//!     rule: "line-endings",
+//!     lines_changed: audit::count_changed_lines(b"a\r\nb\r\n", b"a\nb\n"),
+//!     before: "crlf",
+//!     after: "lf",
+//!   })?;
+//!   Ok(())
+//! }
+//! ```
+//!
+//! Each line has the UTC `timestamp` of the change, the absolute path of the `file`, the `rule`
+//! that changed it, the number of `lines_changed` and the conventions `before` and `after`.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// An audit log file that changes are appended to
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+  path: PathBuf,
+}
+
+/// A change made to a file
+#[derive(Debug, PartialEq, Clone)]
+pub struct Change<'a> {
+  /// File that was changed
+  pub path: &'a Path,
+  /// What was changed, such as `line-endings` or `line-beginnings`
+  pub rule: &'a str,
+  /// Number of lines that were changed
+  pub lines_changed: usize,
+  /// Convention of the file before the change
+  pub before: &'a str,
+  /// Convention of the file after the change
+  pub after: &'a str,
+}
+
+impl AuditLog {
+  /// Open an audit log, creating it if it doesn't exist.
+  pub fn open(path: &Path) -> io::Result<AuditLog> {
+    OpenOptions::new().create(true).append(true).open(path)?;
+
+    Ok(AuditLog {
+      path: path.to_path_buf(),
+    })
+  }
+
+  /// Append a record of a change.  Each record is written with one call so that records from
+  /// processes sharing the log aren't mixed together.
+  pub fn record(&self, change: &Change) -> io::Result<()> {
+    let path = std::env::current_dir()?.join(change.path);
+    let line = format!(
+      "{{\"timestamp\":{},\"file\":{},\"rule\":{},\"lines_changed\":{},\"before\":{},\"after\":{}}}\n",
+      json_string(&format_timestamp(SystemTime::now())),
+      json_string(&path.to_string_lossy()),
+      json_string(change.rule),
+      change.lines_changed,
+      json_string(change.before),
+      json_string(change.after)
+    );
+
+    OpenOptions::new()
+      .append(true)
+      .open(&self.path)?
+      .write_all(line.as_bytes())
+  }
+}
+
+/// Count the lines that differ between the original and new contents of a file.  Lines end after
+/// a line feed, or a carriage return that isn't followed by one, so that changing line endings
+/// changes every line that has one.
+pub fn count_changed_lines(original: &[u8], new: &[u8]) -> usize {
+  let original_lines = split_lines(original);
+  let new_lines = split_lines(new);
+  let num_different = original_lines
+    .iter()
+    .zip(new_lines.iter())
+    .filter(|(a, b)| a != b)
+    .count();
+
+  num_different + original_lines.len().abs_diff(new_lines.len())
+}
+
+/// Split data into lines that include their line endings
+fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+  let mut lines = Vec::new();
+  let mut start = 0;
+
+  for i in 0..data.len() {
+    if data[i] == b'\n' || (data[i] == b'\r' && data.get(i + 1) != Some(&b'\n')) {
+      lines.push(&data[start..=i]);
+      start = i + 1;
+    }
+  }
+
+  if start < data.len() {
+    lines.push(&data[start..]);
+  }
+
+  lines
+}
+
+/// Format a time as an RFC 3339 UTC timestamp, like `2021-03-04T05:06:07Z`.
+pub fn format_timestamp(time: SystemTime) -> String {
+  let secs = time
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0);
+  // Convert days since the epoch to a civil date, from Howard Hinnant's `civil_from_days`
+  let z = secs / 86400 + 719_468;
+  let era = z / 146_097;
+  let doe = z % 146_097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + (month <= 2) as u64;
+  let secs_of_day = secs % 86400;
+
+  format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+    year,
+    month,
+    day,
+    secs_of_day / 3600,
+    secs_of_day / 60 % 60,
+    secs_of_day % 60
+  )
+}
+
+/// Quote a string for JSON
+fn json_string(s: &str) -> String {
+  let mut quoted = String::with_capacity(s.len() + 2);
+
+  quoted.push('"');
+
+  for c in s.chars() {
+    match c {
+      '"' => quoted.push_str("\\\""),
+      '\\' => quoted.push_str("\\\\"),
+      '\n' => quoted.push_str("\\n"),
+      '\r' => quoted.push_str("\\r"),
+      '\t' => quoted.push_str("\\t"),
+      c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+      c => quoted.push(c),
+    }
+  }
+
+  quoted.push('"');
+  quoted
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Duration;
+
+  #[test]
+  fn test_count_changed_lines() {
+    assert_eq!(count_changed_lines(b"a\r\nb\r\nc", b"a\nb\nc"), 2);
+    assert_eq!(count_changed_lines(b"a\rb\r", b"a\nb\n"), 2);
+    assert_eq!(count_changed_lines(b"\ta\n  b\n", b"    a\n  b\n"), 1);
+    assert_eq!(count_changed_lines(b"a\n", b"a\n\n\n"), 2);
+    assert_eq!(count_changed_lines(b"", b""), 0);
+  }
+
+  #[test]
+  fn test_format_timestamp() {
+    assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    assert_eq!(
+      format_timestamp(UNIX_EPOCH + Duration::from_secs(1_614_834_367)),
+      "2021-03-04T05:06:07Z"
+    );
+    assert_eq!(
+      format_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+      "2000-02-29T00:00:00Z"
+    );
+  }
+
+  #[test]
+  fn test_json_string() {
+    assert_eq!(json_string("a\"b\\c\n\u{1}"), "\"a\\\"b\\\\c\\n\\u0001\"");
+  }
+
+  #[test]
+  fn test_record() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let log_path = temp_dir.path().join("audit.log");
+    let audit_log = AuditLog::open(&log_path).unwrap();
+    let change = Change {
+      path: Path::new("a.txt"),
+      rule: "line-beginnings",
+      lines_changed: 3,
+      before: "tabs",
+      after: "spaces",
+    };
+
+    audit_log.record(&change).unwrap();
+    audit_log.record(&change).unwrap();
+
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("{\"timestamp\":\""));
+    assert!(lines[0].ends_with(
+      "a.txt\",\"rule\":\"line-beginnings\",\"lines_changed\":3,\"before\":\"tabs\",\"after\":\"spaces\"}"
+    ));
+
+    temp_dir.close().unwrap();
+  }
+}
@@ -0,0 +1,1615 @@
+//! Run a tool over many input files, exactly as the `ender`, `spacer` and `trimmer` command line
+//! tools do.
+//!
+//! [`input_files()`] finds the input files from the arguments, a list of file names, the
+//! directories below them or the files tracked by git.  [`run_batch()`] then runs a tool such as
+//! [`run_ender()`](crate::run::run_ender) on each of them, writing the reports with a
+//! [`ReportWriter`], running the `exec` command for the files modified in place, and finishing
+//! with the totals, the input files that were skipped and an exit status.
+//!
+//! ```
+//! use whitespace_rs::batch::{self, BatchOptions};
+//! use whitespace_rs::run::{self, EnderRunOptions, EolTarget};
+//!
+//! let temp_dir = tempfile::tempdir().unwrap();
+//! let input_path = temp_dir.path().join("a.txt");
+//! let input_file = input_path.to_str().unwrap();
+//!
+//! std::fs::write(input_file, "abc\r\nxyz\n").unwrap();
+//!
+//! let options = BatchOptions::default().stats_output(Some("none".to_string()));
+//! let input_files = batch::input_files(&[input_file], &options).unwrap();
+//! let run_options = EnderRunOptions::default().new_eol(Some(EolTarget::Lf));
+//! let status = batch::run_batch(&input_files, &options, |input_file, output_file| {
+//!   run::run_ender(input_file, output_file, &run_options)
+//! });
+//!
+//! assert_eq!(status, 0);
+//! ```
+
+use crate::error::{self, ErrorCode};
+use crate::run::{EnderReport, SpacerReport, TrimmerReport};
+use crate::{ascii, git, interrupt, patch, paths};
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// {grcov-excl-start}
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+/// Formats for reports
+pub enum ReportFormat {
+  /// A line of text for each input file
+  #[default]
+  Text,
+  /// One JSON document with the reports, the errors and the summary, once every input file is
+  /// done
+  Json,
+  /// A `file:line:col: warning: ...` line for each line that differs from the convention
+  Gnu,
+  /// A line of JSON for each report as soon as its input file is done
+  Ndjson,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+/// Orders for reports
+pub enum SortOrder {
+  /// By input file name
+  Path,
+  /// By number of violations, most first
+  Violations,
+  /// By input file size, largest first
+  Size,
+}
+// {grcov-excl-end}
+
+/// Parse `text`, `json`, `gnu` or `ndjson`, ignoring case.
+impl FromStr for ReportFormat {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "text" => Ok(ReportFormat::Text),
+      "json" => Ok(ReportFormat::Json),
+      "gnu" => Ok(ReportFormat::Gnu),
+      "ndjson" => Ok(ReportFormat::Ndjson),
+      _ => Err(format!("'{}' is not one of text, json, gnu or ndjson", s)),
+    }
+  }
+}
+
+impl ReportFormat {
+  /// Names of the formats, as they are parsed
+  pub const NAMES: &'static [&'static str] = &["text", "json", "gnu", "ndjson"];
+}
+
+/// Parse `path`, `violations` or `size`, ignoring case.
+impl FromStr for SortOrder {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "path" => Ok(SortOrder::Path),
+      "violations" => Ok(SortOrder::Violations),
+      "size" => Ok(SortOrder::Size),
+      _ => Err(format!("'{}' is not one of path, violations or size", s)),
+    }
+  }
+}
+
+impl SortOrder {
+  /// Names of the orders, as they are parsed
+  pub const NAMES: &'static [&'static str] = &["path", "violations", "size"];
+}
+
+/// A report on one input file that [`run_batch()`] can write and count
+pub trait BatchReport: Serialize {
+  /// What an input file that isn't consistent has, e.g. `mixed line endings`
+  const INCONSISTENCY: &'static str = "inconsistent whitespace";
+
+  /// Input file name
+  fn name(&self) -> &str;
+  /// Input file size in bytes
+  fn size(&self) -> usize;
+  /// Number of lines that differ from the convention
+  fn violations(&self) -> usize;
+  /// Text of the report line
+  fn text(&self) -> &str;
+  /// GNU style diagnostics for the lines that differ from the convention
+  fn diagnostics(&self) -> &[String];
+  /// `true` if the input file was modified in place
+  fn modified(&self) -> bool;
+
+  /// `true` if the input file follows one convention
+  fn is_consistent(&self) -> bool {
+    true
+  }
+
+  /// `true` if converting the input file would change it, when only checking
+  fn needs_change(&self) -> bool {
+    false
+  }
+
+  /// Take the shell command that makes the changes to the input file.
+  fn take_script(&mut self) -> Option<String>;
+  /// Take the unified diff of the changes to the input file.
+  fn take_diff(&mut self) -> Option<String>;
+  /// Add the time taken for the input file to the report.
+  fn add_timing(&mut self, elapsed: Duration);
+}
+
+/// Implement the [`BatchReport`] methods that are the same for every report type.
+macro_rules! batch_report_fields {
+  () => {
+    fn name(&self) -> &str {
+      &self.name
+    }
+
+    fn size(&self) -> usize {
+      self.size
+    }
+
+    fn violations(&self) -> usize {
+      self.violations
+    }
+
+    fn text(&self) -> &str {
+      &self.text
+    }
+
+    fn diagnostics(&self) -> &[String] {
+      &self.diagnostics
+    }
+
+    fn modified(&self) -> bool {
+      self.modified
+    }
+
+    fn take_script(&mut self) -> Option<String> {
+      self.script.take()
+    }
+
+    fn take_diff(&mut self) -> Option<String> {
+      self.diff.take()
+    }
+
+    fn add_timing(&mut self, elapsed: Duration) {
+      self.seconds = Some(elapsed.as_secs_f64());
+      self.text += &format!(", {}", timing_text(self.size, elapsed));
+    }
+  };
+}
+
+impl BatchReport for EnderReport {
+  const INCONSISTENCY: &'static str = "mixed line endings";
+
+  batch_report_fields!();
+
+  fn is_consistent(&self) -> bool {
+    self.is_consistent
+  }
+
+  fn needs_change(&self) -> bool {
+    self.needs_change
+  }
+}
+
+impl BatchReport for SpacerReport {
+  const INCONSISTENCY: &'static str = "mixed line beginnings";
+
+  batch_report_fields!();
+
+  fn is_consistent(&self) -> bool {
+    self.is_consistent
+  }
+
+  fn needs_change(&self) -> bool {
+    self.needs_change
+  }
+}
+
+impl BatchReport for TrimmerReport {
+  batch_report_fields!();
+}
+
+/// Totals for a run over the input files
+#[derive(Serialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct Summary {
+  /// Input files read and reported on
+  pub scanned: usize,
+  /// Input files that were, or would be, changed
+  pub changed: usize,
+  /// Input files with nothing to change
+  pub clean: usize,
+  /// Input files not read, e.g. for being binary, too large or already done
+  pub skipped: usize,
+  /// Input files that couldn't be processed because of errors
+  pub errored: usize,
+}
+
+impl fmt::Display for Summary {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "summary: {} scanned, {} changed, {} clean, {} skipped, {} errored",
+      self.scanned, self.changed, self.clean, self.skipped, self.errored
+    )
+  }
+}
+
+/// An input file that couldn't be processed
+#[derive(Serialize, Debug, PartialEq, Eq, Clone)]
+pub struct FileError {
+  /// Input file name
+  pub name: String,
+  /// Stable code for the cause of the error
+  pub code: ErrorCode,
+  /// Error message
+  pub message: String,
+}
+
+impl FileError {
+  /// Make the entry for an input file that failed with `err`.
+  pub fn new(name: &str, err: &(dyn Error + 'static)) -> Self {
+    FileError {
+      name: name.to_string(),
+      code: error::error_code(err),
+      message: err.to_string(),
+    }
+  }
+}
+
+/// Destination and format for reports
+pub struct ReportWriter<R> {
+  writer: Box<dyn Write>,
+  format: ReportFormat,
+  sort: Option<SortOrder>,
+  ascii: bool,
+  reports: Vec<R>,
+  /// Totals for the reports so far
+  pub summary: Summary,
+  /// Input files that couldn't be processed
+  pub errors: Vec<FileError>,
+}
+
+impl<R: BatchReport> ReportWriter<R> {
+  /// Make a report writer.  With `ascii` characters that aren't ASCII are escaped as `\uXXXX`.
+  pub fn new(
+    writer: Box<dyn Write>,
+    format: ReportFormat,
+    sort: Option<SortOrder>,
+    ascii: bool,
+  ) -> Self {
+    ReportWriter {
+      writer,
+      format,
+      sort,
+      ascii,
+      reports: Vec::new(),
+      summary: Summary::default(),
+      errors: Vec::new(),
+    }
+  }
+
+  /// Write a report now, or keep it until [`ReportWriter::finish()`] if reports are sorted or
+  /// written as JSON.
+  pub fn add(&mut self, report: R) -> io::Result<()> {
+    self.summary.scanned += 1;
+
+    if report.violations() > 0 {
+      self.summary.changed += 1;
+    } else {
+      self.summary.clean += 1;
+    }
+
+    if self.sort.is_none() && self.format != ReportFormat::Json {
+      self.write(&report)?;
+    } else {
+      self.reports.push(report);
+    }
+
+    Ok(())
+  }
+
+  /// Write a line, escaping characters that aren't ASCII if asked to.
+  fn write_line(&mut self, line: &str) -> io::Result<()> {
+    if self.ascii {
+      writeln!(self.writer, "{}", ascii::escape_non_ascii(line))
+    } else {
+      writeln!(self.writer, "{}", line)
+    }
+  }
+
+  /// Write a report as a text line, as GNU style diagnostics or as a line of JSON.
+  fn write(&mut self, report: &R) -> io::Result<()> {
+    match self.format {
+      ReportFormat::Gnu => {
+        for diagnostic in report.diagnostics().iter() {
+          self.write_line(diagnostic)?;
+        }
+      }
+      ReportFormat::Ndjson => {
+        self.write_line(&serde_json::to_string(report)?)?;
+        self.writer.flush()?;
+      }
+      _ => self.write_line(report.text())?,
+    }
+
+    Ok(())
+  }
+
+  /// Write the reports that were kept, and the summary if they are written as JSON or NDJSON.
+  pub fn finish(&mut self) -> io::Result<()> {
+    sort_reports(&mut self.reports, self.sort);
+
+    match self.format {
+      ReportFormat::Json => {
+        #[derive(Serialize)]
+        struct Output<'a, R> {
+          files: &'a [R],
+          errors: &'a [FileError],
+          summary: &'a Summary,
+        }
+
+        let json = serde_json::to_string_pretty(&Output {
+          files: &self.reports,
+          errors: &self.errors,
+          summary: &self.summary,
+        })?;
+
+        self.write_line(&json)?;
+      }
+      _ => {
+        for report in std::mem::take(&mut self.reports).iter() {
+          self.write(report)?;
+        }
+
+        if self.format == ReportFormat::Ndjson {
+          #[derive(Serialize)]
+          struct ErrorOutput<'a> {
+            error: &'a FileError,
+          }
+
+          #[derive(Serialize)]
+          struct Output<'a> {
+            summary: &'a Summary,
+          }
+
+          let lines = self
+            .errors
+            .iter()
+            .map(|error| serde_json::to_string(&ErrorOutput { error }))
+            .collect::<Result<Vec<_>, _>>()?;
+
+          for line in lines.iter() {
+            self.write_line(line)?;
+          }
+
+          let json = serde_json::to_string(&Output {
+            summary: &self.summary,
+          })?;
+
+          self.write_line(&json)?;
+        }
+      }
+    }
+
+    self.reports.clear();
+    self.writer.flush()
+  }
+}
+
+/// Options for [`input_files()`] and [`run_batch()`]
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct BatchOptions {
+  /// File to read more input file names from, one per line, or `-` for STDIN
+  pub files_from: Option<String>,
+  /// Input file names are separated by NUL characters, and read from STDIN without `files_from`
+  pub null: bool,
+  /// Replace input directories with the text files below them
+  pub recursive: bool,
+  /// With `recursive`, don't leave out files matched by `.gitignore` or `.ignore` files
+  pub no_ignore: bool,
+  /// Only keep the input files tracked by git
+  pub git_tracked: bool,
+  /// Output file, for a single input file
+  pub output_file: Option<String>,
+  /// Pattern for output file names, as for [`paths::patterned_path()`]
+  pub out_pattern: Option<String>,
+  /// Directory to write each output file to at the relative path of its input file, copying
+  /// binary and skipped input files unchanged
+  pub output_dir: Option<String>,
+  /// Suffix appended to input file names to give output file names
+  pub suffix: Option<String>,
+  /// File name to use for input read from STDIN
+  pub stdin_filename: Option<String>,
+  /// Skip input files larger than this many bytes
+  pub max_size: Option<u64>,
+  /// File to write a shell script of the changes to, or `-` for STDOUT
+  pub emit_script: Option<String>,
+  /// Shell command to run for each file modified in place, with `{}` replaced by its path
+  pub exec: Option<String>,
+  /// Write the path of each file modified in place to STDOUT
+  pub print_changed: bool,
+  /// File listing the input files that are done, so that an interrupted run can be resumed
+  pub progress: Option<String>,
+  /// Skip the input files already listed in the `progress` file
+  pub resume: bool,
+  /// Format of the reports
+  pub format: ReportFormat,
+  /// Order of the reports, or `None` to write each as soon as its input file is done
+  pub sort: Option<SortOrder>,
+  /// Where to write the reports: `stdout`, `stderr`, `none` or a file name, or `None` for STDOUT
+  pub stats_output: Option<String>,
+  /// Escape characters that aren't ASCII in reports and the lists of skipped files
+  pub ascii: bool,
+  /// Add the time taken to reports, and write totals to STDERR at the end
+  pub timings: bool,
+  /// Stop at the first input file that isn't consistent or has an error
+  pub fail_fast: bool,
+  /// Exit with status 1 if any input files were modified in place
+  pub hook_mode: bool,
+}
+
+impl BatchOptions {
+  /// Set [`files_from`](BatchOptions::files_from)
+  pub fn files_from(mut self, files_from: Option<String>) -> Self {
+    self.files_from = files_from;
+    self
+  }
+
+  /// Set [`null`](BatchOptions::null)
+  pub fn null(mut self, null: bool) -> Self {
+    self.null = null;
+    self
+  }
+
+  /// Set [`recursive`](BatchOptions::recursive)
+  pub fn recursive(mut self, recursive: bool) -> Self {
+    self.recursive = recursive;
+    self
+  }
+
+  /// Set [`no_ignore`](BatchOptions::no_ignore)
+  pub fn no_ignore(mut self, no_ignore: bool) -> Self {
+    self.no_ignore = no_ignore;
+    self
+  }
+
+  /// Set [`git_tracked`](BatchOptions::git_tracked)
+  pub fn git_tracked(mut self, git_tracked: bool) -> Self {
+    self.git_tracked = git_tracked;
+    self
+  }
+
+  /// Set [`output_file`](BatchOptions::output_file)
+  pub fn output_file(mut self, output_file: Option<String>) -> Self {
+    self.output_file = output_file;
+    self
+  }
+
+  /// Set [`out_pattern`](BatchOptions::out_pattern)
+  pub fn out_pattern(mut self, out_pattern: Option<String>) -> Self {
+    self.out_pattern = out_pattern;
+    self
+  }
+
+  /// Set [`output_dir`](BatchOptions::output_dir)
+  pub fn output_dir(mut self, output_dir: Option<String>) -> Self {
+    self.output_dir = output_dir;
+    self
+  }
+
+  /// Set [`suffix`](BatchOptions::suffix)
+  pub fn suffix(mut self, suffix: Option<String>) -> Self {
+    self.suffix = suffix;
+    self
+  }
+
+  /// Set [`stdin_filename`](BatchOptions::stdin_filename)
+  pub fn stdin_filename(mut self, stdin_filename: Option<String>) -> Self {
+    self.stdin_filename = stdin_filename;
+    self
+  }
+
+  /// Set [`max_size`](BatchOptions::max_size)
+  pub fn max_size(mut self, max_size: Option<u64>) -> Self {
+    self.max_size = max_size;
+    self
+  }
+
+  /// Set [`emit_script`](BatchOptions::emit_script)
+  pub fn emit_script(mut self, emit_script: Option<String>) -> Self {
+    self.emit_script = emit_script;
+    self
+  }
+
+  /// Set [`exec`](BatchOptions::exec)
+  pub fn exec(mut self, exec: Option<String>) -> Self {
+    self.exec = exec;
+    self
+  }
+
+  /// Set [`print_changed`](BatchOptions::print_changed)
+  pub fn print_changed(mut self, print_changed: bool) -> Self {
+    self.print_changed = print_changed;
+    self
+  }
+
+  /// Set [`progress`](BatchOptions::progress)
+  pub fn progress(mut self, progress: Option<String>) -> Self {
+    self.progress = progress;
+    self
+  }
+
+  /// Set [`resume`](BatchOptions::resume)
+  pub fn resume(mut self, resume: bool) -> Self {
+    self.resume = resume;
+    self
+  }
+
+  /// Set [`format`](BatchOptions::format)
+  pub fn format(mut self, format: ReportFormat) -> Self {
+    self.format = format;
+    self
+  }
+
+  /// Set [`sort`](BatchOptions::sort)
+  pub fn sort(mut self, sort: Option<SortOrder>) -> Self {
+    self.sort = sort;
+    self
+  }
+
+  /// Set [`stats_output`](BatchOptions::stats_output)
+  pub fn stats_output(mut self, stats_output: Option<String>) -> Self {
+    self.stats_output = stats_output;
+    self
+  }
+
+  /// Set [`ascii`](BatchOptions::ascii)
+  pub fn ascii(mut self, ascii: bool) -> Self {
+    self.ascii = ascii;
+    self
+  }
+
+  /// Set [`timings`](BatchOptions::timings)
+  pub fn timings(mut self, timings: bool) -> Self {
+    self.timings = timings;
+    self
+  }
+
+  /// Set [`fail_fast`](BatchOptions::fail_fast)
+  pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+    self.fail_fast = fail_fast;
+    self
+  }
+
+  /// Set [`hook_mode`](BatchOptions::hook_mode)
+  pub fn hook_mode(mut self, hook_mode: bool) -> Self {
+    self.hook_mode = hook_mode;
+    self
+  }
+}
+
+/// Get the input files from the arguments and the input file names listed in
+/// [`files_from`](BatchOptions::files_from), replacing directories with the text files below them
+/// with [`recursive`](BatchOptions::recursive) and keeping only the files tracked by git with
+/// [`git_tracked`](BatchOptions::git_tracked).  Wildcards in the arguments are expanded on
+/// Windows, and each input file is only given once.
+pub fn input_files(args: &[&str], options: &BatchOptions) -> Result<Vec<String>, Box<dyn Error>> {
+  let mut input_files = if cfg!(windows) {
+    paths::expand_wildcards(args)
+  } else {
+    args.iter().map(|arg| arg.to_string()).collect()
+  };
+  let files_from = options
+    .files_from
+    .as_deref()
+    .or(if options.null { Some("-") } else { None });
+
+  if args.contains(&"-") && files_from == Some("-") {
+    return Err(
+      error::Error::new(
+        ErrorCode::Stdin,
+        "STDIN can't be used for both input file names and an input file",
+      )
+      .into(),
+    );
+  }
+
+  input_files.extend(listed_files(files_from, options.null)?);
+
+  if options.recursive {
+    input_files = recursive_files(input_files, !options.no_ignore)?;
+  }
+
+  if options.git_tracked {
+    input_files = git_tracked_files(input_files)?;
+  }
+
+  let input_files = paths::unique_files(&input_files);
+
+  if input_files.is_empty() {
+    return Err(error::Error::new(ErrorCode::Other, "no input files").into());
+  }
+
+  if options.output_file.is_some() && input_files.len() > 1 {
+    return Err(
+      error::Error::new(
+        ErrorCode::Other,
+        "--output can only be used with a single input file",
+      )
+      .into(),
+    );
+  }
+
+  Ok(input_files)
+}
+
+// {grcov-excl-start}
+/// Run a tool on each input file with `run(input_file, output_file)`, and return the exit status
+/// for the run.  Reports go to [`stats_output`](BatchOptions::stats_output), while the totals,
+/// the input files that were skipped and errors go to STDERR.
+///
+/// The exit status is 0 for success, 1 if any input files were modified in place with
+/// [`hook_mode`](BatchOptions::hook_mode) or need changing when only checking, -1 if any input
+/// files had errors and 130 if the run was interrupted.
+pub fn run_batch<R, F>(input_files: &[String], options: &BatchOptions, mut run: F) -> i32
+where
+  R: BatchReport,
+  F: FnMut(&str, Option<&str>) -> Result<R, Box<dyn Error>>,
+{
+  let writer = match open_stats_output(options.stats_output.as_deref().unwrap_or("stdout")) {
+    Ok(writer) => writer,
+    Err(err) => return error_status(err),
+  };
+  let mut report_writer = ReportWriter::new(writer, options.format, options.sort, options.ascii);
+  let ascii_text = |text: &str| -> String {
+    if options.ascii {
+      ascii::escape_non_ascii(text).into_owned()
+    } else {
+      text.to_string()
+    }
+  };
+  let mut too_large = Vec::new();
+  let passthrough = options.output_dir.is_some();
+  let mut num_copied = 0;
+  let mut num_modified = 0;
+  let mut num_needs_change = 0;
+  let (mut num_timed, mut total_bytes, mut total_elapsed) = (0, 0, Duration::default());
+
+  if let Err(err) = interrupt::install_handler() {
+    return error_status(err);
+  }
+
+  let mut progress = match options
+    .progress
+    .as_deref()
+    .map(|path| paths::ProgressFile::open(Path::new(path), options.resume))
+  {
+    Some(Ok(progress)) => Some(progress),
+    Some(Err(err)) => return error_status(err),
+    None => None,
+  };
+  let mut script_writer = match options.emit_script.as_deref().map(|path| {
+    if path == "-" {
+      Ok(paths::OutputWriter::stdout())
+    } else {
+      paths::create_output(Path::new(path))
+    }
+  }) {
+    Some(Ok(writer)) => Some(writer),
+    Some(Err(err)) => return error_status(err),
+    None => None,
+  };
+  // The header is written with the first script, once an input file has been converted
+  let mut has_script = false;
+  let mut num_processed = 0;
+
+  for input_file in input_files {
+    if interrupt::is_interrupted() {
+      break;
+    }
+
+    num_processed += 1;
+
+    if progress
+      .as_ref()
+      .is_some_and(|progress| progress.is_done(input_file))
+    {
+      report_writer.summary.skipped += 1;
+      continue;
+    }
+
+    let input_name = match options.stdin_filename.as_deref() {
+      Some(name) if input_file == "-" => name,
+      _ => input_file,
+    };
+    let output_file = match output_path(input_name, options) {
+      Ok(path) => path.map(|path| path.to_string_lossy().to_string()),
+      Err(err) => return error_status(err),
+    };
+    let copy_through = || {
+      output_file
+        .as_deref()
+        .filter(|_| passthrough && input_file != "-")
+        .map(|output_file| paths::copy_file(Path::new(input_file), Path::new(output_file)))
+    };
+
+    if let Some(size) = options.max_size.and_then(|max_size| {
+      std::fs::metadata(input_file)
+        .ok()
+        .map(|metadata| metadata.len())
+        .filter(|&size| size > max_size)
+    }) {
+      too_large.push((input_name.to_string(), size));
+      report_writer.summary.skipped += 1;
+
+      match copy_through() {
+        Some(Ok(_)) => num_copied += 1,
+        Some(Err(err)) => report_writer.errors.push(FileError::new(input_name, &err)),
+        None => (),
+      }
+
+      continue;
+    }
+
+    if passthrough && input_file != "-" && paths::is_binary_file(input_file).unwrap_or(false) {
+      report_writer.summary.skipped += 1;
+
+      match copy_through() {
+        Some(Ok(_)) => num_copied += 1,
+        Some(Err(err)) => report_writer.errors.push(FileError::new(input_name, &err)),
+        None => (),
+      }
+
+      continue;
+    }
+
+    let start = Instant::now();
+    let result = run(input_file, output_file.as_deref()).and_then(|mut report| {
+      let is_consistent = report.is_consistent();
+      let modified = report.modified();
+
+      if modified {
+        num_modified += 1;
+      }
+
+      if report.needs_change() {
+        num_needs_change += 1;
+      }
+
+      if let (Some(writer), Some(script)) = (script_writer.as_mut(), report.take_script()) {
+        if !has_script {
+          writer.write_all(patch::SHELL_SCRIPT_HEADER.as_bytes())?;
+          has_script = true;
+        }
+
+        writer.write_all(script.as_bytes())?;
+      }
+
+      if let Some(diff) = report.take_diff() {
+        let mut stdout = io::stdout();
+
+        stdout.write_all(diff.as_bytes())?;
+        stdout.flush()?;
+      }
+
+      if options.timings {
+        let elapsed = start.elapsed();
+
+        report.add_timing(elapsed);
+        num_timed += 1;
+        total_bytes += report.size();
+        total_elapsed += elapsed;
+      }
+
+      report_writer.add(report)?;
+
+      if let (true, Some(exec)) = (modified, options.exec.as_deref()) {
+        run_exec(exec, input_file)?;
+      }
+
+      if modified && options.print_changed {
+        let mut stdout = io::stdout();
+
+        stdout.write_all(changed_file_entry(input_file, options.null).as_bytes())?;
+        stdout.flush()?;
+      }
+
+      Ok(is_consistent)
+    });
+
+    match result {
+      Ok(is_consistent) => {
+        if let Some(Err(err)) = progress
+          .as_mut()
+          .map(|progress| progress.mark_done(input_file))
+        {
+          report_writer.finish().ok();
+          return error_status(err);
+        }
+
+        if !is_consistent && options.fail_fast {
+          report_writer.finish().ok();
+          eprintln!("error: '{}' has {}", input_name, R::INCONSISTENCY);
+          return 1;
+        }
+      }
+      Err(err) => {
+        if options.fail_fast {
+          report_writer.finish().ok();
+          return error_status(err);
+        }
+
+        report_writer.errors.push(FileError::new(input_name, &*err));
+
+        match copy_through() {
+          Some(Ok(_)) => num_copied += 1,
+          Some(Err(err)) => report_writer.errors.push(FileError::new(input_name, &err)),
+          None => (),
+        }
+      }
+    }
+  }
+
+  report_writer.summary.errored = report_writer.errors.len();
+
+  if let Err(err) = report_writer.finish() {
+    return error_status(err);
+  }
+
+  if let Some(Err(err)) = script_writer.map(paths::OutputWriter::finish) {
+    return error_status(err);
+  }
+
+  if options.timings {
+    eprintln!(
+      "total: {} files, {} bytes, {}",
+      num_timed,
+      total_bytes,
+      timing_text(total_bytes, total_elapsed)
+    );
+  }
+
+  if input_files.len() > 1
+    && options.format != ReportFormat::Json
+    && options.format != ReportFormat::Ndjson
+  {
+    eprintln!("{}", report_writer.summary);
+  }
+
+  if num_copied > 0 {
+    eprintln!(
+      "copied {} binary or skipped input files unchanged to the output directory",
+      num_copied
+    );
+  }
+
+  if let (false, Some(max_size)) = (too_large.is_empty(), options.max_size) {
+    eprint!("{}", ascii_text(&too_large_section(&too_large, max_size)));
+  }
+
+  if !report_writer.errors.is_empty() {
+    eprint!(
+      "{}",
+      ascii_text(&skipped_section(&report_writer.errors, input_files.len()))
+    );
+  }
+
+  if interrupt::is_interrupted() {
+    eprintln!(
+      "error: interrupted after {} of {} input files",
+      num_processed,
+      input_files.len()
+    );
+    return 130;
+  }
+
+  if let (true, Some(progress)) = (report_writer.errors.is_empty(), progress) {
+    if let Err(err) = progress.remove() {
+      return error_status(err);
+    }
+  }
+
+  if !report_writer.errors.is_empty() {
+    -1
+  } else if (options.hook_mode && num_modified > 0) || num_needs_change > 0 {
+    1
+  } else {
+    0
+  }
+}
+
+/// Write an error that stops the run to STDERR, and get the exit status for it.
+fn error_status(err: impl fmt::Display) -> i32 {
+  eprintln!("error: {}", err);
+  -1
+}
+// {grcov-excl-end}
+
+/// Get the output file for an input file from the output file, pattern, directory and suffix
+/// options, in that order, or `None` if there are none.
+fn output_path(
+  input_name: &str,
+  options: &BatchOptions,
+) -> Result<Option<PathBuf>, Box<dyn Error>> {
+  let mut output_path = options.output_file.as_deref().map(PathBuf::from);
+
+  if let Some(pattern) = options.out_pattern.as_deref() {
+    output_path = Some(paths::patterned_path(Path::new(input_name), pattern)?);
+  }
+
+  if let Some(dir) = options.output_dir.as_deref() {
+    output_path = Some(paths::mirrored_path(
+      output_path
+        .as_deref()
+        .unwrap_or_else(|| Path::new(input_name)),
+      Path::new(dir),
+    ));
+  }
+
+  if let Some(suffix) = options.suffix.as_deref() {
+    output_path = Some(paths::suffixed_path(
+      output_path
+        .as_deref()
+        .unwrap_or_else(|| Path::new(input_name)),
+      suffix,
+    ));
+  }
+
+  Ok(output_path)
+}
+
+/// Read the input file names listed in a file, or in STDIN for `-`.
+fn listed_files(files_from: Option<&str>, null: bool) -> Result<Vec<String>, Box<dyn Error>> {
+  match files_from {
+    Some(path) => Ok(paths::read_file_list(&mut paths::open_input(path)?, null)?),
+    None => Ok(Vec::new()),
+  }
+}
+
+/// Keep the input files that are tracked by git, or get the tracked files under the current
+/// directory if there are no input files.
+fn git_tracked_files(input_files: Vec<String>) -> io::Result<Vec<String>> {
+  let current_dir = std::env::current_dir()?;
+  let tracked = git::tracked_files(&current_dir)?;
+
+  if input_files.is_empty() {
+    let current_dir = std::fs::canonicalize(current_dir)?;
+
+    return Ok(
+      tracked
+        .iter()
+        .filter_map(|path| path.strip_prefix(&current_dir).ok())
+        .map(|path| path.to_string_lossy().to_string())
+        .collect(),
+    );
+  }
+
+  let tracked: std::collections::HashSet<PathBuf> = tracked.into_iter().collect();
+
+  Ok(
+    input_files
+      .into_iter()
+      .filter(|file| {
+        file == "-" || std::fs::canonicalize(file).is_ok_and(|path| tracked.contains(&path))
+      })
+      .collect(),
+  )
+}
+
+/// Replace the input directories with the text files below them, or list those below the current
+/// directory if there are no input files.
+fn recursive_files(input_files: Vec<String>, use_ignore_files: bool) -> io::Result<Vec<String>> {
+  let input_files = if input_files.is_empty() {
+    vec![".".to_string()]
+  } else {
+    input_files
+  };
+  let mut files = Vec::new();
+
+  for file in input_files {
+    if file != "-" && Path::new(&file).is_dir() {
+      files.extend(
+        paths::walk_text_files(Path::new(&file), use_ignore_files)?
+          .iter()
+          .map(|path| path.to_string_lossy().to_string()),
+      );
+    } else {
+      files.push(file);
+    }
+  }
+
+  Ok(files)
+}
+
+/// Format the list of input files that were skipped because of errors.
+fn skipped_section(skipped: &[FileError], num_files: usize) -> String {
+  let mut s = format!(
+    "error: skipped {} of {} input files\n",
+    skipped.len(),
+    num_files
+  );
+
+  for err in skipped {
+    s += &format!("  '{}': {}\n", err.name, err.message);
+  }
+
+  s
+}
+
+/// Describe the input files that were skipped for being larger than `max_size` bytes.
+fn too_large_section(too_large: &[(String, u64)], max_size: u64) -> String {
+  let mut s = format!(
+    "warning: skipped {} input files larger than {} bytes\n",
+    too_large.len(),
+    max_size
+  );
+
+  for (name, size) in too_large {
+    s += &format!("  '{}': {} bytes\n", name, size);
+  }
+
+  s
+}
+
+/// Make the entry for a file modified in place, ending in a line feed or a NUL character.
+fn changed_file_entry(path: &str, null: bool) -> String {
+  format!("{}{}", path, if null { '\0' } else { '\n' })
+}
+
+/// Make the shell command for a file by replacing `{}` in `exec` with the quoted path, or
+/// appending it if there is no `{}`.
+fn exec_command(exec: &str, path: &str) -> String {
+  let quoted = if cfg!(windows) {
+    format!("\"{}\"", path)
+  } else {
+    format!("'{}'", path.replace('\'', "'\\''"))
+  };
+
+  if exec.contains("{}") {
+    exec.replace("{}", &quoted)
+  } else {
+    format!("{} {}", exec, quoted)
+  }
+}
+
+/// Run the shell command for a file, failing if it doesn't succeed.
+fn run_exec(exec: &str, path: &str) -> Result<(), Box<dyn Error>> {
+  let command = exec_command(exec, path);
+  let status = if cfg!(windows) {
+    std::process::Command::new("cmd")
+      .arg("/C")
+      .arg(&command)
+      .status()?
+  } else {
+    std::process::Command::new("sh")
+      .arg("-c")
+      .arg(&command)
+      .status()?
+  };
+
+  if status.success() {
+    Ok(())
+  } else {
+    Err(
+      error::Error::new(
+        ErrorCode::Exec,
+        format!("'{}' failed with {}", command, status),
+      )
+      .into(),
+    )
+  }
+}
+
+/// Describe the time taken to process some bytes and the rate.
+fn timing_text(num_bytes: usize, elapsed: Duration) -> String {
+  let seconds = elapsed.as_secs_f64();
+
+  format!(
+    "{:.3} ms, {:.1} MB/s",
+    seconds * 1000.0,
+    if seconds > 0.0 {
+      num_bytes as f64 / seconds / 1_000_000.0
+    } else {
+      0.0
+    }
+  )
+}
+
+/// Open the destination for reports, which is `stdout`, `stderr`, `none` or a file name.
+fn open_stats_output(output: &str) -> io::Result<Box<dyn Write>> {
+  Ok(match output {
+    "stdout" => Box::new(io::stdout()),
+    "stderr" => Box::new(io::stderr()),
+    "none" => Box::new(io::sink()),
+    path => Box::new(BufWriter::new(File::create(paths::extended_length_path(
+      Path::new(path),
+    ))?)),
+  })
+}
+
+/// Sort reports by path, by violations (most first) or by size (largest first).  Reports that
+/// compare equal stay in input order.
+fn sort_reports<R: BatchReport>(reports: &mut [R], sort: Option<SortOrder>) {
+  match sort {
+    Some(SortOrder::Path) => reports.sort_by(|a, b| a.name().cmp(b.name())),
+    Some(SortOrder::Violations) => reports.sort_by_key(|r| std::cmp::Reverse(r.violations())),
+    Some(SortOrder::Size) => reports.sort_by_key(|r| std::cmp::Reverse(r.size())),
+    None => (),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::run::{self, EnderRunOptions, EolTarget, SpacerRunOptions, TrimmerRunOptions};
+  use crate::spacer::BolOptions;
+
+  #[test]
+  fn test_report_format() {
+    assert_eq!("NDJSON".parse::<ReportFormat>(), Ok(ReportFormat::Ndjson));
+    assert_eq!("size".parse::<SortOrder>(), Ok(SortOrder::Size));
+    assert!("xml".parse::<ReportFormat>().is_err());
+    assert!(ReportFormat::NAMES
+      .iter()
+      .all(|name| name.parse::<ReportFormat>().is_ok()));
+    assert!(SortOrder::NAMES
+      .iter()
+      .all(|name| name.parse::<SortOrder>().is_ok()));
+  }
+
+  #[test]
+  fn test_report_writer_json() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let stats_path = temp_dir.path().join("stats.json");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\n").unwrap();
+
+    let mut report_writer = ReportWriter::new(
+      open_stats_output(stats_path.to_str().unwrap()).unwrap(),
+      ReportFormat::Json,
+      None,
+      false,
+    );
+
+    report_writer
+      .add(run::run_ender(input_file, None, &EnderRunOptions::default()).unwrap())
+      .unwrap();
+    report_writer.errors.push(FileError::new(
+      "missing_file.txt",
+      &*run::run_ender("missing_file.txt", None, &EnderRunOptions::default())
+        .err()
+        .unwrap(),
+    ));
+    report_writer.finish().unwrap();
+
+    let json: serde_json::Value =
+      serde_json::from_str(&std::fs::read_to_string(stats_path).unwrap()).unwrap();
+
+    assert_eq!(json["files"][0]["name"], input_file);
+    assert_eq!(json["files"][0]["size"], 4);
+    assert_eq!(json["errors"][0]["name"], "missing_file.txt");
+    assert_eq!(json["errors"][0]["code"], "E_IO");
+    assert_eq!(json["summary"]["scanned"], 1);
+    assert_eq!(json["summary"]["clean"], 1);
+    assert_eq!(json["files"][0]["eol_info"]["num_bytes"], 4);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_report_writer_trimmer_json() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let stats_path = temp_dir.path().join("stats.json");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc \n").unwrap();
+
+    let mut report_writer = ReportWriter::new(
+      open_stats_output(stats_path.to_str().unwrap()).unwrap(),
+      ReportFormat::Json,
+      None,
+      false,
+    );
+
+    report_writer
+      .add(run::run_trimmer(input_file, None, &TrimmerRunOptions::default()).unwrap())
+      .unwrap();
+    report_writer.finish().unwrap();
+
+    let json: serde_json::Value =
+      serde_json::from_str(&std::fs::read_to_string(stats_path).unwrap()).unwrap();
+
+    assert_eq!(json["files"][0]["violations"], 1);
+    assert_eq!(json["files"][0]["trailing_info"]["spaces"], 1);
+    assert_eq!(json["summary"]["changed"], 1);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_report_writer_ndjson() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let stats_path = temp_dir.path().join("stats.ndjson");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\n").unwrap();
+
+    let mut report_writer = ReportWriter::new(
+      open_stats_output(stats_path.to_str().unwrap()).unwrap(),
+      ReportFormat::Ndjson,
+      None,
+      false,
+    );
+
+    report_writer
+      .add(run::run_ender(input_file, None, &EnderRunOptions::default()).unwrap())
+      .unwrap();
+    report_writer.finish().unwrap();
+
+    let stats = std::fs::read_to_string(stats_path).unwrap();
+    let lines: Vec<serde_json::Value> = stats
+      .lines()
+      .map(|line| serde_json::from_str(line).unwrap())
+      .collect();
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["name"], input_file);
+    assert_eq!(lines[0]["size"], 4);
+    assert_eq!(lines[1]["summary"]["scanned"], 1);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_report_writer_ascii() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let stats_path = temp_dir.path().join("stats.json");
+    let input_path = temp_dir.path().join("müll.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\n").unwrap();
+
+    for format in [ReportFormat::Text, ReportFormat::Json].iter() {
+      let mut report_writer = ReportWriter::new(
+        open_stats_output(stats_path.to_str().unwrap()).unwrap(),
+        *format,
+        None,
+        true,
+      );
+
+      report_writer
+        .add(run::run_ender(input_file, None, &EnderRunOptions::default()).unwrap())
+        .unwrap();
+      report_writer.finish().unwrap();
+      drop(report_writer);
+
+      let stats = std::fs::read_to_string(&stats_path).unwrap();
+
+      assert!(stats.is_ascii());
+      assert!(stats.contains("m\\u00fcll.txt"));
+
+      if *format == ReportFormat::Json {
+        let json: serde_json::Value = serde_json::from_str(&stats).unwrap();
+
+        assert_eq!(json["files"][0]["name"], input_file);
+      }
+    }
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_report_writer_gnu() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let stats_path = temp_dir.path().join("stats.txt");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = EnderRunOptions::default().diagnostics(true);
+
+    std::fs::write(input_file, "a\nb\r\nc\n").unwrap();
+
+    let mut report_writer = ReportWriter::new(
+      open_stats_output(stats_path.to_str().unwrap()).unwrap(),
+      ReportFormat::Gnu,
+      Some(SortOrder::Path),
+      false,
+    );
+
+    report_writer
+      .add(run::run_ender(input_file, None, &options).unwrap())
+      .unwrap();
+    report_writer.finish().unwrap();
+
+    assert_eq!(
+      std::fs::read_to_string(stats_path).unwrap(),
+      format!(
+        "{}:2:2: warning: crlf line ending, expected lf\n",
+        input_file
+      )
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_input_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let list_path = temp_dir.path().join("files.txt");
+    let list_file = list_path.to_str().unwrap();
+    let a_path = temp_dir.path().join("a.txt");
+    let b_path = temp_dir.path().join("b.txt");
+    let (a_file, b_file) = (a_path.to_str().unwrap(), b_path.to_str().unwrap());
+
+    std::fs::write(a_file, "a\n").unwrap();
+    std::fs::write(b_file, "b\n").unwrap();
+    std::fs::write(list_file, format!("{}\n{}\n", b_file, a_file)).unwrap();
+
+    let options = BatchOptions::default().files_from(Some(list_file.to_string()));
+
+    assert_eq!(
+      input_files(&[a_file], &options).unwrap(),
+      vec![a_file, b_file]
+    );
+    assert!(input_files(&[], &BatchOptions::default()).is_err());
+    assert!(input_files(
+      &["a.txt", "b.txt"],
+      &BatchOptions::default().output_file(Some("c.txt".to_string()))
+    )
+    .is_err());
+
+    let err = input_files(&["-"], &BatchOptions::default().null(true)).unwrap_err();
+
+    assert_eq!(error::error_code(&*err), ErrorCode::Stdin);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_listed_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let list_path = temp_dir.path().join("files.txt");
+    let list_file = list_path.to_str().unwrap();
+
+    std::fs::write(list_file, "a.txt\0b.txt\0").unwrap();
+
+    assert!(listed_files(None, false).unwrap().is_empty());
+    assert_eq!(
+      listed_files(Some(list_file), true).unwrap(),
+      vec!["a.txt", "b.txt"]
+    );
+    assert_eq!(
+      listed_files(Some(list_file), false).unwrap(),
+      vec!["a.txt\0b.txt\0"]
+    );
+    assert!(listed_files(Some("missing_file.txt"), false).is_err());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_recursive_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_str().unwrap().to_string();
+    let sub_path = temp_dir.path().join("sub");
+
+    std::fs::create_dir(&sub_path).unwrap();
+    std::fs::write(sub_path.join("a.txt"), "a\n").unwrap();
+    std::fs::write(sub_path.join("b.bin"), b"b\0").unwrap();
+
+    assert_eq!(
+      recursive_files(vec![dir, "-".to_string(), "c.txt".to_string()], true).unwrap(),
+      vec![
+        sub_path.join("a.txt").to_string_lossy().to_string(),
+        "-".to_string(),
+        "c.txt".to_string()
+      ]
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_output_path() {
+    let options = BatchOptions::default()
+      .output_dir(Some("out".to_string()))
+      .suffix(Some(".fixed".to_string()));
+
+    assert_eq!(
+      output_path("a.txt", &BatchOptions::default()).unwrap(),
+      None
+    );
+    assert_eq!(
+      output_path("a.txt", &options).unwrap(),
+      Some(Path::new("out").join("a.txt.fixed"))
+    );
+    assert_eq!(
+      output_path(
+        "a.txt",
+        &BatchOptions::default().out_pattern(Some("{stem}.lf{ext}".to_string()))
+      )
+      .unwrap(),
+      Some(PathBuf::from("a.lf.txt"))
+    );
+  }
+
+  #[test]
+  fn test_run_batch() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let stats_path = temp_dir.path().join("stats.json");
+    let input_paths = [temp_dir.path().join("a.txt"), temp_dir.path().join("b.txt")];
+    let input_files: Vec<String> = input_paths
+      .iter()
+      .map(|path| path.to_string_lossy().to_string())
+      .collect();
+
+    std::fs::write(&input_paths[0], "abc\r\n").unwrap();
+    std::fs::write(&input_paths[1], "abc\n").unwrap();
+
+    let options = BatchOptions::default()
+      .hook_mode(true)
+      .format(ReportFormat::Json)
+      .stats_output(Some(stats_path.to_string_lossy().to_string()));
+    let run_options = EnderRunOptions::default()
+      .new_eol(Some(EolTarget::Lf))
+      .in_place(true);
+    let run = |input_file: &str, output_file: Option<&str>| {
+      run::run_ender(input_file, output_file, &run_options)
+    };
+
+    assert_eq!(run_batch(&input_files, &options, run), 1);
+    assert_eq!(std::fs::read_to_string(&input_paths[0]).unwrap(), "abc\n");
+
+    let json: serde_json::Value =
+      serde_json::from_str(&std::fs::read_to_string(&stats_path).unwrap()).unwrap();
+
+    assert_eq!(json["summary"]["scanned"], 2);
+    assert_eq!(json["summary"]["changed"], 1);
+    assert_eq!(run_batch(&input_files, &options, run), 0);
+
+    let missing_files = vec![temp_dir
+      .path()
+      .join("missing_file.txt")
+      .to_string_lossy()
+      .to_string()];
+
+    assert_eq!(run_batch(&missing_files, &options, run), -1);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_batch_fail_fast() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("a.txt");
+    let input_files = vec![input_path.to_string_lossy().to_string()];
+    let options = BatchOptions::default()
+      .fail_fast(true)
+      .format(ReportFormat::Json);
+    let run_options = SpacerRunOptions::default()
+      .bol_options(BolOptions::default().directives(true))
+      .diagnostics(true);
+    let run = |input_file: &str, output_file: Option<&str>| {
+      run::run_spacer(input_file, output_file, &run_options)
+    };
+
+    std::fs::write(&input_path, "  a\n# ws:disable\n\tb\n# ws:enable\n  c\n").unwrap();
+    assert_eq!(run_batch(&input_files, &options, run), 0);
+
+    std::fs::write(&input_path, "  a\n\tb\n  c\n").unwrap();
+    assert_eq!(run_batch(&input_files, &options, run), 1);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_skipped_section() {
+    let err = run::run_ender("missing_file.txt", None, &EnderRunOptions::default())
+      .err()
+      .unwrap();
+    let file_error = FileError::new("missing_file.txt", &*err);
+
+    assert_eq!(file_error.code, ErrorCode::Io);
+    assert_eq!(
+      skipped_section(&[file_error], 3),
+      format!(
+        "error: skipped 1 of 3 input files\n  'missing_file.txt': {}\n",
+        err
+      )
+    );
+  }
+
+  #[test]
+  fn test_summary() {
+    let summary = Summary {
+      scanned: 3,
+      changed: 1,
+      clean: 2,
+      skipped: 4,
+      errored: 5,
+    };
+
+    assert_eq!(
+      summary.to_string(),
+      "summary: 3 scanned, 1 changed, 2 clean, 4 skipped, 5 errored"
+    );
+  }
+
+  #[test]
+  fn test_too_large_section() {
+    assert_eq!(
+      too_large_section(&[("big.txt".to_string(), 2048)], 1024),
+      "warning: skipped 1 input files larger than 1024 bytes\n  'big.txt': 2048 bytes\n"
+    );
+  }
+
+  #[test]
+  fn test_changed_file_entry() {
+    assert_eq!(changed_file_entry("a b.txt", false), "a b.txt\n");
+    assert_eq!(changed_file_entry("a\nb.txt", true), "a\nb.txt\0");
+  }
+
+  #[test]
+  fn test_exec_command() {
+    if cfg!(windows) {
+      assert_eq!(exec_command("git add {}", "a b.txt"), "git add \"a b.txt\"");
+    } else {
+      assert_eq!(exec_command("git add {}", "a b.txt"), "git add 'a b.txt'");
+      assert_eq!(exec_command("touch", "it's.txt"), "touch 'it'\\''s.txt'");
+    }
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn test_run_exec() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("a.txt");
+
+    run_exec("touch {}", path.to_str().unwrap()).unwrap();
+
+    assert!(path.exists());
+    assert!(run_exec("false", "a.txt").is_err());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_timing_text() {
+    assert_eq!(
+      timing_text(3_000_000, Duration::from_millis(1500)),
+      "1500.000 ms, 2.0 MB/s"
+    );
+    assert_eq!(timing_text(10, Duration::default()), "0.000 ms, 0.0 MB/s");
+  }
+
+  #[test]
+  fn test_sort_reports() {
+    let report = |name: &str, size, violations| EnderReport {
+      name: name.to_string(),
+      size,
+      violations,
+      ..Default::default()
+    };
+    let mut reports = vec![report("b", 10, 0), report("c", 5, 3), report("a", 20, 1)];
+    let names =
+      |reports: &[EnderReport]| -> Vec<String> { reports.iter().map(|r| r.name.clone()).collect() };
+
+    sort_reports(&mut reports, Some(SortOrder::Path));
+    assert_eq!(names(&reports), vec!["a", "b", "c"]);
+    sort_reports(&mut reports, Some(SortOrder::Violations));
+    assert_eq!(names(&reports), vec!["c", "a", "b"]);
+    sort_reports(&mut reports, Some(SortOrder::Size));
+    assert_eq!(names(&reports), vec!["a", "b", "c"]);
+  }
+}
@@ -1,9 +1,15 @@
-use clap::{arg_enum, value_t, App, Arg};
+use clap::{arg_enum, value_t, App, Arg, ArgGroup, ArgMatches};
+use std::collections::HashMap;
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::io::{Seek, SeekFrom, Write};
+use std::ffi::OsString;
 use std::path::Path;
+use whitespace_rs::audit::AuditLog;
+use whitespace_rs::batch::{self, BatchOptions, ReportFormat, SortOrder};
+use whitespace_rs::config::{self, SettingArg};
+use whitespace_rs::decode::DecodePolicy;
+use whitespace_rs::encoding::Encoding;
+use whitespace_rs::journal::Journal;
+use whitespace_rs::run::{self, BolTarget, SpacerRunOptions};
 use whitespace_rs::spacer::*;
 
 // {grcov-excl-start}
@@ -18,122 +24,796 @@ arg_enum! {
 }
 
 fn main() {
-    let matches = App::new("Spacer")
-        .version("2.1.2+20210904.0")
-        .author("John Lyon-Smith")
-        .about(
-            "Beginning of line normalizer. Defaults to reporting types count of spaces, tab and mixed beginnings.",
-        )
-        .arg(
-            Arg::with_name("input_file")
-                .help("Input file in UTF-8 format.")
-                .value_name("FILE")
-                .index(1)
-                .required(true),
-        )
-        .arg(
-            Arg::with_name("output_file")
-                .help("Output file in UTF-8 format.  Uses STDOUT if not specified")
-                .long("output")
-                .short("o")
-                .takes_value(true)
-                .value_name("FILE"),
+    run_cli(std::env::args_os().collect());
+}
+
+/// Run `spacer` with its command line arguments, from its own binary or the `whitespace` multi-call
+/// binary.
+pub fn run_cli(args: Vec<OsString>) {
+    fn app(settings: &[(String, String)]) -> App<'_, '_> {
+        App::new("Spacer")
+            .version("2.1.2+20210904.0")
+            .author("John Lyon-Smith")
+            .about(
+                "Beginning of line normalizer. Defaults to reporting types count of spaces, tab and mixed beginnings.",
+            )
+            .arg(
+                Arg::with_name("input_file")
+                    .help("Input files in UTF-8 format.  Use '-' to read from STDIN.")
+                    .value_name("FILE")
+                    .index(1)
+                    .multiple(true)
+                    .required_unless_one(&["files_from", "null", "git_tracked", "recursive"]),
+            )
+            .arg(
+                Arg::with_name("recursive")
+                    .help("Process the text files in input directories and their subdirectories, or in the current directory if none are given.  Binary files, .git directories, target and node_modules directories and files matched by .gitignore or .ignore files are left out. [env: WHITESPACE_RECURSIVE]")
+                    .long("recursive")
+                    .overrides_with("no_recursive"),
+            )
+            .arg(
+                Arg::with_name("no_recursive")
+                    .help("Turn off --recursive, e.g. when WHITESPACE_RECURSIVE or the configuration file turns it on.")
+                    .long("no-recursive")
+                    .overrides_with("recursive"),
+            )
+            .arg(
+                Arg::with_name("no_ignore")
+                    .help("With --recursive, don't leave out the files matched by .gitignore or .ignore files, or target and node_modules directories. [env: WHITESPACE_NO_IGNORE]")
+                    .long("no-ignore")
+                    .overrides_with("no_no_ignore"),
+            )
+            .arg(
+                Arg::with_name("no_no_ignore")
+                    .help("Turn off --no-ignore, e.g. when WHITESPACE_NO_IGNORE or the configuration file turns it on.")
+                    .long("ignore")
+                    .overrides_with("no_ignore"),
+            )
+            .arg(
+                Arg::with_name("git_tracked")
+                    .help("Only process input files tracked by git, or every tracked file under the current directory if none are given. [env: WHITESPACE_GIT_TRACKED]")
+                    .long("git-tracked")
+                    .overrides_with("no_git_tracked"),
+            )
+            .arg(
+                Arg::with_name("no_git_tracked")
+                    .help("Turn off --git-tracked, e.g. when WHITESPACE_GIT_TRACKED or the configuration file turns it on.")
+                    .long("no-git-tracked")
+                    .overrides_with("git_tracked"),
+            )
+            .arg(
+                Arg::with_name("files_from")
+                    .help("Read more input file names from FILE, one per line.  Use '-' to read them from STDIN.")
+                    .long("files-from")
+                    .takes_value(true)
+                    .value_name("FILE"),
+            )
+            .arg(
+                Arg::with_name("null")
+                    .help("Input file names read with --files-from are separated by NUL characters, e.g. from 'find -print0' or 'git ls-files -z'.  Without --files-from they are read from STDIN.")
+                    .long("null")
+                    .short("0"),
+            )
+            .arg(
+                Arg::with_name("hook_mode")
+                    .help("Run as a pre-commit hook.  Input files are converted in place and the exit status is 1 if any were modified.")
+                    .long("hook-mode")
+                    .conflicts_with_all(&["output_file", "output_dir", "suffix", "out_pattern"]),
+            )
+            .arg(
+                Arg::with_name("in_place")
+                    .help("Convert input files in place, to their most common line beginning if no new one is given.  Each is written to a temporary file that is renamed over it.")
+                    .long("in-place")
+                    .short("i")
+                    .conflicts_with_all(&["output_file", "output_dir", "suffix", "out_pattern"]),
+            )
+            .group(
+                ArgGroup::with_name("modify")
+                    .args(&["hook_mode", "in_place"])
+                    .multiple(true),
+            )
+            .arg(
+                Arg::with_name("check")
+                    .help("Check if converting would change any input files, without writing anything, and exit with status 1 if it would.  Input files are checked against their most common line beginning if no new one is given.  Ignored if it only comes from the environment or configuration file and another output is given. [env: WHITESPACE_CHECK]")
+                    .long("check")
+                    .conflicts_with_all(&["output_file", "output_dir", "suffix", "out_pattern", "modify"])
+                    .overrides_with("no_check"),
+            )
+            .arg(
+                Arg::with_name("no_check")
+                    .help("Turn off --check, e.g. when WHITESPACE_CHECK or the configuration file turns it on.")
+                    .long("no-check")
+                    .overrides_with("check"),
+            )
+            .arg(
+                Arg::with_name("emit_script")
+                    .help("Write a shell script that makes the changes with 'patch' to FILE, or STDOUT for '-', instead of changing any files.  Input files are converted to their most common convention if no new one is given.")
+                    .long("emit-script")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .conflicts_with_all(&["output_file", "output_dir", "suffix", "out_pattern", "modify", "check"]),
+            )
+            .arg(
+                Arg::with_name("diff")
+                    .help("Write a unified diff of the changes to STDOUT, with tabs, line endings and trailing spaces made visible, instead of changing any files. Input files are converted to their most common line beginning if no new one is given.  Use --stats-output to keep the reports out of the diffs.")
+                    .long("diff")
+                    .conflicts_with_all(&["output_file", "output_dir", "suffix", "out_pattern", "modify", "check", "emit_script"]),
+            )
+            .arg(
+                Arg::with_name("journal")
+                    .help("Directory to write a journal of the files modified in place to, so that 'whitespace undo' can restore them.")
+                    .long("journal")
+                    .takes_value(true)
+                    .value_name("DIR")
+                    .setting("WHITESPACE_JOURNAL", settings)
+                    .requires("modify"),
+            )
+            .arg(
+                Arg::with_name("print_changed")
+                    .help("Write the path of each file modified in place to STDOUT, one per line, or followed by a NUL character with -0, e.g. for 'git add --pathspec-from-file=- --pathspec-file-nul'.  Use --stats-output to keep the reports out of the list. [env: WHITESPACE_PRINT_CHANGED]")
+                    .long("print-changed")
+                    .requires("modify")
+                    .overrides_with("no_print_changed"),
+            )
+            .arg(
+                Arg::with_name("no_print_changed")
+                    .help("Turn off --print-changed, e.g. when WHITESPACE_PRINT_CHANGED or the configuration file turns it on.")
+                    .long("no-print-changed")
+                    .overrides_with("print_changed"),
+            )
+            .arg(
+                Arg::with_name("audit_log")
+                    .help("File to append a JSON line to for each file modified in place, with the time, file, rule, number of lines changed and the conventions before and after.")
+                    .long("audit-log")
+                    .takes_value(true)
+                    .value_name("PATH")
+                    .setting("WHITESPACE_AUDIT_LOG", settings)
+                    .requires("modify"),
+            )
+            .arg(
+                Arg::with_name("exec")
+                    .help("Shell command to run for each file modified in place, e.g. 'git add {}'.  '{}' is replaced with the file path, which is appended if there is no '{}'.")
+                    .long("exec")
+                    .takes_value(true)
+                    .value_name("CMD")
+                    .setting("WHITESPACE_EXEC", settings)
+                    .requires("modify"),
+            )
+            .arg(
+                Arg::with_name("progress")
+                    .help("File to list the input files in as they are processed, so that an interrupted run can be resumed.  It is deleted once every input file has been processed without errors.")
+                    .long("progress")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .setting("WHITESPACE_PROGRESS", settings),
+            )
+            .arg(
+                Arg::with_name("resume")
+                    .help("Skip the input files already listed in the --progress file.")
+                    .long("resume")
+                    .requires("progress"),
+            )
+            .arg(
+                Arg::with_name("stdin_filename")
+                    .help("File name to use for input read from STDIN in reports and output file names.")
+                    .long("stdin-filename")
+                    .takes_value(true)
+                    .value_name("FILE"),
+            )
+            .arg(
+                Arg::with_name("output_file")
+                    .help("Output file in UTF-8 format.  Uses STDOUT if not specified.  Only valid with a single input file.")
+                    .long("output")
+                    .short("o")
+                    .takes_value(true)
+                    .value_name("FILE"),
+            )
+            .arg(
+                Arg::with_name("force")
+                    .help("Overwrite the output file given with --output if it already exists. [env: WHITESPACE_FORCE]")
+                    .long("force")
+                    .short("f")
+                    .overrides_with("no_force"),
+            )
+            .arg(
+                Arg::with_name("no_force")
+                    .help("Turn off --force, e.g. when WHITESPACE_FORCE or the configuration file turns it on.")
+                    .long("no-force")
+                    .overrides_with("force"),
+            )
+            .arg(
+                Arg::with_name("output_dir")
+                    .help("Output directory.  Each output file is written to the same relative path as its input file, and binary or skipped files are copied unchanged.")
+                    .long("output-dir")
+                    .short("d")
+                    .takes_value(true)
+                    .value_name("DIR")
+                    .conflicts_with("output_file"),
+            )
+            .arg(
+                Arg::with_name("suffix")
+                    .help("Suffix appended to the input file name to give the output file name, e.g. '.fixed'.")
+                    .long("suffix")
+                    .takes_value(true)
+                    .value_name("SUFFIX")
+                    .conflicts_with("output_file"),
+            )
+            .arg(
+                Arg::with_name("out_pattern")
+                    .help("Pattern for output file names using the variables {dir}, {name}, {stem} and {ext}, e.g. '{dir}/{stem}.lf{ext}'.")
+                    .long("out-pattern")
+                    .takes_value(true)
+                    .value_name("PATTERN")
+                    .conflicts_with_all(&["output_file", "suffix"]),
+            )
+            .arg(
+                Arg::with_name("bol_arg")
+                    .help("Standardize line beginnings")
+                    .long("new-bol")
+                    .short("n")
+                    .takes_value(true)
+                    .possible_values(&BeginningOfLineArg::variants())
+                    .case_insensitive(true)
+                    .setting("WHITESPACE_BOL", settings),
+            )
+            .arg(
+                Arg::with_name("tab_size")
+                    .help("Tab size for both input and output file")
+                    .long("tab-size")
+                    .short("t")
+                    .takes_value(true)
+                    .value_name("TAB_SIZE")
+                    .default_value("4")
+                    .setting("WHITESPACE_TAB_SIZE", settings),
+            )
+            .arg(
+                Arg::with_name("infer_tab_size")
+                    .help("Infer the tab size of input files indented with both tabs and spaces from how their lines align, instead of using --tab-size for them. [env: WHITESPACE_INFER_TAB_SIZE]")
+                    .long("infer-tab-size")
+                    .overrides_with("no_infer_tab_size"),
+            )
+            .arg(
+                Arg::with_name("no_infer_tab_size")
+                    .help("Turn off --infer-tab-size, e.g. when WHITESPACE_INFER_TAB_SIZE or the configuration file turns it on.")
+                    .long("no-infer-tab-size")
+                    .overrides_with("infer_tab_size"),
+            )
+            .arg(
+                Arg::with_name("round_down")
+                    .help("When tabifying, rounds extra spaces down to a whole number of tabs [env: WHITESPACE_ROUND_DOWN]")
+                    .long("round-down")
+                    .short("r")
+                    .overrides_with("no_round_down"),
+            )
+            .arg(
+                Arg::with_name("no_round_down")
+                    .help("Turn off --round-down, e.g. when WHITESPACE_ROUND_DOWN or the configuration file turns it on.")
+                    .long("no-round-down")
+                    .overrides_with("round_down"),
+            )
+            .arg(
+                Arg::with_name("bol_strategy")
+                    .help("How --new-bol auto decides if tabs or spaces are most common: by the lines indented with only tabs or only spaces, or weighted to count smart tabs lines as tabs.")
+                    .long("bol-strategy")
+                    .takes_value(true)
+                    .value_name("STRATEGY")
+                    .possible_values(BolStrategy::NAMES)
+                    .case_insensitive(true)
+                    .default_value("lines")
+                    .setting("WHITESPACE_BOL_STRATEGY", settings),
+            )
+            .arg(
+                Arg::with_name("preserve_blank_line_ws")
+                    .help("Leave the whitespace on whitespace-only lines unchanged. [env: WHITESPACE_PRESERVE_BLANK_LINE_WS]")
+                    .long("preserve-blank-line-ws")
+                    .overrides_with("no_preserve_blank_line_ws"),
+            )
+            .arg(
+                Arg::with_name("no_preserve_blank_line_ws")
+                    .help("Turn off --preserve-blank-line-ws, e.g. when WHITESPACE_PRESERVE_BLANK_LINE_WS or the configuration file turns it on.")
+                    .long("no-preserve-blank-line-ws")
+                    .overrides_with("preserve_blank_line_ws"),
+            )
+            .arg(
+                Arg::with_name("align_continuations")
+                    .help("Keep continuation lines aligned with an opening delimiter, or a word after it, on an earlier line, e.g. wrapped function arguments.  Tabs are only used up to the indentation of the line with the delimiter. [env: WHITESPACE_ALIGN_CONTINUATIONS]")
+                    .long("align-continuations")
+                    .overrides_with("no_align_continuations"),
+            )
+            .arg(
+                Arg::with_name("no_align_continuations")
+                    .help("Turn off --align-continuations, e.g. when WHITESPACE_ALIGN_CONTINUATIONS or the configuration file turns it on.")
+                    .long("no-align-continuations")
+                    .overrides_with("align_continuations"),
+            )
+            .arg(
+                Arg::with_name("protect_strings")
+                    .help("Leave lines that begin inside multi-line string literals or here-docs unchanged.  Uses a lightweight lexer that understands quotes, raw strings and here-docs in common languages. [env: WHITESPACE_PROTECT_STRINGS]")
+                    .long("protect-strings")
+                    .overrides_with("no_protect_strings"),
+            )
+            .arg(
+                Arg::with_name("no_protect_strings")
+                    .help("Turn off --protect-strings, e.g. when WHITESPACE_PROTECT_STRINGS or the configuration file turns it on.")
+                    .long("no-protect-strings")
+                    .overrides_with("protect_strings"),
+            )
+            .arg(
+                Arg::with_name("markdown")
+                    .help("Leave lines in Markdown fenced and indented code blocks unchanged. [env: WHITESPACE_MARKDOWN]")
+                    .long("markdown")
+                    .overrides_with("no_markdown"),
+            )
+            .arg(
+                Arg::with_name("no_markdown")
+                    .help("Turn off --markdown, e.g. when WHITESPACE_MARKDOWN or the configuration file turns it on.")
+                    .long("no-markdown")
+                    .overrides_with("markdown"),
+            )
+            .arg(
+                Arg::with_name("replace_unusual_spaces")
+                    .help("Replace non-breaking, zero-width and other unusual spaces at the beginning of lines along with the spaces and tabs. [env: WHITESPACE_REPLACE_UNUSUAL_SPACES]")
+                    .long("replace-unusual-spaces")
+                    .overrides_with("no_replace_unusual_spaces"),
+            )
+            .arg(
+                Arg::with_name("no_replace_unusual_spaces")
+                    .help("Turn off --replace-unusual-spaces, e.g. when WHITESPACE_REPLACE_UNUSUAL_SPACES or the configuration file turns it on.")
+                    .long("no-replace-unusual-spaces")
+                    .overrides_with("replace_unusual_spaces"),
+            )
+            .arg(
+                Arg::with_name("safe_only")
+                    .help("Leave lines unchanged if converting them would change their alignment with the lines around them, e.g. with --round-down. [env: WHITESPACE_SAFE_ONLY]")
+                    .long("safe-only")
+                    .overrides_with("no_safe_only"),
+            )
+            .arg(
+                Arg::with_name("no_safe_only")
+                    .help("Turn off --safe-only, e.g. when WHITESPACE_SAFE_ONLY or the configuration file turns it on.")
+                    .long("no-safe-only")
+                    .overrides_with("safe_only"),
+            )
+            .arg(
+                Arg::with_name("indent_above")
+                    .help("Only change lines indented by more than this many columns.")
+                    .long("indent-above")
+                    .takes_value(true)
+                    .value_name("COLUMNS")
+                    .validator(is_columns)
+                    .setting("WHITESPACE_INDENT_ABOVE", settings),
+            )
+            .arg(
+                Arg::with_name("indent_below")
+                    .help("Only change lines indented by fewer than this many columns.")
+                    .long("indent-below")
+                    .takes_value(true)
+                    .value_name("COLUMNS")
+                    .validator(is_columns)
+                    .setting("WHITESPACE_INDENT_BELOW", settings),
+            )
+            .arg(
+                Arg::with_name("decode_policy")
+                    .help("What to do with bytes that aren't valid UTF-8: fail, replace each invalid sequence with U+FFFD or copy the bytes unchanged.  With bytes only ASCII is decoded and every other byte is copied unchanged.  Input files that begin with invalid UTF-8 are only read as Windows-1252 or ISO-8859-1 with strict.")
+                    .long("decode-policy")
+                    .takes_value(true)
+                    .value_name("POLICY")
+                    .possible_values(DecodePolicy::NAMES)
+                    .case_insensitive(true)
+                    .default_value("strict")
+                    .setting("WHITESPACE_DECODE_POLICY", settings),
+            )
+            .arg(
+                Arg::with_name("from_encoding")
+                    .help("Read input files in this encoding instead of detecting it.")
+                    .long("from-encoding")
+                    .takes_value(true)
+                    .value_name("ENCODING")
+                    .possible_values(Encoding::NAMES)
+                    .case_insensitive(true)
+                    .setting("WHITESPACE_FROM_ENCODING", settings),
+            )
+            .arg(
+                Arg::with_name("to_encoding")
+                    .help("Write converted output in this encoding instead of the encoding of the input file.  Input files are converted to their most common line beginning if no new one is given.")
+                    .long("to-encoding")
+                    .alias("output-encoding")
+                    .takes_value(true)
+                    .value_name("ENCODING")
+                    .possible_values(Encoding::NAMES)
+                    .case_insensitive(true)
+                    .setting("WHITESPACE_TO_ENCODING", settings),
+            )
+            .arg(
+                Arg::with_name("verify")
+                    .help("Verify the converted output before writing it.  Nothing is written if verification fails. [env: WHITESPACE_VERIFY]")
+                    .long("verify")
+                    .overrides_with("no_verify"),
+            )
+            .arg(
+                Arg::with_name("no_verify")
+                    .help("Turn off --verify, e.g. when WHITESPACE_VERIFY or the configuration file turns it on.")
+                    .long("no-verify")
+                    .overrides_with("verify"),
+            )
+            .arg(
+                Arg::with_name("counts")
+                    .help("Include the number of characters, words and bytes in the report. [env: WHITESPACE_COUNTS]")
+                    .long("counts")
+                    .overrides_with("no_counts"),
+            )
+            .arg(
+                Arg::with_name("no_counts")
+                    .help("Turn off --counts, e.g. when WHITESPACE_COUNTS or the configuration file turns it on.")
+                    .long("no-counts")
+                    .overrides_with("counts"),
+            )
+            .arg(
+                Arg::with_name("max_size")
+                    .help("Skip input files larger than this size, which can have a K, M or G suffix.  The skipped files are listed at the end.")
+                    .long("max-size")
+                    .takes_value(true)
+                    .value_name("SIZE")
+                    .setting("WHITESPACE_MAX_SIZE", settings)
+                    .validator(|s| config::parse_size(&s).map(|_| ())),
+            )
+            .arg(
+                Arg::with_name("max_memory_size")
+                    .help("Fail for input files larger than this size, which can have a K, M or G suffix, rather than read them into memory to verify, check, modify in place or make a script or diff.")
+                    .long("max-memory-size")
+                    .takes_value(true)
+                    .value_name("SIZE")
+                    .setting("WHITESPACE_MAX_MEMORY_SIZE", settings)
+                    .validator(|s| config::parse_size(&s).map(|_| ())),
+            )
+            .arg(
+                Arg::with_name("max_line_length")
+                    .help("Fail for lines whose beginning, or whole line with --protect-strings or --markdown, is longer than this size, which can have a K, M or G suffix, rather than buffer them.")
+                    .long("max-line-length")
+                    .takes_value(true)
+                    .value_name("SIZE")
+                    .setting("WHITESPACE_MAX_LINE_LENGTH", settings)
+                    .validator(|s| config::parse_size(&s).map(|_| ())),
+            )
+            .arg(
+                Arg::with_name("ascii")
+                    .help("Escape characters that aren't ASCII, such as in file names, as \\uXXXX in reports and the lists of skipped files, for logging systems that mangle them. [env: WHITESPACE_ASCII]")
+                    .long("ascii")
+                    .overrides_with("no_ascii"),
+            )
+            .arg(
+                Arg::with_name("no_ascii")
+                    .help("Turn off --ascii, e.g. when WHITESPACE_ASCII or the configuration file turns it on.")
+                    .long("no-ascii")
+                    .overrides_with("ascii"),
+            )
+            .arg(
+                Arg::with_name("timings")
+                    .help("Include the time taken and bytes per second in the report, and write totals to STDERR at the end. [env: WHITESPACE_TIMINGS]")
+                    .long("timings")
+                    .overrides_with("no_timings"),
+            )
+            .arg(
+                Arg::with_name("no_timings")
+                    .help("Turn off --timings, e.g. when WHITESPACE_TIMINGS or the configuration file turns it on.")
+                    .long("no-timings")
+                    .overrides_with("timings"),
+            )
+            .arg(
+                Arg::with_name("fail_fast")
+                    .help("Stop at the first input file that has mixed line beginnings and exit with status 1, or that has an error.  Otherwise input files with errors are skipped and listed at the end. [env: WHITESPACE_FAIL_FAST]")
+                    .long("fail-fast")
+                    .overrides_with("no_fail_fast"),
+            )
+            .arg(
+                Arg::with_name("no_fail_fast")
+                    .help("Turn off --fail-fast, e.g. when WHITESPACE_FAIL_FAST or the configuration file turns it on.")
+                    .long("no-fail-fast")
+                    .overrides_with("fail_fast"),
+            )
+            .arg(
+                Arg::with_name("sort")
+                    .help("Print report lines after all input files are processed, sorted by path, by violations (most first) or by size (largest first).")
+                    .long("sort")
+                    .takes_value(true)
+                    .possible_values(SortOrder::NAMES)
+                    .case_insensitive(true)
+                    .setting("WHITESPACE_SORT", settings),
+            )
+            .arg(
+                Arg::with_name("stats_format")
+                    .help("Format of the reports.  'gnu' writes a 'file:line:col: warning: ...' line for each line that differs from the new or most common convention, which editors and CI log scanners understand.  'ndjson' writes each report as a line of JSON as soon as the input file is done.")
+                    .long("stats-format")
+                    .alias("format")
+                    .takes_value(true)
+                    .possible_values(ReportFormat::NAMES)
+                    .case_insensitive(true)
+                    .default_value("text")
+                    .setting("WHITESPACE_STATS_FORMAT", settings),
+            )
+            .arg(
+                Arg::with_name("stats_output")
+                    .help("Where to write the reports: 'stdout', 'stderr', 'none' or a file name.")
+                    .long("stats-output")
+                    .takes_value(true)
+                    .value_name("OUTPUT")
+                    .default_value("stdout")
+                    .setting("WHITESPACE_STATS_OUTPUT", settings),
+            )
+            .arg(
+                Arg::with_name("profile")
+                    .help("Use the settings in the [profile.NAME] table of the .whitespace.toml file in the current directory or the closest one above it, as well as those at the top of the file.")
+                    .long("profile")
+                    .takes_value(true)
+                    .value_name("NAME")
+                    .env("WHITESPACE_PROFILE"),
+            )
+    }
+
+    let matches = app(&[]).get_matches_from(args.clone());
+    let config = match config::load_config_file(Path::new("."), matches.value_of("profile")) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(-1);
+        }
+    };
+    // Settings from the configuration file are the defaults of the arguments, so make and parse
+    // them again to pick up the settings
+    let settings = &config.settings;
+    let matches = if settings.is_empty() {
+        matches
+    } else {
+        app(settings).get_matches_from(args.clone())
+    };
+    let flag = |arg: &str| match config::flag_arg(&matches, arg, settings) {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(-1);
+        }
+    };
+
+    let batch_options = BatchOptions::default()
+        .files_from(matches.value_of("files_from").map(|path| path.to_string()))
+        .null(matches.is_present("null"))
+        .recursive(flag("recursive"))
+        .no_ignore(flag("no_ignore"))
+        .git_tracked(flag("git_tracked"))
+        .output_file(matches.value_of("output_file").map(|path| path.to_string()))
+        .out_pattern(
+            matches
+                .value_of("out_pattern")
+                .map(|pattern| pattern.to_string()),
         )
-        .arg(
-            Arg::with_name("bol_arg")
-                .help("Standardize line beginnings")
-                .long("new-bol")
-                .short("n")
-                .takes_value(true)
-                .possible_values(&BeginningOfLineArg::variants())
-                .case_insensitive(true),
+        .output_dir(matches.value_of("output_dir").map(|dir| dir.to_string()))
+        .suffix(matches.value_of("suffix").map(|suffix| suffix.to_string()))
+        .stdin_filename(
+            matches
+                .value_of("stdin_filename")
+                .map(|name| name.to_string()),
         )
-        .arg(
-            Arg::with_name("tab_size")
-                .help("Tab size for both input and output file")
-                .long("tab-size")
-                .short("t")
-                .takes_value(true)
-                .value_name("TAB_SIZE")
-                .default_value("4"),
+        .max_size(
+            matches
+                .value_of("max_size")
+                .and_then(|s| config::parse_size(s).ok()),
         )
-        .arg(
-            Arg::with_name("round_down")
-                .help("When tabifying, rounds extra spaces down to a whole number of tabs")
-                .long("round-down")
-                .short("r"),
+        .emit_script(matches.value_of("emit_script").map(|path| path.to_string()))
+        .exec(matches.value_of("exec").map(|exec| exec.to_string()))
+        .print_changed(flag("print_changed"))
+        .progress(matches.value_of("progress").map(|path| path.to_string()))
+        .resume(matches.is_present("resume"))
+        .format(value_t!(matches, "stats_format", ReportFormat).unwrap_or_default())
+        .sort(value_t!(matches, "sort", SortOrder).ok())
+        .stats_output(
+            matches
+                .value_of("stats_output")
+                .map(|output| output.to_string()),
         )
-        .get_matches();
-
-    let result = run(
-        matches.value_of("input_file").unwrap(),
-        matches.value_of("output_file"),
-        value_t!(matches, "bol_arg", BeginningOfLineArg).ok(),
-        usize::from_str_radix(matches.value_of("tab_size").unwrap(), 10).unwrap_or(4),
-        matches.is_present("round_down"),
-    );
+        .ascii(flag("ascii"))
+        .timings(flag("timings"))
+        .fail_fast(flag("fail_fast"))
+        .hook_mode(matches.is_present("hook_mode"));
+    let file_args: Vec<&str> = matches
+        .values_of("input_file")
+        .map_or_else(Vec::new, |values| values.collect());
+    let input_files = match batch::input_files(&file_args, &batch_options) {
+        Ok(input_files) => input_files,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(-1);
+        }
+    };
 
-    if let Err(ref err) = result {
-        eprintln!("error: {}", err);
-        std::process::exit(-1);
+    /// Make the options for converting files from the arguments and the settings for them.
+    fn run_options(
+        matches: &ArgMatches,
+        settings: &[(String, String)],
+        diagnostics: bool,
+        journal: Option<Journal>,
+        audit_log: Option<AuditLog>,
+    ) -> Result<SpacerRunOptions, Box<dyn Error>> {
+        let hook_mode = matches.is_present("hook_mode");
+        let in_place = hook_mode || matches.is_present("in_place");
+        let emit_script = matches.is_present("emit_script");
+        let diff = matches.is_present("diff");
+        // The other outputs conflict with --check, but WHITESPACE_CHECK is only a default so they
+        // override it
+        let check = matches.is_present("check")
+            || (config::flag_arg(matches, "check", settings)?
+                && ![
+                    "output_file",
+                    "output_dir",
+                    "suffix",
+                    "out_pattern",
+                    "modify",
+                    "emit_script",
+                    "diff",
+                ]
+                .iter()
+                .any(|arg| matches.is_present(arg)));
+        let output_encoding = value_t!(matches, "to_encoding", Encoding).ok();
+        Ok(SpacerRunOptions::default()
+            .new_bol(
+                value_t!(matches, "bol_arg", BeginningOfLineArg)
+                    .ok()
+                    .map(|bol_arg| match bol_arg {
+                        BeginningOfLineArg::Tabs => BolTarget::Tabs,
+                        BeginningOfLineArg::Spaces => BolTarget::Spaces,
+                        BeginningOfLineArg::Auto => BolTarget::Auto,
+                    })
+                    .or(
+                        if in_place || emit_script || diff || check || output_encoding.is_some() {
+                            Some(BolTarget::Auto)
+                        } else {
+                            None
+                        },
+                    ),
+            )
+            .tab_size(
+                matches
+                    .value_of("tab_size")
+                    .unwrap()
+                    .parse::<usize>()
+                    .unwrap_or(4),
+            )
+            .infer_tab_size(config::flag_arg(matches, "infer_tab_size", settings)?)
+            .round_down(config::flag_arg(matches, "round_down", settings)?)
+            .bol_strategy(value_t!(matches, "bol_strategy", BolStrategy).unwrap_or_default())
+            .bol_options(
+                BolOptions::default()
+                    .directives(true)
+                    .preserve_blank_line_ws(config::flag_arg(
+                        matches,
+                        "preserve_blank_line_ws",
+                        settings,
+                    )?)
+                    .indent_above(value_t!(matches, "indent_above", usize).ok())
+                    .indent_below(value_t!(matches, "indent_below", usize).ok())
+                    .align_continuations(config::flag_arg(
+                        matches,
+                        "align_continuations",
+                        settings,
+                    )?)
+                    .protect_strings(config::flag_arg(matches, "protect_strings", settings)?)
+                    .markdown(config::flag_arg(matches, "markdown", settings)?)
+                    .safe_only(config::flag_arg(matches, "safe_only", settings)?)
+                    .replace_unusual_spaces(config::flag_arg(
+                        matches,
+                        "replace_unusual_spaces",
+                        settings,
+                    )?)
+                    .max_line_length(
+                        matches
+                            .value_of("max_line_length")
+                            .and_then(|s| config::parse_size(s).ok())
+                            .map(|size| size as usize),
+                    )
+                    .decode_policy(
+                        value_t!(matches, "decode_policy", DecodePolicy).unwrap_or_default(),
+                    ),
+            )
+            .verify(config::flag_arg(matches, "verify", settings)?)
+            .counts(config::flag_arg(matches, "counts", settings)?)
+            .in_place(in_place)
+            .emit_script(emit_script)
+            .diff(diff)
+            .check(check)
+            .input_encoding(value_t!(matches, "from_encoding", Encoding).ok())
+            .output_encoding(output_encoding)
+            .diagnostics(diagnostics)
+            .journal(journal)
+            .audit_log(audit_log)
+            .stdin_filename(
+                matches
+                    .value_of("stdin_filename")
+                    .map(|name| name.to_string()),
+            )
+            .no_clobber(
+                matches.is_present("output_file") && !config::flag_arg(matches, "force", settings)?,
+            )
+            .max_memory_size(
+                matches
+                    .value_of("max_memory_size")
+                    .and_then(|s| config::parse_size(s).ok()),
+            ))
     }
-}
-// {grcov-excl-end}
 
-pub fn run(
-    input_file: &str,
-    output_file: Option<&str>,
-    bol_arg: Option<BeginningOfLineArg>,
-    tab_size: usize,
-    round_down: bool,
-) -> Result<(), Box<dyn Error>> {
-    let mut reader = BufReader::new(File::open(Path::new(input_file))?);
-    let bol_info = read_bol_info(&mut reader)?;
-    let bol_type = |s: usize, t: usize| {
-        if t > 0 {
-            if s > 0 {
-                "mixed"
-            } else {
-                "tabs"
-            }
-        } else {
-            "spaces"
+    let journal = match matches
+        .value_of("journal")
+        .map(|dir| Journal::create(Path::new(dir)))
+    {
+        Some(Ok(journal)) => Some(journal),
+        Some(Err(err)) => {
+            eprintln!("error: {}", err);
+            std::process::exit(-1);
+        }
+        None => None,
+    };
+    let audit_log = match matches
+        .value_of("audit_log")
+        .map(|path| AuditLog::open(Path::new(path)))
+    {
+        Some(Ok(audit_log)) => Some(audit_log),
+        Some(Err(err)) => {
+            eprintln!("error: {}", err);
+            std::process::exit(-1);
         }
+        None => None,
     };
+    let diagnostics = batch_options.format == ReportFormat::Gnu;
+    let options = match run_options(
+        &matches,
+        settings,
+        diagnostics,
+        journal.clone(),
+        audit_log.clone(),
+    ) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(-1);
+        }
+    };
+    // Files that [[override]] tables in the configuration file apply to get options made from
+    // the settings layered over them, once for each combination of overrides
+    let mut override_options = HashMap::new();
 
-    print!(
-        "'{}', {}",
-        input_file,
-        bol_type(bol_info.spaces, bol_info.tabs),
-    );
+    std::process::exit(batch::run_batch(
+        &input_files,
+        &batch_options,
+        |input_file, output_file| {
+            let overrides = config.overrides_for(Path::new(input_file));
 
-    if let Some(bol_arg) = bol_arg {
-        let new_bol = match bol_arg {
-            BeginningOfLineArg::Auto => bol_info.get_common_bol(tab_size, round_down),
-            BeginningOfLineArg::Tabs => BeginningOfLine::Tabs(tab_size, round_down),
-            BeginningOfLineArg::Spaces => BeginningOfLine::Spaces(tab_size),
-        };
+            if overrides.is_empty() {
+                return run::run_spacer(input_file, output_file, &options);
+            }
 
-        reader.seek(SeekFrom::Start(0))?;
+            if !override_options.contains_key(&overrides) {
+                let settings = config.settings_with(&overrides);
+                let matches = app(&settings).get_matches_from(args.clone());
+                let options = run_options(
+                    &matches,
+                    &settings,
+                    diagnostics,
+                    journal.clone(),
+                    audit_log.clone(),
+                )?;
 
-        let mut writer: Box<dyn Write> = match output_file {
-            Some(path) => Box::new(BufWriter::new(File::create(Path::new(path))?)),
-            None => Box::new(std::io::stdout()),
-        };
-        let bol_info = write_new_bols(&mut reader, &mut writer, new_bol)?;
+                override_options.insert(overrides.clone(), options);
+            }
 
-        println!(
-            " -> '{}', {}",
-            if let Some(file) = output_file {
-                file
-            } else {
-                "STDOUT"
-            },
-            bol_type(bol_info.spaces, bol_info.tabs)
-        )
-    }
+            run::run_spacer(input_file, output_file, &override_options[&overrides])
+        },
+    ));
+}
+// {grcov-excl-end}
 
-    Ok(())
+/// Check that an argument is a number of columns
+fn is_columns(s: String) -> Result<(), String> {
+    s.parse::<usize>()
+        .map(|_| ())
+        .map_err(|_| format!("'{}' is not a number of columns", s))
 }
 
 #[cfg(test)]
@@ -141,70 +821,8 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_run_tabs() {
-        let temp_dir = tempfile::tempdir().unwrap();
-        let input_path = temp_dir.path().join("input_file.txt");
-        let input_file = input_path.to_str().unwrap();
-
-        std::fs::write(input_file, "\t\tabc\r").unwrap();
-
-        run(input_file, None, Some(BeginningOfLineArg::Spaces), 4, true).unwrap();
-
-        temp_dir.close().unwrap();
-    }
-
-    #[test]
-    fn test_run_status_only() {
-        let temp_dir = tempfile::tempdir().unwrap();
-        let input_path = temp_dir.path().join("input_file.txt");
-        let input_file = input_path.to_str().unwrap();
-
-        std::fs::write(input_file, "\t\tabc\r").unwrap();
-
-        run(input_file, None, None, 4, false).unwrap();
-
-        temp_dir.close().unwrap();
-    }
-
-    #[test]
-    fn test_run_auto_spaces() {
-        let temp_dir = tempfile::tempdir().unwrap();
-        let output_path = temp_dir.path().join("output_file.txt");
-        let input_path = temp_dir.path().join("input_file.txt");
-        let input_file = input_path.to_str().unwrap();
-
-        std::fs::write(input_file, "\t  abc\r").unwrap();
-
-        run(
-            input_file,
-            Some(output_path.to_str().unwrap()),
-            Some(BeginningOfLineArg::Auto),
-            2,
-            true,
-        )
-        .unwrap();
-
-        temp_dir.close().unwrap();
-    }
-
-    #[test]
-    fn test_run_auto_tabs() {
-        let temp_dir = tempfile::tempdir().unwrap();
-        let output_path = temp_dir.path().join("output_file.txt");
-        let input_path = temp_dir.path().join("input_file.txt");
-        let input_file = input_path.to_str().unwrap();
-
-        std::fs::write(input_file, "\t\n\t\n\t\t abc\r").unwrap();
-
-        run(
-            input_file,
-            Some(output_path.to_str().unwrap()),
-            Some(BeginningOfLineArg::Auto),
-            2,
-            true,
-        )
-        .unwrap();
-
-        temp_dir.close().unwrap();
+    fn test_is_columns() {
+        assert!(is_columns("8".to_string()).is_ok());
+        assert!(is_columns("-1".to_string()).is_err());
     }
 }
@@ -0,0 +1,317 @@
+use clap::{value_t, App, Arg, ArgMatches};
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::OsString;
+use std::path::Path;
+use whitespace_rs::batch::{self, BatchOptions, ReportFormat};
+use whitespace_rs::config::{self, SettingArg};
+use whitespace_rs::journal::Journal;
+use whitespace_rs::run::{self, TrimmerRunOptions};
+
+// {grcov-excl-start}
+fn main() {
+    run_cli(std::env::args_os().collect());
+}
+
+/// Run `trimmer` with its command line arguments, from its own binary or the `whitespace`
+/// multi-call binary.
+pub fn run_cli(args: Vec<OsString>) {
+    fn app(settings: &[(String, String)]) -> App<'_, '_> {
+        App::new("Trimmer")
+            .version("2.1.2+20210904.0")
+            .author("John Lyon-Smith")
+            .about("Trailing whitespace remover.  Defaults to reporting lines that end in spaces or tabs.")
+            .arg(
+                Arg::with_name("input_file")
+                    .help("Input files in UTF-8 format.  Use '-' to read from STDIN.")
+                    .value_name("FILE")
+                    .index(1)
+                    .multiple(true)
+                    .required_unless_one(&["files_from", "null"]),
+            )
+            .arg(
+                Arg::with_name("files_from")
+                    .help("Read more input file names from FILE, one per line.  Use '-' to read them from STDIN.")
+                    .long("files-from")
+                    .takes_value(true)
+                    .value_name("FILE"),
+            )
+            .arg(
+                Arg::with_name("null")
+                    .help("Input file names read with --files-from are separated by NUL characters, e.g. from 'find -print0' or 'git ls-files -z'.  Without --files-from they are read from STDIN.")
+                    .long("null")
+                    .short("0"),
+            )
+            .arg(
+                Arg::with_name("hook_mode")
+                    .help("Run as a pre-commit hook.  Trailing whitespace is removed in place and the exit status is 1 if any input files were modified.")
+                    .long("hook-mode")
+                    .conflicts_with("output_file"),
+            )
+            .arg(
+                Arg::with_name("emit_script")
+                    .help("Write a shell script that makes the changes with 'patch' to FILE, or STDOUT for '-', instead of changing any files.")
+                    .long("emit-script")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .conflicts_with_all(&["output_file", "hook_mode"]),
+            )
+            .arg(
+                Arg::with_name("diff")
+                    .help("Write a unified diff of the changes to STDOUT, with tabs, line endings and trailing spaces made visible, instead of changing any files.  Use --stats-output to keep the reports out of the diffs.")
+                    .long("diff")
+                    .conflicts_with_all(&["output_file", "hook_mode", "emit_script"]),
+            )
+            .arg(
+                Arg::with_name("journal")
+                    .help("Directory to write a journal of the files modified in place to, so that 'whitespace undo' can restore them.")
+                    .long("journal")
+                    .takes_value(true)
+                    .value_name("DIR")
+                    .setting("WHITESPACE_JOURNAL", settings)
+                    .requires("hook_mode"),
+            )
+            .arg(
+                Arg::with_name("stdin_filename")
+                    .help("File name to use for input read from STDIN in reports.")
+                    .long("stdin-filename")
+                    .takes_value(true)
+                    .value_name("FILE"),
+            )
+            .arg(
+                Arg::with_name("output_file")
+                    .help("Output file in UTF-8 format.  Uses STDOUT if not specified.  Only valid with a single input file.")
+                    .long("output")
+                    .short("o")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .required(false),
+            )
+            .arg(
+                Arg::with_name("force")
+                    .help("Overwrite the output file given with --output if it already exists. [env: WHITESPACE_FORCE]")
+                    .long("force")
+                    .short("f")
+                    .overrides_with("no_force"),
+            )
+            .arg(
+                Arg::with_name("no_force")
+                    .help("Turn off --force, e.g. when WHITESPACE_FORCE or the configuration file turns it on.")
+                    .long("no-force")
+                    .overrides_with("force"),
+            )
+            .arg(
+                Arg::with_name("trim")
+                    .help("Write the input files without trailing whitespace.")
+                    .long("trim")
+                    .short("t"),
+            )
+            .arg(
+                Arg::with_name("verify")
+                    .help("Verify the trimmed output before writing it.  Nothing is written if verification fails. [env: WHITESPACE_VERIFY]")
+                    .long("verify")
+                    .overrides_with("no_verify"),
+            )
+            .arg(
+                Arg::with_name("no_verify")
+                    .help("Turn off --verify, e.g. when WHITESPACE_VERIFY or the configuration file turns it on.")
+                    .long("no-verify")
+                    .overrides_with("verify"),
+            )
+            .arg(
+                Arg::with_name("counts")
+                    .help("Include the number of characters, words and bytes in the report. [env: WHITESPACE_COUNTS]")
+                    .long("counts")
+                    .overrides_with("no_counts"),
+            )
+            .arg(
+                Arg::with_name("no_counts")
+                    .help("Turn off --counts, e.g. when WHITESPACE_COUNTS or the configuration file turns it on.")
+                    .long("no-counts")
+                    .overrides_with("counts"),
+            )
+            .arg(
+                Arg::with_name("max_memory_size")
+                    .help("Fail for input files larger than this size, which can have a K, M or G suffix, rather than read them into memory to verify, modify in place or make a script or diff.")
+                    .long("max-memory-size")
+                    .takes_value(true)
+                    .value_name("SIZE")
+                    .setting("WHITESPACE_MAX_MEMORY_SIZE", settings)
+                    .validator(|s| config::parse_size(&s).map(|_| ())),
+            )
+            .arg(
+                Arg::with_name("fail_fast")
+                    .help("Stop at the first input file that has an error.  Otherwise input files with errors are skipped and listed at the end. [env: WHITESPACE_FAIL_FAST]")
+                    .long("fail-fast")
+                    .overrides_with("no_fail_fast"),
+            )
+            .arg(
+                Arg::with_name("no_fail_fast")
+                    .help("Turn off --fail-fast, e.g. when WHITESPACE_FAIL_FAST or the configuration file turns it on.")
+                    .long("no-fail-fast")
+                    .overrides_with("fail_fast"),
+            )
+            .arg(
+                Arg::with_name("stats_format")
+                    .help("Format of the reports.  'gnu' writes a 'file:line:col: warning: ...' line for each line with trailing whitespace, which editors and CI log scanners understand.")
+                    .long("stats-format")
+                    .alias("format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json", "gnu"])
+                    .case_insensitive(true)
+                    .default_value("text")
+                    .setting("WHITESPACE_STATS_FORMAT", settings),
+            )
+            .arg(
+                Arg::with_name("stats_output")
+                    .help("Where to write the reports: 'stdout', 'stderr', 'none' or a file name.")
+                    .long("stats-output")
+                    .takes_value(true)
+                    .value_name("OUTPUT")
+                    .default_value("stdout")
+                    .setting("WHITESPACE_STATS_OUTPUT", settings),
+            )
+            .arg(
+                Arg::with_name("profile")
+                    .help("Use the settings in the [profile.NAME] table of the .whitespace.toml file in the current directory or the closest one above it, as well as those at the top of the file.")
+                    .long("profile")
+                    .takes_value(true)
+                    .value_name("NAME")
+                    .env("WHITESPACE_PROFILE"),
+            )
+    }
+
+    let matches = app(&[]).get_matches_from(args.clone());
+    let config = match config::load_config_file(Path::new("."), matches.value_of("profile")) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(-1);
+        }
+    };
+    // Settings from the configuration file are the defaults of the arguments, so make and parse
+    // them again to pick up the settings
+    let settings = &config.settings;
+    let matches = if settings.is_empty() {
+        matches
+    } else {
+        app(settings).get_matches_from(args.clone())
+    };
+    let flag = |arg: &str| match config::flag_arg(&matches, arg, settings) {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(-1);
+        }
+    };
+
+    let batch_options = BatchOptions::default()
+        .files_from(matches.value_of("files_from").map(|path| path.to_string()))
+        .null(matches.is_present("null"))
+        .output_file(matches.value_of("output_file").map(|path| path.to_string()))
+        .stdin_filename(
+            matches
+                .value_of("stdin_filename")
+                .map(|name| name.to_string()),
+        )
+        .emit_script(matches.value_of("emit_script").map(|path| path.to_string()))
+        .format(value_t!(matches, "stats_format", ReportFormat).unwrap_or_default())
+        .stats_output(
+            matches
+                .value_of("stats_output")
+                .map(|output| output.to_string()),
+        )
+        .fail_fast(flag("fail_fast"))
+        .hook_mode(matches.is_present("hook_mode"));
+    let file_args: Vec<&str> = matches
+        .values_of("input_file")
+        .map_or_else(Vec::new, |values| values.collect());
+    let input_files = match batch::input_files(&file_args, &batch_options) {
+        Ok(input_files) => input_files,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(-1);
+        }
+    };
+
+    /// Make the options for converting files from the arguments and the settings for them.
+    fn run_options(
+        matches: &ArgMatches,
+        settings: &[(String, String)],
+        diagnostics: bool,
+        journal: Option<Journal>,
+    ) -> Result<TrimmerRunOptions, Box<dyn Error>> {
+        let hook_mode = matches.is_present("hook_mode");
+        let emit_script = matches.is_present("emit_script");
+        let diff = matches.is_present("diff");
+        Ok(TrimmerRunOptions::default()
+            .trim(matches.is_present("trim") || hook_mode || emit_script || diff)
+            .directives(true)
+            .verify(config::flag_arg(matches, "verify", settings)?)
+            .counts(config::flag_arg(matches, "counts", settings)?)
+            .in_place(hook_mode)
+            .emit_script(emit_script)
+            .diff(diff)
+            .diagnostics(diagnostics)
+            .journal(journal)
+            .stdin_filename(
+                matches
+                    .value_of("stdin_filename")
+                    .map(|name| name.to_string()),
+            )
+            .no_clobber(
+                matches.is_present("output_file") && !config::flag_arg(matches, "force", settings)?,
+            )
+            .max_memory_size(
+                matches
+                    .value_of("max_memory_size")
+                    .and_then(|s| config::parse_size(s).ok()),
+            ))
+    }
+
+    let journal = match matches
+        .value_of("journal")
+        .map(|dir| Journal::create(Path::new(dir)))
+    {
+        Some(Ok(journal)) => Some(journal),
+        Some(Err(err)) => {
+            eprintln!("error: {}", err);
+            std::process::exit(-1);
+        }
+        None => None,
+    };
+    let diagnostics = batch_options.format == ReportFormat::Gnu;
+    let options = match run_options(&matches, settings, diagnostics, journal.clone()) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(-1);
+        }
+    };
+    // Files that [[override]] tables in the configuration file apply to get options made from
+    // the settings layered over them, once for each combination of overrides
+    let mut override_options = HashMap::new();
+
+    std::process::exit(batch::run_batch(
+        &input_files,
+        &batch_options,
+        |input_file, output_file| {
+            let overrides = config.overrides_for(Path::new(input_file));
+
+            if overrides.is_empty() {
+                return run::run_trimmer(input_file, output_file, &options);
+            }
+
+            if !override_options.contains_key(&overrides) {
+                let settings = config.settings_with(&overrides);
+                let matches = app(&settings).get_matches_from(args.clone());
+                let options = run_options(&matches, &settings, diagnostics, journal.clone())?;
+
+                override_options.insert(overrides.clone(), options);
+            }
+
+            run::run_trimmer(input_file, output_file, &override_options[&overrides])
+        },
+    ));
+}
+// {grcov-excl-end}
@@ -0,0 +1,1429 @@
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use whitespace_rs::compare::{self, CompareOptions, Conventions};
+use whitespace_rs::config::{self, Config, SettingArg};
+use whitespace_rs::ender::{self, EndOfLine};
+use whitespace_rs::error::{self, ErrorCode};
+use whitespace_rs::filetype::FileType;
+use whitespace_rs::rules::{
+    self, LineBeginnings, LineEndings, Rule, RuleOptions, Severity, TrailingWhitespace,
+    WithSeverity,
+};
+use whitespace_rs::spacer::{self, BeginningOfLine, BolOptions};
+use whitespace_rs::{archive, journal, notebook, patch, paths};
+
+// The tools are built into this binary as well, without using their own main()
+#[path = "ender.rs"]
+#[allow(dead_code)]
+mod ender_cli;
+#[path = "spacer.rs"]
+#[allow(dead_code)]
+mod spacer_cli;
+#[path = "trimmer.rs"]
+#[allow(dead_code)]
+mod trimmer_cli;
+
+/// Tools that the multi-call binary can run
+#[derive(Debug, PartialEq)]
+enum Tool {
+    Ender,
+    Spacer,
+    Trimmer,
+    Whitespace,
+}
+
+// {grcov-excl-start}
+fn main() {
+    match multi_call_tool(std::env::args_os().collect()) {
+        (Tool::Ender, args) => ender_cli::run_cli(args),
+        (Tool::Spacer, args) => spacer_cli::run_cli(args),
+        (Tool::Trimmer, args) => trimmer_cli::run_cli(args),
+        (Tool::Whitespace, args) => run_cli(args),
+    }
+}
+
+/// Run the `whitespace` subcommands with their command line arguments.
+fn run_cli(args: Vec<OsString>) {
+    fn app(settings: &[(String, String)]) -> App<'_, '_> {
+        App::new("Whitespace")
+            .version("2.1.2+20210904.0")
+            .author("John Lyon-Smith")
+            .about("Whitespace tools.  Also runs ender, spacer and trimmer as 'whitespace ender ...', 'whitespace spacer ...' and 'whitespace trimmer ...', or when run through a link named ender, spacer or trimmer.")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("wdiff")
+                    .about("Compare two files ignoring line endings, trailing whitespace and optionally indentation style.  Exits with status 1 if they differ.")
+                    .arg(
+                        Arg::with_name("left_file")
+                            .help("First file in UTF-8 format.  Use '-' to read from STDIN.")
+                            .value_name("FILE1")
+                            .index(1)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("right_file")
+                            .help("Second file in UTF-8 format.  Use '-' to read from STDIN.")
+                            .value_name("FILE2")
+                            .index(2)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("ignore_indentation")
+                            .help("Treat tabs and spaces at the beginning of lines as the same if they indent the same number of columns. [env: WHITESPACE_IGNORE_INDENTATION]")
+                            .long("ignore-indentation")
+                            .short("i")
+                            .overrides_with("no_ignore_indentation"),
+                    )
+                    .arg(
+                        Arg::with_name("no_ignore_indentation")
+                            .help("Turn off --ignore-indentation, e.g. when WHITESPACE_IGNORE_INDENTATION or the configuration file turns it on.")
+                            .long("no-ignore-indentation")
+                            .overrides_with("ignore_indentation"),
+                    )
+                    .arg(
+                        Arg::with_name("markdown")
+                            .help("Treat two or more trailing spaces as a Markdown hard line break, which differs from other trailing whitespace. [env: WHITESPACE_MARKDOWN]")
+                            .long("markdown")
+                            .overrides_with("no_markdown"),
+                    )
+                    .arg(
+                        Arg::with_name("no_markdown")
+                            .help("Turn off --markdown, e.g. when WHITESPACE_MARKDOWN or the configuration file turns it on.")
+                            .long("no-markdown")
+                            .overrides_with("markdown"),
+                    )
+                    .arg(
+                        Arg::with_name("tab_size")
+                            .help("Tab size used to compare indentation")
+                            .long("tab-size")
+                            .short("t")
+                            .takes_value(true)
+                            .value_name("TAB_SIZE")
+                            .env("WHITESPACE_TAB_SIZE")
+                            .default_value("4"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("compare")
+                    .about("Report files in two directory trees that use different line ending or beginning conventions.  Exits with status 1 if any do.")
+                    .arg(
+                        Arg::with_name("left_dir")
+                            .help("First directory")
+                            .value_name("DIR1")
+                            .index(1)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("right_dir")
+                            .help("Second directory")
+                            .value_name("DIR2")
+                            .index(2)
+                            .required(true),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("archive")
+                    .about("Report on the text files inside a .tar, .tar.gz or .zip archive, and optionally write a new archive with their line endings or beginnings normalized.")
+                    .arg(
+                        Arg::with_name("archive_file")
+                            .help("Input archive")
+                            .value_name("ARCHIVE")
+                            .index(1)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("output_file")
+                            .help("Output archive of the same type as the input archive.  Required with --new-eol or --new-bol.")
+                            .long("output")
+                            .short("o")
+                            .takes_value(true)
+                            .value_name("ARCHIVE"),
+                    )
+                    .arg(
+                        Arg::with_name("new_eol")
+                            .help("Write new line endings.")
+                            .long("new-eol")
+                            .takes_value(true)
+                            .possible_values(&["cr", "lf", "crlf"])
+                            .case_insensitive(true)
+                            .requires("output_file"),
+                    )
+                    .arg(
+                        Arg::with_name("new_bol")
+                            .help("Write new line beginnings.")
+                            .long("new-bol")
+                            .takes_value(true)
+                            .possible_values(&["tabs", "spaces"])
+                            .case_insensitive(true)
+                            .requires("output_file"),
+                    )
+                    .arg(
+                        Arg::with_name("tab_size")
+                            .help("Tab size for new line beginnings")
+                            .long("tab-size")
+                            .short("t")
+                            .takes_value(true)
+                            .value_name("TAB_SIZE")
+                            .env("WHITESPACE_TAB_SIZE")
+                            .default_value("4"),
+                    )
+                    .arg(
+                        Arg::with_name("round_down")
+                            .help("When tabifying, rounds extra spaces down to a whole number of tabs [env: WHITESPACE_ROUND_DOWN]")
+                            .long("round-down")
+                            .short("r"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("apply")
+                    .about("Apply a unified diff to the files it names, as long as it only changes whitespace.  Nothing is written unless every hunk applies.")
+                    .arg(
+                        Arg::with_name("patch_file")
+                            .help("Unified diff, such as the output of 'git diff' or 'diff -u'.  Use '-' to read from STDIN.")
+                            .value_name("PATCH")
+                            .index(1)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("directory")
+                            .help("Directory that the paths in the diff are relative to")
+                            .long("directory")
+                            .short("d")
+                            .takes_value(true)
+                            .value_name("DIR"),
+                    )
+                    .arg(
+                        Arg::with_name("check")
+                            .help("Check that the diff applies without changing any files")
+                            .long("check"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("undo")
+                    .about("Restore the files in a journal written by the --journal option of ender, spacer or trimmer.  Nothing is written unless every file can be restored.")
+                    .arg(
+                        Arg::with_name("journal_dir")
+                            .help("Journal directory")
+                            .value_name("JOURNAL")
+                            .index(1)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("check")
+                            .help("Check that the files can be restored without changing them")
+                            .long("check"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("notebook")
+                    .about("Normalize the whitespace in the code and Markdown cells of Jupyter notebooks in place, leaving their metadata, outputs and JSON layout alone.  Line endings become line feeds and trailing whitespace is removed from code cells.  Nothing is written unless every notebook can be read.")
+                    .arg(
+                        Arg::with_name("notebook_file")
+                            .help("Notebook (.ipynb) files")
+                            .value_name("NOTEBOOK")
+                            .index(1)
+                            .multiple(true)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("new_bol")
+                            .help("Write new line beginnings in the cells.")
+                            .long("new-bol")
+                            .takes_value(true)
+                            .possible_values(&["tabs", "spaces"])
+                            .case_insensitive(true),
+                    )
+                    .arg(
+                        Arg::with_name("tab_size")
+                            .help("Tab size for new line beginnings")
+                            .long("tab-size")
+                            .short("t")
+                            .takes_value(true)
+                            .value_name("TAB_SIZE")
+                            .env("WHITESPACE_TAB_SIZE")
+                            .default_value("4"),
+                    )
+                    .arg(
+                        Arg::with_name("check")
+                            .help("Report the cells that would change without changing any files")
+                            .long("check"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("tab-sizes")
+                    .about("Report how well each of several tab sizes fits the indentation of files, as the number of lines it leaves misaligned with the line before them or at odd columns, to help choose the --tab-size to convert a legacy file with.")
+                    .arg(
+                        Arg::with_name("input_file")
+                            .help("Input files, or '-' for STDIN")
+                            .value_name("INPUT_FILE")
+                            .index(1)
+                            .multiple(true)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("tab_sizes")
+                            .help("Tab sizes to try")
+                            .long("tab-sizes")
+                            .takes_value(true)
+                            .value_name("TAB_SIZES")
+                            .use_delimiter(true)
+                            .validator(|s| {
+                                s.parse::<usize>()
+                                    .map(|_| ())
+                                    .map_err(|_| format!("'{}' is not a tab size", s))
+                            })
+                            .default_value("2,4,8"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("lint")
+                    .about("Check files with a set of rules, reporting each problem as a 'file:line:col: error: ...' or 'file:line:col: warning: ...' line, and optionally fix them in place.  Exits with status 1 if there are errors that weren't fixed.")
+                    .arg(
+                        Arg::with_name("input_file")
+                            .help("Input files")
+                            .value_name("INPUT_FILE")
+                            .index(1)
+                            .multiple(true)
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::with_name("rules")
+                            .help("Rules to check, as NAME or NAME=SEVERITY where the severity is off, warning or error (the default).  The rules are line-endings, line-beginnings and trailing-whitespace.")
+                            .long("rules")
+                            .takes_value(true)
+                            .value_name("RULES")
+                            .use_delimiter(true)
+                            .validator(|s| lint_rule_setting(&s).map(|_| ()))
+                            .default_value("line-endings,line-beginnings,trailing-whitespace")
+                            .setting("WHITESPACE_RULES", settings),
+                    )
+                    .arg(
+                        Arg::with_name("new_eol")
+                            .help("Line ending that lines must end with")
+                            .long("new-eol")
+                            .takes_value(true)
+                            .possible_values(&["cr", "lf", "crlf"])
+                            .case_insensitive(true)
+                            .default_value("lf"),
+                    )
+                    .arg(
+                        Arg::with_name("new_bol")
+                            .help("Indentation that lines must begin with")
+                            .long("new-bol")
+                            .takes_value(true)
+                            .possible_values(&["tabs", "spaces"])
+                            .case_insensitive(true)
+                            .default_value("spaces"),
+                    )
+                    .arg(
+                        Arg::with_name("tab_size")
+                            .help("Tab size for line beginnings")
+                            .long("tab-size")
+                            .short("t")
+                            .takes_value(true)
+                            .value_name("TAB_SIZE")
+                            .default_value("4")
+                            .setting("WHITESPACE_TAB_SIZE", settings),
+                    )
+                    .arg(
+                        Arg::with_name("round_down")
+                            .help("When tabifying, rounds extra spaces down to a whole number of tabs [env: WHITESPACE_ROUND_DOWN]")
+                            .long("round-down")
+                            .short("r")
+                            .overrides_with("no_round_down"),
+                    )
+                    .arg(
+                        Arg::with_name("no_round_down")
+                            .help("Turn off --round-down, e.g. when WHITESPACE_ROUND_DOWN or the configuration file turns it on.")
+                            .long("no-round-down")
+                            .overrides_with("round_down"),
+                    )
+                    .arg(
+                        Arg::with_name("fix")
+                            .help("Fix the problems in place")
+                            .long("fix"),
+                    )
+                    .arg(
+                        Arg::with_name("profile")
+                            .help("Use the settings in the [profile.NAME] table of the .whitespace.toml file in the current directory or the closest one above it, as well as those at the top of the file.")
+                            .long("profile")
+                            .takes_value(true)
+                            .value_name("NAME")
+                            .env("WHITESPACE_PROFILE"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("self-test")
+                    .about("Convert built-in samples with mixed line endings, mixed indentation and UTF-8 edge cases and check the results, to confirm that the tools work on this platform.  Exits with status 1 if any check fails."),
+            )
+    }
+
+    let matches = app(&[]).get_matches_from(args.clone());
+    let config = match matches.subcommand() {
+        ("lint", Some(sub_matches)) => {
+            match config::load_config_file(Path::new("."), sub_matches.value_of("profile")) {
+                Ok(config) => config,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    std::process::exit(-1);
+                }
+            }
+        }
+        _ => Config::default(),
+    };
+    // Settings from the configuration file are the defaults of the arguments, so make and parse
+    // them again to pick up the settings
+    let matches = if config.settings.is_empty() {
+        matches
+    } else {
+        app(&config.settings).get_matches_from(args.clone())
+    };
+
+    let result = match matches.subcommand() {
+        ("wdiff", Some(sub_matches)) => wdiff(sub_matches),
+        ("compare", Some(sub_matches)) => run_compare(
+            sub_matches.value_of("left_dir").unwrap(),
+            sub_matches.value_of("right_dir").unwrap(),
+        ),
+        ("archive", Some(sub_matches)) => archive(sub_matches),
+        ("apply", Some(sub_matches)) => run_apply(
+            sub_matches.value_of("patch_file").unwrap(),
+            sub_matches.value_of("directory"),
+            sub_matches.is_present("check"),
+        )
+        .map(|_| true),
+        ("undo", Some(sub_matches)) => run_undo(
+            sub_matches.value_of("journal_dir").unwrap(),
+            sub_matches.is_present("check"),
+        )
+        .map(|_| true),
+        ("notebook", Some(sub_matches)) => notebook(sub_matches),
+        ("tab-sizes", Some(sub_matches)) => run_tab_sizes(
+            &sub_matches
+                .values_of("input_file")
+                .unwrap()
+                .collect::<Vec<_>>(),
+            &sub_matches
+                .values_of("tab_sizes")
+                .unwrap()
+                .filter_map(|s| s.parse::<usize>().ok())
+                .collect::<Vec<_>>(),
+        )
+        .map(|_| true),
+        ("lint", Some(sub_matches)) => lint(sub_matches, &config, |settings| {
+            let matches = app(settings).get_matches_from(args.clone());
+
+            lint_settings_rules(matches.subcommand_matches("lint").unwrap(), settings)
+        }),
+        ("self-test", Some(_)) => run_self_test(),
+        _ => unreachable!(),
+    };
+
+    match result {
+        Ok(true) => (),
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(-1);
+        }
+    }
+}
+
+/// Run the `wdiff` subcommand.  Returns `true` if the files are equivalent.
+fn wdiff(matches: &ArgMatches) -> Result<bool, Box<dyn Error>> {
+    let options = CompareOptions::default()
+        .ignore_indentation(config::flag_arg(matches, "ignore_indentation", &[])?)
+        .tab_size(
+            matches
+                .value_of("tab_size")
+                .unwrap()
+                .parse::<usize>()
+                .unwrap_or(4),
+        )
+        .markdown(config::flag_arg(matches, "markdown", &[])?);
+
+    run_wdiff(
+        matches.value_of("left_file").unwrap(),
+        matches.value_of("right_file").unwrap(),
+        &options,
+    )
+}
+
+/// Run the `archive` subcommand.  Returns `true`.
+fn archive(matches: &ArgMatches) -> Result<bool, Box<dyn Error>> {
+    let tab_size = matches
+        .value_of("tab_size")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or(4);
+    let round_down = config::flag_arg(matches, "round_down", &[])?;
+    let new_eol = match matches.value_of("new_eol") {
+        Some(s) => Some(s.parse::<EndOfLine>()?),
+        None => None,
+    };
+    let new_bol = match matches
+        .value_of("new_bol")
+        .map(|s| s.parse::<BeginningOfLine>())
+    {
+        Some(Ok(BeginningOfLine::Tabs(_, _))) => Some(BeginningOfLine::Tabs(tab_size, round_down)),
+        Some(Ok(BeginningOfLine::Spaces(_))) => Some(BeginningOfLine::Spaces(tab_size)),
+        Some(Err(err)) => return Err(From::from(err)),
+        None => None,
+    };
+
+    run_archive(
+        matches.value_of("archive_file").unwrap(),
+        matches.value_of("output_file"),
+        new_eol,
+        new_bol,
+    )?;
+
+    Ok(true)
+}
+
+/// Run the `lint` subcommand with the settings from the configuration file, using
+/// `override_rules` to make the rules from the settings for files that its `[[override]]` tables
+/// apply to.  Returns `true` if there are no problems left.
+fn lint<F>(
+    matches: &ArgMatches,
+    config: &Config,
+    mut override_rules: F,
+) -> Result<bool, Box<dyn Error>>
+where
+    F: FnMut(&[(String, String)]) -> Result<Vec<Box<dyn Rule>>, Box<dyn Error>>,
+{
+    let rules = lint_settings_rules(matches, &config.settings)?;
+    let mut rules_for_overrides = HashMap::new();
+    let mut num_errors = 0;
+
+    for input_file in matches.values_of("input_file").unwrap() {
+        let overrides = config.overrides_for(Path::new(input_file));
+        let rules = if overrides.is_empty() {
+            &rules
+        } else {
+            if !rules_for_overrides.contains_key(&overrides) {
+                let override_rules = override_rules(&config.settings_with(&overrides))?;
+
+                rules_for_overrides.insert(overrides.clone(), override_rules);
+            }
+
+            &rules_for_overrides[&overrides]
+        };
+
+        num_errors += run_lint(&[input_file], rules, matches.is_present("fix"))?;
+    }
+
+    Ok(num_errors == 0)
+}
+
+/// Make the rules for the `lint` subcommand from its arguments and the settings they were made
+/// with.
+fn lint_settings_rules(
+    matches: &ArgMatches,
+    settings: &[(String, String)],
+) -> Result<Vec<Box<dyn Rule>>, Box<dyn Error>> {
+    let tab_size = matches
+        .value_of("tab_size")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or(4);
+    let round_down = config::flag_arg(matches, "round_down", settings)?;
+    let new_eol = matches.value_of("new_eol").unwrap().parse::<EndOfLine>()?;
+    let new_bol = match matches
+        .value_of("new_bol")
+        .unwrap()
+        .parse::<BeginningOfLine>()?
+    {
+        BeginningOfLine::Tabs(_, _) => BeginningOfLine::tabs(tab_size).round_down(round_down),
+        BeginningOfLine::Spaces(_) => BeginningOfLine::spaces(tab_size),
+    };
+
+    lint_rules(
+        &matches.values_of("rules").unwrap().collect::<Vec<_>>(),
+        new_eol,
+        new_bol,
+    )
+}
+// {grcov-excl-end}
+
+/// Parse a `NAME` or `NAME=SEVERITY` rule setting.  The severity defaults to error.
+fn lint_rule_setting(s: &str) -> Result<(&str, Severity), String> {
+    let (name, severity) = match s.split_once('=') {
+        Some((name, severity)) => (name.trim(), severity.trim().parse::<Severity>()?),
+        None => (s.trim(), Severity::Error),
+    };
+
+    if rules::NAMES.contains(&name) {
+        Ok((name, severity))
+    } else {
+        Err(format!(
+            "'{}' is not one of {}",
+            name,
+            rules::NAMES.join(", ")
+        ))
+    }
+}
+
+/// Make the built-in rules from `NAME` or `NAME=SEVERITY` settings.
+fn lint_rules(
+    settings: &[&str],
+    new_eol: EndOfLine,
+    new_bol: BeginningOfLine,
+) -> Result<Vec<Box<dyn Rule>>, Box<dyn Error>> {
+    let mut rules: Vec<Box<dyn Rule>> = Vec::new();
+
+    for setting in settings.iter() {
+        let (name, severity) = lint_rule_setting(setting)?;
+        let rule: Box<dyn Rule> = match name {
+            "line-endings" => Box::new(LineEndings(new_eol)),
+            "line-beginnings" => Box::new(LineBeginnings(new_bol)),
+            _ => Box::new(TrailingWhitespace),
+        };
+
+        rules.push(Box::new(WithSeverity::new(rule, severity)));
+    }
+
+    Ok(rules)
+}
+
+/// Check each input file with `rules`, writing a diagnostic with the severity of its rule for each
+/// problem, or fix them in place if `fix` is set.
+///
+/// Returns the number of errors that weren't fixed.
+fn run_lint(
+    input_files: &[&str],
+    rules: &[Box<dyn Rule>],
+    fix: bool,
+) -> Result<usize, Box<dyn Error>> {
+    let mut num_errors = 0;
+
+    for input_file in input_files.iter() {
+        if fix {
+            let (_, modified) = paths::process_file(input_file, |reader, writer| {
+                rules::fix_lines_with_options(
+                    reader,
+                    writer,
+                    rules,
+                    &RuleOptions::default().directives(true),
+                )
+            })
+            .map_err(|err| format!("'{}': {}", input_file, err))?;
+
+            if modified {
+                println!("'{}', fixed", input_file);
+            }
+
+            continue;
+        }
+
+        let issues = rules::check_lines_with_options(
+            &mut paths::open_input(input_file)?,
+            rules,
+            &RuleOptions::default().directives(true),
+        )
+        .map_err(|err| format!("'{}': {}", input_file, err))?;
+
+        for issue in issues.iter() {
+            println!(
+                "{}:{}:{}: {}: {} [{}]",
+                input_file, issue.line, issue.column, issue.severity, issue.message, issue.rule
+            );
+        }
+
+        num_errors += issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .count();
+    }
+
+    Ok(num_errors)
+}
+
+// {grcov-excl-start}
+/// Run the `notebook` subcommand.  Returns `true`.
+fn notebook(matches: &ArgMatches) -> Result<bool, Box<dyn Error>> {
+    let tab_size = matches
+        .value_of("tab_size")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or(4);
+    let new_bol = match matches.value_of("new_bol") {
+        Some(s) => match s.parse::<BeginningOfLine>()? {
+            BeginningOfLine::Tabs(_, _) => Some(BeginningOfLine::Tabs(tab_size, false)),
+            BeginningOfLine::Spaces(_) => Some(BeginningOfLine::Spaces(tab_size)),
+        },
+        None => None,
+    };
+
+    run_notebook(
+        &matches
+            .values_of("notebook_file")
+            .unwrap()
+            .collect::<Vec<_>>(),
+        new_bol,
+        matches.is_present("check"),
+    )?;
+
+    Ok(true)
+}
+// {grcov-excl-end}
+
+/// Choose the tool to run from the name the binary was run as, such as through a link named
+/// `ender`, or from a leading `ender`, `spacer` or `trimmer` argument, which then takes the place
+/// of the binary name.
+fn multi_call_tool(mut args: Vec<OsString>) -> (Tool, Vec<OsString>) {
+    let tool = |name: Option<&str>| match name {
+        Some("ender") => Some(Tool::Ender),
+        Some("spacer") => Some(Tool::Spacer),
+        Some("trimmer") => Some(Tool::Trimmer),
+        _ => None,
+    };
+    let bin_name = args
+        .first()
+        .and_then(|arg| Path::new(arg).file_stem())
+        .and_then(|stem| stem.to_str());
+
+    if let Some(tool) = tool(bin_name) {
+        return (tool, args);
+    }
+
+    match tool(args.get(1).and_then(|arg| arg.to_str())) {
+        Some(tool) => {
+            args.remove(0);
+            (tool, args)
+        }
+        None => (Tool::Whitespace, args),
+    }
+}
+
+/// Compare two files and report whether they are equivalent.  Returns `true` if they are.
+fn run_wdiff(
+    left_file: &str,
+    right_file: &str,
+    options: &CompareOptions,
+) -> Result<bool, Box<dyn Error>> {
+    if left_file == "-" && right_file == "-" {
+        return Err(
+            error::Error::new(ErrorCode::Stdin, "only one file can be read from STDIN").into(),
+        );
+    }
+
+    let mut left = paths::open_input(left_file)?;
+    let mut right = paths::open_input(right_file)?;
+
+    match compare::compare_ignoring_whitespace(&mut left, &mut right, options)? {
+        None => {
+            println!("'{}' and '{}' are equivalent", left_file, right_file);
+            Ok(true)
+        }
+        Some(difference) => {
+            println!(
+                "'{}' and '{}' differ at line {}",
+                left_file, right_file, difference.line
+            );
+
+            match difference.left {
+                Some(line) => println!("< {}", line),
+                None => println!("< (end of file)"),
+            }
+
+            match difference.right {
+                Some(line) => println!("> {}", line),
+                None => println!("> (end of file)"),
+            }
+
+            Ok(false)
+        }
+    }
+}
+
+/// Compare the conventions of the files in two directory trees and report the files that
+/// differ.  Returns `true` if none do.
+fn run_compare(left_dir: &str, right_dir: &str) -> Result<bool, Box<dyn Error>> {
+    let divergences = compare::compare_trees(Path::new(left_dir), Path::new(right_dir))?;
+    let describe =
+        |conventions: &Conventions| format!("eol {}, bol {}", conventions.eol, conventions.bol);
+
+    for divergence in divergences.iter() {
+        println!(
+            "'{}', {} -> {}",
+            divergence.path.to_string_lossy(),
+            describe(&divergence.left),
+            describe(&divergence.right)
+        );
+    }
+
+    Ok(divergences.is_empty())
+}
+
+/// Report on the files in an archive and optionally write a new archive with new line endings
+/// or beginnings.  Returns the number of files changed.
+fn run_archive(
+    archive_file: &str,
+    output_file: Option<&str>,
+    new_eol: Option<EndOfLine>,
+    new_bol: Option<BeginningOfLine>,
+) -> Result<usize, Box<dyn Error>> {
+    let report = |name: &str, data: &[u8]| -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        let label = format!("'{}:{}'", archive_file, name);
+
+        if std::str::from_utf8(data).is_err() {
+            println!("{}, binary", label);
+            return Ok(None);
+        }
+
+        let conventions = compare::read_conventions(&mut &data[..])?;
+        let mut text = format!(
+            "{}, eol {}, bol {}",
+            label, conventions.eol, conventions.bol
+        );
+        let mut new_data = data.to_vec();
+        let file_type = FileType::detect(Path::new(name), data);
+
+        if file_type.preserves_whitespace() && (new_eol.is_some() || new_bol.is_some()) {
+            println!("{}, left unchanged as a patch", text);
+            return Ok(None);
+        }
+
+        if let Some(new_eol) = new_eol {
+            let mut output = Vec::new();
+
+            ender::write_new_eols(&mut new_data.as_slice(), &mut output, new_eol)?;
+            new_data = output;
+        }
+
+        match new_bol {
+            Some(BeginningOfLine::Tabs(_, _)) if !file_type.allows_tabs() => {
+                text += ", YAML can't be indented with tabs";
+            }
+            Some(new_bol) => {
+                let mut output = Vec::new();
+
+                spacer::write_new_bols_with_options(
+                    &mut new_data.as_slice(),
+                    &mut output,
+                    new_bol,
+                    &BolOptions::default().markdown(file_type == FileType::Markdown),
+                )?;
+                new_data = output;
+            }
+            None => (),
+        }
+
+        let changed = new_data != data;
+
+        if output_file.is_some() {
+            let conventions = compare::read_conventions(&mut new_data.as_slice())?;
+
+            text += &format!(" -> eol {}, bol {}", conventions.eol, conventions.bol);
+        }
+
+        println!("{}", text);
+        Ok(if changed { Some(new_data) } else { None })
+    };
+
+    match output_file {
+        Some(output_file) => {
+            archive::rewrite_archive(Path::new(archive_file), Path::new(output_file), report)
+        }
+        None => {
+            archive::read_archive(Path::new(archive_file), |name, data| {
+                report(name, data).map(|_| ())
+            })?;
+            Ok(0)
+        }
+    }
+}
+
+/// Find the file for a path in a diff, dropping the `b/` prefix that git adds if there's no such
+/// directory.
+fn patch_file_path(directory: Option<&str>, path: &str) -> PathBuf {
+    let base = Path::new(directory.unwrap_or("."));
+    let file_path = base.join(path);
+
+    match path.strip_prefix("b/") {
+        Some(stripped) if !file_path.exists() => base.join(stripped),
+        _ => file_path,
+    }
+}
+
+/// Apply a diff that only changes whitespace to the files it names, writing them only if every
+/// hunk applies.  Returns the number of files changed.
+fn run_apply(
+    patch_file: &str,
+    directory: Option<&str>,
+    check: bool,
+) -> Result<usize, Box<dyn Error>> {
+    let mut text = String::new();
+
+    paths::open_input(patch_file)?.read_to_string(&mut text)?;
+
+    let mut new_files = Vec::new();
+
+    for file_patch in patch::parse_patch(&text)? {
+        let file_path = patch_file_path(directory, &file_patch.path);
+        let mut content = String::new();
+
+        paths::open_input(&file_path.to_string_lossy())?.read_to_string(&mut content)?;
+        new_files.push((
+            file_path,
+            file_patch.hunks.len(),
+            patch::apply_patch(&content, &file_patch)?,
+        ));
+    }
+
+    for (file_path, num_hunks, new_content) in new_files.iter() {
+        if !check {
+            let mut output = paths::create_output(file_path)?;
+
+            output.write_all(new_content.as_bytes())?;
+            output.finish()?;
+        }
+
+        println!(
+            "'{}', {} {} {}",
+            file_path.to_string_lossy(),
+            num_hunks,
+            if *num_hunks == 1 { "hunk" } else { "hunks" },
+            if check { "can be applied" } else { "applied" }
+        );
+    }
+
+    Ok(if check { 0 } else { new_files.len() })
+}
+
+/// Restore the files in a journal.  Returns the number of files restored.
+fn run_undo(journal_dir: &str, check: bool) -> Result<usize, Box<dyn Error>> {
+    let restored = journal::undo(Path::new(journal_dir), check)?;
+
+    for path in restored.iter() {
+        println!(
+            "'{}', {}",
+            path.to_string_lossy(),
+            if check { "can be restored" } else { "restored" }
+        );
+    }
+
+    Ok(if check { 0 } else { restored.len() })
+}
+
+/// Normalize the whitespace in the cells of notebooks, writing them only if every one can be
+/// read.  Returns the number of files changed.
+fn run_notebook(
+    notebook_files: &[&str],
+    new_bol: Option<BeginningOfLine>,
+    check: bool,
+) -> Result<usize, Box<dyn Error>> {
+    let mut new_files = Vec::new();
+
+    for notebook_file in notebook_files.iter() {
+        let mut text = String::new();
+
+        paths::open_input(notebook_file)?.read_to_string(&mut text)?;
+
+        let (new_text, num_changed) = notebook::normalize_notebook(&text, new_bol)
+            .map_err(|err| format!("'{}': {}", notebook_file, err))?;
+
+        new_files.push((notebook_file, num_changed, new_text));
+    }
+
+    for (notebook_file, num_changed, new_text) in new_files.iter() {
+        if !check && *num_changed > 0 {
+            paths::replace_file(Path::new(notebook_file), new_text.as_bytes())?;
+        }
+
+        println!(
+            "'{}', {} {} {}",
+            notebook_file,
+            num_changed,
+            if *num_changed == 1 { "cell" } else { "cells" },
+            if check { "to normalize" } else { "normalized" }
+        );
+    }
+
+    Ok(if check {
+        0
+    } else {
+        new_files.iter().filter(|(_, n, _)| *n > 0).count()
+    })
+}
+
+/// Report how well each of `tab_sizes` fits the indentation of each input file.  Returns the
+/// tab size that fits each one best, if any.
+fn run_tab_sizes(
+    input_files: &[&str],
+    tab_sizes: &[usize],
+) -> Result<Vec<Option<usize>>, Box<dyn Error>> {
+    let mut best_tab_sizes = Vec::new();
+
+    for input_file in input_files.iter() {
+        let fits = spacer::tab_size_fits(&mut paths::open_input(input_file)?, tab_sizes)
+            .map_err(|err| format!("'{}': {}", input_file, err))?;
+        let best_tab_size = spacer::best_tab_size(&fits);
+
+        match best_tab_size {
+            Some(tab_size) => println!("'{}', tab size {} fits best", input_file, tab_size),
+            None => println!("'{}', no single tab size fits best", input_file),
+        }
+
+        for fit in fits.iter() {
+            println!(
+                "  {}: {} misaligned lines, {} odd columns",
+                fit.tab_size, fit.misaligned_lines, fit.odd_columns
+            );
+        }
+
+        best_tab_sizes.push(best_tab_size);
+    }
+
+    Ok(best_tab_sizes)
+}
+
+/// A conversion checked by `self-test`
+enum Conversion {
+    Eol(EndOfLine),
+    Bol(BeginningOfLine),
+}
+
+/// A sample converted by `self-test`, with the expected output or `None` if it must be rejected
+struct SelfTestCase {
+    name: &'static str,
+    input: &'static [u8],
+    conversion: Conversion,
+    expected: Option<&'static [u8]>,
+}
+
+const SELF_TEST_CASES: &[SelfTestCase] = &[
+    SelfTestCase {
+        name: "mixed line endings to lf",
+        input: b"a\r\nb\rc\nd",
+        conversion: Conversion::Eol(EndOfLine::Lf),
+        expected: Some(b"a\nb\nc\nd"),
+    },
+    SelfTestCase {
+        name: "mixed line endings to crlf",
+        input: b"a\r\nb\rc\nd\r",
+        conversion: Conversion::Eol(EndOfLine::CrLf),
+        expected: Some(b"a\r\nb\r\nc\r\nd\r\n"),
+    },
+    SelfTestCase {
+        name: "mixed line endings to cr",
+        input: b"a\r\n\nb\n",
+        conversion: Conversion::Eol(EndOfLine::Cr),
+        expected: Some(b"a\r\rb\r"),
+    },
+    SelfTestCase {
+        name: "tabs to spaces",
+        input: b"\ta\n\t\tb\n  c\n",
+        conversion: Conversion::Bol(BeginningOfLine::Spaces(4)),
+        expected: Some(b"    a\n        b\n  c\n"),
+    },
+    SelfTestCase {
+        name: "spaces to tabs",
+        input: b"    a\n      b\n",
+        conversion: Conversion::Bol(BeginningOfLine::Tabs(4, false)),
+        expected: Some(b"\ta\n\t  b\n"),
+    },
+    SelfTestCase {
+        name: "spaces to tabs rounding down",
+        input: b"    a\n      b\n",
+        conversion: Conversion::Bol(BeginningOfLine::Tabs(4, true)),
+        expected: Some(b"\ta\n\tb\n"),
+    },
+    SelfTestCase {
+        name: "mixed indentation to spaces",
+        input: b"  \ta\n\t  b\r\n",
+        conversion: Conversion::Bol(BeginningOfLine::Spaces(4)),
+        expected: Some(b"    a\n      b\r\n"),
+    },
+    SelfTestCase {
+        name: "multi-byte UTF-8 line endings",
+        input: "\u{feff}\u{fc}\r\n\u{1f600}\t\r\u{2028}".as_bytes(),
+        conversion: Conversion::Eol(EndOfLine::Lf),
+        expected: Some("\u{feff}\u{fc}\n\u{1f600}\t\n\u{2028}".as_bytes()),
+    },
+    SelfTestCase {
+        name: "multi-byte UTF-8 indentation",
+        input: "\t\u{fc} \u{1f600}\n\t\t\u{3000}\n".as_bytes(),
+        conversion: Conversion::Bol(BeginningOfLine::Spaces(2)),
+        expected: Some("  \u{fc} \u{1f600}\n    \u{3000}\n".as_bytes()),
+    },
+    SelfTestCase {
+        name: "empty input",
+        input: b"",
+        conversion: Conversion::Eol(EndOfLine::CrLf),
+        expected: Some(b""),
+    },
+    SelfTestCase {
+        name: "invalid UTF-8 rejected",
+        input: b"a\xff\r\n",
+        conversion: Conversion::Eol(EndOfLine::Lf),
+        expected: None,
+    },
+    SelfTestCase {
+        name: "truncated UTF-8 rejected",
+        input: b"\ta\xc3",
+        conversion: Conversion::Bol(BeginningOfLine::Spaces(4)),
+        expected: None,
+    },
+];
+
+/// Convert a `self-test` sample and check that the output is expected and verifies.
+fn check_self_test_case(case: &SelfTestCase) -> Result<bool, Box<dyn Error>> {
+    let mut output = Vec::new();
+    let result = match case.conversion {
+        Conversion::Eol(new_eol) => {
+            ender::write_new_eols(&mut &case.input[..], &mut output, new_eol).map(|_| ())
+        }
+        Conversion::Bol(new_bol) => {
+            spacer::write_new_bols(&mut &case.input[..], &mut output, new_bol).map(|_| ())
+        }
+    };
+    let expected = match (case.expected, result) {
+        (None, result) => return Ok(result.is_err()),
+        (Some(_), Err(err)) => return Err(err),
+        (Some(expected), Ok(())) => expected,
+    };
+
+    Ok(output == expected
+        && match case.conversion {
+            Conversion::Eol(new_eol) => {
+                ender::verify_new_eols(&mut &case.input[..], &mut output.as_slice(), new_eol)?
+            }
+            Conversion::Bol(new_bol) => {
+                spacer::verify_new_bols(&mut &case.input[..], &mut output.as_slice(), new_bol)?
+            }
+        })
+}
+
+/// Run the built-in `self-test` samples and report each one.  Returns `true` if all pass.
+fn run_self_test() -> Result<bool, Box<dyn Error>> {
+    let mut num_failed = 0;
+
+    for case in SELF_TEST_CASES.iter() {
+        match check_self_test_case(case) {
+            Ok(true) => println!("'{}', ok", case.name),
+            Ok(false) => {
+                num_failed += 1;
+                println!("'{}', FAILED", case.name);
+            }
+            Err(err) => {
+                num_failed += 1;
+                println!("'{}', FAILED, {}", case.name, err);
+            }
+        }
+    }
+
+    println!(
+        "{} of {} checks passed",
+        SELF_TEST_CASES.len() - num_failed,
+        SELF_TEST_CASES.len()
+    );
+
+    Ok(num_failed == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_call_tool() {
+        let args = |args: &[&str]| -> Vec<OsString> { args.iter().map(OsString::from).collect() };
+
+        assert_eq!(
+            multi_call_tool(args(&["/usr/bin/ender", "-n", "lf"])),
+            (Tool::Ender, args(&["/usr/bin/ender", "-n", "lf"]))
+        );
+        assert_eq!(multi_call_tool(args(&["spacer.exe"])).0, Tool::Spacer);
+        assert_eq!(
+            multi_call_tool(args(&["whitespace", "trimmer", "-t"])),
+            (Tool::Trimmer, args(&["trimmer", "-t"]))
+        );
+        assert_eq!(
+            multi_call_tool(args(&["whitespace", "spacer", "a.txt"])),
+            (Tool::Spacer, args(&["spacer", "a.txt"]))
+        );
+        assert_eq!(
+            multi_call_tool(args(&["whitespace", "wdiff", "ender"])),
+            (Tool::Whitespace, args(&["whitespace", "wdiff", "ender"]))
+        );
+        assert_eq!(multi_call_tool(args(&[])), (Tool::Whitespace, args(&[])));
+    }
+
+    #[test]
+    fn test_run_wdiff() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let left_path = temp_dir.path().join("left.txt");
+        let right_path = temp_dir.path().join("right.txt");
+        let left_file = left_path.to_str().unwrap();
+        let right_file = right_path.to_str().unwrap();
+
+        std::fs::write(left_file, "abc  \r\n\txyz\r\n").unwrap();
+        std::fs::write(right_file, "abc\n    xyz").unwrap();
+
+        assert!(!run_wdiff(left_file, right_file, &CompareOptions::default()).unwrap());
+        assert!(run_wdiff(
+            left_file,
+            right_file,
+            &CompareOptions::default().ignore_indentation(true)
+        )
+        .unwrap());
+        assert!(run_wdiff("-", "-", &CompareOptions::default()).is_err());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_run_archive() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let input_path = temp_dir.path().join("input.tar");
+        let output_path = temp_dir.path().join("output.tar");
+        let input_file = input_path.to_str().unwrap();
+        let output_file = output_path.to_str().unwrap();
+        let mut builder = tar::Builder::new(std::fs::File::create(&input_path).unwrap());
+
+        for (name, data) in [
+            ("a.txt", &b"a\r\n\tb\r\n"[..]),
+            ("b.bin", &[0xff][..]),
+            ("c.diff", &b"@@ -1 +1 @@\r\n \ta\r\n"[..]),
+        ]
+        .iter()
+        {
+            let mut header = tar::Header::new_gnu();
+
+            header.set_size(data.len() as u64);
+            builder.append_data(&mut header, name, *data).unwrap();
+        }
+
+        builder.finish().unwrap();
+
+        assert_eq!(run_archive(input_file, None, None, None).unwrap(), 0);
+        assert_eq!(
+            run_archive(
+                input_file,
+                Some(output_file),
+                Some(EndOfLine::Lf),
+                Some(BeginningOfLine::Spaces(2))
+            )
+            .unwrap(),
+            1
+        );
+
+        let mut contents = Vec::new();
+
+        archive::read_archive(&output_path, |name, data| {
+            contents.push((name.to_string(), data.to_vec()));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            contents,
+            vec![
+                ("a.txt".to_string(), b"a\n  b\n".to_vec()),
+                ("b.bin".to_string(), vec![0xff]),
+                ("c.diff".to_string(), b"@@ -1 +1 @@\r\n \ta\r\n".to_vec())
+            ]
+        );
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_run_apply() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let directory = temp_dir.path().to_str();
+        let patch_path = temp_dir.path().join("fix.diff");
+        let patch_file = patch_path.to_str().unwrap();
+        let file_path = temp_dir.path().join("a.txt");
+
+        std::fs::write(&file_path, "abc\n\tdef\n").unwrap();
+        std::fs::write(
+            &patch_path,
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n abc\n-\tdef\n+  def\n",
+        )
+        .unwrap();
+
+        assert_eq!(run_apply(patch_file, directory, true).unwrap(), 0);
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "abc\n\tdef\n");
+        assert_eq!(run_apply(patch_file, directory, false).unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "abc\n  def\n");
+        assert!(run_apply(patch_file, directory, false).is_err());
+
+        std::fs::write(
+            &patch_path,
+            "--- a.txt\n+++ a.txt\n@@ -1 +1 @@\n-abc\n+abd\n",
+        )
+        .unwrap();
+
+        assert!(run_apply(patch_file, directory, false).is_err());
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "abc\n  def\n");
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_run_undo() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let journal_path = temp_dir.path().join("journal");
+        let journal_dir = journal_path.to_str().unwrap();
+        let file_path = temp_dir.path().join("a.txt");
+        let journal = journal::Journal::create(&journal_path).unwrap();
+
+        std::fs::write(&file_path, "a\n").unwrap();
+        journal.record(&file_path, b"a\r\n", b"a\n").unwrap();
+
+        assert_eq!(run_undo(journal_dir, true).unwrap(), 0);
+        assert_eq!(run_undo(journal_dir, false).unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "a\r\n");
+        assert!(run_undo(journal_dir, false).is_err());
+        assert!(run_undo("missing_dir", false).is_err());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_run_notebook() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let notebook_path = temp_dir.path().join("a.ipynb");
+        let notebook_file = notebook_path.to_str().unwrap();
+        let text =
+            "{\"cells\": [{\"cell_type\": \"code\", \"source\": [\"if x:  \\n\", \"\\tpass\"]}]}\n";
+
+        std::fs::write(&notebook_path, text).unwrap();
+
+        assert_eq!(run_notebook(&[notebook_file], None, true).unwrap(), 0);
+        assert_eq!(std::fs::read_to_string(&notebook_path).unwrap(), text);
+        assert!(run_notebook(&[notebook_file, "missing.ipynb"], None, false).is_err());
+        assert_eq!(
+            run_notebook(&[notebook_file], Some(BeginningOfLine::Spaces(2)), false).unwrap(),
+            1
+        );
+        assert_eq!(
+            std::fs::read_to_string(&notebook_path).unwrap(),
+            "{\"cells\": [{\"cell_type\": \"code\", \"source\": [\"if x:\\n\", \"  pass\"]}]}\n"
+        );
+        assert_eq!(run_notebook(&[notebook_file], None, false).unwrap(), 0);
+
+        std::fs::write(&notebook_path, "{\"cells\": [}").unwrap();
+
+        let err = run_notebook(&[notebook_file], None, false).unwrap_err();
+
+        assert!(err
+            .to_string()
+            .starts_with(&format!("'{}': expected", notebook_file)));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_run_tab_sizes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let spaces_path = temp_dir.path().join("spaces.c");
+        let tabs_path = temp_dir.path().join("tabs.c");
+        let spaces_file = spaces_path.to_str().unwrap();
+        let tabs_file = tabs_path.to_str().unwrap();
+
+        std::fs::write(&spaces_path, "a {\n    b {\n        c;\n    }\n}\n").unwrap();
+        std::fs::write(&tabs_path, "a {\n\tb;\n}\n").unwrap();
+
+        assert_eq!(
+            run_tab_sizes(&[spaces_file, tabs_file], &[2, 4, 8]).unwrap(),
+            vec![Some(4), None]
+        );
+        assert_eq!(
+            run_tab_sizes(&[spaces_file], &[8, 2]).unwrap(),
+            vec![Some(2)]
+        );
+        assert!(run_tab_sizes(&["missing.c"], &[4]).is_err());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_run_lint() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("a.txt");
+        let file = path.to_str().unwrap();
+        let rules = lint_rules(
+            &["line-endings", "trailing-whitespace"],
+            EndOfLine::Lf,
+            BeginningOfLine::spaces(4),
+        )
+        .unwrap();
+        let warning_rules = lint_rules(
+            &["line-endings=warning", "line-beginnings=off"],
+            EndOfLine::Lf,
+            BeginningOfLine::spaces(4),
+        )
+        .unwrap();
+
+        std::fs::write(&path, "\tabc \r\nxyz\n").unwrap();
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(run_lint(&[file], &warning_rules, false).unwrap(), 0);
+        assert_eq!(run_lint(&[file], &rules, false).unwrap(), 2);
+        assert_eq!(run_lint(&[file], &rules, true).unwrap(), 0);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "\tabc\nxyz\n");
+        assert_eq!(run_lint(&[file], &rules, false).unwrap(), 0);
+        assert!(run_lint(&["missing.txt"], &rules, false).is_err());
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_lint_rule_setting() {
+        assert_eq!(
+            lint_rule_setting("line-endings"),
+            Ok(("line-endings", Severity::Error))
+        );
+        assert_eq!(
+            lint_rule_setting("trailing-whitespace = Warning"),
+            Ok(("trailing-whitespace", Severity::Warning))
+        );
+        assert!(lint_rule_setting("line-endings=fatal").is_err());
+        assert!(lint_rule_setting("tabs").is_err());
+    }
+
+    #[test]
+    fn test_run_compare() {
+        let left_dir = tempfile::tempdir().unwrap();
+        let right_dir = tempfile::tempdir().unwrap();
+        let left = left_dir.path().to_str().unwrap();
+        let right = right_dir.path().to_str().unwrap();
+
+        std::fs::write(left_dir.path().join("a.txt"), "\tabc\n").unwrap();
+        std::fs::write(right_dir.path().join("a.txt"), "\tabc\n").unwrap();
+
+        assert!(run_compare(left, right).unwrap());
+
+        std::fs::write(right_dir.path().join("a.txt"), "  abc\n").unwrap();
+
+        assert!(!run_compare(left, right).unwrap());
+        assert!(run_compare(left, "missing_dir").is_err());
+
+        left_dir.close().unwrap();
+        right_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_run_self_test() {
+        for case in SELF_TEST_CASES.iter() {
+            assert!(check_self_test_case(case).unwrap(), "{}", case.name);
+        }
+
+        assert!(run_self_test().unwrap());
+        assert!(!check_self_test_case(&SelfTestCase {
+            name: "wrong",
+            input: b"a\r\n",
+            conversion: Conversion::Eol(EndOfLine::Lf),
+            expected: Some(b"a\r\n"),
+        })
+        .unwrap());
+    }
+}
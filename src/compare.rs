@@ -0,0 +1,395 @@
+//! Compare files while ignoring differences in whitespace.
+//!
+//! To check that two [`Read`] trait objects have the same content apart from line endings,
+//! trailing whitespace and optionally indentation style use [`compare_ignoring_whitespace()`]:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::compare::{self, CompareOptions};
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let difference = compare::compare_ignoring_whitespace(
+//!     &mut "abc  \r\n\txyz\r\n".as_bytes(),
+//!     &mut "abc\n    xyz".as_bytes(),
+//!     &CompareOptions::default().ignore_indentation(true),
+//!   )?;
+//!
+//!   println!("{:?}", difference);
+//!   Ok(())
+//! }
+//! ```
+//!
+//! To find the files in two directory trees that use different line ending or beginning
+//! conventions use [`compare_trees()`].
+
+use crate::ender::read_eol_info;
+use crate::lines::{self, LineAction};
+use crate::paths;
+use crate::spacer::{indent_width, read_bol_info};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Options for [`compare_ignoring_whitespace()`]
+#[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
+pub struct CompareOptions {
+  /// Treat tabs and spaces at the beginning of lines as the same if they indent the same number
+  /// of columns
+  pub ignore_indentation: bool,
+  /// Tab size used to compare indentation
+  pub tab_size: usize,
+  /// Treat two or more trailing spaces as a Markdown hard line break, which is different from
+  /// other trailing whitespace
+  pub markdown: bool,
+}
+
+impl Default for CompareOptions {
+  fn default() -> Self {
+    CompareOptions {
+      ignore_indentation: false,
+      tab_size: 4,
+      markdown: false,
+    }
+  }
+}
+
+impl CompareOptions {
+  /// Set [`ignore_indentation`](CompareOptions::ignore_indentation)
+  pub fn ignore_indentation(mut self, ignore_indentation: bool) -> Self {
+    self.ignore_indentation = ignore_indentation;
+    self
+  }
+
+  /// Set [`tab_size`](CompareOptions::tab_size)
+  pub fn tab_size(mut self, tab_size: usize) -> Self {
+    self.tab_size = tab_size;
+    self
+  }
+
+  /// Set [`markdown`](CompareOptions::markdown)
+  pub fn markdown(mut self, markdown: bool) -> Self {
+    self.markdown = markdown;
+    self
+  }
+}
+
+/// The first line that differs between two files
+#[derive(Debug, PartialEq, Clone)]
+pub struct Difference {
+  /// Line number, starting at 1
+  pub line: usize,
+  /// Normalized line from the first file, or `None` if the first file has fewer lines
+  pub left: Option<String>,
+  /// Normalized line from the second file, or `None` if the second file has fewer lines
+  pub right: Option<String>,
+}
+
+/// Line ending and beginning conventions of a file
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Conventions {
+  /// `cr`, `lf`, `crlf`, `mixed` or `none`
+  pub eol: &'static str,
+  /// `spaces`, `tabs`, `smart tabs`, `mixed` or `none`
+  pub bol: &'static str,
+}
+
+/// A file with different conventions in two directory trees
+#[derive(Debug, PartialEq, Clone)]
+pub struct Divergence {
+  /// Path of the file relative to both directories
+  pub path: PathBuf,
+  /// Conventions of the file in the first directory
+  pub left: Conventions,
+  /// Conventions of the file in the second directory
+  pub right: Conventions,
+}
+
+/// Read the line ending and beginning conventions of a file
+pub fn read_conventions(reader: &mut dyn Read) -> Result<Conventions, Box<dyn Error>> {
+  let mut buf = Vec::new();
+
+  reader.read_to_end(&mut buf)?;
+
+  let eol_info = read_eol_info(&mut buf.as_slice())?;
+  let bol_info = read_bol_info(&mut buf.as_slice())?;
+
+  Ok(Conventions {
+    eol: if eol_info.num_endings() > 1 {
+      "mixed"
+    } else if eol_info.cr > 0 {
+      "cr"
+    } else if eol_info.lf > 0 {
+      "lf"
+    } else if eol_info.crlf > 0 {
+      "crlf"
+    } else {
+      "none"
+    },
+    bol: if bol_info.is_smart_tabs() {
+      "smart tabs"
+    } else if bol_info.mixed > 0 || (bol_info.spaces > 0 && bol_info.tabs > 0) {
+      "mixed"
+    } else if bol_info.tabs > 0 {
+      "tabs"
+    } else if bol_info.spaces > 0 {
+      "spaces"
+    } else {
+      "none"
+    },
+  })
+}
+
+/// Find the files in both of two directory trees that have different conventions.
+///
+/// Files that are only in one of the trees are ignored, as are files that can't be read as
+/// UTF-8 in either tree.
+pub fn compare_trees(left_dir: &Path, right_dir: &Path) -> Result<Vec<Divergence>, Box<dyn Error>> {
+  let read_file_conventions = |path: &Path| -> Option<Conventions> {
+    let file = File::open(paths::extended_length_path(path)).ok()?;
+
+    read_conventions(&mut BufReader::new(file)).ok()
+  };
+  let right_files = paths::walk_files(right_dir)?;
+  let mut divergences = Vec::new();
+
+  for path in paths::walk_files(left_dir)? {
+    if right_files.binary_search(&path).is_err() {
+      continue;
+    }
+
+    let left = read_file_conventions(&left_dir.join(&path));
+    let right = read_file_conventions(&right_dir.join(&path));
+
+    if let (Some(left), Some(right)) = (left, right) {
+      if left != right {
+        divergences.push(Divergence { path, left, right });
+      }
+    }
+  }
+
+  Ok(divergences)
+}
+
+/// Compare two files ignoring line endings, trailing spaces and tabs, and optionally indentation
+/// style.
+///
+/// Returns `None` if the files are equivalent, otherwise the first line that differs.
+pub fn compare_ignoring_whitespace(
+  left: &mut dyn Read,
+  right: &mut dyn Read,
+  options: &CompareOptions,
+) -> Result<Option<Difference>, Box<dyn Error>> {
+  let left_lines = normalized_lines(left, options)?;
+  let right_lines = normalized_lines(right, options)?;
+
+  for i in 0..left_lines.len().max(right_lines.len()) {
+    let (left, right) = (left_lines.get(i), right_lines.get(i));
+
+    if left != right {
+      return Ok(Some(Difference {
+        line: i + 1,
+        left: left.cloned(),
+        right: right.cloned(),
+      }));
+    }
+  }
+
+  Ok(None)
+}
+
+/// Read the lines of a file without line endings or trailing whitespace, and with indentation
+/// expanded to spaces if ignoring indentation
+fn normalized_lines(
+  reader: &mut dyn Read,
+  options: &CompareOptions,
+) -> Result<Vec<String>, Box<dyn Error>> {
+  let mut normalized = Vec::new();
+
+  lines::process_lines(reader, &mut io::sink(), |line| {
+    let content = line.content.trim_end_matches([' ', '\t']);
+    let hard_break =
+      options.markdown && line.content.len() - line.content.trim_end_matches(' ').len() >= 2;
+
+    let mut s = if options.ignore_indentation {
+      let text = content.trim_start_matches([' ', '\t']);
+      let indent = &content[..content.len() - text.len()];
+
+      format!(
+        "{}{}",
+        " ".repeat(indent_width(indent, options.tab_size)),
+        text
+      )
+    } else {
+      content.to_string()
+    };
+
+    if hard_break {
+      s.push_str("  ");
+    }
+
+    normalized.push(s);
+
+    LineAction::Drop
+  })?;
+
+  Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_compare_ignoring_whitespace() {
+    let options = CompareOptions::default();
+
+    assert_eq!(
+      compare_ignoring_whitespace(
+        &mut "abc \r\n\txyz\r".as_bytes(),
+        &mut "abc\n\txyz\t\n".as_bytes(),
+        &options
+      )
+      .unwrap(),
+      None
+    );
+    assert_eq!(
+      compare_ignoring_whitespace(
+        &mut "abc\n\txyz\n".as_bytes(),
+        &mut "abc\n    xyz\n".as_bytes(),
+        &options
+      )
+      .unwrap(),
+      Some(Difference {
+        line: 2,
+        left: Some("\txyz".to_string()),
+        right: Some("    xyz".to_string()),
+      })
+    );
+    assert_eq!(
+      compare_ignoring_whitespace(&mut "abc\n".as_bytes(), &mut "abc\n\n".as_bytes(), &options)
+        .unwrap(),
+      Some(Difference {
+        line: 2,
+        left: None,
+        right: Some("".to_string()),
+      })
+    );
+  }
+
+  #[test]
+  fn test_compare_markdown_hard_breaks() {
+    let options = CompareOptions {
+      markdown: true,
+      ..Default::default()
+    };
+
+    assert_eq!(
+      compare_ignoring_whitespace(
+        &mut "a  \nb \n".as_bytes(),
+        &mut "a   \nb\t\n".as_bytes(),
+        &options
+      )
+      .unwrap(),
+      None
+    );
+    assert_eq!(
+      compare_ignoring_whitespace(
+        &mut "a  \nb\n".as_bytes(),
+        &mut "a\nb\n".as_bytes(),
+        &options
+      )
+      .unwrap(),
+      Some(Difference {
+        line: 1,
+        left: Some("a  ".to_string()),
+        right: Some("a".to_string()),
+      })
+    );
+  }
+
+  #[test]
+  fn test_read_conventions() {
+    assert_eq!(
+      read_conventions(&mut "a\r\n\tb\r\n".as_bytes()).unwrap(),
+      Conventions {
+        eol: "crlf",
+        bol: "tabs"
+      }
+    );
+    assert_eq!(
+      read_conventions(&mut "a\n  b\r\n".as_bytes()).unwrap(),
+      Conventions {
+        eol: "mixed",
+        bol: "spaces"
+      }
+    );
+    assert_eq!(
+      read_conventions(&mut "a".as_bytes()).unwrap(),
+      Conventions {
+        eol: "none",
+        bol: "none"
+      }
+    );
+  }
+
+  #[test]
+  fn test_compare_trees() {
+    let left_dir = tempfile::tempdir().unwrap();
+    let right_dir = tempfile::tempdir().unwrap();
+
+    std::fs::create_dir(left_dir.path().join("src")).unwrap();
+    std::fs::create_dir(right_dir.path().join("src")).unwrap();
+    std::fs::write(left_dir.path().join("src").join("a.rs"), "a\n  b\n").unwrap();
+    std::fs::write(right_dir.path().join("src").join("a.rs"), "a\r\n  b\r\n").unwrap();
+    std::fs::write(left_dir.path().join("b.rs"), "a\n\tb\n").unwrap();
+    std::fs::write(right_dir.path().join("b.rs"), "x\n\ty\n").unwrap();
+    std::fs::write(left_dir.path().join("c.rs"), "a\n").unwrap();
+    std::fs::write(right_dir.path().join("c.bin"), [0xffu8, 0xfe]).unwrap();
+
+    assert_eq!(
+      compare_trees(left_dir.path(), right_dir.path()).unwrap(),
+      vec![Divergence {
+        path: Path::new("src").join("a.rs"),
+        left: Conventions {
+          eol: "lf",
+          bol: "spaces"
+        },
+        right: Conventions {
+          eol: "crlf",
+          bol: "spaces"
+        },
+      }]
+    );
+
+    left_dir.close().unwrap();
+    right_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_compare_ignoring_indentation() {
+    let options = CompareOptions {
+      ignore_indentation: true,
+      tab_size: 2,
+      ..Default::default()
+    };
+
+    assert_eq!(
+      compare_ignoring_whitespace(
+        &mut "abc\n\t xyz\n".as_bytes(),
+        &mut "abc\n   xyz\n".as_bytes(),
+        &options
+      )
+      .unwrap(),
+      None
+    );
+    assert!(compare_ignoring_whitespace(
+      &mut "\txyz\n".as_bytes(),
+      &mut " xyz\n".as_bytes(),
+      &options
+    )
+    .unwrap()
+    .is_some());
+  }
+}
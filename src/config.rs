@@ -0,0 +1,935 @@
+//! Configuration layered between the built-in defaults and command line arguments.
+//!
+//! Settings can be given in `WHITESPACE_*` environment variables so that they can be set once,
+//! for example in a CI image, instead of on every command line.  Options that take values read
+//! their environment variables through `clap`.  Use [`env_flag()`] for options that don't, which
+//! fails for values that aren't booleans rather than ignore them:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::config;
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let verify = config::env_flag("WHITESPACE_VERIFY")?.unwrap_or(false);
+//!
+//!   println!("{}", verify);
+//!   Ok(())
+//! }
+//! ```
+//!
+//! The command line tools have a `--no-NAME` argument for each such option to turn it off again
+//! whatever the environment says, which [`flag_arg()`] takes into account.
+//!
+//! Use [`parse_size()`] for sizes like `10M` given in arguments or environment variables.
+//!
+//! Settings can also be kept in a `.whitespace.toml` file, found by [`find_config_file()`] in the
+//! current directory or the closest one above it.  Its keys are the names of the environment
+//! variables without the `WHITESPACE_` prefix, in lower case.  Settings at the top of the file
+//! always apply, and named profiles in `[profile.NAME]` tables override them when selected:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::config;
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let text = "verify = true\n\n[profile.strict]\neol = \"lf\"\n";
+//!   let settings = config::read_settings(text, Some("strict"))?;
+//!
+//!   assert_eq!(settings[1], ("WHITESPACE_EOL".to_string(), "lf".to_string()));
+//!   Ok(())
+//! }
+//! ```
+//!
+//! [`load_config_file()`] returns the settings, which the command line tools use as the defaults
+//! of their arguments, so that environment variables and then command line arguments take
+//! precedence over them.  Use [`flag()`] for the options that don't take values.  Keys that aren't
+//! in [`SETTING_KEYS`] are an error.
+//!
+//! `[[override]]` tables give settings for the paths that match their `paths` glob patterns,
+//! which the tools layer over the others for those files with [`Config::overrides_for()`] and
+//! [`Config::settings_with()`]:
+//!
+//! ```
+//! use std::error::Error;
+//! use std::path::Path;
+//! use whitespace_rs::config;
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let text = "eol = \"lf\"\n\n[[override]]\npaths = [\"vendor/\"]\neol = \"crlf\"\n";
+//!   let config = config::read_config(text, None)?;
+//!   let overrides = config.overrides_for(Path::new("vendor/lib.c"));
+//!
+//!   assert_eq!(
+//!     config.settings_with(&overrides),
+//!     vec![("WHITESPACE_EOL".to_string(), "crlf".to_string())]
+//!   );
+//!   Ok(())
+//! }
+//! ```
+
+use crate::error::{self, ErrorCode};
+use crate::ignore::matches_glob;
+use std::error::Error;
+use std::path::{Component, Path, PathBuf};
+
+/// Name of the configuration file
+pub const CONFIG_FILE_NAME: &str = ".whitespace.toml";
+
+/// Keys that can be set in the configuration file, which are the names of the environment
+/// variables without the `WHITESPACE_` prefix
+pub const SETTING_KEYS: &[&str] = &[
+  "align_continuations",
+  "ascii",
+  "audit_log",
+  "autocrlf",
+  "bol",
+  "bol_strategy",
+  "check",
+  "counts",
+  "decode_policy",
+  "eol",
+  "exec",
+  "fail_fast",
+  "final_newline",
+  "force",
+  "from_encoding",
+  "git_tracked",
+  "indent_above",
+  "indent_below",
+  "infer_tab_size",
+  "journal",
+  "markdown",
+  "max_line_length",
+  "max_memory_size",
+  "max_size",
+  "no_ignore",
+  "preserve_blank_line_ws",
+  "print_changed",
+  "progress",
+  "protect_strings",
+  "recursive",
+  "replace_unusual_spaces",
+  "round_down",
+  "rules",
+  "safe_only",
+  "sort",
+  "stats_format",
+  "stats_output",
+  "tab_size",
+  "tie_break",
+  "timings",
+  "to_encoding",
+  "unicode_separators",
+  "verify",
+];
+
+/// Get the value of a boolean environment variable.
+///
+/// `1`, `true`, `yes` and `on` are true and `0`, `false`, `no`, `off` and the empty string are
+/// false, ignoring case.  Returns `None` if the variable is not set, and fails if it has any other
+/// value.
+pub fn env_flag(name: &str) -> Result<Option<bool>, Box<dyn Error>> {
+  match std::env::var(name) {
+    Ok(value) => parse_flag(&value, name).map(Some),
+    Err(_) => Ok(None),
+  }
+}
+
+/// Get the value of a boolean option from its environment variable, or from `settings` if the
+/// variable isn't set.  Values are understood as for [`env_flag()`].
+pub fn flag(name: &str, settings: &[(String, String)]) -> Result<Option<bool>, Box<dyn Error>> {
+  match (env_flag(name)?, setting(settings, name)) {
+    (None, Some(value)) => parse_flag(value, name).map(Some),
+    (value, _) => Ok(value),
+  }
+}
+
+/// Get the value of the boolean command line argument `arg`, which is off if `--no-ARG` is given
+/// after it, and otherwise on if it is given or if [`flag()`] says so for the environment
+/// variable `WHITESPACE_ARG`.  The `--no-ARG` argument is named `no_` followed by `arg`.
+#[cfg(feature = "cli")]
+pub fn flag_arg(
+  matches: &clap::ArgMatches,
+  arg: &str,
+  settings: &[(String, String)],
+) -> Result<bool, Box<dyn Error>> {
+  if matches.is_present(format!("no_{}", arg)) {
+    Ok(false)
+  } else if matches.is_present(arg) {
+    Ok(true)
+  } else {
+    Ok(flag(&format!("WHITESPACE_{}", arg.to_uppercase()), settings)?.unwrap_or(false))
+  }
+}
+
+/// Get the value of the setting for an environment variable name.
+pub fn setting<'a>(settings: &'a [(String, String)], name: &str) -> Option<&'a str> {
+  settings
+    .iter()
+    .find(|(n, _)| n == name)
+    .map(|(_, value)| value.as_str())
+}
+
+/// Add a way to give the value of a command line argument in a configuration file.
+#[cfg(feature = "cli")]
+pub trait SettingArg<'a> {
+  /// Read the value of the argument from the environment variable `name` if it isn't given, or
+  /// from its setting in `settings` if the variable isn't set either.  Call it after any
+  /// `default_value()`, which would replace the setting.
+  fn setting(self, name: &'a str, settings: &'a [(String, String)]) -> Self;
+}
+
+#[cfg(feature = "cli")]
+impl<'a, 'b> SettingArg<'a> for clap::Arg<'a, 'b> {
+  fn setting(self, name: &'a str, settings: &'a [(String, String)]) -> Self {
+    match setting(settings, name) {
+      Some(value) => self.env(name).default_value(value),
+      None => self.env(name),
+    }
+  }
+}
+
+/// Parse a size in bytes with an optional `K`, `M` or `G` suffix for multiples of 1024, which
+/// can be followed by `B`, ignoring case.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+  let upper = s.trim().to_uppercase();
+  let digits = upper.trim_end_matches('B');
+  let (digits, multiplier) = match digits.char_indices().last() {
+    Some((i, 'K')) => (&digits[..i], 1 << 10),
+    Some((i, 'M')) => (&digits[..i], 1 << 20),
+    Some((i, 'G')) => (&digits[..i], 1 << 30),
+    _ => (digits, 1),
+  };
+
+  digits
+    .trim()
+    .parse::<u64>()
+    .ok()
+    .and_then(|n| n.checked_mul(multiplier))
+    .ok_or_else(|| format!("'{}' is not a size like 1000, 64K, 10M or 2G", s))
+}
+
+/// Find the configuration file in `dir` or the closest directory above it.
+pub fn find_config_file(dir: &Path) -> Option<PathBuf> {
+  dir
+    .ancestors()
+    .map(|dir| dir.join(CONFIG_FILE_NAME))
+    .find(|path| path.is_file())
+}
+
+/// Keys that apply to the whole run, which can't be set in `[[override]]` tables
+pub const RUN_KEYS: &[&str] = &[
+  "ascii",
+  "audit_log",
+  "exec",
+  "fail_fast",
+  "git_tracked",
+  "journal",
+  "max_size",
+  "no_ignore",
+  "print_changed",
+  "progress",
+  "recursive",
+  "sort",
+  "stats_format",
+  "stats_output",
+  "timings",
+];
+
+/// The settings read from a configuration file
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct Config {
+  /// Settings from the top of the file and the selected profile
+  pub settings: Vec<(String, String)>,
+  /// The `[[override]]` tables, in the order they appear
+  pub overrides: Vec<Override>,
+  /// Directory that the paths of the overrides are relative to
+  pub dir: PathBuf,
+}
+
+/// Settings from an `[[override]]` table that apply to some paths
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct Override {
+  /// Glob patterns of the paths that the settings apply to
+  pub paths: Vec<String>,
+  /// Settings that replace those of the whole run
+  pub settings: Vec<(String, String)>,
+}
+
+impl Override {
+  /// Check if a `/` separated path relative to the configuration file matches one of the
+  /// patterns.  Patterns without a `/` match the file name in any directory, and patterns ending
+  /// in `/` match everything below a directory, as in `.gitignore` files.
+  pub fn matches(&self, relative_path: &str) -> bool {
+    let path = relative_path.chars().collect::<Vec<_>>();
+    let name = match relative_path.rfind('/') {
+      Some(i) => &relative_path[i + 1..],
+      None => relative_path,
+    }
+    .chars()
+    .collect::<Vec<_>>();
+
+    self.paths.iter().any(|pattern| {
+      let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+      match pattern.strip_suffix('/') {
+        Some(dir) => matches_glob(&format!("{}/**", dir).chars().collect::<Vec<_>>(), &path),
+        None if pattern.contains('/') => matches_glob(&pattern.chars().collect::<Vec<_>>(), &path),
+        None => matches_glob(&pattern.chars().collect::<Vec<_>>(), &name),
+      }
+    })
+  }
+}
+
+impl Config {
+  /// Get the indexes of the overrides that apply to `path`, which is relative to the current
+  /// directory or absolute.  Paths outside [`dir`](Config::dir) match none of them.
+  pub fn overrides_for(&self, path: &Path) -> Vec<usize> {
+    if self.overrides.is_empty() {
+      return Vec::new();
+    }
+
+    let relative_path = match absolute_path(path).strip_prefix(absolute_path(&self.dir)) {
+      Ok(relative_path) => relative_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"),
+      Err(_) => return Vec::new(),
+    };
+
+    (0..self.overrides.len())
+      .filter(|&i| self.overrides[i].matches(&relative_path))
+      .collect()
+  }
+
+  /// Get the settings with those of the overrides at `indexes` layered over them in order.
+  pub fn settings_with(&self, indexes: &[usize]) -> Vec<(String, String)> {
+    let mut settings = self.settings.clone();
+
+    for &i in indexes {
+      for (name, value) in &self.overrides[i].settings {
+        settings.retain(|(n, _)| n != name);
+        settings.push((name.clone(), value.clone()));
+      }
+    }
+
+    settings
+  }
+}
+
+/// Read the settings in the text of a configuration file as pairs of environment variable names
+/// and values.  The settings of `profile` replace those at the top of the file.  Only the part of
+/// TOML that settings need is understood: `key = value` lines with boolean, integer and string
+/// values, `[profile.NAME]` tables and comments.  Keys must be in [`SETTING_KEYS`].  Use
+/// [`read_config()`] to get the `[[override]]` tables too.
+pub fn read_settings(
+  text: &str,
+  profile: Option<&str>,
+) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+  Ok(read_config(text, profile)?.settings)
+}
+
+/// Read the settings in the text of a configuration file as for [`read_settings()`], along with
+/// its `[[override]]` tables.  Each override has a `paths` array of glob patterns, which can also
+/// be called `patterns`, and settings for the files that match them apart from those in
+/// [`RUN_KEYS`].  Overrides apply whichever profile is selected.
+pub fn read_config(text: &str, profile: Option<&str>) -> Result<Config, Box<dyn Error>> {
+  /// The table that the lines being read belong to
+  enum Table<'a> {
+    Top,
+    Profile(&'a str),
+    Override,
+  }
+
+  let mut config = Config::default();
+  let mut table = Table::Top;
+  let mut found_profile = false;
+  let mut override_lines = Vec::new();
+
+  for (i, line) in text.lines().enumerate() {
+    let config_error = |message: &str| -> Box<dyn Error> {
+      error::Error::new(
+        ErrorCode::Config,
+        format!("{} line {}: {}", CONFIG_FILE_NAME, i + 1, message),
+      )
+      .into()
+    };
+    let line = line.trim();
+
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    if let Some(header) = line.strip_prefix('[') {
+      let header = strip_comment(header).trim_end();
+
+      if header
+        .strip_prefix('[')
+        .and_then(|header| header.strip_suffix("]]"))
+        .map(|name| name.trim())
+        == Some("override")
+      {
+        config.overrides.push(Override::default());
+        override_lines.push(i + 1);
+        table = Table::Override;
+        continue;
+      }
+
+      let name = header
+        .strip_suffix(']')
+        .and_then(|name| name.trim().strip_prefix("profile."))
+        .map(|name| name.trim())
+        .filter(|name| is_bare_key(name))
+        .ok_or_else(|| config_error("only [profile.NAME] and [[override]] tables are supported"))?;
+
+      found_profile |= Some(name) == profile;
+      table = Table::Profile(name);
+      continue;
+    }
+
+    let (key, value) = line
+      .split_once('=')
+      .ok_or_else(|| config_error("expected 'key = value'"))?;
+    let key = key.trim();
+
+    if !is_bare_key(key) {
+      return Err(config_error(&format!("'{}' is not a valid key", key)));
+    }
+
+    let setting_key = key.to_lowercase().replace('-', "_");
+
+    if let (Table::Override, "paths" | "patterns") = (&table, setting_key.as_str()) {
+      let paths =
+        parse_array(value.trim()).ok_or_else(|| config_error("expected an array of strings"))?;
+
+      config.overrides.last_mut().unwrap().paths.extend(paths);
+      continue;
+    }
+
+    if !SETTING_KEYS.contains(&setting_key.as_str()) {
+      return Err(config_error(&format!("unknown key '{}'", key)));
+    }
+
+    let value = parse_value(value.trim())
+      .ok_or_else(|| config_error("expected a boolean, integer or string value"))?;
+    let name = format!("WHITESPACE_{}", setting_key.to_uppercase());
+    let settings = match table {
+      Table::Top => &mut config.settings,
+      Table::Profile(name) if Some(name) == profile => &mut config.settings,
+      Table::Profile(_) => continue,
+      Table::Override if RUN_KEYS.contains(&setting_key.as_str()) => {
+        return Err(config_error(&format!(
+          "'{}' applies to the whole run, so it can't be set in [[override]] tables",
+          key
+        )))
+      }
+      Table::Override => &mut config.overrides.last_mut().unwrap().settings,
+    };
+
+    settings.retain(|(n, _)| *n != name);
+    settings.push((name, value));
+  }
+
+  if let Some(i) = config.overrides.iter().position(|o| o.paths.is_empty()) {
+    return Err(
+      error::Error::new(
+        ErrorCode::Config,
+        format!(
+          "{} line {}: [[override]] table without paths",
+          CONFIG_FILE_NAME, override_lines[i]
+        ),
+      )
+      .into(),
+    );
+  }
+
+  match profile {
+    Some(profile) if !found_profile => Err(
+      error::Error::new(
+        ErrorCode::Config,
+        format!("there is no profile '{}' in {}", profile, CONFIG_FILE_NAME),
+      )
+      .into(),
+    ),
+    _ => Ok(config),
+  }
+}
+
+/// Find the configuration file for `dir` and read the settings and overrides in it for
+/// `profile`.  Returns no settings if there is no configuration file, which is an error if a
+/// profile is asked for.
+pub fn load_config_file(dir: &Path, profile: Option<&str>) -> Result<Config, Box<dyn Error>> {
+  let path = match find_config_file(&absolute_path(dir)) {
+    Some(path) => path,
+    None if profile.is_some() => {
+      return Err(
+        error::Error::new(
+          ErrorCode::Config,
+          format!("there is no {} for the profile", CONFIG_FILE_NAME),
+        )
+        .into(),
+      )
+    }
+    None => return Ok(Config::default()),
+  };
+  let mut config = read_config(&std::fs::read_to_string(&path)?, profile)?;
+
+  config.dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+  Ok(config)
+}
+
+/// Make a path absolute by joining it to the current directory and removing `.` and `..`
+/// components, without following links
+fn absolute_path(path: &Path) -> PathBuf {
+  let mut absolute_path = std::env::current_dir().unwrap_or_default();
+
+  for component in path.components() {
+    match component {
+      Component::CurDir => (),
+      Component::ParentDir => {
+        absolute_path.pop();
+      }
+      component => absolute_path.push(component),
+    }
+  }
+
+  absolute_path
+}
+
+/// Check that a key is made of ASCII letters, digits, `_` and `-`
+fn is_bare_key(key: &str) -> bool {
+  !key.is_empty()
+    && key
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Parse the boolean value of the environment variable or setting `name` as for [`env_flag()`]
+fn parse_flag(s: &str, name: &str) -> Result<bool, Box<dyn Error>> {
+  match s.trim().to_lowercase().as_str() {
+    "1" | "true" | "yes" | "on" => Ok(true),
+    "" | "0" | "false" | "no" | "off" => Ok(false),
+    _ => Err(
+      error::Error::new(
+        ErrorCode::Config,
+        format!(
+          "'{}' for {} is not one of 1, true, yes, on, 0, false, no or off",
+          s, name
+        ),
+      )
+      .into(),
+    ),
+  }
+}
+
+/// Remove a comment from the end of an unquoted value
+fn strip_comment(s: &str) -> &str {
+  s.split('#').next().unwrap_or("")
+}
+
+/// Parse a quoted string at the start of `s`, returning it and the rest of `s`
+fn parse_string(s: &str) -> Option<(String, &str)> {
+  if let Some(rest) = s.strip_prefix('\'') {
+    let end = rest.find('\'')?;
+
+    return Some((rest[..end].to_string(), &rest[end + 1..]));
+  }
+
+  let rest = s.strip_prefix('"')?;
+  let mut value = String::new();
+  let mut chars = rest.char_indices();
+
+  while let Some((i, c)) = chars.next() {
+    match c {
+      '"' => return Some((value, &rest[i + 1..])),
+      '\\' => value.push(match chars.next()?.1 {
+        'n' => '\n',
+        't' => '\t',
+        '"' => '"',
+        '\\' => '\\',
+        _ => return None,
+      }),
+      c => value.push(c),
+    }
+  }
+
+  None
+}
+
+/// Parse an array of strings on one line, followed by an optional comment
+fn parse_array(s: &str) -> Option<Vec<String>> {
+  let mut rest = s.strip_prefix('[')?.trim_start();
+  let mut values = Vec::new();
+
+  loop {
+    if let Some(after) = rest.strip_prefix(']') {
+      return strip_comment(after).trim().is_empty().then_some(values);
+    }
+
+    let (value, after) = parse_string(rest)?;
+    let after = after.trim_start();
+
+    values.push(value);
+    rest = match after.strip_prefix(',') {
+      Some(after) => after.trim_start(),
+      None if after.starts_with(']') => after,
+      None => return None,
+    };
+  }
+}
+
+/// Parse a boolean, integer or string value, followed by an optional comment
+fn parse_value(s: &str) -> Option<String> {
+  if s.starts_with('\'') || s.starts_with('"') {
+    let (value, rest) = parse_string(s)?;
+
+    return strip_comment(rest).trim().is_empty().then_some(value);
+  }
+
+  let value = strip_comment(s).trim();
+
+  match value {
+    "true" | "false" => Some(value.to_string()),
+    _ => value
+      .replace('_', "")
+      .parse::<u64>()
+      .ok()
+      .map(|n| n.to_string()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_env_flag() {
+    std::env::set_var("WHITESPACE_TEST_FLAG_ON", "Yes");
+    std::env::set_var("WHITESPACE_TEST_FLAG_OFF", "0");
+    std::env::set_var("WHITESPACE_TEST_FLAG_BAD", "maybe");
+
+    assert_eq!(env_flag("WHITESPACE_TEST_FLAG_ON").unwrap(), Some(true));
+    assert_eq!(env_flag("WHITESPACE_TEST_FLAG_OFF").unwrap(), Some(false));
+    assert_eq!(env_flag("WHITESPACE_TEST_FLAG_UNSET").unwrap(), None);
+
+    let err = env_flag("WHITESPACE_TEST_FLAG_BAD").unwrap_err();
+
+    assert_eq!(error::error_code(&*err), ErrorCode::Config);
+    assert_eq!(
+      err.to_string(),
+      "'maybe' for WHITESPACE_TEST_FLAG_BAD is not one of 1, true, yes, on, 0, false, no or off"
+    );
+
+    let settings = vec![
+      ("WHITESPACE_TEST_FLAG_ON".to_string(), "false".to_string()),
+      ("WHITESPACE_TEST_FLAG_UNSET".to_string(), "true".to_string()),
+      ("WHITESPACE_TEST_FLAG_OTHER".to_string(), "2".to_string()),
+    ];
+
+    assert_eq!(
+      flag("WHITESPACE_TEST_FLAG_ON", &settings).unwrap(),
+      Some(true)
+    );
+    assert_eq!(
+      flag("WHITESPACE_TEST_FLAG_UNSET", &settings).unwrap(),
+      Some(true)
+    );
+    assert_eq!(flag("WHITESPACE_TEST_FLAG_NONE", &settings).unwrap(), None);
+    assert!(flag("WHITESPACE_TEST_FLAG_OTHER", &settings).is_err());
+    assert_eq!(
+      setting(&settings, "WHITESPACE_TEST_FLAG_UNSET"),
+      Some("true")
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "cli")]
+  fn test_setting_arg() {
+    let settings = vec![("WHITESPACE_TEST_SETTING".to_string(), "lf".to_string())];
+    let app = || {
+      clap::App::new("test").arg(
+        clap::Arg::with_name("eol")
+          .long("eol")
+          .takes_value(true)
+          .default_value("auto")
+          .setting("WHITESPACE_TEST_SETTING", &settings),
+      )
+    };
+
+    let matches = app().get_matches_from(vec!["test"]);
+
+    assert_eq!(matches.value_of("eol"), Some("lf"));
+
+    let matches = app().get_matches_from(vec!["test", "--eol", "crlf"]);
+
+    assert_eq!(matches.value_of("eol"), Some("crlf"));
+
+    std::env::set_var("WHITESPACE_TEST_SETTING", "cr");
+
+    let matches = app().get_matches_from(vec!["test"]);
+
+    assert_eq!(matches.value_of("eol"), Some("cr"));
+  }
+
+  #[test]
+  #[cfg(feature = "cli")]
+  fn test_flag_arg() {
+    let app = || {
+      clap::App::new("test")
+        .arg(
+          clap::Arg::with_name("test_flag_arg")
+            .long("test-flag-arg")
+            .overrides_with("no_test_flag_arg"),
+        )
+        .arg(
+          clap::Arg::with_name("no_test_flag_arg")
+            .long("no-test-flag-arg")
+            .overrides_with("test_flag_arg"),
+        )
+    };
+    let on = vec![("WHITESPACE_TEST_FLAG_ARG".to_string(), "on".to_string())];
+    let bad = vec![("WHITESPACE_TEST_FLAG_ARG".to_string(), "maybe".to_string())];
+
+    let matches = app().get_matches_from(vec!["test"]);
+
+    assert!(!flag_arg(&matches, "test_flag_arg", &[]).unwrap());
+    assert!(flag_arg(&matches, "test_flag_arg", &on).unwrap());
+    assert!(flag_arg(&matches, "test_flag_arg", &bad).is_err());
+
+    let matches = app().get_matches_from(vec!["test", "--no-test-flag-arg"]);
+
+    assert!(!flag_arg(&matches, "test_flag_arg", &on).unwrap());
+
+    let matches = app().get_matches_from(vec!["test", "--no-test-flag-arg", "--test-flag-arg"]);
+
+    assert!(flag_arg(&matches, "test_flag_arg", &[]).unwrap());
+
+    let matches = app().get_matches_from(vec!["test", "--test-flag-arg", "--no-test-flag-arg"]);
+
+    assert!(!flag_arg(&matches, "test_flag_arg", &on).unwrap());
+  }
+
+  #[test]
+  fn test_parse_size() {
+    assert_eq!(parse_size("1000"), Ok(1000));
+    assert_eq!(parse_size("64k"), Ok(64 * 1024));
+    assert_eq!(parse_size("10MB"), Ok(10 * 1024 * 1024));
+    assert_eq!(parse_size(" 2G "), Ok(2 * 1024 * 1024 * 1024));
+    assert!(parse_size("10X").is_err());
+    assert!(parse_size("M").is_err());
+    assert!(parse_size("-1").is_err());
+  }
+
+  #[test]
+  fn test_read_settings() {
+    let text = r#"
+# Report only
+stats_format = "json"  # for CI
+verify = true
+
+[profile.strict]
+eol = 'lf'
+verify = false
+max-size = 10_000
+
+[profile.legacy]
+eol = "crlf"
+"#;
+
+    assert_eq!(
+      read_settings(text, None).unwrap(),
+      vec![
+        ("WHITESPACE_STATS_FORMAT".to_string(), "json".to_string()),
+        ("WHITESPACE_VERIFY".to_string(), "true".to_string()),
+      ]
+    );
+    assert_eq!(
+      read_settings(text, Some("strict")).unwrap(),
+      vec![
+        ("WHITESPACE_STATS_FORMAT".to_string(), "json".to_string()),
+        ("WHITESPACE_EOL".to_string(), "lf".to_string()),
+        ("WHITESPACE_VERIFY".to_string(), "false".to_string()),
+        ("WHITESPACE_MAX_SIZE".to_string(), "10000".to_string()),
+      ]
+    );
+
+    let err = read_settings(text, Some("lenient")).unwrap_err();
+
+    assert_eq!(error::error_code(&*err), ErrorCode::Config);
+    assert_eq!(
+      err.to_string(),
+      "there is no profile 'lenient' in .whitespace.toml"
+    );
+  }
+
+  #[test]
+  fn test_read_settings_errors() {
+    for (text, message) in [
+      (
+        "[tool]\n",
+        ".whitespace.toml line 1: only [profile.NAME] and [[override]] tables are supported",
+      ),
+      (
+        "\nverify\n",
+        ".whitespace.toml line 2: expected 'key = value'",
+      ),
+      (
+        "a b = 1\n",
+        ".whitespace.toml line 1: 'a b' is not a valid key",
+      ),
+      (
+        "eol = lf\n",
+        ".whitespace.toml line 1: expected a boolean, integer or string value",
+      ),
+      (
+        "eol = \"lf\n",
+        ".whitespace.toml line 1: expected a boolean, integer or string value",
+      ),
+      (
+        "verify = true\nvrify = true\n",
+        ".whitespace.toml line 2: unknown key 'vrify'",
+      ),
+      (
+        "profile = \"strict\"\n",
+        ".whitespace.toml line 1: unknown key 'profile'",
+      ),
+      (
+        "paths = [\"*.rs\"]\n",
+        ".whitespace.toml line 1: unknown key 'paths'",
+      ),
+      (
+        "[[override]]\npaths = \"*.rs\"\n",
+        ".whitespace.toml line 2: expected an array of strings",
+      ),
+      (
+        "[[override]]\npaths = [\"*.rs\"]\nrecursive = true\n",
+        ".whitespace.toml line 3: 'recursive' applies to the whole run, so it can't be set in [[override]] tables",
+      ),
+      (
+        "[[override]]\npaths = [\"*.rs\"]\n\n[[override]]\neol = \"lf\"\n",
+        ".whitespace.toml line 4: [[override]] table without paths",
+      ),
+    ] {
+      assert_eq!(read_settings(text, None).unwrap_err().to_string(), message);
+    }
+  }
+
+  #[test]
+  fn test_read_config() {
+    let text = r#"
+eol = "lf"
+
+[profile.strict]
+verify = true
+
+[[override]]
+paths = ["vendor/", "*.min.js"]  # not ours
+eol = "auto"
+rules = ""
+
+[[ override ]]
+patterns = ['/docs/**/*.md']
+tab-size = 2
+"#;
+    let config = read_config(text, Some("strict")).unwrap();
+
+    assert_eq!(
+      config.settings,
+      vec![
+        ("WHITESPACE_EOL".to_string(), "lf".to_string()),
+        ("WHITESPACE_VERIFY".to_string(), "true".to_string()),
+      ]
+    );
+    assert_eq!(config.overrides.len(), 2);
+    assert_eq!(config.overrides[0].paths, vec!["vendor/", "*.min.js"]);
+    assert_eq!(
+      config.overrides[1].settings,
+      vec![("WHITESPACE_TAB_SIZE".to_string(), "2".to_string())]
+    );
+    assert_eq!(config.overrides_for(Path::new("vendor/a/b.c")), vec![0]);
+    assert_eq!(config.overrides_for(Path::new("./web/app.min.js")), vec![0]);
+    assert_eq!(
+      config.overrides_for(Path::new("docs/x/../guide/a.md")),
+      vec![1]
+    );
+    assert_eq!(
+      config.overrides_for(Path::new("src/docs/a.md")),
+      Vec::<usize>::new()
+    );
+    assert_eq!(
+      config.overrides_for(Path::new("../vendor/a.c")),
+      Vec::<usize>::new()
+    );
+    assert_eq!(
+      config.settings_with(&[0, 1]),
+      vec![
+        ("WHITESPACE_VERIFY".to_string(), "true".to_string()),
+        ("WHITESPACE_EOL".to_string(), "auto".to_string()),
+        ("WHITESPACE_RULES".to_string(), "".to_string()),
+        ("WHITESPACE_TAB_SIZE".to_string(), "2".to_string()),
+      ]
+    );
+    assert_eq!(config.settings_with(&[]), config.settings);
+  }
+
+  #[test]
+  fn test_parse_array() {
+    assert_eq!(parse_array("[]"), Some(vec![]));
+    assert_eq!(
+      parse_array(r#"[ "a", 'b\c' ,] # x"#),
+      Some(vec!["a".to_string(), "b\\c".to_string()])
+    );
+    assert_eq!(parse_array(r#"["a" "b"]"#), None);
+    assert_eq!(parse_array(r#"["a", 1]"#), None);
+    assert_eq!(parse_array(r#"["a""#), None);
+    assert_eq!(parse_array(r#"["a"] b"#), None);
+  }
+
+  #[test]
+  fn test_parse_value() {
+    assert_eq!(parse_value("true"), Some("true".to_string()));
+    assert_eq!(parse_value("4 # columns"), Some("4".to_string()));
+    assert_eq!(parse_value(r#""a#b\"c" # x"#), Some("a#b\"c".to_string()));
+    assert_eq!(parse_value("'a\\b'"), Some("a\\b".to_string()));
+    assert_eq!(parse_value("'a' b"), None);
+    assert_eq!(parse_value("-1"), None);
+  }
+
+  #[test]
+  fn test_load_config_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let sub_dir = temp_dir.path().join("a/b");
+
+    std::fs::create_dir_all(&sub_dir).unwrap();
+    assert_eq!(find_config_file(&sub_dir), None);
+    assert!(load_config_file(&sub_dir, Some("strict")).is_err());
+    assert_eq!(load_config_file(&sub_dir, None).unwrap(), Config::default());
+
+    std::fs::write(
+      temp_dir.path().join(CONFIG_FILE_NAME),
+      "tab_size = 2\nverify = true\n\n[profile.strict]\ntab_size = 8\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+      find_config_file(&sub_dir),
+      Some(temp_dir.path().join(CONFIG_FILE_NAME))
+    );
+    let config = load_config_file(&sub_dir, Some("strict")).unwrap();
+
+    assert_eq!(
+      config.settings,
+      vec![
+        ("WHITESPACE_VERIFY".to_string(), "true".to_string()),
+        ("WHITESPACE_TAB_SIZE".to_string(), "8".to_string()),
+      ]
+    );
+    assert_eq!(config.dir, temp_dir.path());
+
+    temp_dir.close().unwrap();
+  }
+}
@@ -0,0 +1,490 @@
+//! Decode UTF-8 input a character at a time, with a choice of what to do with invalid bytes.
+//!
+//! Wrap the bytes of a reader in a [`Decoder`] with a [`DecodePolicy`]:
+//!
+//! ```
+//! use std::io::Read;
+//! use whitespace_rs::decode::{DecodePolicy, Decoder};
+//!
+//! let input = b"caf\xe9\n";
+//! let decoder = Decoder::new(input.bytes(), DecodePolicy::Replace);
+//! let text = decoder.collect::<Result<String, _>>().unwrap();
+//!
+//! assert_eq!(text, "caf\u{fffd}\n");
+//! ```
+//!
+//! With [`DecodePolicy::Strict`] invalid UTF-8 is an I/O error wrapping a [`DecodeError`] that
+//! gives where it is.  Use [`decode_error()`] to get it from the error:
+//!
+//! ```
+//! use std::io::Read;
+//! use whitespace_rs::decode::{self, DecodePolicy, Decoder};
+//!
+//! let input = b"abc\n d\xffe\n";
+//! let err = Decoder::new(input.bytes(), DecodePolicy::Strict)
+//!   .collect::<Result<String, _>>()
+//!   .unwrap_err();
+//! let decode_error = decode::decode_error(&err).unwrap();
+//!
+//! assert_eq!((decode_error.line, decode_error.column), (2, 3));
+//! assert_eq!(decode_error.offset, 6);
+//! ```
+//!
+//! With [`DecodePolicy::PassThroughBytes`] each invalid byte is decoded as one of the private use
+//! characters U+10FF80 to U+10FFFF, and [`write_char()`] writes it as the original byte again.
+//! Valid characters in that range are passed through as their four bytes, so that they are
+//! written unchanged too.
+//! [`DecodePolicy::Bytes`] does the same for every byte that isn't ASCII, so that input in any
+//! ASCII compatible encoding, or with binary bytes in it, is copied exactly.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::iter::Peekable;
+use std::str::FromStr;
+
+/// First of the characters that invalid bytes 0x80 to 0xff are passed through as
+const PASS_THROUGH_BASE: u32 = 0x10ff00;
+
+// {grcov-excl-start}
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+/// What to do with bytes that aren't valid UTF-8
+pub enum DecodePolicy {
+  /// Fail with an error
+  #[default]
+  Strict,
+  /// Replace each invalid sequence with U+FFFD, the Unicode replacement character
+  Replace,
+  /// Copy the invalid bytes to the output unchanged
+  PassThroughBytes,
+  /// Don't decode UTF-8, and copy every byte that isn't ASCII to the output unchanged
+  Bytes,
+}
+// {grcov-excl-end}
+
+/// Parse `strict`, `replace`, `pass-through` or `bytes`, ignoring case.
+impl FromStr for DecodePolicy {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "strict" => Ok(DecodePolicy::Strict),
+      "replace" => Ok(DecodePolicy::Replace),
+      "pass-through" => Ok(DecodePolicy::PassThroughBytes),
+      "bytes" => Ok(DecodePolicy::Bytes),
+      _ => Err(format!(
+        "'{}' is not one of strict, replace, pass-through or bytes",
+        s
+      )),
+    }
+  }
+}
+
+impl DecodePolicy {
+  /// Names of the policies, as they are parsed
+  pub const NAMES: &'static [&'static str] = &["strict", "replace", "pass-through", "bytes"];
+
+  /// Get the number of bytes of input that `c` was decoded from.  Replacement characters count as
+  /// the three bytes they are written as.
+  pub fn byte_len(self, c: char) -> usize {
+    match (self, passed_through_byte(c)) {
+      (DecodePolicy::PassThroughBytes, Some(_)) | (DecodePolicy::Bytes, Some(_)) => 1,
+      _ => c.len_utf8(),
+    }
+  }
+}
+
+/// Get the byte that a character decoded with [`DecodePolicy::PassThroughBytes`] or
+/// [`DecodePolicy::Bytes`] stands for, if it stands for one.
+pub fn passed_through_byte(c: char) -> Option<u8> {
+  match c as u32 {
+    n if passed_through_byte_code(n) => Some((n - PASS_THROUGH_BASE) as u8),
+    _ => None,
+  }
+}
+
+/// Check if a code point is one of those that bytes are passed through as
+fn passed_through_byte_code(code: u32) -> bool {
+  (PASS_THROUGH_BASE + 0x80..=PASS_THROUGH_BASE + 0xff).contains(&code)
+}
+
+/// Write a character decoded with `policy`, as the original byte if it was passed through.
+pub fn write_char<W: Write + ?Sized>(
+  writer: &mut W,
+  c: char,
+  policy: DecodePolicy,
+) -> io::Result<()> {
+  match (policy, passed_through_byte(c)) {
+    (DecodePolicy::PassThroughBytes, Some(b)) | (DecodePolicy::Bytes, Some(b)) => {
+      writer.write_all(&[b])
+    }
+    _ => writer.write_all(c.encode_utf8(&mut [0u8; 4]).as_bytes()),
+  }
+}
+
+/// Where invalid UTF-8 was found
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct DecodeError {
+  /// Offset of the first invalid byte from the start of the input, in bytes
+  pub offset: u64,
+  /// Line number, starting from 1.  Carriage returns, line feeds and carriage return and line feed
+  /// pairs each end a line.
+  pub line: usize,
+  /// Column number in characters, starting from 1
+  pub column: usize,
+  /// The invalid bytes
+  pub bytes: Vec<u8>,
+}
+
+impl fmt::Display for DecodeError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "invalid UTF-8 sequence {} at line {}, column {} (byte offset {})",
+      self
+        .bytes
+        .iter()
+        .map(|b| format!("{:#04x}", b))
+        .collect::<Vec<_>>()
+        .join(" "),
+      self.line,
+      self.column,
+      self.offset
+    )
+  }
+}
+
+impl Error for DecodeError {}
+
+/// Get the [`DecodeError`] from an error, if it is one or is an I/O error that wraps one.
+pub fn decode_error<'a>(err: &'a (dyn Error + 'static)) -> Option<&'a DecodeError> {
+  match err.downcast_ref::<io::Error>() {
+    Some(err) => err.get_ref()?.downcast_ref::<DecodeError>(),
+    None => err.downcast_ref::<DecodeError>(),
+  }
+}
+
+/// An iterator that decodes the UTF-8 characters in an iterator of bytes, like that from
+/// [`Read::bytes()`](std::io::Read::bytes)
+pub struct Decoder<I: Iterator<Item = io::Result<u8>>> {
+  bytes: Peekable<I>,
+  policy: DecodePolicy,
+  /// Invalid bytes still to be passed through, last first
+  pending: Vec<u8>,
+  /// Number of bytes read
+  offset: u64,
+  /// Offset of the first byte of the character being decoded
+  start: u64,
+  /// Line and column of the next character
+  line: usize,
+  column: usize,
+  /// The last character was a carriage return, so a line feed doesn't start a new line
+  after_cr: bool,
+}
+
+impl<I: Iterator<Item = io::Result<u8>>> Decoder<I> {
+  /// Decode `bytes`, handling invalid bytes according to `policy`.
+  pub fn new(bytes: I, policy: DecodePolicy) -> Self {
+    Decoder {
+      bytes: bytes.peekable(),
+      policy,
+      pending: Vec::new(),
+      offset: 0,
+      start: 0,
+      line: 1,
+      column: 1,
+      after_cr: false,
+    }
+  }
+
+  /// Start tracking positions from `offset`, `line` and `column` instead of the start of the input,
+  /// for bytes that follow others decoded some other way.  `after_cr` is true if the last of those
+  /// was a carriage return.
+  pub(crate) fn starting_at(
+    mut self,
+    offset: u64,
+    line: usize,
+    column: usize,
+    after_cr: bool,
+  ) -> Self {
+    self.offset = offset;
+    self.start = offset;
+    self.line = line;
+    self.column = column;
+    self.after_cr = after_cr;
+    self
+  }
+
+  /// Take the next byte if it continues a sequence and is in `range`.
+  fn next_continuation(&mut self, range: std::ops::RangeInclusive<u8>) -> Option<u8> {
+    match self.bytes.peek() {
+      Some(Ok(b)) if range.contains(b) => {
+        let b = *b;
+
+        self.bytes.next();
+        self.offset += 1;
+        Some(b)
+      }
+      _ => None,
+    }
+  }
+
+  /// Handle the longest invalid sequence `invalid` according to the policy.
+  fn invalid(&mut self, invalid: &[u8]) -> io::Result<char> {
+    match self.policy {
+      DecodePolicy::Strict => Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        DecodeError {
+          offset: self.start,
+          line: self.line,
+          column: self.column,
+          bytes: invalid.to_vec(),
+        },
+      )),
+      DecodePolicy::Replace => Ok(char::REPLACEMENT_CHARACTER),
+      DecodePolicy::PassThroughBytes | DecodePolicy::Bytes => {
+        self.pending.extend(invalid[1..].iter().rev());
+        Ok(pass_through(invalid[0]))
+      }
+    }
+  }
+}
+
+/// Get the character that passes through an invalid byte.
+fn pass_through(b: u8) -> char {
+  char::from_u32(PASS_THROUGH_BASE + b as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+impl<I: Iterator<Item = io::Result<u8>>> Iterator for Decoder<I> {
+  type Item = io::Result<char>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let value = self.decode_next();
+
+    if let Some(Ok(c)) = value {
+      match c {
+        '\r' => {
+          self.line += 1;
+          self.column = 1;
+        }
+        '\n' if self.after_cr => self.column = 1,
+        '\n' => {
+          self.line += 1;
+          self.column = 1;
+        }
+        _ => self.column += 1,
+      }
+
+      self.after_cr = c == '\r';
+    }
+
+    value
+  }
+}
+
+impl<I: Iterator<Item = io::Result<u8>>> Decoder<I> {
+  /// Decode the next character, without tracking its position.
+  fn decode_next(&mut self) -> Option<io::Result<char>> {
+    if let Some(b) = self.pending.pop() {
+      return Some(Ok(pass_through(b)));
+    }
+
+    self.start = self.offset;
+
+    let first = match self.bytes.next()? {
+      Ok(b) => b,
+      Err(err) => return Some(Err(err)),
+    };
+
+    self.offset += 1;
+
+    // The ranges of the second byte that are valid after each first byte, which rule out overlong
+    // encodings, surrogates and characters past U+10FFFF
+    let (len, second_range) = match first {
+      0x00..=0x7f => return Some(Ok(first as char)),
+      _ if self.policy == DecodePolicy::Bytes => return Some(Ok(pass_through(first))),
+      0xc2..=0xdf => (2, 0x80..=0xbf),
+      0xe0 => (3, 0xa0..=0xbf),
+      0xed => (3, 0x80..=0x9f),
+      0xe1..=0xef => (3, 0x80..=0xbf),
+      0xf0 => (4, 0x90..=0xbf),
+      0xf4 => (4, 0x80..=0x8f),
+      0xf1..=0xf3 => (4, 0x80..=0xbf),
+      _ => return Some(self.invalid(&[first])),
+    };
+    let mut seq = [first, 0, 0, 0];
+
+    for i in 1..len {
+      let range = if i == 1 {
+        second_range.clone()
+      } else {
+        0x80..=0xbf
+      };
+
+      match self.next_continuation(range) {
+        Some(b) => seq[i] = b,
+        None => return Some(self.invalid(&seq[..i])),
+      }
+    }
+
+    let code = match len {
+      2 => (seq[0] as u32 & 0x1f) << 6 | (seq[1] as u32 & 0x3f),
+      3 => (seq[0] as u32 & 0x0f) << 12 | (seq[1] as u32 & 0x3f) << 6 | (seq[2] as u32 & 0x3f),
+      _ => {
+        (seq[0] as u32 & 0x07) << 18
+          | (seq[1] as u32 & 0x3f) << 12
+          | (seq[2] as u32 & 0x3f) << 6
+          | (seq[3] as u32 & 0x3f)
+      }
+    };
+
+    // A valid character in the range that bytes are passed through as would be written back as
+    // one byte, so pass through its four bytes instead
+    if self.policy == DecodePolicy::PassThroughBytes && passed_through_byte_code(code) {
+      self.pending.extend(seq[1..].iter().rev());
+
+      return Some(Ok(pass_through(first)));
+    }
+
+    Some(Ok(
+      char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER),
+    ))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Read;
+
+  fn decode(input: &[u8], policy: DecodePolicy) -> io::Result<String> {
+    Decoder::new(input.bytes(), policy).collect()
+  }
+
+  #[test]
+  fn test_decode_valid() {
+    let text = "a\u{e9}\u{20ac}\u{1f600}\u{10ffff}\n";
+
+    for policy in [DecodePolicy::Strict, DecodePolicy::Replace] {
+      assert_eq!(decode(text.as_bytes(), policy).unwrap(), text);
+    }
+
+    let text = "a\u{e9}\u{20ac}\u{1f600}\u{10ff7f}\n";
+
+    assert_eq!(
+      decode(text.as_bytes(), DecodePolicy::PassThroughBytes).unwrap(),
+      text
+    );
+  }
+
+  #[test]
+  fn test_decode_invalid() {
+    let error = decode(b"a\xff", DecodePolicy::Strict).unwrap_err();
+
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(
+      decode_error(&error),
+      Some(&DecodeError {
+        offset: 1,
+        line: 1,
+        column: 2,
+        bytes: vec![0xff],
+      })
+    );
+    assert_eq!(
+      error.to_string(),
+      "invalid UTF-8 sequence 0xff at line 1, column 2 (byte offset 1)"
+    );
+
+    let error = decode(b"\xc3\xa9\r\n\r\xe2\x82(", DecodePolicy::Strict).unwrap_err();
+
+    assert_eq!(
+      decode_error(&error),
+      Some(&DecodeError {
+        offset: 5,
+        line: 3,
+        column: 1,
+        bytes: vec![0xe2, 0x82],
+      })
+    );
+    assert!(decode(b"\xc3", DecodePolicy::Strict).is_err());
+    assert!(decode(b"\xed\xa0\x80", DecodePolicy::Strict).is_err());
+    assert!(decode(b"\xc0\xaf", DecodePolicy::Strict).is_err());
+    assert_eq!(
+      decode(b"a\xffb\xe2\x82c\xf0\x9f\x98", DecodePolicy::Replace).unwrap(),
+      "a\u{fffd}b\u{fffd}c\u{fffd}"
+    );
+    assert_eq!(
+      decode(b"\xe2\x82\xe2\x82\xac", DecodePolicy::Replace).unwrap(),
+      "\u{fffd}\u{20ac}"
+    );
+  }
+
+  #[test]
+  fn test_pass_through_bytes() {
+    let input = b"caf\xe9\n\xe2\x82\n";
+    let mut output = Vec::new();
+
+    for value in Decoder::new(input.bytes(), DecodePolicy::PassThroughBytes) {
+      let c = value.unwrap();
+
+      write_char(&mut output, c, DecodePolicy::PassThroughBytes).unwrap();
+    }
+
+    assert_eq!(output, input);
+
+    // Valid characters that bytes are passed through as are passed through as their bytes
+    let input = "a\u{10ff80}b\u{10ffff}".as_bytes();
+    let decoded = decode(input, DecodePolicy::PassThroughBytes).unwrap();
+    let mut output = Vec::new();
+
+    for c in decoded.chars() {
+      write_char(&mut output, c, DecodePolicy::PassThroughBytes).unwrap();
+    }
+
+    assert_eq!(output, input);
+    assert_eq!(
+      decoded
+        .chars()
+        .map(|c| DecodePolicy::PassThroughBytes.byte_len(c))
+        .sum::<usize>(),
+      input.len()
+    );
+    assert_eq!(passed_through_byte('\u{10ffe9}'), Some(0xe9));
+    assert_eq!(passed_through_byte('\u{10ff7f}'), None);
+    assert_eq!(passed_through_byte('a'), None);
+    assert_eq!(DecodePolicy::PassThroughBytes.byte_len('\u{10ffe9}'), 1);
+    assert_eq!(DecodePolicy::Strict.byte_len('\u{10ffe9}'), 4);
+  }
+
+  #[test]
+  fn test_bytes() {
+    let input = [&b"caf\xe9\t"[..], "\u{10ffff}".as_bytes(), b"\xff"].concat();
+    let decoder = Decoder::new(input.as_slice().bytes(), DecodePolicy::Bytes);
+    let mut output = Vec::new();
+    let mut num_bytes = 0;
+
+    for value in decoder {
+      let c = value.unwrap();
+
+      assert!(c.is_ascii() || passed_through_byte(c).is_some());
+      num_bytes += DecodePolicy::Bytes.byte_len(c);
+      write_char(&mut output, c, DecodePolicy::Bytes).unwrap();
+    }
+
+    assert_eq!(output, input);
+    assert_eq!(num_bytes, input.len());
+  }
+
+  #[test]
+  fn test_parse_decode_policy() {
+    assert_eq!("Strict".parse(), Ok(DecodePolicy::Strict));
+    assert_eq!("replace".parse(), Ok(DecodePolicy::Replace));
+    assert_eq!("pass-through".parse(), Ok(DecodePolicy::PassThroughBytes));
+    assert_eq!("BYTES".parse(), Ok(DecodePolicy::Bytes));
+    assert!("ignore".parse::<DecodePolicy>().is_err());
+  }
+}
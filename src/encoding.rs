@@ -0,0 +1,310 @@
+//! Read and write text files in UTF-16, Windows-1252 and ISO-8859-1 as well as UTF-8.
+//!
+//! The other modules work on UTF-8.  Use [`Encoding::detect()`] to find the encoding of a file
+//! from the start of its data, [`decode()`] to turn it into UTF-8 text and [`encode()`] to write
+//! the text back in the same or another encoding:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::encoding::{self, Encoding};
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let data = b"\xff\xfea\x00\r\x00\n\x00";
+//!   let encoding = Encoding::detect(data);
+//!
+//!   assert_eq!(encoding, Encoding::Utf16Le);
+//!   assert_eq!(encoding::decode(data, encoding)?, "a\r\n");
+//!   assert_eq!(encoding::encode("a\n", encoding)?, b"\xff\xfea\x00\n\x00");
+//!   Ok(())
+//! }
+//! ```
+
+use crate::error::{self, ErrorCode};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Byte order mark of UTF-16 little-endian files
+const UTF16_LE_BOM: &[u8] = b"\xff\xfe";
+/// Byte order mark of UTF-16 big-endian files
+const UTF16_BE_BOM: &[u8] = b"\xfe\xff";
+/// Characters of the Windows-1252 bytes 0x80 to 0x9f.  The five bytes that Windows-1252 doesn't
+/// define are the C1 control characters, as in ISO-8859-1.
+const WINDOWS_1252_C1: [char; 32] = [
+  '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+  '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}', '\u{90}',
+  '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}', '\u{2dc}',
+  '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+// {grcov-excl-start}
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+/// Text file encodings
+pub enum Encoding {
+  /// UTF-8, with or without a byte order mark
+  Utf8,
+  /// UTF-16 little-endian, with a byte order mark
+  Utf16Le,
+  /// UTF-16 big-endian, with a byte order mark
+  Utf16Be,
+  /// Windows-1252, the superset of ISO-8859-1 that Windows uses for Western European languages
+  Windows1252,
+  /// ISO-8859-1, also known as Latin-1
+  Latin1,
+}
+// {grcov-excl-end}
+
+/// Parse `utf-8`, `utf-16le`, `utf-16be`, `windows-1252` (or `cp1252`) or `iso-8859-1` (or
+/// `latin-1`), ignoring case and the `-`.
+impl FromStr for Encoding {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().replace('-', "").as_str() {
+      "utf8" => Ok(Encoding::Utf8),
+      "utf16le" => Ok(Encoding::Utf16Le),
+      "utf16be" => Ok(Encoding::Utf16Be),
+      "windows1252" | "cp1252" => Ok(Encoding::Windows1252),
+      "iso88591" | "latin1" => Ok(Encoding::Latin1),
+      _ => Err(format!(
+        "'{}' is not one of utf-8, utf-16le, utf-16be, windows-1252 or iso-8859-1",
+        s
+      )),
+    }
+  }
+}
+
+impl fmt::Display for Encoding {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(match self {
+      Encoding::Utf8 => "utf-8",
+      Encoding::Utf16Le => "utf-16le",
+      Encoding::Utf16Be => "utf-16be",
+      Encoding::Windows1252 => "windows-1252",
+      Encoding::Latin1 => "iso-8859-1",
+    })
+  }
+}
+
+impl Encoding {
+  /// Names of the encodings, as they are displayed
+  pub const NAMES: &'static [&'static str] = &[
+    "utf-8",
+    "utf-16le",
+    "utf-16be",
+    "windows-1252",
+    "iso-8859-1",
+  ];
+
+  /// Detect the encoding of a file from the start of its data.
+  ///
+  /// Files with a UTF-16 byte order mark are UTF-16.  Files that start with bytes that aren't
+  /// valid UTF-8 are ISO-8859-1 if they have a byte that Windows-1252 doesn't define, and
+  /// Windows-1252 otherwise.  All other files are UTF-8.
+  pub fn detect(head: &[u8]) -> Encoding {
+    if head.starts_with(UTF16_LE_BOM) {
+      Encoding::Utf16Le
+    } else if head.starts_with(UTF16_BE_BOM) {
+      Encoding::Utf16Be
+    } else if matches!(std::str::from_utf8(head), Err(err) if err.error_len().is_some()) {
+      if head
+        .iter()
+        .any(|b| matches!(b, 0x81 | 0x8d | 0x8f | 0x90 | 0x9d))
+      {
+        Encoding::Latin1
+      } else {
+        Encoding::Windows1252
+      }
+    } else {
+      Encoding::Utf8
+    }
+  }
+}
+
+/// Decode the data of a file into UTF-8 text, without the UTF-16 byte order mark.
+pub fn decode(data: &[u8], encoding: Encoding) -> Result<String, Box<dyn Error>> {
+  let (data, from_bytes): (_, fn([u8; 2]) -> u16) = match encoding {
+    Encoding::Utf8 => return Ok(String::from_utf8(data.to_vec())?),
+    Encoding::Utf16Le => (
+      data.strip_prefix(UTF16_LE_BOM).unwrap_or(data),
+      u16::from_le_bytes,
+    ),
+    Encoding::Utf16Be => (
+      data.strip_prefix(UTF16_BE_BOM).unwrap_or(data),
+      u16::from_be_bytes,
+    ),
+    Encoding::Windows1252 => {
+      return Ok(
+        data
+          .iter()
+          .map(|&b| match b {
+            0x80..=0x9f => WINDOWS_1252_C1[b as usize - 0x80],
+            _ => b as char,
+          })
+          .collect(),
+      )
+    }
+    Encoding::Latin1 => return Ok(data.iter().map(|&b| b as char).collect()),
+  };
+
+  if data.len() % 2 != 0 {
+    return Err(
+      error::Error::new(
+        ErrorCode::Encoding,
+        format!("{} input has an odd number of bytes", encoding),
+      )
+      .into(),
+    );
+  }
+
+  char::decode_utf16(
+    data
+      .chunks_exact(2)
+      .map(|pair| from_bytes([pair[0], pair[1]])),
+  )
+  .collect::<Result<String, _>>()
+  .map_err(|err| {
+    error::Error::new(
+      ErrorCode::Encoding,
+      format!(
+        "{} input has an unpaired surrogate {:#06x}",
+        encoding,
+        err.unpaired_surrogate()
+      ),
+    )
+    .into()
+  })
+}
+
+/// Encode UTF-8 text, adding a byte order mark for UTF-16.
+///
+/// Fails if the text has a character that Windows-1252 or ISO-8859-1 can't represent.
+pub fn encode(text: &str, encoding: Encoding) -> Result<Vec<u8>, Box<dyn Error>> {
+  let (bom, to_bytes): (_, fn(u16) -> [u8; 2]) = match encoding {
+    Encoding::Utf8 => return Ok(text.as_bytes().to_vec()),
+    Encoding::Utf16Le => (UTF16_LE_BOM, u16::to_le_bytes),
+    Encoding::Utf16Be => (UTF16_BE_BOM, u16::to_be_bytes),
+    Encoding::Windows1252 | Encoding::Latin1 => {
+      return text
+        .chars()
+        .map(|c| encode_byte(c, encoding))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| {
+          let c = text
+            .chars()
+            .find(|&c| encode_byte(c, encoding).is_none())
+            .unwrap_or_default();
+
+          error::Error::new(
+            ErrorCode::Encoding,
+            format!("U+{:04X} can't be written in {}", c as u32, encoding),
+          )
+          .into()
+        })
+    }
+  };
+  let mut data = bom.to_vec();
+
+  for unit in text.encode_utf16() {
+    data.extend_from_slice(&to_bytes(unit));
+  }
+
+  Ok(data)
+}
+
+/// Encode a character as a single byte of Windows-1252 or ISO-8859-1, if it has one.
+fn encode_byte(c: char, encoding: Encoding) -> Option<u8> {
+  match (encoding, c as u32) {
+    (Encoding::Windows1252, 0x80..=0x9f) | (Encoding::Windows1252, 0x100..) => WINDOWS_1252_C1
+      .iter()
+      .position(|&other| other == c)
+      .map(|i| 0x80 + i as u8),
+    (_, 0..=0xff) => Some(c as u8),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_encoding() {
+    assert_eq!("UTF-8".parse(), Ok(Encoding::Utf8));
+    assert_eq!("utf16le".parse(), Ok(Encoding::Utf16Le));
+    assert_eq!("utf-16be".parse(), Ok(Encoding::Utf16Be));
+    assert_eq!("CP1252".parse(), Ok(Encoding::Windows1252));
+    assert_eq!("latin-1".parse(), Ok(Encoding::Latin1));
+    assert!("koi8-r".parse::<Encoding>().is_err());
+    assert_eq!(Encoding::Utf16Le.to_string(), "utf-16le");
+  }
+
+  #[test]
+  fn test_detect() {
+    assert_eq!(Encoding::detect(b"\xff\xfea\x00"), Encoding::Utf16Le);
+    assert_eq!(Encoding::detect(b"\xfe\xff\x00a"), Encoding::Utf16Be);
+    assert_eq!(Encoding::detect(b"\xef\xbb\xbfa"), Encoding::Utf8);
+    assert_eq!(Encoding::detect(b""), Encoding::Utf8);
+    assert_eq!(Encoding::detect(b"a\xc3"), Encoding::Utf8);
+    assert_eq!(Encoding::detect(b"caf\xe9 \x80"), Encoding::Windows1252);
+    assert_eq!(Encoding::detect(b"caf\xe9 \x81"), Encoding::Latin1);
+  }
+
+  #[test]
+  fn test_decode() {
+    assert_eq!(
+      decode(b"\xfe\xff\x00a\xd8\x3d\xde\x00\x00\n", Encoding::Utf16Be).unwrap(),
+      "a\u{1f600}\n"
+    );
+    assert_eq!(decode(b"a\n", Encoding::Utf8).unwrap(), "a\n");
+    assert_eq!(
+      error::error_code(&*decode(b"\xff\xfea", Encoding::Utf16Le).unwrap_err()),
+      ErrorCode::Encoding
+    );
+    assert_eq!(
+      error::error_code(&*decode(b"\xff\xfe\x00\xd8", Encoding::Utf16Le).unwrap_err()),
+      ErrorCode::Encoding
+    );
+    assert!(decode(b"\xff", Encoding::Utf8).is_err());
+    assert_eq!(
+      decode(b"\x80\x81\xe9", Encoding::Windows1252).unwrap(),
+      "\u{20ac}\u{81}\u{e9}"
+    );
+    assert_eq!(
+      decode(b"\x80\x81\xe9", Encoding::Latin1).unwrap(),
+      "\u{80}\u{81}\u{e9}"
+    );
+  }
+
+  #[test]
+  fn test_encode() {
+    let text = "a\u{1f600}\r\n";
+
+    assert_eq!(encode(text, Encoding::Utf8).unwrap(), text.as_bytes());
+
+    for encoding in [Encoding::Utf16Le, Encoding::Utf16Be] {
+      let data = encode(text, encoding).unwrap();
+
+      assert_eq!(Encoding::detect(&data), encoding);
+      assert_eq!(decode(&data, encoding).unwrap(), text);
+    }
+
+    let data = (0..=255).collect::<Vec<u8>>();
+
+    for encoding in [Encoding::Windows1252, Encoding::Latin1] {
+      assert_eq!(
+        encode(&decode(&data, encoding).unwrap(), encoding).unwrap(),
+        data
+      );
+    }
+
+    assert_eq!(encode("\u{20ac}", Encoding::Windows1252).unwrap(), b"\x80");
+    assert_eq!(
+      error::error_code(&*encode("\u{20ac}", Encoding::Latin1).unwrap_err()),
+      ErrorCode::Encoding
+    );
+    assert!(encode("\u{80}", Encoding::Windows1252).is_err());
+  }
+}
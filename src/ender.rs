@@ -33,13 +33,36 @@
 //!   Ok(())
 //! }
 //! ```
+//!
+//! Use [`needs_new_eols()`] to check if a file would be changed without writing anything, and
+//! [`normalize_str()`] to normalize the line endings of text that is already in memory.
+//!
+//! Use [`EolOptions`] with the `_with_options` functions to choose what happens to bytes that
+//! aren't valid UTF-8, and to treat the Unicode line separators NEL, LS and PS as line endings.
+//!
+//! With [`EolOptions::directives`] the line endings of the lines after a `ws:disable` comment, up
+//! to and including the next `ws:enable` comment, are left unchanged.
+//!
+//! Each function has a `_generic` version that takes concrete reader and writer types instead of
+//! trait objects, so that its decoding loop can be inlined for them.
 
+use crate::decode::{self, passed_through_byte, DecodePolicy, Decoder};
+use crate::error::{self, ErrorCode};
+use crate::lines::Directives;
 use std::error::Error;
-use std::io::{Read, Write};
-use utf8_decode::UnsafeDecoder;
+use std::fmt;
+use std::io::{self, BufReader, Read, Write};
+use std::iter::{Peekable, Sum};
+use std::ops::{Add, AddAssign};
+use std::str::{self, FromStr};
+
+/// Number of bytes read at a time when scanning valid UTF-8 for line endings
+const SCAN_CHUNK_SIZE: usize = 64 * 1024;
 
 // {grcov-excl-start}
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 /// Types of line endings.
 pub enum EndOfLine {
   /// Carriage return.
@@ -51,8 +74,92 @@ pub enum EndOfLine {
 }
 // {grcov-excl-end}
 
-/// File line information.
-#[derive(Debug, PartialEq)]
+/// Parse `cr`, `lf` or `crlf`, ignoring case.
+impl FromStr for EndOfLine {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "cr" => Ok(EndOfLine::Cr),
+      "lf" => Ok(EndOfLine::Lf),
+      "crlf" => Ok(EndOfLine::CrLf),
+      _ => Err(format!("'{}' is not one of cr, lf or crlf", s)),
+    }
+  }
+}
+
+impl EndOfLine {
+  /// Get the characters of the line ending
+  pub fn as_str(self) -> &'static str {
+    match self {
+      EndOfLine::Cr => "\r",
+      EndOfLine::Lf => "\n",
+      EndOfLine::CrLf => "\r\n",
+    }
+  }
+
+  /// Get the line ending of the platform, CRLF on Windows and LF elsewhere
+  pub fn native() -> Self {
+    if cfg!(windows) {
+      EndOfLine::CrLf
+    } else {
+      EndOfLine::Lf
+    }
+  }
+}
+
+impl fmt::Display for EndOfLine {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(match self {
+      EndOfLine::Cr => "cr",
+      EndOfLine::Lf => "lf",
+      EndOfLine::CrLf => "crlf",
+    })
+  }
+}
+
+// {grcov-excl-start}
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+/// How [`EolInfo::get_common_eol_with()`] chooses between line endings that are equally common
+pub enum TieBreak {
+  /// Choose LF
+  #[default]
+  Lf,
+  /// Choose CRLF
+  CrLf,
+  /// Choose the line ending of the platform, see [`EndOfLine::native()`]
+  Native,
+  /// Fail with an error
+  Error,
+}
+// {grcov-excl-end}
+
+/// Parse `lf`, `crlf`, `native` or `error`, ignoring case.
+impl FromStr for TieBreak {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "lf" => Ok(TieBreak::Lf),
+      "crlf" => Ok(TieBreak::CrLf),
+      "native" => Ok(TieBreak::Native),
+      "error" => Ok(TieBreak::Error),
+      _ => Err(format!("'{}' is not one of lf, crlf, native or error", s)),
+    }
+  }
+}
+
+impl TieBreak {
+  /// Names of the tie-breaks, as they are parsed
+  pub const NAMES: &'static [&'static str] = &["lf", "crlf", "native", "error"];
+}
+
+/// File line information.  The default has no lines, like the sum of no files.
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
 pub struct EolInfo {
   /// Number of lines that end in carriage return
   pub cr: usize,
@@ -60,15 +167,56 @@ pub struct EolInfo {
   pub lf: usize,
   /// Number of lines that end in carriage return/line feed
   pub crlf: usize,
+  /// Number of lines that end in U+0085 NEXT LINE, only counted with
+  /// [`EolOptions::unicode_separators`]
+  pub nel: usize,
+  /// Number of lines that end in U+2028 LINE SEPARATOR, only counted with
+  /// [`EolOptions::unicode_separators`]
+  pub ls: usize,
+  /// Number of lines that end in U+2029 PARAGRAPH SEPARATOR, only counted with
+  /// [`EolOptions::unicode_separators`]
+  pub ps: usize,
   /// Total number of lines in the file (includes lines with no ending)
   pub num_lines: usize,
+  /// Number of empty lines at the end of the file, not counting the empty last line after the
+  /// final line ending
+  pub trailing_blank_lines: usize,
+  /// Number of empty lines, not counting the empty last line after the final line ending
+  pub blank_lines: usize,
+  /// Number of lines that only contain whitespace
+  pub whitespace_lines: usize,
+  /// 1 if the file isn't empty and its last line has no ending, or the number of such files when
+  /// the information for several files is added together
+  pub missing_final_newline: usize,
+  /// Total number of characters in the file
+  pub num_chars: usize,
+  /// Total number of whitespace separated words in the file
+  pub num_words: usize,
+  /// Total number of bytes in the file
+  pub num_bytes: usize,
+  /// Numbers of the lines, starting from 1, that end in carriage return.  Only recorded by the
+  /// `read_eol_info` functions with [`EolOptions::line_numbers`].
+  pub cr_lines: Vec<usize>,
+  /// Numbers of the lines, starting from 1, that end in line feed.  Only recorded by the
+  /// `read_eol_info` functions with [`EolOptions::line_numbers`].
+  pub lf_lines: Vec<usize>,
+  /// Numbers of the lines, starting from 1, that end in carriage return/line feed.  Only recorded
+  /// by the `read_eol_info` functions with [`EolOptions::line_numbers`].
+  pub crlf_lines: Vec<usize>,
 }
 
-impl Eq for EolInfo {}
-
 impl EolInfo {
-  /// Get the most common end-of-line based on the info.
-  pub fn get_common_eol(self: &Self) -> EndOfLine {
+  /// Create the information for an empty file, which has one line with no ending.
+  pub fn new() -> Self {
+    EolInfo {
+      num_lines: 1,
+      ..Default::default()
+    }
+  }
+
+  /// Get the most common end-of-line based on the info, preferring LF and then CRLF when they
+  /// are equally common.
+  pub fn get_common_eol(&self) -> EndOfLine {
     let mut n = self.lf;
     let mut eol = EndOfLine::Lf;
 
@@ -84,86 +232,746 @@ impl EolInfo {
     eol
   }
 
-  pub fn num_endings(self: &Self) -> usize {
-    (self.cr > 0) as usize + (self.lf > 0) as usize + (self.crlf > 0) as usize
+  /// Get the most common end-of-line based on the info, using `tie_break` to choose between the
+  /// line endings that are equally common.  If the line ending it chooses isn't one of them, LF is
+  /// preferred and then CRLF, as by [`get_common_eol()`](EolInfo::get_common_eol).
+  ///
+  /// A file with no line endings isn't a tie, and gets the line ending that `tie_break` chooses,
+  /// or LF for [`TieBreak::Error`].
+  pub fn get_common_eol_with(&self, tie_break: TieBreak) -> Result<EndOfLine, Box<dyn Error>> {
+    let counts = [
+      (EndOfLine::Lf, self.lf),
+      (EndOfLine::CrLf, self.crlf),
+      (EndOfLine::Cr, self.cr),
+    ];
+    let max_count = counts.iter().map(|(_, n)| *n).max().unwrap_or_default();
+    let tied: Vec<EndOfLine> = counts
+      .iter()
+      .filter(|(_, n)| *n == max_count)
+      .map(|(eol, _)| *eol)
+      .collect();
+    let preferred = match tie_break {
+      TieBreak::Lf | TieBreak::Error => EndOfLine::Lf,
+      TieBreak::CrLf => EndOfLine::CrLf,
+      TieBreak::Native => EndOfLine::native(),
+    };
+
+    if tie_break == TieBreak::Error && max_count > 0 && tied.len() > 1 {
+      let names: Vec<String> = tied.iter().map(EndOfLine::to_string).collect();
+
+      return Err(
+        error::Error::new(
+          ErrorCode::Ambiguous,
+          format!(
+            "{} line endings are equally common, {} of each",
+            names.join(" and "),
+            max_count
+          ),
+        )
+        .into(),
+      );
+    }
+
+    Ok(if tied.contains(&preferred) {
+      preferred
+    } else {
+      tied[0]
+    })
+  }
+
+  pub fn num_endings(&self) -> usize {
+    [self.cr, self.lf, self.crlf, self.nel, self.ls, self.ps]
+      .iter()
+      .filter(|n| **n > 0)
+      .count()
+  }
+
+  /// Check if the file ends with a line ending, or is empty.
+  pub fn has_final_newline(&self) -> bool {
+    self.missing_final_newline == 0
+  }
+
+  /// Get the number of line endings that are not `eol`.
+  pub fn num_other_endings(&self, eol: EndOfLine) -> usize {
+    self.num_separators()
+      + match eol {
+        EndOfLine::Cr => self.lf + self.crlf,
+        EndOfLine::Lf => self.cr + self.crlf,
+        EndOfLine::CrLf => self.cr + self.lf,
+      }
+  }
+
+  /// Get the number of line endings that are Unicode line separators
+  pub fn num_separators(&self) -> usize {
+    self.nel + self.ls + self.ps
+  }
+
+  /// Count a line of `line_len` characters, not including its ending, as blank or whitespace.
+  pub(crate) fn count_line(&mut self, line_len: usize, is_whitespace_line: bool) {
+    if line_len == 0 {
+      self.blank_lines += 1;
+    } else if is_whitespace_line {
+      self.whitespace_lines += 1;
+    }
+  }
+
+  /// Get the numbers of the lines that end in `eol`, if they were recorded.
+  pub fn lines_ending_in(&self, eol: EndOfLine) -> &[usize] {
+    match eol {
+      EndOfLine::Cr => &self.cr_lines,
+      EndOfLine::Lf => &self.lf_lines,
+      EndOfLine::CrLf => &self.crlf_lines,
+    }
+  }
+
+  /// Combine with the information for the chunk of the file that immediately follows this one.
+  ///
+  /// Each chunk must be split immediately after a line feed so that no line ending or word is
+  /// split between chunks.  The empty last line of this chunk is the first line of the next
+  /// chunk, so it is only counted once, and the line numbers of the next chunk are shifted to
+  /// follow on from it.
+  pub fn append_chunk(&mut self, other: &EolInfo) {
+    let trailing_blank_lines =
+      if other.num_chars == other.cr + other.lf + 2 * other.crlf + other.num_separators() {
+        self.trailing_blank_lines + other.trailing_blank_lines
+      } else {
+        other.trailing_blank_lines
+      };
+    let missing_final_newline = if other.num_chars == 0 {
+      self.missing_final_newline
+    } else {
+      other.missing_final_newline
+    };
+
+    let line_offset = self.num_lines.saturating_sub(1);
+    let lens = [
+      self.cr_lines.len(),
+      self.lf_lines.len(),
+      self.crlf_lines.len(),
+    ];
+
+    *self += other;
+
+    for (lines, len) in [&mut self.cr_lines, &mut self.lf_lines, &mut self.crlf_lines]
+      .iter_mut()
+      .zip(lens)
+    {
+      lines[len..].iter_mut().for_each(|n| *n += line_offset);
+    }
+
+    self.num_lines = self.num_lines.saturating_sub(1);
+    self.trailing_blank_lines = trailing_blank_lines;
+    self.missing_final_newline = missing_final_newline;
+  }
+}
+
+/// Combine the information for separate files.  Every file has at least one line, so the
+/// number of lines is the sum of the lines in each file.  The line numbers of each file are kept
+/// as they are, one file after another.
+impl AddAssign<&EolInfo> for EolInfo {
+  fn add_assign(&mut self, other: &EolInfo) {
+    self.cr += other.cr;
+    self.lf += other.lf;
+    self.crlf += other.crlf;
+    self.nel += other.nel;
+    self.ls += other.ls;
+    self.ps += other.ps;
+    self.num_lines += other.num_lines;
+    self.trailing_blank_lines += other.trailing_blank_lines;
+    self.blank_lines += other.blank_lines;
+    self.whitespace_lines += other.whitespace_lines;
+    self.missing_final_newline += other.missing_final_newline;
+    self.num_chars += other.num_chars;
+    self.num_words += other.num_words;
+    self.num_bytes += other.num_bytes;
+    self.cr_lines.extend(&other.cr_lines);
+    self.lf_lines.extend(&other.lf_lines);
+    self.crlf_lines.extend(&other.crlf_lines);
+  }
+}
+
+impl AddAssign for EolInfo {
+  fn add_assign(&mut self, other: Self) {
+    *self += &other;
+  }
+}
+
+impl Add for EolInfo {
+  type Output = Self;
+
+  fn add(mut self, other: Self) -> Self {
+    self += other;
+    self
+  }
+}
+
+/// Combine the information for a set of files.  The sum of no files has no lines.
+impl Sum for EolInfo {
+  fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+    iter.fold(EolInfo::default(), Add::add)
   }
 }
 
 /// Read end-of-line information for a file.
 pub fn read_eol_info(reader: &mut dyn Read) -> Result<EolInfo, Box<dyn Error>> {
-  let mut eol_info = EolInfo {
-    cr: 0,
-    lf: 0,
-    crlf: 0,
-    num_lines: 1,
+  read_eol_info_generic(reader, &EolOptions::default())
+}
+
+/// Read end-of-line information for a file, decoding it as `options` says.
+pub fn read_eol_info_with_options(
+  reader: &mut dyn Read,
+  options: &EolOptions,
+) -> Result<EolInfo, Box<dyn Error>> {
+  read_eol_info_generic(reader, options)
+}
+
+/// Generic version of [`read_eol_info_with_options()`] that can be inlined for a concrete reader
+/// type.
+pub fn read_eol_info_generic<R: Read + ?Sized>(
+  reader: &mut R,
+  options: &EolOptions,
+) -> Result<EolInfo, Box<dyn Error>> {
+  let policy = options.decode_policy;
+  let mut counter = EolCounter {
+    unicode_separators: options.unicode_separators,
+    line_numbers: options.line_numbers,
+    directives: Directives::new(options.directives),
+    ..Default::default()
   };
-  let mut decoder = UnsafeDecoder::new(reader.bytes()).peekable();
+
+  if policy == DecodePolicy::Bytes {
+    for value in Decoder::new(BufReader::new(reader).bytes(), policy) {
+      let c = value?;
+
+      counter.push(c, policy.byte_len(c));
+    }
+
+    return Ok(counter.finish());
+  }
+
+  // Line endings can't be part of a longer UTF-8 sequence, so valid UTF-8 is searched for them a
+  // chunk at a time.  Anything invalid, and everything after it, goes through a decoder.
+  let mut buf = vec![0; SCAN_CHUNK_SIZE];
+  let mut num_carried = 0;
+  let mut offset = 0;
 
   loop {
-    let c;
-    match decoder.next() {
-      Some(value) => c = value?,
-      None => break,
+    let num_read = match reader.read(&mut buf[num_carried..]) {
+      Ok(n) => n,
+      Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+      Err(err) => return Err(err.into()),
     };
-    if c == '\r' {
-      if matches!(decoder.peek(), Some(Ok(c)) if *c == '\n') {
-        eol_info.crlf += 1;
-        decoder.next();
-      } else {
+    let len = num_carried + num_read;
+    let (text, keep_scanning) = match str::from_utf8(&buf[..len]) {
+      Ok(text) => (text, true),
+      Err(err) => (
+        str::from_utf8(&buf[..err.valid_up_to()]).unwrap_or_default(),
+        // A character split by the end of the chunk is carried over to the next one
+        err.error_len().is_none() && num_read > 0,
+      ),
+    };
+    // The decoder passes through the bytes of characters that bytes are passed through as
+    let (text, keep_scanning) = match policy {
+      DecodePolicy::PassThroughBytes => match text.find(|c| passed_through_byte(c).is_some()) {
+        Some(i) => (&text[..i], false),
+        None => (text, keep_scanning),
+      },
+      _ => (text, keep_scanning),
+    };
+    let valid_len = text.len();
+
+    counter.push_str(text, policy);
+    offset += valid_len as u64;
+
+    if !keep_scanning {
+      let bytes = buf[valid_len..len]
+        .iter()
+        .map(|b| Ok(*b))
+        .chain(BufReader::new(reader).bytes());
+      let decoder = Decoder::new(bytes, policy).starting_at(
+        offset,
+        counter.eol_info.num_lines,
+        counter.line_len + 1,
+        counter.after_cr,
+      );
+
+      for value in decoder {
+        let c = value?;
+
+        counter.push(c, policy.byte_len(c));
+      }
+
+      break;
+    } else if num_read == 0 {
+      break;
+    }
+
+    buf.copy_within(valid_len..len, 0);
+    num_carried = len - valid_len;
+  }
+
+  Ok(counter.finish())
+}
+
+/// End-of-line information for a file built up a character at a time
+#[derive(Debug)]
+struct EolCounter {
+  eol_info: EolInfo,
+  /// The last character wasn't whitespace
+  in_word: bool,
+  /// Number of characters in the current line so far, not including its ending
+  line_len: usize,
+  /// The current line has nothing but whitespace so far
+  is_whitespace_line: bool,
+  /// The last character was a carriage return
+  after_cr: bool,
+  /// The last carriage return ended a line disabled with a directive
+  disabled_cr: bool,
+  /// Tracks the lines disabled with a directive, whose line endings aren't counted
+  directives: Directives,
+  /// Count Unicode line separators as line endings
+  unicode_separators: bool,
+  /// Record the numbers of the lines with each line ending
+  line_numbers: bool,
+}
+
+impl Default for EolCounter {
+  fn default() -> Self {
+    EolCounter {
+      eol_info: EolInfo::new(),
+      in_word: false,
+      line_len: 0,
+      is_whitespace_line: true,
+      after_cr: false,
+      disabled_cr: false,
+      directives: Directives::default(),
+      unicode_separators: false,
+      line_numbers: false,
+    }
+  }
+}
+
+impl EolCounter {
+  /// Count a character decoded from `byte_len` bytes.
+  fn push(&mut self, c: char, byte_len: usize) {
+    let eol_info = &mut self.eol_info;
+    let after_cr = self.after_cr;
+
+    eol_info.num_chars += 1;
+    eol_info.num_bytes += byte_len;
+    self.after_cr = c == '\r';
+
+    if c.is_whitespace() {
+      self.in_word = false;
+    } else {
+      self.is_whitespace_line = false;
+
+      if !self.in_word {
+        eol_info.num_words += 1;
+        self.in_word = true;
+      }
+    }
+
+    let is_disabled = self.directives.is_disabled();
+
+    match c {
+      '\n' if after_cr => {
+        if !self.disabled_cr {
+          eol_info.cr -= 1;
+          eol_info.crlf += 1;
+
+          if self.line_numbers {
+            eol_info.cr_lines.pop();
+            eol_info.crlf_lines.push(eol_info.num_lines - 1);
+          }
+        }
+
+        return;
+      }
+      '\r' | '\n' if is_disabled => (),
+      '\u{85}' | '\u{2028}' | '\u{2029}' if is_disabled && self.unicode_separators => (),
+      '\r' => {
         eol_info.cr += 1;
+
+        if self.line_numbers {
+          eol_info.cr_lines.push(eol_info.num_lines);
+        }
       }
+      '\n' => {
+        eol_info.lf += 1;
+
+        if self.line_numbers {
+          eol_info.lf_lines.push(eol_info.num_lines);
+        }
+      }
+      '\u{85}' if self.unicode_separators => eol_info.nel += 1,
+      '\u{2028}' if self.unicode_separators => eol_info.ls += 1,
+      '\u{2029}' if self.unicode_separators => eol_info.ps += 1,
+      _ => {
+        self.line_len += 1;
+        self.directives.push(c);
+        return;
+      }
+    }
+
+    self.disabled_cr = c == '\r' && is_disabled;
+    self.directives.end_line();
+    eol_info.num_lines += 1;
+    eol_info.trailing_blank_lines = if self.line_len == 0 {
+      eol_info.trailing_blank_lines + 1
+    } else {
+      0
+    };
+    eol_info.count_line(self.line_len, self.is_whitespace_line);
+    self.line_len = 0;
+    self.is_whitespace_line = true;
+  }
+
+  /// Count the characters of valid UTF-8 text.
+  fn push_str(&mut self, text: &str, policy: DecodePolicy) {
+    let bytes = text.as_bytes();
+    let mut start = 0;
+
+    for end in memchr::memchr2_iter(b'\r', b'\n', bytes) {
+      self.push_line_part(&text[start..end], policy);
+      self.push(bytes[end] as char, 1);
+      start = end + 1;
+    }
+
+    self.push_line_part(&text[start..], policy);
+  }
+
+  /// Count part of a line with no CR or LF line endings in it, only decoding the characters that
+  /// aren't ASCII.
+  fn push_line_part(&mut self, s: &str, policy: DecodePolicy) {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+      let b = bytes[i];
+      let is_whitespace = if b.is_ascii() {
+        self.eol_info.num_bytes += 1;
+        self.directives.push(b as char);
+        i += 1;
+        // The ASCII characters that `char::is_whitespace()` is true for
+        matches!(b, b'\t'..=b'\r' | b' ')
+      } else {
+        let c = s[i..].chars().next().unwrap_or_default();
+
+        i += c.len_utf8();
+
+        if self.unicode_separators && is_unicode_separator(c) {
+          self.push(c, policy.byte_len(c));
+          continue;
+        }
+
+        self.eol_info.num_bytes += policy.byte_len(c);
+        self.directives.push(c);
+        c.is_whitespace()
+      };
+
+      self.eol_info.num_chars += 1;
+      self.line_len += 1;
+
+      if is_whitespace {
+        self.in_word = false;
+      } else {
+        self.is_whitespace_line = false;
+
+        if !self.in_word {
+          self.eol_info.num_words += 1;
+          self.in_word = true;
+        }
+      }
+    }
 
-      eol_info.num_lines += 1;
-    } else if c == '\n' {
-      eol_info.lf += 1;
-      eol_info.num_lines += 1;
+    if !s.is_empty() {
+      self.after_cr = false;
     }
   }
 
-  Ok(eol_info)
+  /// Count the last line and get the information.
+  fn finish(mut self) -> EolInfo {
+    if self.line_len > 0 {
+      self.eol_info.trailing_blank_lines = 0;
+      self.eol_info.missing_final_newline = if self.directives.is_disabled() { 0 } else { 1 };
+      self
+        .eol_info
+        .count_line(self.line_len, self.is_whitespace_line);
+    }
+
+    self.eol_info
+  }
+}
+
+/// Options for [`write_new_eols_with_options()`].  Start from the default, which only changes the
+/// line endings that are already there and fails on invalid UTF-8, and use the setters to change
+/// it:
+///
+/// ```
+/// use whitespace_rs::decode::DecodePolicy;
+/// use whitespace_rs::ender::EolOptions;
+///
+/// let options = EolOptions::default()
+///   .final_newline(true)
+///   .decode_policy(DecodePolicy::Replace);
+///
+/// assert!(options.final_newline);
+/// ```
+#[derive(Debug, Default, PartialEq, Clone)]
+#[non_exhaustive]
+pub struct EolOptions {
+  /// End the last line with the new line ending if it isn't empty and has no ending
+  pub final_newline: bool,
+  /// What to do with bytes that aren't valid UTF-8
+  pub decode_policy: DecodePolicy,
+  /// Treat U+0085 NEXT LINE, U+2028 LINE SEPARATOR and U+2029 PARAGRAPH SEPARATOR as line
+  /// endings, counting them and converting them to the new line ending
+  pub unicode_separators: bool,
+  /// Record the numbers of the lines with each line ending in [`EolInfo::cr_lines`],
+  /// [`EolInfo::lf_lines`] and [`EolInfo::crlf_lines`] when reading line ending information
+  pub line_numbers: bool,
+  /// Leave the line endings of the lines after a `ws:disable` comment, up to and including the
+  /// next `ws:enable` comment, unchanged
+  pub directives: bool,
+}
+
+impl EolOptions {
+  /// Set [`decode_policy`](EolOptions::decode_policy)
+  pub fn decode_policy(mut self, decode_policy: DecodePolicy) -> Self {
+    self.decode_policy = decode_policy;
+    self
+  }
+
+  /// Set [`final_newline`](EolOptions::final_newline)
+  pub fn final_newline(mut self, final_newline: bool) -> Self {
+    self.final_newline = final_newline;
+    self
+  }
+
+  /// Set [`unicode_separators`](EolOptions::unicode_separators)
+  pub fn unicode_separators(mut self, unicode_separators: bool) -> Self {
+    self.unicode_separators = unicode_separators;
+    self
+  }
+
+  /// Set [`line_numbers`](EolOptions::line_numbers)
+  pub fn line_numbers(mut self, line_numbers: bool) -> Self {
+    self.line_numbers = line_numbers;
+    self
+  }
+
+  /// Set [`directives`](EolOptions::directives)
+  pub fn directives(mut self, directives: bool) -> Self {
+    self.directives = directives;
+    self
+  }
+
+  /// Check if `c` is a line ending, or the CR of a CRLF
+  fn is_eol_char(&self, c: char) -> bool {
+    c == '\r' || c == '\n' || (self.unicode_separators && is_unicode_separator(c))
+  }
+}
+
+/// Check if `c` is one of the Unicode line separators NEL, LS or PS
+pub fn is_unicode_separator(c: char) -> bool {
+  matches!(c, '\u{85}' | '\u{2028}' | '\u{2029}')
+}
+
+/// Get the characters of the line ending that starts with `c`, taking the LF of a CRLF from
+/// `decoder`, or `None` if `c` doesn't start one
+fn take_eol<I: Iterator<Item = io::Result<char>>>(
+  c: char,
+  decoder: &mut Peekable<I>,
+  options: &EolOptions,
+) -> Option<&'static str> {
+  match c {
+    '\r' if matches!(decoder.peek(), Some(Ok(c)) if *c == '\n') => {
+      decoder.next();
+      Some("\r\n")
+    }
+    '\r' => Some("\r"),
+    '\n' => Some("\n"),
+    '\u{85}' if options.unicode_separators => Some("\u{85}"),
+    '\u{2028}' if options.unicode_separators => Some("\u{2028}"),
+    '\u{2029}' if options.unicode_separators => Some("\u{2029}"),
+    _ => None,
+  }
 }
 
 /// Write input file out with new end-of-lines.
+///
+/// Returns the number of lines written, including the empty last line after the final line
+/// ending.
 pub fn write_new_eols(
   reader: &mut dyn Read,
   writer: &mut dyn Write,
   new_eol: EndOfLine,
 ) -> Result<usize, Box<dyn Error>> {
-  let mut num_lines = 1;
-  let newline_chars = match new_eol {
-    EndOfLine::Cr => "\r".as_bytes(),
-    EndOfLine::Lf => "\n".as_bytes(),
-    EndOfLine::CrLf => "\r\n".as_bytes(),
-  };
-  let mut decoder = UnsafeDecoder::new(reader.bytes()).peekable();
-  let mut buf = [0u8; 4];
+  write_new_eols_generic(reader, writer, new_eol, &EolOptions::default())
+}
 
-  loop {
-    let c;
+/// Write input file out with new end-of-lines, using `options` to control how the last line ends.
+pub fn write_new_eols_with_options(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  new_eol: EndOfLine,
+  options: &EolOptions,
+) -> Result<usize, Box<dyn Error>> {
+  write_new_eols_generic(reader, writer, new_eol, options)
+}
+
+/// Generic version of [`write_new_eols_with_options()`] that can be inlined for concrete reader
+/// and writer types.
+pub fn write_new_eols_generic<R: Read + ?Sized, W: Write + ?Sized>(
+  reader: &mut R,
+  writer: &mut W,
+  new_eol: EndOfLine,
+  options: &EolOptions,
+) -> Result<usize, Box<dyn Error>> {
+  let mut num_lines = 1;
+  let mut decoder = Decoder::new(BufReader::new(reader).bytes(), options.decode_policy).peekable();
+  let mut directives = Directives::new(options.directives);
+  let mut at_bol = true;
 
-    match decoder.next() {
-      Some(value) => c = value?,
-      None => break,
+  while let Some(value) = decoder.next() {
+    let c = value?;
+    let eol = match take_eol(c, &mut decoder, options) {
+      Some(eol) => eol,
+      None => {
+        decode::write_char(writer, c, options.decode_policy)?;
+        directives.push(c);
+        at_bol = false;
+        continue;
+      }
     };
-    if c == '\r' {
-      if matches!(decoder.peek(), Some(Ok(c)) if *c == '\n') {
-        decoder.next();
+
+    num_lines += 1;
+    writer.write_all(
+      if directives.is_disabled() {
+        eol
+      } else {
+        new_eol.as_str()
       }
+      .as_bytes(),
+    )?;
+    directives.end_line();
+    at_bol = true;
+  }
 
-      num_lines += 1;
-      writer.write(newline_chars)?;
-    } else if c == '\n' {
-      num_lines += 1;
-      writer.write(newline_chars)?;
-    } else {
-      writer.write(c.encode_utf8(&mut buf).as_bytes())?;
-    }
+  if options.final_newline && !at_bol && !directives.is_disabled() {
+    num_lines += 1;
+    writer.write_all(new_eol.as_str().as_bytes())?;
   }
+
   writer.flush()?;
 
   Ok(num_lines)
 }
 
+/// Get `s` with every line ending changed to `new_eol`.
+pub fn normalize_str(s: &str, new_eol: EndOfLine) -> String {
+  let mut output = Vec::with_capacity(s.len());
+
+  // A string is always valid UTF-8 and writing to a vector can't fail
+  write_new_eols_generic(
+    &mut s.as_bytes(),
+    &mut output,
+    new_eol,
+    &EolOptions::default(),
+  )
+  .unwrap();
+  String::from_utf8(output).unwrap()
+}
+
+/// Check if [`write_new_eols_with_options()`] would change the input file, without writing
+/// anything.
+pub fn needs_new_eols(
+  reader: &mut dyn Read,
+  new_eol: EndOfLine,
+  options: &EolOptions,
+) -> Result<bool, Box<dyn Error>> {
+  let mut decoder = Decoder::new(BufReader::new(reader).bytes(), options.decode_policy).peekable();
+  let mut directives = Directives::new(options.directives);
+  let mut at_bol = true;
+
+  while let Some(value) = decoder.next() {
+    let c = value?;
+    let eol = match take_eol(c, &mut decoder, options) {
+      Some(eol) => eol,
+      None => {
+        directives.push(c);
+        at_bol = false;
+        continue;
+      }
+    };
+
+    if eol != new_eol.as_str() && !directives.is_disabled() {
+      return Ok(true);
+    }
+
+    directives.end_line();
+    at_bol = true;
+  }
+
+  Ok(options.final_newline && !at_bol && !directives.is_disabled())
+}
+
+/// Verify converted output against the original input.
+///
+/// Returns `true` if the sequence of non end-of-line characters is identical in both and every
+/// line in the converted output ends with `new_eol`.
+pub fn verify_new_eols(
+  original: &mut dyn Read,
+  converted: &mut dyn Read,
+  new_eol: EndOfLine,
+) -> Result<bool, Box<dyn Error>> {
+  verify_new_eols_generic(original, converted, new_eol, &EolOptions::default())
+}
+
+/// Verify output converted with [`write_new_eols_with_options()`] against the original input.
+pub fn verify_new_eols_with_options(
+  original: &mut dyn Read,
+  converted: &mut dyn Read,
+  new_eol: EndOfLine,
+  options: &EolOptions,
+) -> Result<bool, Box<dyn Error>> {
+  verify_new_eols_generic(original, converted, new_eol, options)
+}
+
+/// Generic version of [`verify_new_eols_with_options()`] that can be inlined for concrete reader
+/// types.
+pub fn verify_new_eols_generic<R1: Read + ?Sized, R2: Read + ?Sized>(
+  original: &mut R1,
+  converted: &mut R2,
+  new_eol: EndOfLine,
+  options: &EolOptions,
+) -> Result<bool, Box<dyn Error>> {
+  let mut original_chars = Decoder::new(BufReader::new(original).bytes(), options.decode_policy)
+    .filter(|value| !matches!(value, Ok(c) if options.is_eol_char(*c)));
+  let mut decoder =
+    Decoder::new(BufReader::new(converted).bytes(), options.decode_policy).peekable();
+  let mut directives = Directives::new(options.directives);
+
+  while let Some(value) = decoder.next() {
+    let c = value?;
+
+    match take_eol(c, &mut decoder, options) {
+      Some(eol) if eol != new_eol.as_str() && !directives.is_disabled() => return Ok(false),
+      Some(_) => directives.end_line(),
+      None => {
+        if original_chars.next().transpose()? != Some(c) {
+          return Ok(false);
+        }
+
+        directives.push(c);
+      }
+    }
+  }
+
+  Ok(original_chars.next().transpose()?.is_none())
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -178,7 +986,20 @@ mod tests {
         cr: 0,
         lf: 1,
         crlf: 0,
+        nel: 0,
+        ls: 0,
+        ps: 0,
         num_lines: 2,
+        trailing_blank_lines: 1,
+        blank_lines: 1,
+        whitespace_lines: 0,
+        missing_final_newline: 0,
+        num_chars: 1,
+        num_words: 0,
+        num_bytes: 1,
+        cr_lines: vec![],
+        lf_lines: vec![],
+        crlf_lines: vec![],
       }
     );
   }
@@ -193,7 +1014,20 @@ mod tests {
         cr: 1,
         lf: 0,
         crlf: 0,
+        nel: 0,
+        ls: 0,
+        ps: 0,
         num_lines: 2,
+        trailing_blank_lines: 1,
+        blank_lines: 1,
+        whitespace_lines: 0,
+        missing_final_newline: 0,
+        num_chars: 1,
+        num_words: 0,
+        num_bytes: 1,
+        cr_lines: vec![],
+        lf_lines: vec![],
+        crlf_lines: vec![],
       }
     );
   }
@@ -208,7 +1042,20 @@ mod tests {
         cr: 0,
         lf: 0,
         crlf: 1,
+        nel: 0,
+        ls: 0,
+        ps: 0,
         num_lines: 2,
+        trailing_blank_lines: 1,
+        blank_lines: 1,
+        whitespace_lines: 0,
+        missing_final_newline: 0,
+        num_chars: 2,
+        num_words: 0,
+        num_bytes: 2,
+        cr_lines: vec![],
+        lf_lines: vec![],
+        crlf_lines: vec![],
       }
     );
   }
@@ -223,11 +1070,380 @@ mod tests {
         cr: 1,
         lf: 1,
         crlf: 1,
+        nel: 0,
+        ls: 0,
+        ps: 0,
         num_lines: 4,
+        trailing_blank_lines: 3,
+        blank_lines: 3,
+        whitespace_lines: 0,
+        missing_final_newline: 0,
+        num_chars: 4,
+        num_words: 0,
+        num_bytes: 4,
+        cr_lines: vec![],
+        lf_lines: vec![],
+        crlf_lines: vec![],
       }
     );
   }
 
+  #[test]
+  fn test_read_eol_info_counts() {
+    let eol_info = read_eol_info(&mut "abc def\r\n  ü\n".as_bytes()).unwrap();
+
+    assert_eq!(
+      (eol_info.num_chars, eol_info.num_words, eol_info.num_bytes),
+      (13, 3, 14)
+    );
+  }
+
+  #[test]
+  fn test_read_eol_info_scan() {
+    // Returns the input a byte at a time, so that every character and CRLF is split between reads
+    struct ByteReader<'a>(&'a [u8]);
+
+    impl Read for ByteReader<'_> {
+      fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = (&self.0[..self.0.len().min(1)]).read(buf)?;
+
+        self.0 = &self.0[n..];
+        Ok(n)
+      }
+    }
+
+    let decoded_eol_info = |input: &[u8], policy| {
+      let mut counter = EolCounter::default();
+
+      for value in Decoder::new(input.bytes(), policy) {
+        let c = value.unwrap();
+
+        counter.push(c, policy.byte_len(c));
+      }
+
+      counter.finish()
+    };
+    let inputs: [&[u8]; 5] = [
+      b"a b\r\n\x0b\r\r\n\n  ",
+      "caf\u{e9} \u{2003}x\u{85}\r\n\u{10ff80}".as_bytes(),
+      b"ab\xe9\r\ncd\r\n",
+      b"\r\n\xf0\x9f\x98",
+      b"",
+    ];
+
+    for input in inputs.iter() {
+      for policy in [DecodePolicy::Replace, DecodePolicy::PassThroughBytes] {
+        let options = EolOptions::default().decode_policy(policy);
+        let expected = decoded_eol_info(input, policy);
+
+        assert_eq!(
+          read_eol_info_with_options(&mut &input[..], &options).unwrap(),
+          expected
+        );
+        assert_eq!(
+          read_eol_info_with_options(&mut ByteReader(input), &options).unwrap(),
+          expected
+        );
+      }
+    }
+
+    let err = read_eol_info(&mut ByteReader(b"ab\r\r\n \xc3\xa9\xff")).unwrap_err();
+    let decode_error = decode::decode_error(&*err).unwrap();
+
+    assert_eq!(
+      (decode_error.line, decode_error.column, decode_error.offset),
+      (3, 3, 8)
+    );
+  }
+
+  #[test]
+  fn test_read_eol_info_trailing_blank_lines() {
+    let trailing_blank_lines = |s: &str| {
+      read_eol_info(&mut s.as_bytes())
+        .unwrap()
+        .trailing_blank_lines
+    };
+
+    assert_eq!(trailing_blank_lines(""), 0);
+    assert_eq!(trailing_blank_lines("abc\n"), 0);
+    assert_eq!(trailing_blank_lines("abc\n\n\r\n"), 2);
+    assert_eq!(trailing_blank_lines("abc\n\nxyz"), 0);
+    assert_eq!(trailing_blank_lines("\n \n"), 0);
+  }
+
+  #[test]
+  fn test_parse_end_of_line() {
+    assert_eq!("lf".parse::<EndOfLine>().unwrap(), EndOfLine::Lf);
+    assert_eq!("CRLF".parse::<EndOfLine>().unwrap(), EndOfLine::CrLf);
+    assert_eq!("Cr".parse::<EndOfLine>().unwrap(), EndOfLine::Cr);
+    assert!("auto".parse::<EndOfLine>().is_err());
+    assert_eq!(EndOfLine::CrLf.to_string(), "crlf");
+    assert_eq!(EndOfLine::CrLf.as_str(), "\r\n");
+  }
+
+  #[test]
+  fn test_get_common_eol_with() {
+    let eol_info = |cr, lf, crlf| EolInfo {
+      cr,
+      lf,
+      crlf,
+      ..EolInfo::new()
+    };
+    let common = |eol_info: &EolInfo, tie_break| eol_info.get_common_eol_with(tie_break).unwrap();
+
+    assert_eq!(common(&eol_info(0, 2, 2), TieBreak::CrLf), EndOfLine::CrLf);
+    assert_eq!(common(&eol_info(0, 2, 2), TieBreak::Lf), EndOfLine::Lf);
+    assert_eq!(
+      common(&eol_info(0, 2, 2), TieBreak::Native),
+      EndOfLine::native()
+    );
+    assert_eq!(common(&eol_info(0, 1, 2), TieBreak::Lf), EndOfLine::CrLf);
+    assert_eq!(common(&eol_info(3, 2, 3), TieBreak::Lf), EndOfLine::CrLf);
+    assert_eq!(common(&eol_info(0, 0, 0), TieBreak::CrLf), EndOfLine::CrLf);
+    assert_eq!(common(&eol_info(0, 0, 0), TieBreak::Error), EndOfLine::Lf);
+    assert_eq!(common(&eol_info(1, 2, 0), TieBreak::Error), EndOfLine::Lf);
+
+    let err = eol_info(0, 2, 2)
+      .get_common_eol_with(TieBreak::Error)
+      .unwrap_err();
+
+    assert_eq!(error::error_code(&*err), ErrorCode::Ambiguous);
+    assert_eq!(
+      err.to_string(),
+      "lf and crlf line endings are equally common, 2 of each"
+    );
+    assert_eq!("Native".parse::<TieBreak>().unwrap(), TieBreak::Native);
+    assert!("cr".parse::<TieBreak>().is_err());
+  }
+
+  #[test]
+  fn test_end_of_line_keys() {
+    let mut counts = std::collections::BTreeMap::new();
+
+    for eol in [EndOfLine::CrLf, EndOfLine::Lf, EndOfLine::Cr, EndOfLine::Lf] {
+      *counts.entry(eol).or_insert(0) += 1;
+    }
+
+    assert_eq!(
+      counts.into_iter().collect::<Vec<_>>(),
+      vec![(EndOfLine::Cr, 1), (EndOfLine::Lf, 2), (EndOfLine::CrLf, 1)]
+    );
+
+    let infos: std::collections::HashSet<EolInfo> = ["a\n", "b\n", "a\r\n"]
+      .iter()
+      .map(|s| read_eol_info(&mut s.as_bytes()).unwrap())
+      .collect();
+
+    assert_eq!(infos.len(), 2);
+  }
+
+  #[test]
+  fn test_num_other_endings() {
+    let eol_info = read_eol_info(&mut "\n\n\r\n\r".as_bytes()).unwrap();
+
+    assert_eq!(eol_info.num_other_endings(EndOfLine::Lf), 2);
+    assert_eq!(eol_info.num_other_endings(EndOfLine::CrLf), 3);
+    assert_eq!(eol_info.num_other_endings(eol_info.get_common_eol()), 2);
+  }
+
+  #[test]
+  fn test_read_eol_info_blank_lines() {
+    let eol_info = read_eol_info(&mut "a\n\n \t\r\n\u{c}\nb\n\n  ".as_bytes()).unwrap();
+
+    assert_eq!((eol_info.blank_lines, eol_info.whitespace_lines), (2, 3));
+    assert_eq!(eol_info.num_lines, 7);
+  }
+
+  #[test]
+  fn test_add_eol_info() {
+    let eol_info = read_eol_info(&mut "a\nb".as_bytes()).unwrap()
+      + read_eol_info(&mut "c\r\n".as_bytes()).unwrap();
+
+    assert_eq!(
+      eol_info,
+      EolInfo {
+        cr: 0,
+        lf: 1,
+        crlf: 1,
+        nel: 0,
+        ls: 0,
+        ps: 0,
+        num_lines: 4,
+        trailing_blank_lines: 0,
+        blank_lines: 0,
+        whitespace_lines: 0,
+        missing_final_newline: 1,
+        num_chars: 6,
+        num_words: 3,
+        num_bytes: 6,
+        cr_lines: vec![],
+        lf_lines: vec![],
+        crlf_lines: vec![],
+      }
+    );
+    assert_eq!(
+      vec!["a\n", "", "b\r"]
+        .into_iter()
+        .map(|s| read_eol_info(&mut s.as_bytes()).unwrap())
+        .sum::<EolInfo>()
+        .num_lines,
+      5
+    );
+  }
+
+  #[test]
+  fn test_eol_info_new() {
+    assert_eq!(EolInfo::new(), read_eol_info(&mut "".as_bytes()).unwrap());
+    assert_eq!(EolInfo::default(), Vec::new().into_iter().sum());
+  }
+
+  #[test]
+  fn test_append_chunk() {
+    let mut eol_info = read_eol_info(&mut "a\r\nb\n".as_bytes()).unwrap();
+
+    eol_info.append_chunk(&read_eol_info(&mut "c\rd".as_bytes()).unwrap());
+
+    assert_eq!(
+      eol_info,
+      read_eol_info(&mut "a\r\nb\nc\rd".as_bytes()).unwrap()
+    );
+
+    let mut eol_info = read_eol_info(&mut "a\n\n".as_bytes()).unwrap();
+
+    eol_info.append_chunk(&read_eol_info(&mut "\r\n\n".as_bytes()).unwrap());
+
+    assert_eq!(
+      eol_info,
+      read_eol_info(&mut "a\n\n\r\n\n".as_bytes()).unwrap()
+    );
+
+    let options = EolOptions::default().line_numbers(true);
+    let read = |s: &str| read_eol_info_with_options(&mut s.as_bytes(), &options).unwrap();
+    let mut eol_info = read("a\r\nb\n");
+
+    eol_info.append_chunk(&read("c\rd\r\n"));
+    assert_eq!(eol_info, read("a\r\nb\nc\rd\r\n"));
+  }
+
+  #[test]
+  fn test_read_eol_info_line_numbers() {
+    let input = "a\r\nb\n\rc\r\n\nd";
+    let options = EolOptions::default().line_numbers(true);
+    let eol_info = read_eol_info_with_options(&mut input.as_bytes(), &options).unwrap();
+
+    assert_eq!(eol_info.crlf_lines, vec![1, 4]);
+    assert_eq!(eol_info.lf_lines, vec![2, 5]);
+    assert_eq!(eol_info.cr_lines, vec![3]);
+    assert_eq!(eol_info.lines_ending_in(EndOfLine::Lf), &[2, 5]);
+    assert!(read_eol_info(&mut input.as_bytes())
+      .unwrap()
+      .lf_lines
+      .is_empty());
+  }
+
+  #[test]
+  fn test_normalize_str() {
+    assert_eq!(normalize_str("a\r\nb\rc\n", EndOfLine::Lf), "a\nb\nc\n");
+    assert_eq!(normalize_str("ü\n", EndOfLine::CrLf), "ü\r\n");
+    assert_eq!(normalize_str("", EndOfLine::Cr), "");
+  }
+
+  #[test]
+  fn test_unicode_separators() {
+    let input = "a\u{85}b\u{2028}c\u{2029}\u{2028}d\r\n";
+    let options = EolOptions::default().unicode_separators(true);
+    let eol_info = read_eol_info_with_options(&mut input.as_bytes(), &options).unwrap();
+
+    assert_eq!(
+      (eol_info.nel, eol_info.ls, eol_info.ps, eol_info.crlf),
+      (1, 2, 1, 1)
+    );
+    assert_eq!(eol_info.num_lines, 6);
+    assert_eq!(eol_info.blank_lines, 1);
+    assert_eq!(eol_info.num_words, 4);
+    assert_eq!(eol_info.num_endings(), 4);
+    assert_eq!(eol_info.num_other_endings(EndOfLine::CrLf), 4);
+    assert_eq!(read_eol_info(&mut input.as_bytes()).unwrap().num_lines, 2);
+
+    let mut output = Vec::new();
+
+    assert_eq!(
+      write_new_eols_with_options(&mut input.as_bytes(), &mut output, EndOfLine::Lf, &options)
+        .unwrap(),
+      6
+    );
+    assert_eq!(String::from_utf8(output.clone()).unwrap(), "a\nb\nc\n\nd\n");
+    assert!(verify_new_eols_with_options(
+      &mut input.as_bytes(),
+      &mut output.as_slice(),
+      EndOfLine::Lf,
+      &options
+    )
+    .unwrap());
+    assert!(needs_new_eols(&mut "a\u{2028}".as_bytes(), EndOfLine::Lf, &options).unwrap());
+    assert!(!needs_new_eols(
+      &mut "a\u{2028}".as_bytes(),
+      EndOfLine::Lf,
+      &EolOptions::default()
+    )
+    .unwrap());
+  }
+
+  #[test]
+  fn test_directives() {
+    let input = "a\r\nb # ws:disable\r\nc\rd # ws:enable\r\ne\r\n";
+    let options = EolOptions::default().directives(true);
+    let mut output = Vec::new();
+
+    write_new_eols_with_options(&mut input.as_bytes(), &mut output, EndOfLine::Lf, &options)
+      .unwrap();
+    assert_eq!(
+      String::from_utf8(output).unwrap(),
+      "a\nb # ws:disable\nc\rd # ws:enable\r\ne\n"
+    );
+    assert!(verify_new_eols_with_options(
+      &mut input.as_bytes(),
+      &mut "a\nb # ws:disable\nc\rd # ws:enable\r\ne\n".as_bytes(),
+      EndOfLine::Lf,
+      &options,
+    )
+    .unwrap());
+    assert_eq!(
+      normalize_str(input, EndOfLine::Lf),
+      "a\nb # ws:disable\nc\nd # ws:enable\ne\n"
+    );
+
+    let needs = |input: &str| {
+      needs_new_eols(
+        &mut input.as_bytes(),
+        EndOfLine::Lf,
+        &options.clone().final_newline(true),
+      )
+      .unwrap()
+    };
+
+    assert!(!needs("a\n# ws:disable\nb\r\n"));
+    assert!(needs("# ws:disable\n# ws:enable\nb\r\n"));
+    assert!(!needs("# ws:disable\nb"));
+    assert!(needs("a = 'ws:disable'\nb\r\n"));
+
+    for policy in [DecodePolicy::Strict, DecodePolicy::Bytes] {
+      let options = options.clone().decode_policy(policy).line_numbers(true);
+      let eol_info = read_eol_info_with_options(&mut input.as_bytes(), &options).unwrap();
+
+      assert_eq!((eol_info.crlf, eol_info.cr, eol_info.lf), (3, 0, 0));
+      assert_eq!(eol_info.crlf_lines, vec![1, 2, 5]);
+      assert!(eol_info.cr_lines.is_empty());
+      assert_eq!(eol_info.num_lines, 6);
+    }
+
+    let options = options.final_newline(true);
+    let eol_info =
+      read_eol_info_with_options(&mut "a\n# ws:disable\nb".as_bytes(), &options).unwrap();
+
+    assert_eq!(eol_info.missing_final_newline, 0);
+  }
+
   #[test]
   fn test_write_new_file() {
     let mut input = "abc\n\r\r\n".as_bytes();
@@ -237,4 +1453,161 @@ mod tests {
     assert_eq!(num_lines, 4);
     assert_eq!(String::from_utf8(output).unwrap(), "abc\r\n\r\n\r\n")
   }
+
+  #[test]
+  fn test_write_new_file_final_newline() {
+    let options = EolOptions::default().final_newline(true);
+    let write = |input: &str, options: &EolOptions| {
+      let mut output = Vec::new();
+      let num_lines =
+        write_new_eols_with_options(&mut input.as_bytes(), &mut output, EndOfLine::CrLf, options)
+          .unwrap();
+
+      (String::from_utf8(output).unwrap(), num_lines)
+    };
+
+    assert_eq!(write("a\nb", &options), ("a\r\nb\r\n".to_string(), 3));
+    assert_eq!(write("a\nb\r", &options), ("a\r\nb\r\n".to_string(), 3));
+    assert_eq!(write("", &options), ("".to_string(), 1));
+    assert_eq!(
+      write("a\nb", &EolOptions::default()),
+      ("a\r\nb".to_string(), 2)
+    );
+    assert!(!read_eol_info(&mut "a\nb".as_bytes())
+      .unwrap()
+      .has_final_newline());
+    assert!(read_eol_info(&mut "a\n".as_bytes())
+      .unwrap()
+      .has_final_newline());
+    assert!(read_eol_info(&mut "".as_bytes())
+      .unwrap()
+      .has_final_newline());
+  }
+
+  #[test]
+  fn test_generic_versions() {
+    let input = "abc\n\r\r\n";
+    let mut output = Vec::new();
+
+    assert_eq!(
+      read_eol_info_generic(&mut input.as_bytes(), &EolOptions::default()).unwrap(),
+      read_eol_info(&mut input.as_bytes()).unwrap()
+    );
+    assert_eq!(
+      write_new_eols_generic(
+        &mut input.as_bytes(),
+        &mut output,
+        EndOfLine::Lf,
+        &EolOptions::default()
+      )
+      .unwrap(),
+      4
+    );
+    assert_eq!(output, b"abc\n\n\n");
+    assert!(verify_new_eols_generic(
+      &mut input.as_bytes(),
+      &mut &output[..],
+      EndOfLine::Lf,
+      &EolOptions::default()
+    )
+    .unwrap());
+  }
+
+  #[test]
+  fn test_decode_policy() {
+    let input = b"caf\xe9\r\nb\xff\r\n";
+    let convert = |decode_policy| {
+      let mut output = Vec::new();
+      let options = EolOptions::default().decode_policy(decode_policy);
+
+      write_new_eols_with_options(&mut &input[..], &mut output, EndOfLine::Lf, &options)
+        .map(|_| output)
+    };
+
+    assert!(convert(DecodePolicy::Strict).is_err());
+    assert_eq!(
+      convert(DecodePolicy::Replace).unwrap(),
+      "caf\u{fffd}\nb\u{fffd}\n".as_bytes()
+    );
+
+    let output = convert(DecodePolicy::PassThroughBytes).unwrap();
+    let options = EolOptions::default().decode_policy(DecodePolicy::PassThroughBytes);
+
+    assert_eq!(output, b"caf\xe9\nb\xff\n");
+    assert_eq!(convert(DecodePolicy::Bytes).unwrap(), output);
+    assert_eq!(
+      read_eol_info_with_options(&mut &input[..], &options)
+        .unwrap()
+        .num_bytes,
+      input.len()
+    );
+    assert!(verify_new_eols_with_options(
+      &mut &input[..],
+      &mut &output[..],
+      EndOfLine::Lf,
+      &options
+    )
+    .unwrap());
+
+    // Characters in the range that invalid bytes are passed through as are written unchanged
+    let input = "a\u{10ff80}\r\n\u{10ffff}\r\n".as_bytes();
+    let mut output = Vec::new();
+
+    write_new_eols_with_options(&mut &input[..], &mut output, EndOfLine::Lf, &options).unwrap();
+
+    assert_eq!(output, "a\u{10ff80}\n\u{10ffff}\n".as_bytes());
+    assert_eq!(
+      read_eol_info_with_options(&mut &input[..], &options)
+        .unwrap()
+        .num_bytes,
+      input.len()
+    );
+  }
+
+  #[test]
+  fn test_verify_new_eols() {
+    assert!(verify_new_eols(
+      &mut "abc\n\r\r\n".as_bytes(),
+      &mut "abc\r\n\r\n\r\n".as_bytes(),
+      EndOfLine::CrLf
+    )
+    .unwrap());
+    assert!(!verify_new_eols(
+      &mut "abc\n\r\r\n".as_bytes(),
+      &mut "abc\r\n\n\r\n".as_bytes(),
+      EndOfLine::CrLf
+    )
+    .unwrap());
+    assert!(!verify_new_eols(
+      &mut "abc\n".as_bytes(),
+      &mut "abd\n".as_bytes(),
+      EndOfLine::Lf
+    )
+    .unwrap());
+    assert!(!verify_new_eols(
+      &mut "abc\n".as_bytes(),
+      &mut "ab\n".as_bytes(),
+      EndOfLine::Lf
+    )
+    .unwrap());
+  }
+
+  #[test]
+  fn test_needs_new_eols() {
+    let needs = |input: &str, new_eol, final_newline| {
+      needs_new_eols(
+        &mut input.as_bytes(),
+        new_eol,
+        &EolOptions::default().final_newline(final_newline),
+      )
+      .unwrap()
+    };
+
+    assert!(needs("a\r\nb\n", EndOfLine::Lf, false));
+    assert!(needs("a\rb", EndOfLine::CrLf, false));
+    assert!(!needs("a\nb\n", EndOfLine::Lf, false));
+    assert!(!needs("a\nb", EndOfLine::Lf, false));
+    assert!(needs("a\nb", EndOfLine::Lf, true));
+    assert!(!needs("", EndOfLine::Lf, true));
+  }
 }
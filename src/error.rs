@@ -0,0 +1,186 @@
+//! Error codes that scripts can rely on.
+//!
+//! Errors are returned as `Box<dyn Error>`.  The ones made by this crate are an [`Error`] with an
+//! [`ErrorCode`] that doesn't change between releases, unlike the message.  Use [`error_code()`]
+//! to get the code of any error, including the I/O errors from reading and writing files:
+//!
+//! ```
+//! use whitespace_rs::{ender, error::{self, ErrorCode}};
+//!
+//! let err = ender::read_eol_info(&mut &[0xffu8][..]).unwrap_err();
+//!
+//! assert_eq!(error::error_code(&*err), ErrorCode::BadUtf8);
+//! assert_eq!(ErrorCode::BadUtf8.to_string(), "E_BAD_UTF8");
+//! ```
+
+use crate::decode::DecodeError;
+use std::fmt;
+use std::io;
+
+/// Stable codes for the causes of errors
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ErrorCode {
+  /// A file couldn't be read or written
+  Io,
+  /// Input isn't valid UTF-8
+  BadUtf8,
+  /// The output file is the same as the input file
+  SamePath,
+  /// The output file already exists
+  OutputExists,
+  /// STDIN can't be used this way
+  Stdin,
+  /// Converted output failed verification
+  Verify,
+  /// The conversion isn't allowed for the type of file
+  FileType,
+  /// An archive is the wrong type
+  Archive,
+  /// An output file name pattern is invalid
+  Pattern,
+  /// A patch can't be parsed or applied
+  Patch,
+  /// A journal can't be written or undone
+  Journal,
+  /// A command run for a file failed
+  Exec,
+  /// A configuration file can't be read or doesn't have the profile asked for
+  Config,
+  /// A notebook isn't valid JSON
+  Notebook,
+  /// A line or file is larger than a configured limit
+  Limit,
+  /// Input isn't valid in its encoding, or its encoding can't be used this way
+  Encoding,
+  /// A choice that has to be made from the input can't be made, such as between equally common
+  /// line endings
+  Ambiguous,
+  /// Any other error
+  Other,
+}
+
+impl ErrorCode {
+  /// Get the code as a string like `E_IO`.
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ErrorCode::Io => "E_IO",
+      ErrorCode::BadUtf8 => "E_BAD_UTF8",
+      ErrorCode::SamePath => "E_SAME_PATH",
+      ErrorCode::OutputExists => "E_OUTPUT_EXISTS",
+      ErrorCode::Stdin => "E_STDIN",
+      ErrorCode::Verify => "E_VERIFY",
+      ErrorCode::FileType => "E_FILE_TYPE",
+      ErrorCode::Archive => "E_ARCHIVE",
+      ErrorCode::Pattern => "E_PATTERN",
+      ErrorCode::Patch => "E_PATCH",
+      ErrorCode::Journal => "E_JOURNAL",
+      ErrorCode::Exec => "E_EXEC",
+      ErrorCode::Config => "E_CONFIG",
+      ErrorCode::Notebook => "E_NOTEBOOK",
+      ErrorCode::Limit => "E_LIMIT",
+      ErrorCode::Encoding => "E_ENCODING",
+      ErrorCode::Ambiguous => "E_AMBIGUOUS",
+      ErrorCode::Other => "E_OTHER",
+    }
+  }
+}
+
+impl fmt::Display for ErrorCode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ErrorCode {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self.as_str())
+  }
+}
+
+/// An error with a stable code and a message
+#[derive(Debug, PartialEq, Clone)]
+pub struct Error {
+  code: ErrorCode,
+  message: String,
+}
+
+impl Error {
+  /// Create an error with a code and message.
+  pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+    Error {
+      code,
+      message: message.into(),
+    }
+  }
+
+  /// Get the code of the error.
+  pub fn code(&self) -> ErrorCode {
+    self.code
+  }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(&self.message)
+  }
+}
+
+impl std::error::Error for Error {}
+
+/// Get the code of any error.  I/O errors from decoding invalid UTF-8 are [`ErrorCode::BadUtf8`]
+/// and other I/O errors are [`ErrorCode::Io`].
+pub fn error_code(err: &(dyn std::error::Error + 'static)) -> ErrorCode {
+  if let Some(err) = err.downcast_ref::<Error>() {
+    err.code()
+  } else if err.is::<DecodeError>() {
+    ErrorCode::BadUtf8
+  } else if let Some(err) = err.downcast_ref::<io::Error>() {
+    match err.get_ref() {
+      Some(inner) if inner.is::<Error>() || inner.is::<DecodeError>() => error_code(inner),
+      _ if err.kind() == io::ErrorKind::InvalidData && err.to_string().contains("UTF-8") => {
+        ErrorCode::BadUtf8
+      }
+      _ => ErrorCode::Io,
+    }
+  } else if err.is::<std::str::Utf8Error>() || err.is::<std::string::FromUtf8Error>() {
+    ErrorCode::BadUtf8
+  } else {
+    ErrorCode::Other
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_error_code() {
+    let err: Box<dyn std::error::Error> = Box::new(Error::new(ErrorCode::SamePath, "same"));
+
+    assert_eq!(error_code(&*err), ErrorCode::SamePath);
+    assert_eq!(err.to_string(), "same");
+    assert_eq!(
+      error_code(&io::Error::new(io::ErrorKind::NotFound, "missing")),
+      ErrorCode::Io
+    );
+    assert_eq!(
+      error_code(&io::Error::other(Error::new(ErrorCode::Journal, "journal"))),
+      ErrorCode::Journal
+    );
+    assert_eq!(
+      error_code(&String::from_utf8(vec![0xff]).unwrap_err()),
+      ErrorCode::BadUtf8
+    );
+
+    let err: Box<dyn std::error::Error> = From::from("message");
+
+    assert_eq!(error_code(&*err), ErrorCode::Other);
+  }
+
+  #[test]
+  fn test_error_code_as_str() {
+    assert_eq!(ErrorCode::Io.as_str(), "E_IO");
+    assert_eq!(ErrorCode::BadUtf8.to_string(), "E_BAD_UTF8");
+  }
+}
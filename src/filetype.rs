@@ -0,0 +1,132 @@
+//! Decide how files are treated from their names and contents.
+//!
+//! Some types of file need different handling.  Tabs can't be used to indent YAML, Markdown
+//! code blocks must be left alone, and the whitespace in diffs and patches is part of what they
+//! change.  Use [`FileType::detect()`] with a file name and the start of
+//! its contents:
+//!
+//! ```
+//! use std::path::Path;
+//! use whitespace_rs::filetype::FileType;
+//!
+//! let file_type = FileType::detect(Path::new("config"), b"%YAML 1.2\n---\n");
+//!
+//! assert_eq!(file_type, FileType::Yaml);
+//! assert!(!file_type.allows_tabs());
+//! ```
+
+use std::path::Path;
+
+/// Types of files that need special handling
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FileType {
+  /// YAML, which can't be indented with tabs
+  Yaml,
+  /// Markdown, where code blocks are left unchanged
+  Markdown,
+  /// A diff or patch, where the space that begins context lines and the whitespace in the
+  /// changed lines are part of the patch
+  Patch,
+  /// Any other text file
+  Text,
+}
+
+impl FileType {
+  /// Get the type of a file from its extension, or from `head`, the start of its contents, if
+  /// the extension doesn't say.
+  pub fn detect(path: &Path, head: &[u8]) -> FileType {
+    let extension = path
+      .extension()
+      .map(|ext| ext.to_string_lossy().to_lowercase());
+
+    match extension.as_deref() {
+      Some("yaml") | Some("yml") => FileType::Yaml,
+      Some("md") | Some("markdown") => FileType::Markdown,
+      Some("diff") | Some("patch") => FileType::Patch,
+      _ if is_patch(head) => FileType::Patch,
+      _ if head.starts_with(b"%YAML")
+        || head.starts_with(b"---\n")
+        || head.starts_with(b"---\r\n") =>
+      {
+        FileType::Yaml
+      }
+      _ => FileType::Text,
+    }
+  }
+
+  /// Check if lines can be indented with tabs
+  pub fn allows_tabs(&self) -> bool {
+    *self != FileType::Yaml
+  }
+
+  /// Check if the whitespace at the beginnings and ends of lines is part of the content, so that
+  /// fixing it would corrupt the file
+  pub fn preserves_whitespace(&self) -> bool {
+    *self == FileType::Patch
+  }
+}
+
+/// Check if the start of a file looks like a git diff or a unified diff
+fn is_patch(head: &[u8]) -> bool {
+  let mut lines = head.split(|&b| b == b'\n');
+
+  match (lines.next(), lines.next()) {
+    (Some(first), _) if first.starts_with(b"diff --git ") => true,
+    (Some(first), Some(second)) => first.starts_with(b"--- ") && second.starts_with(b"+++ "),
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_detect() {
+    assert_eq!(FileType::detect(Path::new("a.yml"), b""), FileType::Yaml);
+    assert_eq!(
+      FileType::detect(Path::new("dir/A.YAML"), b""),
+      FileType::Yaml
+    );
+    assert_eq!(
+      FileType::detect(Path::new("a"), b"---\nx: 1\n"),
+      FileType::Yaml
+    );
+    assert_eq!(
+      FileType::detect(Path::new("a"), b"%YAML 1.2\n"),
+      FileType::Yaml
+    );
+    assert_eq!(
+      FileType::detect(Path::new("README.md"), b"---\n"),
+      FileType::Markdown
+    );
+    assert_eq!(
+      FileType::detect(Path::new("a.rs"), b"fn main() {}\n"),
+      FileType::Text
+    );
+    assert_eq!(
+      FileType::detect(Path::new("a.txt"), b"----\n"),
+      FileType::Text
+    );
+    assert_eq!(
+      FileType::detect(Path::new("fix.PATCH"), b""),
+      FileType::Patch
+    );
+    assert_eq!(
+      FileType::detect(Path::new("a"), b"--- a/x.c\n+++ b/x.c\n@@ -1 +1 @@\n"),
+      FileType::Patch
+    );
+    assert_eq!(
+      FileType::detect(Path::new("a"), b"diff --git a/x b/x\n"),
+      FileType::Patch
+    );
+    assert_eq!(
+      FileType::detect(Path::new("a"), b"--- # YAML\nx: 1\n"),
+      FileType::Text
+    );
+    assert!(FileType::Text.allows_tabs());
+    assert!(FileType::Patch.preserves_whitespace());
+    assert!(!FileType::Markdown.preserves_whitespace());
+    assert!(!FileType::Yaml.allows_tabs());
+  }
+}
@@ -0,0 +1,203 @@
+//! Emulate the line ending conversions done by git.
+//!
+//! git decides whether a file is text with the heuristics in [`TextStats`].  To find the line
+//! ending a text file would have after being added and checked out again with a
+//! `core.autocrlf` setting use [`autocrlf_eol()`]:
+//!
+//! ```
+//! use whitespace_rs::ender::EndOfLine;
+//! use whitespace_rs::git::{self, AutoCrlf};
+//!
+//! assert_eq!(git::autocrlf_eol(b"abc\nxyz\r\n", AutoCrlf::True), Some(EndOfLine::CrLf));
+//! assert_eq!(git::autocrlf_eol(b"abc\0\n", AutoCrlf::True), None);
+//! ```
+//!
+//! Use [`tracked_files()`] to find the files in a repository that aren't build outputs or
+//! untracked junk.
+
+use crate::ender::EndOfLine;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Values of git's `core.autocrlf` setting that convert line endings
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AutoCrlf {
+  /// LF in the repository and CRLF in the working tree
+  True,
+  /// LF in the repository and the working tree is left alone on checkout
+  Input,
+}
+
+/// Character counts that git uses to decide if a file is text.
+#[derive(Debug, Default, PartialEq)]
+pub struct TextStats {
+  /// Number of NUL bytes
+  pub nul: usize,
+  /// Number of carriage returns not followed by a line feed
+  pub lone_cr: usize,
+  /// Number of line feeds not preceded by a carriage return
+  pub lone_lf: usize,
+  /// Number of carriage return/line feed pairs
+  pub crlf: usize,
+  /// Number of printable bytes
+  pub printable: usize,
+  /// Number of non-printable bytes
+  pub nonprintable: usize,
+}
+
+impl TextStats {
+  /// Count the characters in a file the same way as git's `gather_stats()`.
+  pub fn new(data: &[u8]) -> TextStats {
+    let mut stats = TextStats::default();
+    let mut i = 0;
+
+    while i < data.len() {
+      match data[i] {
+        b'\r' if data.get(i + 1) == Some(&b'\n') => {
+          stats.crlf += 1;
+          i += 1;
+        }
+        b'\r' => stats.lone_cr += 1,
+        b'\n' => stats.lone_lf += 1,
+        127 => stats.nonprintable += 1,
+        b'\x08' | b'\t' | b'\x1b' | b'\x0c' => stats.printable += 1,
+        0 => {
+          stats.nul += 1;
+          stats.nonprintable += 1;
+        }
+        c if c < 32 => stats.nonprintable += 1,
+        _ => stats.printable += 1,
+      }
+
+      i += 1;
+    }
+
+    // A DOS end of file marker doesn't count as non-printable
+    if data.last() == Some(&b'\x1a') {
+      stats.nonprintable -= 1;
+    }
+
+    stats
+  }
+
+  /// Check if git would treat the file as binary and leave its line endings alone.
+  pub fn is_binary(&self) -> bool {
+    self.lone_cr > 0 || self.nul > 0 || (self.printable >> 7) < self.nonprintable
+  }
+}
+
+/// Get the line ending that a file would have in a fresh checkout after being added to git with
+/// a `core.autocrlf` setting, or `None` if git would leave the file unchanged.
+///
+/// git also leaves files alone that already have CRLF line endings in the index, which can't be
+/// known from the file contents alone.
+pub fn autocrlf_eol(data: &[u8], autocrlf: AutoCrlf) -> Option<EndOfLine> {
+  let stats = TextStats::new(data);
+
+  if stats.is_binary() || stats.lone_lf + stats.crlf == 0 {
+    None
+  } else {
+    match autocrlf {
+      AutoCrlf::True => Some(EndOfLine::CrLf),
+      AutoCrlf::Input => Some(EndOfLine::Lf),
+    }
+  }
+}
+
+/// Run git in `dir` and get its output, failing if git fails.
+fn run_git(dir: &Path, args: &[&str]) -> io::Result<Vec<u8>> {
+  let output = Command::new("git").args(args).current_dir(dir).output()?;
+
+  if output.status.success() {
+    Ok(output.stdout)
+  } else {
+    Err(io::Error::other(format!(
+      "git {} failed: {}",
+      args.join(" "),
+      String::from_utf8_lossy(&output.stderr).trim()
+    )))
+  }
+}
+
+/// Get the canonical paths of the files tracked by the git repository that contains `dir`, by
+/// running `git ls-files`.  Tracked files that have been deleted from the working tree are left
+/// out.
+pub fn tracked_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+  let top_level = run_git(dir, &["rev-parse", "--show-toplevel"])?;
+  let top_level = std::fs::canonicalize(String::from_utf8_lossy(&top_level).trim_end())?;
+  let names = run_git(&top_level, &["ls-files", "-z"])?;
+
+  Ok(
+    names
+      .split(|&b| b == 0)
+      .filter(|name| !name.is_empty())
+      .map(|name| {
+        let name = String::from_utf8_lossy(name);
+
+        top_level.join(name.replace('/', std::path::MAIN_SEPARATOR_STR))
+      })
+      .filter(|path| path.is_file())
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_text_stats() {
+    assert_eq!(
+      TextStats::new(b"a\r\nb\nc\r\x00\x01\t\x1a"),
+      TextStats {
+        nul: 1,
+        lone_cr: 1,
+        lone_lf: 1,
+        crlf: 1,
+        printable: 4,
+        nonprintable: 2,
+      }
+    );
+    assert!(TextStats::new(b"a\rb").is_binary());
+    assert!(TextStats::new(b"a\x00b").is_binary());
+    assert!(TextStats::new(b"\x01").is_binary());
+    assert!(!TextStats::new(&[b'a'; 128][..]).is_binary());
+    assert!(!TextStats::new(b"a\r\nb\n").is_binary());
+  }
+
+  #[test]
+  fn test_autocrlf_eol() {
+    assert_eq!(
+      autocrlf_eol(b"a\nb\n", AutoCrlf::True),
+      Some(EndOfLine::CrLf)
+    );
+    assert_eq!(
+      autocrlf_eol(b"a\r\nb\n", AutoCrlf::Input),
+      Some(EndOfLine::Lf)
+    );
+    assert_eq!(autocrlf_eol(b"a\r\nb\r", AutoCrlf::True), None);
+    assert_eq!(autocrlf_eol(b"abc", AutoCrlf::True), None);
+  }
+
+  #[test]
+  fn test_tracked_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = std::fs::canonicalize(temp_dir.path()).unwrap();
+
+    run_git(&dir, &["init", "-q"]).unwrap();
+    std::fs::create_dir(dir.join("src")).unwrap();
+    std::fs::write(dir.join("src").join("a.txt"), "a\n").unwrap();
+    std::fs::write(dir.join("b.txt"), "b\n").unwrap();
+    std::fs::write(dir.join("untracked.txt"), "c\n").unwrap();
+    run_git(&dir, &["add", "src/a.txt", "b.txt"]).unwrap();
+    std::fs::remove_file(dir.join("b.txt")).unwrap();
+
+    assert_eq!(
+      tracked_files(&dir.join("src")).unwrap(),
+      vec![dir.join("src").join("a.txt")]
+    );
+
+    temp_dir.close().unwrap();
+  }
+}
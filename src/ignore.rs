@@ -0,0 +1,351 @@
+//! Skip the files that `.gitignore` and `.ignore` files say to leave out.
+//!
+//! Load the rules that apply to a directory with [`Ignore::load()`], add the rules for each
+//! subdirectory with [`Ignore::child()`] while walking the tree and use [`Ignore::is_ignored()`]
+//! to check each path:
+//!
+//! ```
+//! use std::path::Path;
+//! use whitespace_rs::ignore::Ignore;
+//!
+//! let dir = std::fs::canonicalize(".").unwrap();
+//! let ignore = Ignore::load(&dir).unwrap();
+//!
+//! assert!(ignore.is_ignored(&dir.join("node_modules"), true));
+//! assert!(!ignore.is_ignored(&dir.join("src"), true));
+//! ```
+//!
+//! Patterns follow the `.gitignore` rules.  Patterns in a `.ignore` file take precedence over
+//! those in a `.gitignore` file in the same directory, and patterns in a subdirectory take
+//! precedence over those in its parents, as with ripgrep.  The `target` and `node_modules`
+//! directories are always ignored unless a pattern like `!target/` includes them again.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Patterns that apply before any ignore files are read
+const DEFAULT_PATTERNS: &str = "target/\nnode_modules/\n";
+
+/// A pattern from an ignore file
+#[derive(Debug, Clone)]
+struct Pattern {
+  /// Pattern without the `!` prefix, trailing `/` or leading `/`
+  glob: Vec<char>,
+  /// Matching paths are included again
+  negated: bool,
+  /// Only directories match
+  dir_only: bool,
+  /// The pattern matches the path relative to the ignore file, not just the file name
+  anchored: bool,
+}
+
+impl Pattern {
+  /// Parse a line of an ignore file, returning `None` for blank lines and comments.
+  fn parse(line: &str) -> Option<Pattern> {
+    let line = line.trim_end();
+
+    if line.is_empty() || line.starts_with('#') {
+      return None;
+    }
+
+    let (negated, line) = match line.strip_prefix('!') {
+      Some(rest) => (true, rest),
+      None => (false, line),
+    };
+    let (dir_only, line) = match line.strip_suffix('/') {
+      Some(rest) => (true, rest),
+      None => (false, line),
+    };
+    let anchored = line.contains('/');
+    let line = line.strip_prefix('/').unwrap_or(line);
+
+    if line.is_empty() {
+      return None;
+    }
+
+    Some(Pattern {
+      glob: line.chars().collect(),
+      negated,
+      dir_only,
+      anchored,
+    })
+  }
+
+  /// Check if a path relative to the directory of the ignore file matches.
+  fn matches(&self, relative_path: &Path, is_dir: bool) -> bool {
+    if self.dir_only && !is_dir {
+      return false;
+    }
+
+    let text = if self.anchored {
+      relative_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+    } else {
+      match relative_path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => return false,
+      }
+    };
+
+    matches_glob(&self.glob, &text.chars().collect::<Vec<_>>())
+  }
+}
+
+/// The patterns read from one ignore file
+#[derive(Debug, Clone)]
+struct IgnoreFile {
+  /// Directory that contains the ignore file
+  dir: PathBuf,
+  patterns: Vec<Pattern>,
+}
+
+impl IgnoreFile {
+  /// Make the patterns for a directory from the text of an ignore file.
+  fn new(dir: &Path, text: &str) -> IgnoreFile {
+    IgnoreFile {
+      dir: dir.to_path_buf(),
+      patterns: text.lines().filter_map(Pattern::parse).collect(),
+    }
+  }
+
+  /// Read the ignore file `name` in `dir`, if there is one.
+  fn read(dir: &Path, name: &str) -> io::Result<Option<IgnoreFile>> {
+    match std::fs::read(dir.join(name)) {
+      Ok(data) => Ok(Some(IgnoreFile::new(dir, &String::from_utf8_lossy(&data)))),
+      Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+      Err(err) => Err(err),
+    }
+  }
+}
+
+/// The ignore file patterns that apply to a directory
+#[derive(Debug, Clone)]
+pub struct Ignore {
+  /// Ignore files from the outermost directory in, each taking precedence over those before it
+  files: Vec<IgnoreFile>,
+}
+
+impl Ignore {
+  /// Load the patterns that apply to the files below an absolute directory path.
+  ///
+  /// The ignore files are read from `dir` and, if it is inside a git repository, from each of
+  /// its parent directories up to the root of the repository.
+  pub fn load(dir: &Path) -> io::Result<Ignore> {
+    let mut ignore = Ignore {
+      files: vec![IgnoreFile::new(dir, DEFAULT_PATTERNS)],
+    };
+    let mut parents = Vec::new();
+
+    if !dir.join(".git").exists() {
+      for parent in dir.ancestors().skip(1) {
+        parents.push(parent);
+
+        if parent.join(".git").exists() {
+          break;
+        }
+      }
+
+      if !parents
+        .last()
+        .is_some_and(|parent| parent.join(".git").exists())
+      {
+        parents.clear();
+      }
+    }
+
+    for parent in parents.iter().rev() {
+      ignore.add_dir(parent)?;
+    }
+
+    ignore.add_dir(dir)?;
+
+    Ok(ignore)
+  }
+
+  /// Get the patterns that apply to a subdirectory, adding those from its ignore files.
+  pub fn child(&self, dir: &Path) -> io::Result<Ignore> {
+    let mut ignore = self.clone();
+
+    ignore.add_dir(dir)?;
+
+    Ok(ignore)
+  }
+
+  /// Add the patterns from the ignore files in a directory.
+  fn add_dir(&mut self, dir: &Path) -> io::Result<()> {
+    for name in [".gitignore", ".ignore"] {
+      if let Some(file) = IgnoreFile::read(dir, name)? {
+        self.files.push(file);
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Check if an absolute path below the directory is ignored.
+  ///
+  /// The last pattern that matches decides, so a `!` pattern can include a path again.
+  pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+    for file in self.files.iter().rev() {
+      let relative_path = match path.strip_prefix(&file.dir) {
+        Ok(relative_path) => relative_path,
+        Err(_) => continue,
+      };
+
+      if let Some(pattern) = file
+        .patterns
+        .iter()
+        .rev()
+        .find(|pattern| pattern.matches(relative_path, is_dir))
+      {
+        return !pattern.negated;
+      }
+    }
+
+    false
+  }
+}
+
+/// Match a `/` separated path against a pattern with `*`, `?`, `[...]` and `**` wildcards.
+///
+/// Only `**` matches across a `/`.
+pub(crate) fn matches_glob(pattern: &[char], path: &[char]) -> bool {
+  match pattern.first() {
+    None => path.is_empty(),
+    Some('*') if pattern.get(1) == Some(&'*') => match pattern.get(2) {
+      None => true,
+      Some('/') => {
+        let rest = &pattern[3..];
+
+        matches_glob(rest, path)
+          || (0..path.len()).any(|i| path[i] == '/' && matches_glob(rest, &path[i + 1..]))
+      }
+      _ => matches_glob(&pattern[1..], path),
+    },
+    Some('*') => (0..=path.len())
+      .take_while(|&i| i == 0 || path[i - 1] != '/')
+      .any(|i| matches_glob(&pattern[1..], &path[i..])),
+    Some('?') => {
+      matches!(path.first(), Some(c) if *c != '/') && matches_glob(&pattern[1..], &path[1..])
+    }
+    Some('[') => match path.first() {
+      Some(c) if *c != '/' => match match_class(pattern, *c) {
+        Some((matched, len)) => matched && matches_glob(&pattern[len..], &path[1..]),
+        None => *c == '[' && matches_glob(&pattern[1..], &path[1..]),
+      },
+      _ => false,
+    },
+    Some('\\') if pattern.len() > 1 => {
+      path.first() == Some(&pattern[1]) && matches_glob(&pattern[2..], &path[1..])
+    }
+    Some(c) => path.first() == Some(c) && matches_glob(&pattern[1..], &path[1..]),
+  }
+}
+
+/// Match a character against the `[...]` class at the start of a pattern.
+///
+/// Returns whether it matched and the length of the class, or `None` if the class isn't closed.
+fn match_class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+  let negated = matches!(pattern.get(1), Some('!') | Some('^'));
+  let start = if negated { 2 } else { 1 };
+  let mut matched = false;
+  let mut i = start;
+
+  while i < pattern.len() {
+    if pattern[i] == ']' && i > start {
+      return Some((matched != negated, i + 1));
+    }
+
+    if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|end| *end != ']') {
+      matched |= pattern[i] <= c && c <= pattern[i + 2];
+      i += 3;
+    } else {
+      matched |= pattern[i] == c;
+      i += 1;
+    }
+  }
+
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_matches_glob() {
+    let glob = |pattern: &str, path: &str| {
+      matches_glob(
+        &pattern.chars().collect::<Vec<_>>(),
+        &path.chars().collect::<Vec<_>>(),
+      )
+    };
+
+    assert!(glob("*.log", "a.log"));
+    assert!(!glob("*.log", "a/b.log"));
+    assert!(glob("a?c", "abc"));
+    assert!(!glob("a?c", "a/c"));
+    assert!(glob("**/b", "b"));
+    assert!(glob("**/b", "a/x/b"));
+    assert!(glob("a/**", "a/x/y"));
+    assert!(glob("a/**/b", "a/b"));
+    assert!(glob("a/**/b", "a/x/y/b"));
+    assert!(!glob("a/**/b", "ab"));
+    assert!(glob("[a-c]x", "bx"));
+    assert!(!glob("[!a-c]x", "bx"));
+    assert!(glob("[]]", "]"));
+    assert!(glob("[ab", "[ab"));
+    assert!(glob("\\#a", "#a"));
+  }
+
+  #[test]
+  fn test_pattern_parse() {
+    assert!(Pattern::parse("").is_none());
+    assert!(Pattern::parse("# comment").is_none());
+
+    let pattern = Pattern::parse("!/build/  ").unwrap();
+
+    assert_eq!(pattern.glob, "build".chars().collect::<Vec<_>>());
+    assert!(pattern.negated && pattern.dir_only && pattern.anchored);
+    assert!(!Pattern::parse("*.o").unwrap().anchored);
+    assert!(Pattern::parse("doc/*.txt").unwrap().anchored);
+  }
+
+  #[test]
+  fn test_is_ignored() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = std::fs::canonicalize(temp_dir.path()).unwrap();
+
+    std::fs::create_dir_all(dir.join(".git")).unwrap();
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join(".gitignore"), "*.log\n*.tmp\n/out/\ndoc/*.txt\n").unwrap();
+    std::fs::write(dir.join(".ignore"), "!keep.log\n").unwrap();
+    std::fs::write(dir.join("sub").join(".gitignore"), "!*.log\n").unwrap();
+
+    let ignore = Ignore::load(&dir).unwrap();
+    let sub_ignore = ignore.child(&dir.join("sub")).unwrap();
+
+    assert!(ignore.is_ignored(&dir.join("a.log"), false));
+    assert!(ignore.is_ignored(&dir.join("x").join("a.log"), false));
+    assert!(!ignore.is_ignored(&dir.join("keep.log"), false));
+    assert!(ignore.is_ignored(&dir.join("out"), true));
+    assert!(!ignore.is_ignored(&dir.join("out"), false));
+    assert!(!ignore.is_ignored(&dir.join("x").join("out"), true));
+    assert!(ignore.is_ignored(&dir.join("doc").join("a.txt"), false));
+    assert!(!ignore.is_ignored(&dir.join("doc").join("x").join("a.txt"), false));
+    assert!(ignore.is_ignored(&dir.join("x").join("target"), true));
+    assert!(ignore.is_ignored(&dir.join("node_modules"), true));
+    assert!(!ignore.is_ignored(&dir.join("a.txt"), false));
+    assert!(!sub_ignore.is_ignored(&dir.join("sub").join("a.log"), false));
+
+    let parent_ignore = Ignore::load(&dir.join("sub")).unwrap();
+
+    assert!(parent_ignore.is_ignored(&dir.join("sub").join("a.tmp"), false));
+    assert!(!parent_ignore.is_ignored(&dir.join("sub").join("a.log"), false));
+
+    temp_dir.close().unwrap();
+  }
+}
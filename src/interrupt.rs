@@ -0,0 +1,60 @@
+//! Stop cleanly when the user presses Ctrl-C.
+//!
+//! Call [`install_handler()`] once at startup, then check [`is_interrupted()`] between units of
+//! work, such as input files, so that the current one can be finished before stopping.  A second
+//! Ctrl-C stops the program immediately as usual.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn handle_sigint(_: libc::c_int) {
+  INTERRUPTED.store(true, Ordering::SeqCst);
+
+  // Let a second Ctrl-C stop the program
+  unsafe {
+    libc::signal(libc::SIGINT, libc::SIG_DFL);
+  }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn handle_ctrl(ctrl_type: u32) -> windows_sys::core::BOOL {
+  use windows_sys::Win32::System::Console::CTRL_C_EVENT;
+
+  // Returning false for a second Ctrl-C passes it to the default handler, which stops the program
+  if ctrl_type == CTRL_C_EVENT && !INTERRUPTED.swap(true, Ordering::SeqCst) {
+    1
+  } else {
+    0
+  }
+}
+
+/// Install a Ctrl-C handler that sets the flag returned by [`is_interrupted()`].
+pub fn install_handler() -> io::Result<()> {
+  #[cfg(unix)]
+  {
+    let handler = handle_sigint as extern "C" fn(libc::c_int);
+
+    if unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) } == libc::SIG_ERR {
+      return Err(io::Error::last_os_error());
+    }
+  }
+
+  #[cfg(windows)]
+  {
+    use windows_sys::Win32::System::Console::SetConsoleCtrlHandler;
+
+    if unsafe { SetConsoleCtrlHandler(Some(handle_ctrl), 1) } == 0 {
+      return Err(io::Error::last_os_error());
+    }
+  }
+
+  Ok(())
+}
+
+/// Check if Ctrl-C has been pressed since the handler was installed.
+pub fn is_interrupted() -> bool {
+  INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,258 @@
+//! Record the files changed in place so that the changes can be undone.
+//!
+//! A journal is a directory holding the hashes of each file before and after it was changed, and
+//! a unified diff that reverses the changes.  Create one with [`Journal::create()`], add each
+//! changed file with [`Journal::record()`], and roll the changes back with [`undo()`]:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::journal::{self, Journal};
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let temp_dir = tempfile::tempdir()?;
+//!   let file_path = temp_dir.path().join("a.txt");
+//!   let journal = Journal::create(&temp_dir.path().join("journal"))?;
+//!
+//!   std::fs::write(&file_path, "  abc\n")?;
+//!   journal.record(&file_path, b"\tabc\n", b"  abc\n")?;
+//!   journal::undo(&temp_dir.path().join("journal"), false)?;
+//!
+//!   assert_eq!(std::fs::read_to_string(&file_path)?, "\tabc\n");
+//!   Ok(())
+//! }
+//! ```
+
+use crate::error::{self, ErrorCode};
+use crate::{patch, paths};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// File listing the hashes and path of each changed file
+const FILES_NAME: &str = "files";
+/// File with the diff that reverses the changes
+const UNDO_NAME: &str = "undo.diff";
+
+/// A journal directory that changes are added to
+#[derive(Debug, Clone)]
+pub struct Journal {
+  dir: PathBuf,
+}
+
+/// Hash file contents with 64-bit FNV-1a, which is stable between builds.
+pub fn content_hash(data: &[u8]) -> u64 {
+  data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+    (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+  })
+}
+
+impl Journal {
+  /// Create a journal in a new or empty directory.
+  pub fn create(dir: &Path) -> io::Result<Journal> {
+    fs::create_dir_all(dir)?;
+
+    if dir.join(FILES_NAME).exists() {
+      return Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        error::Error::new(
+          ErrorCode::Journal,
+          format!("'{}' already has a journal", dir.to_string_lossy()),
+        ),
+      ));
+    }
+
+    fs::write(dir.join(FILES_NAME), "")?;
+    fs::write(dir.join(UNDO_NAME), "")?;
+
+    Ok(Journal {
+      dir: dir.to_path_buf(),
+    })
+  }
+
+  /// Add a file that was changed from `original` to `new`.
+  pub fn record(&self, path: &Path, original: &[u8], new: &[u8]) -> Result<(), Box<dyn Error>> {
+    let path = std::env::current_dir()?.join(path);
+    let path = path.to_string_lossy();
+    let (original_text, new_text) = match (std::str::from_utf8(original), std::str::from_utf8(new))
+    {
+      (Ok(original_text), Ok(new_text)) => (original_text, new_text),
+      _ => {
+        return Err(
+          error::Error::new(
+            ErrorCode::BadUtf8,
+            format!("'{}' can't be journaled because it isn't UTF-8", path),
+          )
+          .into(),
+        )
+      }
+    };
+    let append = |name: &str, text: &str| -> io::Result<()> {
+      OpenOptions::new()
+        .append(true)
+        .open(self.dir.join(name))?
+        .write_all(text.as_bytes())
+    };
+
+    append(
+      UNDO_NAME,
+      &patch::diff(&path, new_text, original_text).to_string(),
+    )?;
+    append(
+      FILES_NAME,
+      &format!(
+        "{:016x} {:016x} {}\n",
+        content_hash(original),
+        content_hash(new),
+        path
+      ),
+    )?;
+
+    Ok(())
+  }
+}
+
+/// Restore the files in a journal to how they were before they were changed.  Nothing is written
+/// unless every file can be restored, so this fails if any file has changed since.  Returns the
+/// files restored, or that would be if `check` is set.
+///
+/// Like the changes that were recorded, files are read and replaced with [`paths::open_input()`]
+/// and [`paths::replace_file()`], so a `.gz` file is decompressed and compressed again.
+pub fn undo(dir: &Path, check: bool) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+  let files = fs::read_to_string(dir.join(FILES_NAME))?;
+  let mut hashes = HashMap::new();
+
+  for line in files.lines() {
+    let mut fields = line.splitn(3, ' ');
+
+    match (fields.next(), fields.next(), fields.next()) {
+      (Some(original_hash), Some(new_hash), Some(path)) => {
+        hashes.insert(path.to_string(), (original_hash, new_hash));
+      }
+      _ => {
+        return Err(
+          error::Error::new(ErrorCode::Journal, format!("bad journal line '{}'", line)).into(),
+        )
+      }
+    }
+  }
+
+  let mut restored = Vec::new();
+
+  for file_patch in patch::parse_patch(&fs::read_to_string(dir.join(UNDO_NAME))?)? {
+    let (original_hash, new_hash) = hashes
+      .get(&file_patch.path)
+      .ok_or_else(|| format!("'{}' is missing from the journal", file_patch.path))?;
+    let mut content = String::new();
+
+    paths::open_input(&file_patch.path)?.read_to_string(&mut content)?;
+
+    if format!("{:016x}", content_hash(content.as_bytes())) != *new_hash {
+      return Err(
+        error::Error::new(
+          ErrorCode::Journal,
+          format!(
+            "'{}' has changed since the journal was written",
+            file_patch.path
+          ),
+        )
+        .into(),
+      );
+    }
+
+    let original = patch::apply_patch(&content, &file_patch)?;
+
+    if format!("{:016x}", content_hash(original.as_bytes())) != *original_hash {
+      return Err(
+        error::Error::new(
+          ErrorCode::Journal,
+          format!("'{}' can't be restored from the journal", file_patch.path),
+        )
+        .into(),
+      );
+    }
+
+    restored.push((PathBuf::from(&file_patch.path), original));
+  }
+
+  if !check {
+    for (path, original) in restored.iter() {
+      paths::replace_file(path, original.as_bytes())?;
+    }
+  }
+
+  Ok(restored.into_iter().map(|(path, _)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_content_hash() {
+    assert_eq!(content_hash(b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(content_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+  }
+
+  #[test]
+  fn test_undo() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let journal_dir = temp_dir.path().join("journal");
+    let a_path = temp_dir.path().join("a.txt");
+    let b_path = temp_dir.path().join("b.txt");
+    let journal = Journal::create(&journal_dir).unwrap();
+
+    assert!(Journal::create(&journal_dir).is_err());
+
+    std::fs::write(&a_path, "a\n  b\n").unwrap();
+    std::fs::write(&b_path, "c\n").unwrap();
+    journal.record(&a_path, b"a\n\tb\n", b"a\n  b\n").unwrap();
+    journal.record(&b_path, b"c\r\n", b"c\n").unwrap();
+
+    assert!(journal.record(&b_path, &[0xff], b"c\n").is_err());
+    assert_eq!(
+      undo(&journal_dir, true).unwrap(),
+      vec![a_path.clone(), b_path.clone()]
+    );
+    assert_eq!(std::fs::read_to_string(&a_path).unwrap(), "a\n  b\n");
+
+    std::fs::write(&b_path, "d\n").unwrap();
+
+    assert!(undo(&journal_dir, false).is_err());
+    assert_eq!(std::fs::read_to_string(&a_path).unwrap(), "a\n  b\n");
+
+    std::fs::write(&b_path, "c\n").unwrap();
+    undo(&journal_dir, false).unwrap();
+
+    assert_eq!(std::fs::read_to_string(&a_path).unwrap(), "a\n\tb\n");
+    assert_eq!(std::fs::read_to_string(&b_path).unwrap(), "c\r\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  #[cfg(feature = "gzip")]
+  fn test_undo_gzip() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let journal_dir = temp_dir.path().join("journal");
+    let path = temp_dir.path().join("a.log.gz");
+    let journal = Journal::create(&journal_dir).unwrap();
+    let mut writer = paths::create_output(&path).unwrap();
+    let mut content = String::new();
+
+    writer.write_all(b"a\nb\n").unwrap();
+    writer.finish().unwrap();
+    journal.record(&path, b"a\r\nb\r\n", b"a\nb\n").unwrap();
+    undo(&journal_dir, false).unwrap();
+    assert_ne!(std::fs::read(&path).unwrap(), b"a\r\nb\r\n");
+
+    paths::open_input(path.to_str().unwrap())
+      .unwrap()
+      .read_to_string(&mut content)
+      .unwrap();
+    assert_eq!(content, "a\r\nb\r\n");
+
+    temp_dir.close().unwrap();
+  }
+}
@@ -0,0 +1,330 @@
+//! A lightweight lexer that finds lines beginning inside string literals.
+//!
+//! The lexer knows just enough about common languages to follow quoted strings, triple quoted
+//! strings, template literals, Rust and C++ raw strings, here-docs, and the comments that might
+//! contain stray quotes.  It works on whole lines so that it can look ahead.
+
+use std::collections::VecDeque;
+
+/// A here-doc that starts on the line after the one that introduces it
+#[derive(Debug, PartialEq)]
+struct HereDoc {
+  /// Text of the line that ends the here-doc
+  tag: String,
+  /// The ending line can be indented, as with `<<-` or `<<~`
+  indented: bool,
+}
+
+/// Where the lexer is at the end of a line
+#[derive(Debug, PartialEq)]
+enum State {
+  Code,
+  BlockComment,
+  /// A string that ends with `end` and can contain backslash escapes
+  Quoted {
+    end: &'static str,
+  },
+  /// A raw string that ends with `end`
+  Raw {
+    end: String,
+  },
+  HereDoc(HereDoc),
+}
+
+/// Tracks whether each line begins inside a string literal.
+#[derive(Debug)]
+pub(crate) struct StringLexer {
+  state: State,
+  here_docs: VecDeque<HereDoc>,
+}
+
+impl Default for StringLexer {
+  fn default() -> Self {
+    StringLexer {
+      state: State::Code,
+      here_docs: VecDeque::new(),
+    }
+  }
+}
+
+/// Check if a byte can be part of an identifier
+fn is_ident(b: u8) -> bool {
+  b.is_ascii_alphanumeric() || b == b'_'
+}
+
+impl StringLexer {
+  /// Check if the next line begins inside a string literal or here-doc.
+  pub(crate) fn in_string(&self) -> bool {
+    matches!(
+      self.state,
+      State::Quoted { .. } | State::Raw { .. } | State::HereDoc(_)
+    )
+  }
+
+  /// Lex one line, without its line ending.
+  pub(crate) fn push_line(&mut self, line: &str) {
+    if let State::HereDoc(here_doc) = &self.state {
+      let text = if here_doc.indented {
+        line.trim_start()
+      } else {
+        line
+      };
+
+      if text.trim_end() == here_doc.tag {
+        self.state = State::Code;
+        self.start_here_doc();
+      }
+
+      return;
+    }
+
+    let b = line.as_bytes();
+    let mut i = 0;
+
+    while i < b.len() {
+      let rest = &b[i..];
+
+      match &self.state {
+        State::Code => {
+          let prev_is_ident = i > 0 && is_ident(b[i - 1]);
+
+          if rest.starts_with(b"/*") {
+            self.state = State::BlockComment;
+            i += 2;
+          } else if let Some((end, len)) = raw_string_start(b, i) {
+            self.state = State::Raw { end };
+            i += len;
+          } else if rest.starts_with(b"//") || (rest[0] == b'#' && !prev_is_ident) {
+            break;
+          } else if rest.starts_with(b"\"\"\"") {
+            self.state = State::Quoted { end: "\"\"\"" };
+            i += 3;
+          } else if rest.starts_with(b"'''") {
+            self.state = State::Quoted { end: "'''" };
+            i += 3;
+          } else if rest[0] == b'"' {
+            self.state = State::Quoted { end: "\"" };
+            i += 1;
+          } else if rest[0] == b'`' {
+            self.state = State::Quoted { end: "`" };
+            i += 1;
+          } else if rest[0] == b'\'' {
+            // Single quotes only count if they close on the same line, which skips lifetimes
+            // and apostrophes
+            i += quoted_len(&rest[1..], b"'").map_or(1, |len| len + 1);
+          } else if let Some((here_doc, len)) = here_doc_start(rest) {
+            self.here_docs.push_back(here_doc);
+            i += len;
+          } else {
+            i += 1;
+          }
+        }
+        State::BlockComment => {
+          if rest.starts_with(b"*/") {
+            self.state = State::Code;
+            i += 2;
+          } else {
+            i += 1;
+          }
+        }
+        State::Quoted { end } => match quoted_len(rest, end.as_bytes()) {
+          Some(len) => {
+            self.state = State::Code;
+            i += len;
+          }
+          None => break,
+        },
+        State::Raw { end } => match line[i..].find(end.as_str()) {
+          Some(pos) => {
+            i += pos + end.len();
+            self.state = State::Code;
+          }
+          None => break,
+        },
+        State::HereDoc(_) => break,
+      }
+    }
+
+    if self.state == State::Code {
+      self.start_here_doc();
+    }
+  }
+
+  /// Start the next here-doc that was introduced, if any
+  fn start_here_doc(&mut self) {
+    if let Some(here_doc) = self.here_docs.pop_front() {
+      self.state = State::HereDoc(here_doc);
+    }
+  }
+}
+
+/// Get the length up to and including `end`, skipping backslash escapes, or `None` if `end` isn't
+/// found.
+fn quoted_len(b: &[u8], end: &[u8]) -> Option<usize> {
+  let mut i = 0;
+
+  while i < b.len() {
+    if b[i] == b'\\' {
+      i += 2;
+    } else if b[i..].starts_with(end) {
+      return Some(i + end.len());
+    } else {
+      i += 1;
+    }
+  }
+
+  None
+}
+
+/// Check for a Rust raw string like `r#"` or a C++ raw string like `R"x(` at `i`, returning the
+/// text that ends it and the length of the text that starts it.
+fn raw_string_start(b: &[u8], i: usize) -> Option<(String, usize)> {
+  if b[i] != b'r' && b[i] != b'R' {
+    return None;
+  }
+
+  let prefix_start = if i > 0 && b[i - 1] == b'b' { i - 1 } else { i };
+
+  if prefix_start > 0 && is_ident(b[prefix_start - 1]) {
+    return None;
+  }
+
+  let hashes = b[i + 1..].iter().take_while(|&&c| c == b'#').count();
+
+  if b.get(i + 1 + hashes) != Some(&b'"') {
+    return None;
+  }
+
+  let start_len = hashes + 2;
+
+  if b[i] == b'R' && hashes == 0 {
+    let delimiter_len = b[i + 2..]
+      .iter()
+      .take(17)
+      .position(|&c| c == b'(')
+      .filter(|&len| b[i + 2..i + 2 + len].iter().all(|&c| is_ident(c)));
+
+    if let Some(len) = delimiter_len {
+      let delimiter = String::from_utf8_lossy(&b[i + 2..i + 2 + len]);
+
+      return Some((format!("){}\"", delimiter), start_len + len + 1));
+    }
+  }
+
+  Some((format!("\"{}", "#".repeat(hashes)), start_len))
+}
+
+/// Check for a here-doc like `<<EOF`, `<<-'EOF'` or `<<~EOS` at the start of `b`, returning it and
+/// the length of its text.  Tags must be upper case so that shift operators aren't mistaken for
+/// here-docs.
+fn here_doc_start(b: &[u8]) -> Option<(HereDoc, usize)> {
+  if !b.starts_with(b"<<") {
+    return None;
+  }
+
+  let mut i = 2;
+
+  if b.get(i) == Some(&b'<') {
+    i += 1;
+  }
+
+  let indented = matches!(b.get(i), Some(b'-') | Some(b'~'));
+
+  if indented {
+    i += 1;
+  }
+
+  let quote = b.get(i).copied().filter(|&c| c == b'\'' || c == b'"');
+
+  if quote.is_some() {
+    i += 1;
+  }
+
+  let tag_len = b[i..]
+    .iter()
+    .take_while(|&&c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == b'_')
+    .count();
+
+  if tag_len < 2 || !b[i].is_ascii_uppercase() {
+    return None;
+  }
+
+  let tag = String::from_utf8_lossy(&b[i..i + tag_len]).to_string();
+
+  i += tag_len;
+
+  if let Some(quote) = quote {
+    if b.get(i) != Some(&quote) {
+      return None;
+    }
+
+    i += 1;
+  }
+
+  Some((HereDoc { tag, indented }, i))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Get whether each line of `text` begins inside a string
+  fn lex(text: &str) -> Vec<bool> {
+    let mut lexer = StringLexer::default();
+
+    text
+      .lines()
+      .map(|line| {
+        let in_string = lexer.in_string();
+
+        lexer.push_line(line);
+        in_string
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_quoted_strings() {
+    assert_eq!(
+      lex("let s = \"a\n  b \\\" c\n\";\nx\n"),
+      vec![false, true, true, false]
+    );
+    assert_eq!(
+      lex("s = '''\n  a\n'''\ndon't\n  x\n"),
+      vec![false, true, true, false, false]
+    );
+    assert_eq!(lex("f('a', `\n  b`)\n  c\n"), vec![false, true, false]);
+    assert_eq!(
+      lex("fn f<'a>(x: &'a str) {\n  x\n}\n"),
+      vec![false, false, false]
+    );
+  }
+
+  #[test]
+  fn test_comments() {
+    assert_eq!(lex("// \"\n  a\n# \"\n  b\n"), vec![false; 4]);
+    assert_eq!(lex("/* \"\n  \" */\n  a\n"), vec![false; 3]);
+  }
+
+  #[test]
+  fn test_raw_strings() {
+    assert_eq!(
+      lex("let s = r#\"\n  \"a\"\n\"#;\n  x\n"),
+      vec![false, true, true, false]
+    );
+    assert_eq!(
+      lex("auto s = R\"x(\n  )\"\n)x\";\n  y\n"),
+      vec![false, true, true, false]
+    );
+    assert_eq!(lex("bar\"\n  a\n\"\n"), vec![false, true, true]);
+  }
+
+  #[test]
+  fn test_here_docs() {
+    assert_eq!(
+      lex("cat <<EOF <<-'END'\n  a\nEOF\n\tb\n\tEND\n  c\n"),
+      vec![false, true, true, true, true, false]
+    );
+    assert_eq!(lex("x = 1<<n\n  a\n"), vec![false, false]);
+  }
+}
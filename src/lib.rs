@@ -3,7 +3,126 @@
 //! This crate provides a set of tools for exploring and fixing common whitespace inconsistencies in text files.
 //! It is compatible with text files in UTF-8 format.
 //!
-//! If installed globally the command line tools `spacer` and `ender` are available for fixing beginnings and endings respectively.
+//! If installed globally the command line tools `spacer`, `ender` and `trimmer` are available for fixing beginnings, endings and trailing whitespace respectively.
+//!
+//! # Features
+//!
+//! - `cli` (default) - builds the command line tools, and the [`batch`] module that runs them over
+//!   many input files.  Turn off default features to use the crate as a library without the
+//!   argument parsing and report formatting dependencies.
+//! - `archive` - the [`archive`] module for files inside tar and zip archives.
+//! - `encoding` - the [`encoding`] module, and reading and writing files in UTF-16, Windows-1252
+//!   and ISO-8859-1 in the [`run`] module.  Otherwise only UTF-8 files are read.
+//! - `gzip` - transparent reading and writing of `.gz` files in [`paths`].
+//! - `interrupt` - the [`interrupt`] module for stopping cleanly on Ctrl-C.
+//! - `lock` - locking the directories written to in [`paths`], so that runs writing to the same
+//!   directory take turns.
+//! - `serde` - `Serialize` for the information types.
+//!
+//! To find out everything about the whitespace in a file in one pass use [`analyze()`], or
+//! [`analyze_str()`] for text that is already in memory.  Use [`capabilities()`] to find out the
+//! version of the crate and the features it was built with.  To do exactly what the command line
+//! tools do for one file use the [`run`] module.
 
+pub mod analyzer;
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod ascii;
+pub mod audit;
+#[cfg(feature = "cli")]
+pub mod batch;
+pub mod compare;
+pub mod config;
+pub mod decode;
+#[cfg(feature = "encoding")]
+pub mod encoding;
 pub mod ender;
+pub mod error;
+pub mod filetype;
+pub mod git;
+pub mod ignore;
+#[cfg(feature = "interrupt")]
+pub mod interrupt;
+pub mod journal;
+mod lexer;
+pub mod lines;
+pub mod normalize;
+pub mod notebook;
+pub mod patch;
+pub mod paths;
+pub mod rules;
+pub mod run;
 pub mod spacer;
+pub mod trimmer;
+
+pub use analyzer::{analyze, analyze_str, WhitespaceReport};
+
+/// The version of the crate and the optional features it was built with
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct Capabilities {
+  /// Version of the crate, like `2.1.2`
+  pub version: &'static str,
+  /// The [`archive`] module is available
+  pub archive: bool,
+  /// The [`encoding`] module is available
+  pub encoding: bool,
+  /// `.gz` files are read and written transparently
+  pub gzip: bool,
+  /// The [`interrupt`] module is available
+  pub interrupt: bool,
+  /// Directories being written to are locked
+  pub lock: bool,
+  /// The information types implement `Serialize`
+  pub serde: bool,
+}
+
+impl Capabilities {
+  /// Get the names of the optional features that were built in.
+  pub fn features(&self) -> Vec<&'static str> {
+    [
+      ("archive", self.archive),
+      ("encoding", self.encoding),
+      ("gzip", self.gzip),
+      ("interrupt", self.interrupt),
+      ("lock", self.lock),
+      ("serde", self.serde),
+    ]
+    .iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| *name)
+    .collect()
+  }
+}
+
+/// Get the version of the crate and the optional features it was built with, e.g. for a host
+/// that is linked with it to check what it can do.
+pub fn capabilities() -> Capabilities {
+  Capabilities {
+    version: env!("CARGO_PKG_VERSION"),
+    archive: cfg!(feature = "archive"),
+    encoding: cfg!(feature = "encoding"),
+    gzip: cfg!(feature = "gzip"),
+    interrupt: cfg!(feature = "interrupt"),
+    lock: cfg!(feature = "lock"),
+    serde: cfg!(feature = "serde"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_capabilities() {
+    let capabilities = capabilities();
+
+    assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(capabilities.serde, cfg!(feature = "serde"));
+    assert_eq!(
+      capabilities.features().contains(&"archive"),
+      cfg!(feature = "archive")
+    );
+  }
+}
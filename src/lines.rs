@@ -0,0 +1,408 @@
+//! Process a file line by line.
+//!
+//! To keep, replace or drop each line of a [`Read`] trait object, create a [`Write`] trait
+//! object and use [`process_lines()`] with a callback that returns a [`LineAction`] for each
+//! [`LineRecord`]:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::lines::{self, LineAction};
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let mut reader = "abc\r\n# comment\nxyz  \n".as_bytes();
+//!   let mut writer = Vec::new();
+//!   let num_lines = lines::process_lines(&mut reader, &mut writer, |line| {
+//!     if line.content.starts_with('#') {
+//!       LineAction::Drop
+//!     } else {
+//!       LineAction::Replace(line.content.trim_end().to_string())
+//!     }
+//!   })?;
+//!
+//!   println!("{}", num_lines);
+//!   Ok(())
+//! }
+//! ```
+//!
+//! To do your own processing of each line, iterate over the content and line ending of each line
+//! with [`lines_with_endings()`]:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::lines;
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   for line in lines::lines_with_endings("abc\r\nxyz".as_bytes()) {
+//!     let (content, ending) = line?;
+//!     println!("{:?} {:?}", content, ending);
+//!   }
+//!
+//!   Ok(())
+//! }
+//! ```
+
+use crate::decode::{DecodePolicy, Decoder};
+use crate::ender::EndOfLine;
+use std::error::Error;
+use std::io::{self, BufReader, Bytes, Read, Write};
+use std::iter::Peekable;
+
+/// A line passed to the [`process_lines()`] callback.
+#[derive(Debug, PartialEq)]
+pub struct LineRecord<'a> {
+  /// Line number, starting at 1
+  pub number: usize,
+  /// Content of the line without the line ending
+  pub content: &'a str,
+  /// Line ending, or `None` for the last line of a file with no final line ending
+  pub ending: Option<EndOfLine>,
+}
+
+/// What to do with a line passed to the [`process_lines()`] callback.
+#[derive(Debug, PartialEq)]
+pub enum LineAction {
+  /// Write the line unchanged
+  Keep,
+  /// Write new content followed by the original line ending
+  Replace(String),
+  /// Don't write the line or its ending
+  Drop,
+}
+
+/// Directive that leaves the whitespace of the lines after it unchanged
+pub const DISABLE_DIRECTIVE: &str = "ws:disable";
+/// Directive that ends the lines left unchanged by [`DISABLE_DIRECTIVE`], after its own line
+pub const ENABLE_DIRECTIVE: &str = "ws:enable";
+
+/// Characters that comment markers are made of
+const COMMENT_MARKER_CHARS: &[char] = &['#', '/', '*', ';', '%', '-', '!', '<'];
+/// Comment markers, one of which a run of [`COMMENT_MARKER_CHARS`] must contain to start a comment
+const COMMENT_MARKERS: &[&str] = &["#", "//", "/*", "*", "--", ";", "%"];
+/// Ends of comments that can follow a directive in the same word
+const COMMENT_ENDS: &[&str] = &["", "*/", "-->"];
+/// Longest word that can hold a directive, with a comment marker before it and the end of the
+/// comment after it
+const MAX_DIRECTIVE_WORD_LEN: usize = 32;
+
+/// Track the `ws:disable` and `ws:enable` directives in the input a character at a time.
+///
+/// A directive is a word in a comment, right after the comment marker, such as `# ws:disable`,
+/// `//ws:disable` or `<!-- ws:disable -->`.  The lines after one with `ws:disable`, up to and
+/// including the next one with `ws:enable`, are disabled.  Whether a line is disabled is known
+/// before any of it is seen, so the input doesn't need to be buffered.
+#[derive(Debug, Default)]
+pub(crate) struct Directives {
+  /// The directives are tracked, otherwise no line is disabled
+  enabled: bool,
+  /// The current line is disabled
+  disabled: bool,
+  /// The current word of the current line, if it isn't too long to hold a directive
+  word: String,
+  /// The current word is too long to hold a directive
+  long_word: bool,
+  /// The word before the current one is a comment marker
+  after_marker: bool,
+  /// The current line contains the directive that changes whether the next line is disabled
+  found: bool,
+}
+
+impl Directives {
+  /// Create a tracker for the directives, which never disables a line unless `enabled`
+  pub(crate) fn new(enabled: bool) -> Self {
+    Directives {
+      enabled,
+      ..Default::default()
+    }
+  }
+
+  /// Check if the current line is disabled
+  pub(crate) fn is_disabled(&self) -> bool {
+    self.disabled
+  }
+
+  /// Track a character of the current line, other than its line ending
+  pub(crate) fn push(&mut self, c: char) {
+    if !self.enabled || self.found {
+      return;
+    }
+
+    if c.is_whitespace() {
+      self.end_word();
+    } else if self.word.len() < MAX_DIRECTIVE_WORD_LEN {
+      self.word.push(c);
+    } else {
+      self.long_word = true;
+    }
+  }
+
+  /// Track the whole of the current line, other than its line ending, and end it
+  pub(crate) fn push_line(&mut self, line: &str) {
+    line.chars().for_each(|c| self.push(c));
+    self.end_line();
+  }
+
+  /// Start the next line
+  pub(crate) fn end_line(&mut self) {
+    if !self.found {
+      self.end_word();
+    }
+
+    if self.found {
+      self.disabled = !self.disabled;
+    }
+
+    self.after_marker = false;
+    self.found = false;
+  }
+
+  /// Check if the current word is the directive that changes whether the next line is disabled
+  fn end_word(&mut self) {
+    if self.word.is_empty() && !self.long_word {
+      return;
+    }
+
+    let directive = if self.disabled {
+      ENABLE_DIRECTIVE
+    } else {
+      DISABLE_DIRECTIVE
+    };
+    let rest = self.word.trim_start_matches(COMMENT_MARKER_CHARS);
+    let marker = &self.word[..self.word.len() - rest.len()];
+    let is_marker = COMMENT_MARKERS.iter().any(|m| marker.contains(m));
+
+    if !self.long_word {
+      self.found = (is_marker || (marker.is_empty() && self.after_marker))
+        && rest
+          .strip_prefix(directive)
+          .is_some_and(|end| COMMENT_ENDS.contains(&end));
+      self.after_marker = is_marker && rest.is_empty();
+    } else {
+      self.after_marker = false;
+    }
+
+    self.word.clear();
+    self.long_word = false;
+  }
+}
+
+/// An iterator over the lines of a [`Read`] trait object, created by [`lines_with_endings()`].
+pub struct LinesWithEndings<R: Read> {
+  decoder: Peekable<Decoder<Bytes<BufReader<R>>>>,
+}
+
+/// Iterate over the content and line ending of each line in the input.
+///
+/// Lines end with CR, LF or CRLF, the same as for [`ender`](crate::ender).  The ending is `None`
+/// for the last line of input with no final line ending, and a final empty line after the last
+/// line ending isn't returned.  Input that isn't valid UTF-8 returns an error.
+pub fn lines_with_endings<R: Read>(reader: R) -> LinesWithEndings<R> {
+  LinesWithEndings {
+    decoder: Decoder::new(BufReader::new(reader).bytes(), DecodePolicy::Strict).peekable(),
+  }
+}
+
+impl<R: Read> Iterator for LinesWithEndings<R> {
+  type Item = io::Result<(String, Option<EndOfLine>)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let mut content = String::new();
+
+    while let Some(value) = self.decoder.next() {
+      let c = match value {
+        Ok(c) => c,
+        Err(err) => return Some(Err(err)),
+      };
+      let ending = if c == '\r' {
+        if matches!(self.decoder.peek(), Some(Ok(c)) if *c == '\n') {
+          self.decoder.next();
+          EndOfLine::CrLf
+        } else {
+          EndOfLine::Cr
+        }
+      } else if c == '\n' {
+        EndOfLine::Lf
+      } else {
+        content.push(c);
+        continue;
+      };
+
+      return Some(Ok((content, Some(ending))));
+    }
+
+    if content.is_empty() {
+      None
+    } else {
+      Some(Ok((content, None)))
+    }
+  }
+}
+
+/// Call `f` for each line in the input and write the lines out according to the returned
+/// [`LineAction`].
+///
+/// A final empty line after the last line ending is not passed to `f`.  Returns the number of
+/// lines written.
+pub fn process_lines<F>(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  mut f: F,
+) -> Result<usize, Box<dyn Error>>
+where
+  F: FnMut(LineRecord) -> LineAction,
+{
+  let mut number = 0;
+  let mut num_written = 0;
+  let mut write_line = |content: &str, ending: Option<EndOfLine>| -> Result<(), Box<dyn Error>> {
+    number += 1;
+
+    let action = f(LineRecord {
+      number,
+      content,
+      ending,
+    });
+    let content = match action {
+      LineAction::Keep => content,
+      LineAction::Replace(ref s) => s.as_str(),
+      LineAction::Drop => return Ok(()),
+    };
+
+    writer.write_all(content.as_bytes())?;
+    writer.write_all(ending.map_or("", EndOfLine::as_str).as_bytes())?;
+    num_written += 1;
+
+    Ok(())
+  };
+
+  for line in lines_with_endings(reader) {
+    let (content, ending) = line?;
+
+    write_line(&content, ending)?;
+  }
+
+  writer.flush()?;
+
+  Ok(num_written)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_process_lines() {
+    let mut records = Vec::new();
+    let mut output = Vec::new();
+    let num_lines = process_lines(&mut "a\r\nb\rc\nd".as_bytes(), &mut output, |line| {
+      records.push((line.number, line.content.to_string(), line.ending));
+
+      match line.content {
+        "b" => LineAction::Drop,
+        "c" => LineAction::Replace("C".to_string()),
+        _ => LineAction::Keep,
+      }
+    })
+    .unwrap();
+
+    assert_eq!(num_lines, 3);
+    assert_eq!(String::from_utf8(output).unwrap(), "a\r\nC\nd");
+    assert_eq!(
+      records,
+      vec![
+        (1, "a".to_string(), Some(EndOfLine::CrLf)),
+        (2, "b".to_string(), Some(EndOfLine::Cr)),
+        (3, "c".to_string(), Some(EndOfLine::Lf)),
+        (4, "d".to_string(), None),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_lines_with_endings() {
+    let lines: Vec<_> = lines_with_endings("a\r\n\rb\nc".as_bytes())
+      .map(Result::unwrap)
+      .collect();
+
+    assert_eq!(
+      lines,
+      vec![
+        ("a".to_string(), Some(EndOfLine::CrLf)),
+        ("".to_string(), Some(EndOfLine::Cr)),
+        ("b".to_string(), Some(EndOfLine::Lf)),
+        ("c".to_string(), None),
+      ]
+    );
+    assert_eq!(lines_with_endings("a\n".as_bytes()).count(), 1);
+    assert!(lines_with_endings(&[b'a', 0xff][..])
+      .next()
+      .unwrap()
+      .is_err());
+  }
+
+  #[test]
+  fn test_directives() {
+    let mut directives = Directives::new(true);
+    let disabled: Vec<_> = [
+      "a",
+      "# ws:disable",
+      "b",
+      "# ws:disable again",
+      "# ws:enable",
+      "c",
+    ]
+    .iter()
+    .map(|line| {
+      let is_disabled = directives.is_disabled();
+
+      line.chars().for_each(|c| directives.push(c));
+      directives.end_line();
+      is_disabled
+    })
+    .collect();
+
+    assert_eq!(disabled, vec![false, false, true, true, true, false]);
+
+    for line in [
+      "x = 1 #ws:disable",
+      "x = 1 // ws:disable",
+      "/*  ws:disable */",
+      "<!-- ws:disable-->",
+      "-- ws:disable",
+      "/// ws:disable for a fixture",
+    ] {
+      let mut directives = Directives::new(true);
+
+      directives.push_line(line);
+      assert!(directives.is_disabled(), "{}", line);
+    }
+
+    for line in [
+      "x ws:disable",
+      "x = \"# ws:disable\"",
+      "# keep ws:disable",
+      "# ws:disabled",
+      "#ws:disable_all",
+      "- ws:disable",
+      "#\"ws:disable",
+    ] {
+      let mut directives = Directives::new(true);
+
+      directives.push_line(line);
+      assert!(!directives.is_disabled(), "{}", line);
+    }
+
+    let mut directives = Directives::new(false);
+
+    directives.push_line("# ws:disable");
+    assert!(!directives.is_disabled());
+  }
+
+  #[test]
+  fn test_process_lines_final_ending() {
+    let mut output = Vec::new();
+    let num_lines =
+      process_lines(&mut "a\n\n".as_bytes(), &mut output, |_| LineAction::Keep).unwrap();
+
+    assert_eq!(num_lines, 2);
+    assert_eq!(String::from_utf8(output).unwrap(), "a\n\n");
+  }
+}
@@ -0,0 +1,174 @@
+//! Normalize whitespace as it is written.
+//!
+//! To give output line endings, and optionally line beginnings, of one kind as it is generated,
+//! wrap a [`Write`] trait object in a [`NormalizingWriter`] and call
+//! [`finish()`](NormalizingWriter::finish) when done:
+//!
+//! ```
+//! use std::error::Error;
+//! use std::io::Write;
+//! use whitespace_rs::ender::EndOfLine;
+//! use whitespace_rs::normalize::NormalizingWriter;
+//! use whitespace_rs::spacer::BeginningOfLine;
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let mut writer =
+//!     NormalizingWriter::new(Vec::new(), EndOfLine::CrLf).bol(Some(BeginningOfLine::Spaces(4)));
+//!
+//!   write!(writer, "fn main() {{\n\tprintln!();\r}}\n")?;
+//!
+//!   let output = writer.finish()?;
+//!
+//!   assert_eq!(output, b"fn main() {\r\n    println!();\r\n}\r\n");
+//!   Ok(())
+//! }
+//! ```
+//!
+//! Output is handled a byte at a time without decoding it, so it can be in any encoding that is a
+//! superset of ASCII, and a CRLF line ending or a line beginning can be split across writes.
+
+use crate::ender::EndOfLine;
+use crate::spacer::{self, BeginningOfLine};
+use std::io::{self, Write};
+
+/// A [`Write`] that converts line endings, and optionally line beginnings, before writing to
+/// another [`Write`].
+#[derive(Debug)]
+pub struct NormalizingWriter<W: Write> {
+  inner: W,
+  new_eol: EndOfLine,
+  new_bol: Option<BeginningOfLine>,
+  /// The last byte written was a CR, so a following LF is part of the same line ending
+  after_cr: bool,
+  /// No bytes other than spaces and tabs have been written on the current line
+  at_bol: bool,
+  /// Spaces and tabs at the beginning of the current line, held back until the line is known to
+  /// go on past them
+  bol: String,
+  /// Converted bytes waiting to be written to `inner`
+  buf: Vec<u8>,
+}
+
+impl<W: Write> NormalizingWriter<W> {
+  /// Create a writer that converts every line ending to `new_eol`.
+  pub fn new(inner: W, new_eol: EndOfLine) -> Self {
+    NormalizingWriter {
+      inner,
+      new_eol,
+      new_bol: None,
+      after_cr: false,
+      at_bol: true,
+      bol: String::new(),
+      buf: Vec::new(),
+    }
+  }
+
+  /// Also convert the spaces and tabs at the beginning of each line to `new_bol`, or leave them
+  /// unchanged if `None`.
+  pub fn bol(mut self, new_bol: Option<BeginningOfLine>) -> Self {
+    self.new_bol = new_bol;
+    self
+  }
+
+  /// Get a reference to the underlying writer.
+  pub fn get_ref(&self) -> &W {
+    &self.inner
+  }
+
+  /// Write the beginning of a last line that has nothing but spaces and tabs, flush and return
+  /// the underlying writer.
+  pub fn finish(mut self) -> io::Result<W> {
+    self.end_bol();
+    self.inner.write_all(&self.buf)?;
+    self.inner.flush()?;
+
+    Ok(self.inner)
+  }
+
+  /// Write the held back beginning of the current line, converted if there is a new one
+  fn end_bol(&mut self) {
+    if let Some(new_bol) = self.new_bol {
+      self
+        .buf
+        .extend_from_slice(spacer::convert_bol(&self.bol, new_bol).as_bytes());
+      self.bol.clear();
+    }
+  }
+}
+
+impl<W: Write> Write for NormalizingWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    for &b in buf {
+      let after_cr = self.after_cr;
+
+      self.after_cr = b == b'\r';
+
+      match b {
+        b'\n' if after_cr => (),
+        b'\r' | b'\n' => {
+          self.end_bol();
+          self.buf.extend_from_slice(self.new_eol.as_str().as_bytes());
+          self.at_bol = true;
+        }
+        b' ' | b'\t' if self.at_bol && self.new_bol.is_some() => self.bol.push(b as char),
+        _ => {
+          if self.at_bol {
+            self.end_bol();
+            self.at_bol = false;
+          }
+
+          self.buf.push(b);
+        }
+      }
+    }
+
+    self.inner.write_all(&self.buf)?;
+    self.buf.clear();
+
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_normalizing_writer() {
+    let mut writer = NormalizingWriter::new(Vec::new(), EndOfLine::Lf);
+
+    for chunk in ["a\r", "\nb\r", "c\r\n", "\r\n\td  "] {
+      writer.write_all(chunk.as_bytes()).unwrap();
+    }
+
+    assert_eq!(writer.get_ref(), b"a\nb\nc\n\n\td  ");
+    assert_eq!(writer.finish().unwrap(), b"a\nb\nc\n\n\td  ");
+  }
+
+  #[test]
+  fn test_normalizing_writer_bol() {
+    let output = |new_bol| {
+      let mut writer = NormalizingWriter::new(Vec::new(), EndOfLine::CrLf).bol(Some(new_bol));
+
+      for chunk in [" ", " \t", "a \tb\n", "\t", "  \n", "  "] {
+        writer.write_all(chunk.as_bytes()).unwrap();
+      }
+
+      String::from_utf8(writer.finish().unwrap()).unwrap()
+    };
+
+    assert_eq!(
+      output(BeginningOfLine::Spaces(4)),
+      "    a \tb\r\n      \r\n  "
+    );
+    assert_eq!(
+      output(BeginningOfLine::Tabs(4, false)),
+      "\ta \tb\r\n\t  \r\n  "
+    );
+    assert_eq!(output(BeginningOfLine::Tabs(4, true)), "\ta \tb\r\n\t\r\n");
+  }
+}
@@ -0,0 +1,541 @@
+//! Normalize whitespace inside the cells of Jupyter notebooks.
+//!
+//! A notebook is JSON, so the line endings and beginnings of the file itself say nothing about
+//! the code in it.  To normalize the whitespace in the source of the code and Markdown cells
+//! only, leaving the metadata, outputs and the layout of the rest of the JSON byte for byte as
+//! they were, use [`normalize_notebook()`]:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::notebook;
+//! use whitespace_rs::spacer::BeginningOfLine;
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let text = r#"{"cells": [{"cell_type": "code", "source": ["if x:  \r\n", "\tpass"]}]}"#;
+//!   let (new_text, num_changed) = notebook::normalize_notebook(text, Some(BeginningOfLine::Spaces(4)))?;
+//!
+//!   assert_eq!(new_text, r#"{"cells": [{"cell_type": "code", "source": ["if x:\n", "    pass"]}]}"#);
+//!   assert_eq!(num_changed, 1);
+//!   Ok(())
+//! }
+//! ```
+//!
+//! Use [`map_cell_sources()`] to change the cell sources in other ways.
+
+use crate::ender::{self, EndOfLine};
+use crate::error::{self, ErrorCode};
+use crate::lines::{self, LineAction};
+use crate::spacer::{self, BeginningOfLine, BolOptions};
+use std::error::Error;
+use std::fmt::Write;
+
+/// Types of notebook cells
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CellType {
+  /// Code cell
+  Code,
+  /// Markdown cell
+  Markdown,
+  /// Raw cell, or a cell of a type that isn't known
+  Raw,
+}
+
+/// The source of a cell and where it is in the notebook text
+#[derive(Debug)]
+struct CellSource {
+  cell_type: CellType,
+  /// Byte range of the `source` value
+  start: usize,
+  end: usize,
+  /// Source as one string
+  text: String,
+  /// Source value is an array of lines, as nbformat writes it, rather than a string
+  is_array: bool,
+}
+
+/// A minimal JSON reader that finds the cell sources without building a tree
+struct Parser<'a> {
+  text: &'a str,
+  pos: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn error(&self, message: &str) -> Box<dyn Error> {
+    error::Error::new(
+      ErrorCode::Notebook,
+      format!("{} at byte {} of the notebook", message, self.pos),
+    )
+    .into()
+  }
+
+  fn skip_whitespace(&mut self) {
+    let rest = &self.text[self.pos..];
+
+    self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\r', '\n']).len();
+  }
+
+  fn peek(&mut self) -> Option<u8> {
+    self.skip_whitespace();
+    self.text.as_bytes().get(self.pos).copied()
+  }
+
+  fn expect(&mut self, b: u8) -> Result<(), Box<dyn Error>> {
+    if self.peek() == Some(b) {
+      self.pos += 1;
+      Ok(())
+    } else {
+      Err(self.error(&format!("expected '{}'", b as char)))
+    }
+  }
+
+  /// Read the items of an object or array, calling `f` for each one after its key, if any
+  fn items<F>(&mut self, open: u8, close: u8, mut f: F) -> Result<(), Box<dyn Error>>
+  where
+    F: FnMut(&mut Self, Option<String>) -> Result<(), Box<dyn Error>>,
+  {
+    self.expect(open)?;
+
+    if self.peek() == Some(close) {
+      self.pos += 1;
+      return Ok(());
+    }
+
+    loop {
+      let key = if open == b'{' {
+        let key = self.string()?;
+
+        self.expect(b':')?;
+        Some(key)
+      } else {
+        None
+      };
+
+      f(self, key)?;
+
+      match self.peek() {
+        Some(b',') => self.pos += 1,
+        Some(b) if b == close => {
+          self.pos += 1;
+          return Ok(());
+        }
+        _ => return Err(self.error(&format!("expected ',' or '{}'", close as char))),
+      }
+    }
+  }
+
+  fn string(&mut self) -> Result<String, Box<dyn Error>> {
+    self.expect(b'"')?;
+
+    let mut s = String::new();
+    let mut chars = self.text[self.pos..].char_indices();
+
+    while let Some((i, c)) = chars.next() {
+      match c {
+        '"' => {
+          self.pos += i + 1;
+          return Ok(s);
+        }
+        '\\' => {
+          let escaped = match chars.next().map(|(_, c)| c) {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => {
+              let unit = match hex_digits(&mut chars) {
+                Some(high @ 0xd800..=0xdbff) => {
+                  match (chars.next(), chars.next(), hex_digits(&mut chars)) {
+                    (Some((_, '\\')), Some((_, 'u')), Some(low @ 0xdc00..=0xdfff)) => {
+                      Some(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+                    }
+                    _ => None,
+                  }
+                }
+                unit => unit,
+              };
+
+              match unit.and_then(char::from_u32) {
+                Some(c) => c,
+                None => return Err(self.error("bad \\u escape")),
+              }
+            }
+            _ => return Err(self.error("bad escape")),
+          };
+
+          s.push(escaped);
+        }
+        c => s.push(c),
+      }
+    }
+
+    Err(self.error("unterminated string"))
+  }
+
+  fn skip_value(&mut self) -> Result<(), Box<dyn Error>> {
+    match self.peek() {
+      Some(b'{') => self.items(b'{', b'}', |parser, _| parser.skip_value()),
+      Some(b'[') => self.items(b'[', b']', |parser, _| parser.skip_value()),
+      Some(b'"') => self.string().map(|_| ()),
+      Some(_) => {
+        let rest = &self.text[self.pos..];
+        let len = rest
+          .find(|c: char| !(c.is_ascii_alphanumeric() || "+-.".contains(c)))
+          .unwrap_or(rest.len());
+
+        if len == 0 {
+          return Err(self.error("expected a value"));
+        }
+
+        self.pos += len;
+        Ok(())
+      }
+      None => Err(self.error("expected a value")),
+    }
+  }
+
+  fn cell(&mut self) -> Result<Option<CellSource>, Box<dyn Error>> {
+    let mut cell_type = CellType::Raw;
+    let mut source = None;
+
+    self.items(b'{', b'}', |parser, key| match key.as_deref() {
+      Some("cell_type") => {
+        cell_type = match parser.string()?.as_str() {
+          "code" => CellType::Code,
+          "markdown" => CellType::Markdown,
+          _ => CellType::Raw,
+        };
+        Ok(())
+      }
+      Some("source") => {
+        let start = parser.pos;
+        let (text, is_array) = if parser.peek() == Some(b'[') {
+          let mut text = String::new();
+
+          parser.items(b'[', b']', |parser, _| {
+            text += &parser.string()?;
+            Ok(())
+          })?;
+          (text, true)
+        } else {
+          (parser.string()?, false)
+        };
+
+        source = Some((start, parser.pos, text, is_array));
+        Ok(())
+      }
+      _ => parser.skip_value(),
+    })?;
+
+    Ok(source.map(|(start, end, text, is_array)| CellSource {
+      cell_type,
+      start,
+      end,
+      text,
+      is_array,
+    }))
+  }
+
+  fn cell_sources(&mut self) -> Result<Vec<CellSource>, Box<dyn Error>> {
+    let mut sources = Vec::new();
+
+    self.items(b'{', b'}', |parser, key| {
+      if key.as_deref() == Some("cells") {
+        parser.items(b'[', b']', |parser, _| {
+          sources.extend(parser.cell()?);
+          Ok(())
+        })
+      } else {
+        parser.skip_value()
+      }
+    })?;
+
+    if self.peek().is_some() {
+      return Err(self.error("unexpected text"));
+    }
+
+    Ok(sources)
+  }
+}
+
+/// Read the four hex digits of a `\\u` escape
+fn hex_digits(chars: &mut std::str::CharIndices) -> Option<u32> {
+  let digits: String = chars.take(4).map(|(_, c)| c).collect();
+
+  u32::from_str_radix(&digits, 16).ok()
+}
+
+/// Quote a string for JSON, leaving characters that aren't ASCII as they are like nbformat
+fn json_string(s: &str) -> String {
+  let mut quoted = String::with_capacity(s.len() + 2);
+
+  quoted.push('"');
+
+  for c in s.chars() {
+    match c {
+      '"' => quoted.push_str("\\\""),
+      '\\' => quoted.push_str("\\\\"),
+      '\n' => quoted.push_str("\\n"),
+      '\r' => quoted.push_str("\\r"),
+      '\t' => quoted.push_str("\\t"),
+      c if (c as u32) < 0x20 => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+      c => quoted.push(c),
+    }
+  }
+
+  quoted.push('"');
+  quoted
+}
+
+/// Write a new source value, splitting it into lines that keep their line feeds if the old one
+/// was an array, and laying the array out the same way as the old one
+fn source_value(old_value: &str, text: &str, is_array: bool) -> String {
+  if !is_array {
+    return json_string(text);
+  }
+
+  let lines: Vec<&str> = text.split_inclusive('\n').collect();
+
+  if lines.is_empty() {
+    return "[]".to_string();
+  }
+
+  let inner = &old_value[1..old_value.len() - 1];
+  let first_sep = &inner[..inner.len() - inner.trim_start().len()];
+  let last_sep = &inner[inner.trim_end().len()..];
+  let sep = match inner.trim().is_empty() {
+    true => ", ".to_string(),
+    false => {
+      // Find the separator after the first string in the old array
+      let mut parser = Parser {
+        text: inner,
+        pos: 0,
+      };
+
+      match parser.string() {
+        Ok(_) => {
+          let after = &inner[parser.pos..];
+          let len = after.len() - after.trim_start_matches([',', ' ', '\t', '\r', '\n']).len();
+
+          if after[..len].contains(',') && parser.pos + len < inner.trim_end().len() {
+            after[..len].to_string()
+          } else {
+            format!(",{}", first_sep)
+          }
+        }
+        Err(_) => format!(",{}", first_sep),
+      }
+    }
+  };
+  let strings: Vec<String> = lines.iter().map(|line| json_string(line)).collect();
+
+  format!("[{}{}{}]", first_sep, strings.join(&sep), last_sep)
+}
+
+/// Call `f` with the type and source of each cell in the text of a notebook and replace the
+/// source with what it returns.  Only the sources that change are rewritten, so the rest of the
+/// text stays as it was.  Returns the new text and the number of cells changed.
+pub fn map_cell_sources<F>(text: &str, mut f: F) -> Result<(String, usize), Box<dyn Error>>
+where
+  F: FnMut(CellType, &str) -> Result<String, Box<dyn Error>>,
+{
+  let sources = Parser { text, pos: 0 }.cell_sources()?;
+  let mut new_text = String::with_capacity(text.len());
+  let mut pos = 0;
+  let mut num_changed = 0;
+
+  for source in sources {
+    let new_source = f(source.cell_type, &source.text)?;
+
+    if new_source != source.text {
+      let old_value = &text[source.start..source.end];
+      let old_value = old_value.trim_start();
+      let start = source.end - old_value.len();
+
+      new_text += &text[pos..start];
+      new_text += &source_value(old_value, &new_source, source.is_array);
+      pos = source.end;
+      num_changed += 1;
+    }
+  }
+
+  new_text += &text[pos..];
+
+  Ok((new_text, num_changed))
+}
+
+/// Normalize the whitespace in the source of a cell.  Code and Markdown cells get line feeds
+/// and optionally `new_bol` line beginnings, leaving lines inside strings and Markdown code
+/// blocks alone.  Trailing whitespace is removed from code cells, but not Markdown cells where
+/// two trailing spaces are a line break.  Raw cells aren't changed.
+pub fn normalize_cell(
+  cell_type: CellType,
+  source: &str,
+  new_bol: Option<BeginningOfLine>,
+) -> Result<String, Box<dyn Error>> {
+  if cell_type == CellType::Raw {
+    return Ok(source.to_string());
+  }
+
+  let mut output = Vec::new();
+
+  ender::write_new_eols(&mut source.as_bytes(), &mut output, EndOfLine::Lf)?;
+
+  if cell_type == CellType::Code {
+    let input = std::mem::take(&mut output);
+
+    lines::process_lines(&mut input.as_slice(), &mut output, |line| {
+      let trimmed = line.content.trim_end_matches([' ', '\t']);
+
+      if trimmed.len() == line.content.len() {
+        LineAction::Keep
+      } else {
+        LineAction::Replace(trimmed.to_string())
+      }
+    })?;
+  }
+
+  if let Some(new_bol) = new_bol {
+    let input = std::mem::take(&mut output);
+    let options = BolOptions::default()
+      .markdown(cell_type == CellType::Markdown)
+      .protect_strings(cell_type == CellType::Code);
+
+    spacer::write_new_bols_with_options(&mut input.as_slice(), &mut output, new_bol, &options)?;
+  }
+
+  Ok(String::from_utf8(output)?)
+}
+
+/// Normalize the whitespace in the code and Markdown cells of the text of a notebook, as
+/// [`normalize_cell()`] does.  Returns the new text and the number of cells changed.
+pub fn normalize_notebook(
+  text: &str,
+  new_bol: Option<BeginningOfLine>,
+) -> Result<(String, usize), Box<dyn Error>> {
+  map_cell_sources(text, |cell_type, source| {
+    normalize_cell(cell_type, source, new_bol)
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const NOTEBOOK: &str = r##"{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "metadata": {},
+   "source": [
+    "# Title  \r\n",
+    "\n",
+    "    code block\n",
+    "  text"
+   ]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "metadata": {"tags": ["a \t b"]},
+   "outputs": [{"output_type": "stream", "text": ["x  \n"]}],
+   "source": [
+    "def f():  \n",
+    "\treturn \"\u00fc\ud83d\ude00\"\t\n"
+   ]
+  },
+  {
+   "cell_type": "raw",
+   "source": "\tx  "
+  }
+ ],
+ "nbformat": 4
+}
+"##;
+
+  #[test]
+  fn test_normalize_notebook() {
+    let (new_text, num_changed) =
+      normalize_notebook(NOTEBOOK, Some(BeginningOfLine::Spaces(4))).unwrap();
+
+    assert_eq!(num_changed, 2);
+    assert_eq!(
+      new_text,
+      NOTEBOOK
+        .replace("\"# Title  \\r\\n\"", "\"# Title  \\n\"")
+        .replace("\"def f():  \\n\"", "\"def f():\\n\"")
+        .replace(
+          "\"\\treturn \\\"\\u00fc\\ud83d\\ude00\\\"\\t\\n\"",
+          "\"    return \\\"ü😀\\\"\\n\""
+        )
+    );
+  }
+
+  #[test]
+  fn test_normalize_notebook_unchanged() {
+    let text = r#"{"cells": [{"source": "x\n", "cell_type": "code"}], "metadata": {}}"#;
+
+    assert_eq!(
+      normalize_notebook(text, None).unwrap(),
+      (text.to_string(), 0)
+    );
+  }
+
+  #[test]
+  fn test_map_cell_sources() {
+    let text = r#"{"cells": [{"cell_type": "code", "source": []}, {"cell_type": "code", "source": ["a\n", "b"]}]}"#;
+    let (new_text, num_changed) =
+      map_cell_sources(text, |_, source| Ok(source.replace('b', "c\n"))).unwrap();
+
+    assert_eq!(num_changed, 1);
+    assert_eq!(
+      new_text,
+      r#"{"cells": [{"cell_type": "code", "source": []}, {"cell_type": "code", "source": ["a\n", "c\n"]}]}"#
+    );
+    assert_eq!(
+      map_cell_sources(text, |_, _| Ok("x\ny".to_string()))
+        .unwrap()
+        .0,
+      r#"{"cells": [{"cell_type": "code", "source": ["x\n", "y"]}, {"cell_type": "code", "source": ["x\n", "y"]}]}"#
+    );
+  }
+
+  #[test]
+  fn test_map_cell_sources_errors() {
+    for (text, message) in [
+      ("[]", "expected '{' at byte 0 of the notebook"),
+      ("{\"cells\": [}", "expected '{' at byte 11 of the notebook"),
+      ("{\"a\": \"\\x\"}", "bad escape at byte 7 of the notebook"),
+      ("{\"a\": 1} x", "unexpected text at byte 9 of the notebook"),
+    ] {
+      let err = map_cell_sources(text, |_, source| Ok(source.to_string())).unwrap_err();
+
+      assert_eq!(error::error_code(&*err), ErrorCode::Notebook);
+      assert_eq!(err.to_string(), message);
+    }
+  }
+
+  #[test]
+  fn test_normalize_cell() {
+    assert_eq!(
+      normalize_cell(
+        CellType::Code,
+        "a = \"\"\"\n\tx\n\"\"\"\n\tb  ",
+        Some(BeginningOfLine::Spaces(2))
+      )
+      .unwrap(),
+      "a = \"\"\"\n\tx\n\"\"\"\n  b"
+    );
+    assert_eq!(
+      normalize_cell(CellType::Markdown, "a  \r\n\tb", None).unwrap(),
+      "a  \n\tb"
+    );
+    assert_eq!(
+      normalize_cell(CellType::Raw, "a  \r\n", None).unwrap(),
+      "a  \r\n"
+    );
+  }
+}
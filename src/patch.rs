@@ -0,0 +1,606 @@
+//! Apply unified diffs that only change whitespace.
+//!
+//! Use [`parse_patch()`] to read a unified diff, such as the output of `git diff` or `diff -u`,
+//! and [`apply_patch()`] to apply the changes for one file to its contents:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::patch;
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let patches = patch::parse_patch("--- a.txt\n+++ a.txt\n@@ -1 +1 @@\n-\tabc\n+    abc\n")?;
+//!
+//!   assert_eq!(patch::apply_patch("\tabc\n", &patches[0])?, "    abc\n");
+//!   Ok(())
+//! }
+//! ```
+//!
+//! Hunks that change anything other than whitespace are rejected, so a patch that was reviewed as
+//! a whitespace fix can't change anything else.  Use [`diff()`] to make a patch, which is written
+//! as a unified diff by its `Display` implementation, or as a shell command that applies it with
+//! `patch` by [`shell_command()`].  Use [`FilePatch::visible()`] to write it with the whitespace
+//! made visible for review.
+
+use crate::error::{self, ErrorCode};
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+/// Number of unchanged lines around the changes in hunks made by [`diff()`]
+const CONTEXT_LINES: usize = 3;
+
+/// Tag that ends the here-doc in [`shell_command()`].  Diff lines always start with a space, `-`,
+/// `+` or `\`, so they can't end it early.
+const HERE_DOC_TAG: &str = "WHITESPACE_EOF";
+
+/// First lines of a shell script made of [`shell_command()`] commands
+pub const SHELL_SCRIPT_HEADER: &str = "#!/bin/sh\n# Whitespace fixes.  Run from the directory that the file paths are relative to.\nset -e\n";
+
+/// A line in a hunk, including its line ending if it has one
+#[derive(Debug, PartialEq, Clone)]
+pub enum HunkLine {
+  /// Line that is the same in both files
+  Context(String),
+  /// Line that is only in the original file
+  Remove(String),
+  /// Line that is only in the new file
+  Add(String),
+}
+
+/// A set of changes to consecutive lines
+#[derive(Debug, PartialEq, Clone)]
+pub struct Hunk {
+  /// Line number of the first line of the hunk in the original file, starting at 1
+  pub old_start: usize,
+  /// Number of lines of the original file in the hunk
+  pub old_len: usize,
+  /// Lines of the hunk
+  pub lines: Vec<HunkLine>,
+}
+
+/// The changes to one file
+#[derive(Debug, PartialEq, Clone)]
+pub struct FilePatch {
+  /// Path of the file, from the `+++` line
+  pub path: String,
+  /// Changes to the file in order
+  pub hunks: Vec<Hunk>,
+}
+
+impl Hunk {
+  /// Get the lines of the original file
+  fn old_lines(&self) -> Vec<&str> {
+    self
+      .lines
+      .iter()
+      .filter_map(|line| match line {
+        HunkLine::Context(s) | HunkLine::Remove(s) => Some(s.as_str()),
+        HunkLine::Add(_) => None,
+      })
+      .collect()
+  }
+
+  /// Get the lines of the new file
+  fn new_lines(&self) -> Vec<&str> {
+    self
+      .lines
+      .iter()
+      .filter_map(|line| match line {
+        HunkLine::Context(s) | HunkLine::Add(s) => Some(s.as_str()),
+        HunkLine::Remove(_) => None,
+      })
+      .collect()
+  }
+
+  /// Check if the hunk only changes whitespace
+  pub fn is_whitespace_only(&self) -> bool {
+    let non_whitespace = |lines: Vec<&str>| -> String {
+      lines
+        .concat()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect()
+    };
+
+    non_whitespace(self.old_lines()) == non_whitespace(self.new_lines())
+  }
+}
+
+/// Format a hunk header range, which omits the length if it is 1
+fn format_range(start: usize, len: usize) -> String {
+  if len == 1 {
+    start.to_string()
+  } else {
+    format!("{},{}", start, len)
+  }
+}
+
+/// Make the whitespace in a diff line visible, as [`FilePatch::visible()`] describes.
+fn visible_line(s: &str) -> String {
+  let content = s.trim_end_matches(['\r', '\n']);
+  let text_len = content.trim_end_matches([' ', '\t']).len();
+  let mut visible = String::new();
+
+  for (i, c) in s.char_indices() {
+    match c {
+      ' ' if i >= text_len && i < content.len() => visible.push('·'),
+      '\t' => visible += "\\t",
+      '\r' => visible += "\\r",
+      '\n' => visible += "\\n",
+      '\\' => visible += "\\\\",
+      c => visible.push(c),
+    }
+  }
+
+  visible
+}
+
+/// A [`FilePatch`] written as a unified diff with its whitespace made visible, from
+/// [`FilePatch::visible()`]
+pub struct VisibleFilePatch<'a>(&'a FilePatch);
+
+impl fmt::Display for VisibleFilePatch<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.0.write(f, true)
+  }
+}
+
+impl FilePatch {
+  /// Get the patch as a unified diff with its whitespace made visible, for people to review
+  /// rather than for `patch` to apply.  Tabs, carriage returns, line feeds and backslashes are
+  /// escaped as `\t`, `\r`, `\n` and `\\`, and spaces at the end of a line are shown as `·`.
+  pub fn visible(&self) -> VisibleFilePatch<'_> {
+    VisibleFilePatch(self)
+  }
+
+  /// Write the patch as a unified diff, with the whitespace in the lines made visible or not.
+  fn write(&self, f: &mut fmt::Formatter, visible: bool) -> fmt::Result {
+    // A tab marks the end of a path with spaces for `patch`
+    let end = if self.path.contains(' ') { "\t" } else { "" };
+
+    writeln!(f, "--- {}{}", self.path, end)?;
+    writeln!(f, "+++ {}{}", self.path, end)?;
+
+    let mut offset: isize = 0;
+
+    for hunk in self.hunks.iter() {
+      let new_len = hunk.new_lines().len();
+      let old_pos = if hunk.old_len == 0 {
+        hunk.old_start
+      } else {
+        hunk.old_start - 1
+      };
+      let new_pos = (old_pos as isize + offset) as usize;
+
+      writeln!(
+        f,
+        "@@ -{} +{} @@",
+        format_range(hunk.old_start, hunk.old_len),
+        format_range(if new_len == 0 { new_pos } else { new_pos + 1 }, new_len)
+      )?;
+
+      for line in hunk.lines.iter() {
+        let (prefix, s) = match line {
+          HunkLine::Context(s) => (' ', s),
+          HunkLine::Remove(s) => ('-', s),
+          HunkLine::Add(s) => ('+', s),
+        };
+
+        if visible {
+          writeln!(f, "{}{}", prefix, visible_line(s))?;
+
+          if !s.ends_with(['\r', '\n']) {
+            writeln!(f, "\\ No newline at end of file")?;
+          }
+        } else {
+          write!(f, "{}{}", prefix, s)?;
+
+          if !s.ends_with('\n') {
+            write!(f, "\n\\ No newline at end of file\n")?;
+          }
+        }
+      }
+
+      offset += new_len as isize - hunk.old_len as isize;
+    }
+
+    Ok(())
+  }
+}
+
+impl fmt::Display for FilePatch {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.write(f, false)
+  }
+}
+
+/// Make a shell command that applies a patch with `patch`, for a script that makes the changes
+/// later.
+pub fn shell_command(file_patch: &FilePatch) -> String {
+  format!(
+    "patch -p0 --forward <<'{0}'\n{1}{0}\n",
+    HERE_DOC_TAG, file_patch
+  )
+}
+
+/// Make the patch that changes `old` into `new`.
+///
+/// When both have the same number of lines, as with most whitespace changes, lines are compared in
+/// pairs.  Otherwise everything from the first to the last changed line is one change.
+pub fn diff(path: &str, old: &str, new: &str) -> FilePatch {
+  let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+  let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+  let mut changes: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+
+  if old_lines.len() == new_lines.len() {
+    for i in 0..old_lines.len() {
+      if old_lines[i] == new_lines[i] {
+        continue;
+      }
+
+      match changes.last_mut() {
+        Some((old_range, new_range)) if old_range.end == i => {
+          old_range.end += 1;
+          new_range.end += 1;
+        }
+        _ => changes.push((i..i + 1, i..i + 1)),
+      }
+    }
+  } else {
+    let prefix = old_lines
+      .iter()
+      .zip(new_lines.iter())
+      .take_while(|(a, b)| a == b)
+      .count();
+    let suffix = old_lines[prefix..]
+      .iter()
+      .rev()
+      .zip(new_lines[prefix..].iter().rev())
+      .take_while(|(a, b)| a == b)
+      .count();
+
+    changes.push((
+      prefix..old_lines.len() - suffix,
+      prefix..new_lines.len() - suffix,
+    ));
+  }
+
+  let context = |lines: &[&str]| -> Vec<HunkLine> {
+    lines
+      .iter()
+      .map(|s| HunkLine::Context(s.to_string()))
+      .collect()
+  };
+  let mut hunks = Vec::new();
+  let mut i = 0;
+
+  while i < changes.len() {
+    let mut j = i;
+
+    // Changes that are close enough to share context go in the same hunk
+    while j + 1 < changes.len() && changes[j + 1].0.start - changes[j].0.end <= 2 * CONTEXT_LINES {
+      j += 1;
+    }
+
+    let start = changes[i].0.start.saturating_sub(CONTEXT_LINES);
+    let end = (changes[j].0.end + CONTEXT_LINES).min(old_lines.len());
+    let mut lines = Vec::new();
+    let mut pos = start;
+
+    for (old_range, new_range) in changes[i..=j].iter() {
+      lines.extend(context(&old_lines[pos..old_range.start]));
+      lines.extend(
+        old_lines[old_range.clone()]
+          .iter()
+          .map(|s| HunkLine::Remove(s.to_string())),
+      );
+      lines.extend(
+        new_lines[new_range.clone()]
+          .iter()
+          .map(|s| HunkLine::Add(s.to_string())),
+      );
+      pos = old_range.end;
+    }
+
+    lines.extend(context(&old_lines[pos..end]));
+
+    let old_len = end - start;
+
+    hunks.push(Hunk {
+      old_start: if old_len == 0 { start } else { start + 1 },
+      old_len,
+      lines,
+    });
+    i = j + 1;
+  }
+
+  FilePatch {
+    path: path.to_string(),
+    hunks,
+  }
+}
+
+/// Parse a `@@ -a,b +c,d @@` hunk header into the original start line and length, and the new
+/// length
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize)> {
+  let mut ranges = line.strip_prefix("@@ -")?.split(" @@").next()?.split(" +");
+  let parse_range = |s: &str| -> Option<(usize, usize)> {
+    match s.split_once(',') {
+      Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+      None => Some((s.parse().ok()?, 1)),
+    }
+  };
+  let (old_start, old_len) = parse_range(ranges.next()?)?;
+  let (_, new_len) = parse_range(ranges.next()?)?;
+
+  Some((old_start, old_len, new_len))
+}
+
+/// Read the file patches in a unified diff.
+pub fn parse_patch(text: &str) -> Result<Vec<FilePatch>, Box<dyn Error>> {
+  let mut patches: Vec<FilePatch> = Vec::new();
+  let mut lines = text.split_inclusive('\n').enumerate().peekable();
+
+  while let Some((i, line)) = lines.next() {
+    if let Some(path) = line.strip_prefix("+++ ") {
+      let path = path.trim_end_matches(['\r', '\n']);
+      let path = path.split('\t').next().unwrap_or(path);
+
+      if path == "/dev/null" {
+        return Err(
+          error::Error::new(
+            ErrorCode::Patch,
+            format!("line {}: deleting files is not a whitespace change", i + 1),
+          )
+          .into(),
+        );
+      }
+
+      patches.push(FilePatch {
+        path: path.to_string(),
+        hunks: Vec::new(),
+      });
+    } else if line.starts_with("@@ ") {
+      let (old_start, old_len, new_len) =
+        parse_hunk_header(line).ok_or_else(|| format!("line {}: bad hunk header", i + 1))?;
+      let patch = patches
+        .last_mut()
+        .ok_or_else(|| format!("line {}: hunk before a '+++' line", i + 1))?;
+      let mut hunk = Hunk {
+        old_start,
+        old_len,
+        lines: Vec::new(),
+      };
+      let (mut old_count, mut new_count) = (0, 0);
+
+      while old_count < old_len || new_count < new_len {
+        let (i, line) = lines
+          .next()
+          .ok_or_else(|| format!("line {}: hunk is incomplete", i + 1))?;
+        let content = line.get(1..).unwrap_or("").to_string();
+
+        match line.chars().next() {
+          Some(' ') => {
+            old_count += 1;
+            new_count += 1;
+            hunk.lines.push(HunkLine::Context(content));
+          }
+          Some('-') => {
+            old_count += 1;
+            hunk.lines.push(HunkLine::Remove(content));
+          }
+          Some('+') => {
+            new_count += 1;
+            hunk.lines.push(HunkLine::Add(content));
+          }
+          // Some tools drop the space from empty context lines
+          Some('\n') | Some('\r') => {
+            old_count += 1;
+            new_count += 1;
+            hunk.lines.push(HunkLine::Context(line.to_string()));
+          }
+          _ => {
+            return Err(
+              error::Error::new(
+                ErrorCode::Patch,
+                format!("line {}: bad line in hunk", i + 1),
+              )
+              .into(),
+            );
+          }
+        }
+
+        if let Some((_, next)) = lines.peek() {
+          if next.starts_with('\\') {
+            lines.next();
+
+            if let Some(HunkLine::Context(s) | HunkLine::Remove(s) | HunkLine::Add(s)) =
+              hunk.lines.last_mut()
+            {
+              if s.ends_with('\n') {
+                s.pop();
+              }
+            }
+          }
+        }
+      }
+
+      patch.hunks.push(hunk);
+    }
+  }
+
+  Ok(patches)
+}
+
+/// Apply the changes for a file to its contents.
+///
+/// Fails if a hunk doesn't match the contents exactly, or changes anything other than whitespace.
+pub fn apply_patch(content: &str, patch: &FilePatch) -> Result<String, Box<dyn Error>> {
+  let lines: Vec<&str> = content.split_inclusive('\n').collect();
+  let mut new_content = String::new();
+  let mut pos = 0;
+
+  for (n, hunk) in patch.hunks.iter().enumerate() {
+    if !hunk.is_whitespace_only() {
+      return Err(
+        error::Error::new(
+          ErrorCode::Patch,
+          format!(
+            "hunk {} of '{}' changes more than whitespace",
+            n + 1,
+            patch.path
+          ),
+        )
+        .into(),
+      );
+    }
+
+    let old_lines = hunk.old_lines();
+    let start = if hunk.old_len == 0 {
+      hunk.old_start
+    } else {
+      hunk.old_start.saturating_sub(1)
+    };
+
+    if start < pos
+      || start + old_lines.len() > lines.len()
+      || lines[start..start + old_lines.len()] != old_lines[..]
+    {
+      return Err(
+        error::Error::new(
+          ErrorCode::Patch,
+          format!("hunk {} of '{}' doesn't match the file", n + 1, patch.path),
+        )
+        .into(),
+      );
+    }
+
+    new_content.extend(lines[pos..start].iter().copied());
+    new_content.extend(hunk.new_lines());
+    pos = start + old_lines.len();
+  }
+
+  new_content.extend(lines[pos..].iter().copied());
+
+  Ok(new_content)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PATCH: &str = "diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1,3 +1,3 @@
+ abc
+-\tdef
++    def
+ ghi
+@@ -5,2 +5,2 @@ fn main() {
+ jkl
+-mno
+\\ No newline at end of file
++mno
+--- b.txt\t2021-09-04 12:00:00
++++ b.txt\t2021-09-04 12:00:00
+@@ -1 +1 @@
+-x\r
++x
+";
+
+  #[test]
+  fn test_parse_patch() {
+    let patches = parse_patch(PATCH).unwrap();
+
+    assert_eq!(patches.len(), 2);
+    assert_eq!(patches[0].path, "b/a.txt");
+    assert_eq!(patches[1].path, "b.txt");
+    assert_eq!(
+      patches[0].hunks[1],
+      Hunk {
+        old_start: 5,
+        old_len: 2,
+        lines: vec![
+          HunkLine::Context("jkl\n".to_string()),
+          HunkLine::Remove("mno".to_string()),
+          HunkLine::Add("mno\n".to_string()),
+        ]
+      }
+    );
+    assert_eq!(
+      patches[1].hunks[0].lines,
+      vec![
+        HunkLine::Remove("x\r\n".to_string()),
+        HunkLine::Add("x\n".to_string())
+      ]
+    );
+    assert!(parse_patch("+++ a\n@@ -1,2 +1,2 @@\n a\n").is_err());
+    assert!(parse_patch("+++ /dev/null\n").is_err());
+  }
+
+  #[test]
+  fn test_apply_patch() {
+    let patches = parse_patch(PATCH).unwrap();
+
+    assert_eq!(
+      apply_patch("abc\n\tdef\nghi\nxyz\njkl\nmno", &patches[0]).unwrap(),
+      "abc\n    def\nghi\nxyz\njkl\nmno\n"
+    );
+    assert_eq!(apply_patch("x\r\n", &patches[1]).unwrap(), "x\n");
+    assert!(apply_patch("abc\n def\nghi\n", &patches[0]).is_err());
+    assert!(apply_patch("", &patches[1]).is_err());
+
+    let patches = parse_patch("+++ a\n@@ -1 +1 @@\n-abc\n+abd\n").unwrap();
+
+    assert!(apply_patch("abc\n", &patches[0]).is_err());
+  }
+
+  #[test]
+  fn test_diff() {
+    let old = "a\n\tb\nc\nd\ne\nf\ng\nh\ni\nj\n\tk";
+    let new = "a\n  b\nc\nd\ne\nf\ng\nh\ni\nj\n  k";
+    let patch = diff("x.txt", old, new);
+
+    assert_eq!(
+      patch.to_string(),
+      "--- x.txt\n+++ x.txt\n@@ -1,5 +1,5 @@\n a\n-\tb\n+  b\n c\n d\n e\n@@ -8,4 +8,4 @@\n h\n i\n j\n-\tk\n\\ No newline at end of file\n+  k\n\\ No newline at end of file\n"
+    );
+
+    for (old, new) in [
+      (old, new),
+      ("a\rb\r", "a\nb\n"),
+      ("a\n\n\n", "a\n"),
+      ("", "\n"),
+      ("a\n", "a\n"),
+    ]
+    .iter()
+    {
+      let patches = parse_patch(&diff("x.txt", old, new).to_string()).unwrap();
+
+      assert_eq!(apply_patch(old, &patches[0]).unwrap(), *new);
+    }
+  }
+
+  #[test]
+  fn test_visible() {
+    assert_eq!(
+      diff("a.txt", "\tx  \r\ny\\z\n", "  x\ny\\z\n")
+        .visible()
+        .to_string(),
+      "--- a.txt\n+++ a.txt\n@@ -1,2 +1,2 @@\n-\\tx··\\r\\n\n+  x\\n\n y\\\\z\\n\n"
+    );
+    assert_eq!(
+      diff("a.txt", "a \t", "a").visible().to_string(),
+      "--- a.txt\n+++ a.txt\n@@ -1 +1 @@\n-a·\\t\n\\ No newline at end of file\n+a\n\\ No newline at end of file\n"
+    );
+  }
+
+  #[test]
+  fn test_shell_command() {
+    assert_eq!(
+      shell_command(&diff("a b.txt", "\tx\n", "  x\n")),
+      "patch -p0 --forward <<'WHITESPACE_EOF'\n--- a b.txt\t\n+++ a b.txt\t\n@@ -1 +1 @@\n-\tx\n+  x\nWHITESPACE_EOF\n"
+    );
+  }
+}
@@ -0,0 +1,1326 @@
+//! Helpers for turning command line arguments into usable file paths.
+//!
+//! Shells on Unix expand wildcards before the arguments reach a program, but `cmd.exe` and
+//! PowerShell do not.  Use [`expand_wildcards()`] to do the expansion on those platforms:
+//!
+//! ```
+//! use whitespace_rs::paths;
+//!
+//! let files = paths::expand_wildcards(&["Cargo.toml", "src/*.rs"]);
+//!
+//! println!("{:?}", files);
+//! ```
+//!
+//! Use [`extended_length_path()`] before opening a file so that paths longer than `MAX_PATH`
+//! work on Windows.  Use [`open_input()`] to open a file, or standard input for `-`, so that it
+//! can be read more than once, [`create_output()`] to create an output file, and
+//! [`replace_file()`] to change a file in place.  [`process_file()`] does all of these to convert
+//! a file in place with a function like [`write_new_eols()`](crate::ender::write_new_eols),
+//! only replacing it if it changes:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::ender::{self, EndOfLine};
+//! use whitespace_rs::paths;
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let temp_dir = tempfile::tempdir()?;
+//!   let path = temp_dir.path().join("a.txt");
+//!
+//!   std::fs::write(&path, "abc\r\nxyz\n")?;
+//!
+//!   let (num_lines, modified) = paths::process_file(path.to_str().unwrap(), |reader, writer| {
+//!     ender::write_new_eols(reader, writer, EndOfLine::Lf)
+//!   })?;
+//!
+//!   assert_eq!((num_lines, modified), (3, true));
+//!   assert_eq!(std::fs::read_to_string(&path)?, "abc\nxyz\n");
+//!   Ok(())
+//! }
+//! ```
+//!
+//! With the `gzip` feature files with a `.gz` extension are decompressed and compressed on the
+//! fly.  With the `lock` feature use [`lock_directory()`] so that runs writing to the same
+//! directory at the same time take turns.  Use a [`ProgressFile`] so that an interrupted run can be resumed.  Use [`is_binary_file()`]
+//! and [`copy_file()`] to pass files that can't be converted through to an output tree, and
+//! [`walk_text_files()`] to find the text files in a directory tree.
+
+#[cfg(feature = "encoding")]
+use crate::encoding::Encoding;
+use crate::error::{self, ErrorCode};
+use crate::ignore::Ignore;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// A [`Read`] that can [`Seek`] back to the start to read the input again.
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Expand `*` and `?` wildcards in the file name part of each argument.
+///
+/// Matching files are returned in sorted order.  Arguments without wildcards, or with wildcards
+/// that don't match any files, are returned unchanged.
+pub fn expand_wildcards(args: &[&str]) -> Vec<String> {
+  let mut files = Vec::new();
+
+  for arg in args {
+    let path = Path::new(arg);
+    let pattern = match path.file_name().and_then(|name| name.to_str()) {
+      Some(name) if name.contains(['*', '?']) => name,
+      _ => {
+        files.push(arg.to_string());
+        continue;
+      }
+    };
+    let dir = match path.parent() {
+      Some(dir) if dir != Path::new("") => dir,
+      _ => Path::new("."),
+    };
+    let mut matches: Vec<String> = match std::fs::read_dir(dir) {
+      Ok(entries) => entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .filter_map(|entry| entry.file_name().to_str().map(|name| name.to_string()))
+        .filter(|name| matches_wildcard(pattern, name))
+        .map(|name| match path.parent() {
+          Some(parent) if parent != Path::new("") => {
+            parent.join(name).to_string_lossy().to_string()
+          }
+          _ => name,
+        })
+        .collect(),
+      Err(_) => Vec::new(),
+    };
+
+    if matches.is_empty() {
+      files.push(arg.to_string());
+    } else {
+      matches.sort();
+      files.append(&mut matches);
+    }
+  }
+
+  files
+}
+
+/// Get the paths of all the files below a directory, relative to it and in sorted order.
+///
+/// Symbolic links are not followed.
+pub fn walk_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+  fn walk(dir: &Path, relative_dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+      let entry = entry?;
+      let file_type = entry.file_type()?;
+      let relative_path = relative_dir.join(entry.file_name());
+
+      if file_type.is_dir() {
+        walk(&entry.path(), &relative_path, files)?;
+      } else if file_type.is_file() {
+        files.push(relative_path);
+      }
+    }
+
+    Ok(())
+  }
+
+  let mut files = Vec::new();
+
+  walk(dir, Path::new(""), &mut files)?;
+  files.sort();
+
+  Ok(files)
+}
+
+/// Get the paths of the text files below a directory, joined to it and in sorted order.
+///
+/// Binary files and the `.git`, `.hg` and `.svn` directories are left out, and with
+/// `use_ignore_files` so are the files and directories that an [`Ignore`] for the directory
+/// ignores.  Files that can't be read to check if they are binary are kept, so that the error is
+/// reported when they are processed.  Symbolic links are not followed.
+pub fn walk_text_files(dir: &Path, use_ignore_files: bool) -> io::Result<Vec<PathBuf>> {
+  fn walk(
+    dir: &Path,
+    abs_dir: &Path,
+    ignore: Option<&Ignore>,
+    files: &mut Vec<PathBuf>,
+  ) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+      let entry = entry?;
+      let file_type = entry.file_type()?;
+      let path = entry.path();
+      // Ignore files match absolute paths, which may not be the ones being returned
+      let abs_path = abs_dir.join(entry.file_name());
+
+      if ignore.is_some_and(|ignore| ignore.is_ignored(&abs_path, file_type.is_dir())) {
+        continue;
+      }
+
+      if file_type.is_dir() {
+        if !matches!(
+          entry.file_name().to_str(),
+          Some(".git") | Some(".hg") | Some(".svn")
+        ) {
+          match ignore {
+            Some(ignore) => walk(&path, &abs_path, Some(&ignore.child(&abs_path)?), files)?,
+            None => walk(&path, &abs_path, None, files)?,
+          }
+        }
+      } else if file_type.is_file()
+        && !path
+          .to_str()
+          .is_some_and(|name| is_binary_file(name).unwrap_or(false))
+      {
+        files.push(path);
+      }
+    }
+
+    Ok(())
+  }
+
+  let mut files = Vec::new();
+
+  if use_ignore_files {
+    let abs_dir = std::fs::canonicalize(dir)?;
+
+    walk(dir, &abs_dir, Some(&Ignore::load(&abs_dir)?), &mut files)?;
+  } else {
+    walk(dir, dir, None, &mut files)?;
+  }
+
+  files.sort();
+
+  Ok(files)
+}
+
+/// Read a list of file names, one per line or separated by NUL characters.
+///
+/// NUL separated lists are produced by `find -print0` and `git ls-files -z`.  Empty names are
+/// ignored.  File names must be valid UTF-8.
+pub fn read_file_list(reader: &mut dyn Read, null: bool) -> io::Result<Vec<String>> {
+  let mut buf = Vec::new();
+
+  reader.read_to_end(&mut buf)?;
+
+  let text = String::from_utf8(buf).map_err(|_| {
+    io::Error::new(
+      io::ErrorKind::InvalidData,
+      "file list contains a name that is not valid UTF-8",
+    )
+  })?;
+  let names: Vec<&str> = if null {
+    text.split('\0').collect()
+  } else {
+    text
+      .split('\n')
+      .map(|name| name.strip_suffix('\r').unwrap_or(name))
+      .collect()
+  };
+
+  Ok(
+    names
+      .into_iter()
+      .filter(|name| !name.is_empty())
+      .map(|name| name.to_string())
+      .collect(),
+  )
+}
+
+/// Convert a path into one that can exceed `MAX_PATH` when opened on Windows.
+///
+/// On Windows the path is made absolute and given the `\\?\` prefix (`\\?\UNC\` for network
+/// shares).  On other platforms the path is returned unchanged.
+pub fn extended_length_path(path: &Path) -> PathBuf {
+  #[cfg(windows)]
+  {
+    let path = match std::path::absolute(path) {
+      Ok(path) => path,
+      Err(_) => return path.to_path_buf(),
+    };
+    let s = path.to_string_lossy();
+
+    if s.starts_with(r"\\?\") {
+      path
+    } else if let Some(share) = s.strip_prefix(r"\\") {
+      PathBuf::from(format!(r"\\?\UNC\{}", share))
+    } else {
+      PathBuf::from(format!(r"\\?\{}", s))
+    }
+  }
+  #[cfg(not(windows))]
+  path.to_path_buf()
+}
+
+/// Get the path of the mirror of an input file inside an output directory.
+///
+/// The relative path of the input file is preserved below `output_dir`.  Root, prefix, `.` and
+/// `..` components are dropped so the result is always inside `output_dir`.
+pub fn mirrored_path(input_path: &Path, output_dir: &Path) -> PathBuf {
+  let mut path = output_dir.to_path_buf();
+
+  for component in input_path.components() {
+    if let Component::Normal(name) = component {
+      path.push(name);
+    }
+  }
+
+  path
+}
+
+/// Get a path with a suffix appended to its file name, e.g. `a.txt` becomes `a.txt.fixed`.
+pub fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+  let mut s = path.as_os_str().to_os_string();
+
+  s.push(suffix);
+  PathBuf::from(s)
+}
+
+/// Get an output path by expanding a pattern with parts of an input path.
+///
+/// The pattern can contain `{dir}` (the parent directory, `.` if there is none), `{name}` (the
+/// file name), `{stem}` (the file name without extension) and `{ext}` (the extension including
+/// the leading `.`, or nothing).  For example, `{dir}/{stem}.lf{ext}` turns `src/a.txt` into
+/// `src/a.lf.txt`.
+pub fn patterned_path(path: &Path, pattern: &str) -> Result<PathBuf, Box<dyn Error>> {
+  let dir = match path.parent() {
+    Some(dir) if dir != Path::new("") => dir.to_string_lossy(),
+    _ => ".".into(),
+  };
+  let name = path.file_name().unwrap_or_default().to_string_lossy();
+  let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+  let ext = match path.extension() {
+    Some(ext) => format!(".{}", ext.to_string_lossy()),
+    None => String::new(),
+  };
+  let mut s = String::new();
+  let mut rest = pattern;
+
+  while let Some(start) = rest.find('{') {
+    let end = match rest[start..].find('}') {
+      Some(end) => start + end,
+      None => {
+        return Err(
+          error::Error::new(
+            ErrorCode::Pattern,
+            format!("unterminated variable in pattern '{}'", pattern),
+          )
+          .into(),
+        )
+      }
+    };
+
+    s.push_str(&rest[..start]);
+    s.push_str(match &rest[start + 1..end] {
+      "dir" => &dir,
+      "name" => &name,
+      "stem" => &stem,
+      "ext" => &ext,
+      var => {
+        return Err(
+          error::Error::new(
+            ErrorCode::Pattern,
+            format!("unknown variable '{{{}}}' in pattern '{}'", var, pattern),
+          )
+          .into(),
+        )
+      }
+    });
+    rest = &rest[end + 1..];
+  }
+
+  s.push_str(rest);
+
+  Ok(PathBuf::from(s))
+}
+
+/// Get the identity of an existing file, which is the same for every path and hard link to it.
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+  use std::os::unix::fs::MetadataExt;
+
+  let metadata = std::fs::metadata(path).ok()?;
+
+  Some((metadata.dev(), metadata.ino()))
+}
+
+/// Get the identity of an existing file, which is the same for every path to it.
+#[cfg(not(unix))]
+fn file_id(path: &Path) -> Option<PathBuf> {
+  path.canonicalize().ok()
+}
+
+/// Check if two paths refer to the same existing file.
+pub fn is_same_file(path1: &Path, path2: &Path) -> bool {
+  match (file_id(path1), file_id(path2)) {
+    (Some(id1), Some(id2)) => id1 == id2,
+    _ => false,
+  }
+}
+
+/// Remove files that refer to the same underlying file as an earlier file in the list.
+///
+/// Overlapping arguments and hard links to the same file are only kept once, in their first
+/// position.  On Unix files are identified by device and inode.  Files that don't exist, and `-`
+/// for standard input, are always kept.
+pub fn unique_files(files: &[String]) -> Vec<String> {
+  let mut ids = HashSet::new();
+
+  files
+    .iter()
+    .filter(|file| match file_id(Path::new(file)) {
+      Some(id) if file.as_str() != "-" => ids.insert(id),
+      _ => true,
+    })
+    .cloned()
+    .collect()
+}
+
+/// Check if a file name has a `.gz` extension.
+pub fn is_gzip_path(path: &Path) -> bool {
+  path
+    .extension()
+    .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+}
+
+/// A `.gz` file decompressed as it is read.  Seeking backwards starts decompressing again from the
+/// start of the file, and seeking forwards or from the end decompresses the data in between, so
+/// that the decompressed data is never all in memory.
+#[cfg(feature = "gzip")]
+struct GzReader {
+  path: PathBuf,
+  decoder: flate2::read::MultiGzDecoder<BufReader<File>>,
+  /// Offset in the decompressed data
+  position: u64,
+  /// Length of the decompressed data, once the end has been reached
+  len: Option<u64>,
+}
+
+#[cfg(feature = "gzip")]
+impl GzReader {
+  fn open(path: PathBuf) -> io::Result<Self> {
+    let decoder = flate2::read::MultiGzDecoder::new(BufReader::new(File::open(&path)?));
+
+    Ok(GzReader {
+      path,
+      decoder,
+      position: 0,
+      len: None,
+    })
+  }
+}
+
+#[cfg(feature = "gzip")]
+impl Read for GzReader {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let n = self.decoder.read(buf)?;
+
+    self.position += n as u64;
+
+    if n == 0 && !buf.is_empty() {
+      self.len = Some(self.position);
+    }
+
+    Ok(n)
+  }
+}
+
+#[cfg(feature = "gzip")]
+impl Seek for GzReader {
+  fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+    let (base, offset) = match pos {
+      io::SeekFrom::Start(n) => (n, 0),
+      io::SeekFrom::Current(n) => (self.position, n),
+      io::SeekFrom::End(n) => match self.len {
+        Some(len) => (len, n),
+        None => {
+          io::copy(self, &mut io::sink())?;
+          (self.position, n)
+        }
+      },
+    };
+    let target = base.checked_add_signed(offset).ok_or_else(|| {
+      io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "invalid seek to a negative position",
+      )
+    })?;
+
+    if target < self.position {
+      *self = GzReader::open(std::mem::take(&mut self.path))?;
+    }
+
+    let skip = target - self.position;
+
+    io::copy(&mut self.by_ref().take(skip), &mut io::sink())?;
+    Ok(self.position)
+  }
+}
+
+/// Open an input file for reading, or standard input if the path is `-`.
+///
+/// Standard input is read into memory so that, like a file, it can be read more than once.  With
+/// the `gzip` feature a `.gz` file is decompressed as it is read, and is decompressed again each
+/// time it is read from the start.
+pub fn open_input(path: &str) -> io::Result<Box<dyn ReadSeek>> {
+  if path == "-" {
+    let mut buf = Vec::new();
+
+    io::stdin().read_to_end(&mut buf)?;
+    Ok(Box::new(Cursor::new(buf)))
+  } else {
+    let extended_path = extended_length_path(Path::new(path));
+
+    #[cfg(feature = "gzip")]
+    {
+      if is_gzip_path(Path::new(path)) {
+        return Ok(Box::new(GzReader::open(extended_path)?));
+      }
+    }
+
+    Ok(Box::new(BufReader::new(File::open(extended_path)?)))
+  }
+}
+
+/// A writer for an output file from [`create_output()`], or for standard output.
+///
+/// Call [`finish()`](OutputWriter::finish) once everything is written.  A `.gz` file is only
+/// completed then, and an error completing it is lost if the writer is just dropped.
+pub struct OutputWriter(OutputKind);
+
+enum OutputKind {
+  Stdout(io::Stdout),
+  File(BufWriter<File>),
+  #[cfg(feature = "gzip")]
+  Gzip(flate2::write::GzEncoder<BufWriter<File>>),
+}
+
+impl OutputWriter {
+  /// Get a writer for standard output
+  pub fn stdout() -> Self {
+    OutputWriter(OutputKind::Stdout(io::stdout()))
+  }
+
+  /// Complete the output, compressing the rest of a `.gz` file, and flush it
+  pub fn finish(self) -> io::Result<()> {
+    match self.0 {
+      OutputKind::Stdout(mut writer) => writer.flush(),
+      OutputKind::File(mut writer) => writer.flush(),
+      #[cfg(feature = "gzip")]
+      OutputKind::Gzip(writer) => writer.finish()?.flush(),
+    }
+  }
+
+  fn writer(&mut self) -> &mut dyn Write {
+    match &mut self.0 {
+      OutputKind::Stdout(writer) => writer,
+      OutputKind::File(writer) => writer,
+      #[cfg(feature = "gzip")]
+      OutputKind::Gzip(writer) => writer,
+    }
+  }
+}
+
+impl Write for OutputWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.writer().write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.writer().flush()
+  }
+}
+
+/// Create an output file, and any missing parent directories.
+///
+/// With the `gzip` feature a `.gz` file is compressed as it is written.  Call
+/// [`OutputWriter::finish()`] to complete it.
+pub fn create_output(path: &Path) -> io::Result<OutputWriter> {
+  let extended_path = extended_length_path(path);
+
+  if let Some(dir) = extended_path.parent() {
+    std::fs::create_dir_all(dir)?;
+  }
+
+  let writer = BufWriter::new(File::create(extended_path)?);
+
+  #[cfg(feature = "gzip")]
+  {
+    if is_gzip_path(path) {
+      return Ok(OutputWriter(OutputKind::Gzip(
+        flate2::write::GzEncoder::new(writer, flate2::Compression::default()),
+      )));
+    }
+  }
+
+  Ok(OutputWriter(OutputKind::File(writer)))
+}
+
+/// Check if a file looks binary, which like git means having a NUL byte in its first 8000 bytes.
+/// With the `encoding` feature files in another encoding, such as those that begin with a UTF-16
+/// byte order mark, are text.  Compressed files are checked after decompression, of only their
+/// first 8000 bytes.
+pub fn is_binary_file(path: &str) -> io::Result<bool> {
+  let mut head = Vec::new();
+
+  open_input(path)?.take(8000).read_to_end(&mut head)?;
+
+  #[cfg(feature = "encoding")]
+  {
+    if Encoding::detect(&head) != Encoding::Utf8 {
+      return Ok(false);
+    }
+  }
+
+  Ok(head.contains(&0))
+}
+
+/// Copy a file unchanged, creating any missing parent directories of the destination.
+pub fn copy_file(from: &Path, to: &Path) -> io::Result<u64> {
+  let extended_to = extended_length_path(to);
+
+  if let Some(dir) = extended_to.parent() {
+    std::fs::create_dir_all(dir)?;
+  }
+
+  std::fs::copy(extended_length_path(from), extended_to)
+}
+
+/// Replace the contents of a file by writing a temporary file next to it and renaming it over the
+/// original, so that the file is never left partly written.  The file keeps its permissions and,
+/// if it is a symbolic link, the file it links to is replaced.
+pub fn replace_file(path: &Path, data: &[u8]) -> io::Result<()> {
+  let path = std::fs::canonicalize(extended_length_path(path))?;
+  let file_name = path
+    .file_name()
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+  let mut temp_name = std::ffi::OsString::from(".whitespace-tmp.");
+
+  // Keep the extension so that a .gz file is still compressed
+  temp_name.push(file_name);
+
+  let temp_path = path.with_file_name(temp_name);
+  let result = (|| -> io::Result<()> {
+    let mut writer = create_output(&temp_path)?;
+
+    writer.write_all(data)?;
+    writer.finish()?;
+    std::fs::set_permissions(&temp_path, std::fs::metadata(&path)?.permissions())?;
+    std::fs::rename(&temp_path, &path)
+  })();
+
+  if result.is_err() {
+    std::fs::remove_file(&temp_path).ok();
+  }
+
+  result
+}
+
+/// Convert a file in place by calling `f` with a reader for its contents and a writer for the new
+/// contents, and replacing it with [`replace_file()`] if they differ.
+///
+/// Returns what `f` returns and whether the file was replaced.  Nothing is written if `f` fails.
+/// The whole file is read into memory.
+pub fn process_file<T, F>(path: &str, f: F) -> Result<(T, bool), Box<dyn Error>>
+where
+  F: FnOnce(&mut dyn Read, &mut dyn Write) -> Result<T, Box<dyn Error>>,
+{
+  if path == "-" {
+    return Err(error::Error::new(ErrorCode::Stdin, "STDIN can't be modified in place").into());
+  }
+
+  let mut input = Vec::new();
+  let mut output = Vec::new();
+
+  open_input(path)?.read_to_end(&mut input)?;
+
+  let value = f(&mut input.as_slice(), &mut output)?;
+  let modified = output != input;
+
+  if modified {
+    replace_file(Path::new(path), &output)?;
+  }
+
+  Ok((value, modified))
+}
+
+/// A file listing the input files that have been processed, so that an interrupted run can skip
+/// them when it is resumed
+#[derive(Debug)]
+pub struct ProgressFile {
+  path: PathBuf,
+  file: File,
+  done: HashSet<String>,
+}
+
+impl ProgressFile {
+  /// Open a progress file.  If `resume` is set the files already listed in it are treated as
+  /// done, otherwise it is started again.
+  pub fn open(path: &Path, resume: bool) -> io::Result<ProgressFile> {
+    let mut done = HashSet::new();
+
+    if resume && path.exists() {
+      let mut reader = File::open(path)?;
+
+      done.extend(read_file_list(&mut reader, false)?);
+    }
+
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+    if !resume {
+      file.set_len(0)?;
+    }
+
+    Ok(ProgressFile {
+      path: path.to_path_buf(),
+      file,
+      done,
+    })
+  }
+
+  /// Check if an input file was processed by an earlier run.
+  pub fn is_done(&self, name: &str) -> bool {
+    self.done.contains(name)
+  }
+
+  /// Record that an input file has been processed.
+  pub fn mark_done(&mut self, name: &str) -> io::Result<()> {
+    writeln!(self.file, "{}", name)?;
+    self.file.flush()?;
+    self.done.insert(name.to_string());
+    Ok(())
+  }
+
+  /// Delete the progress file once the run is complete.
+  pub fn remove(self) -> io::Result<()> {
+    drop(self.file);
+    std::fs::remove_file(self.path)
+  }
+}
+
+/// An advisory lock on a directory, which is released when dropped.  The lock file is removed
+/// then too.
+#[cfg(feature = "lock")]
+#[derive(Debug)]
+pub struct DirectoryLock {
+  path: PathBuf,
+  file: Option<File>,
+}
+
+#[cfg(feature = "lock")]
+impl Drop for DirectoryLock {
+  fn drop(&mut self) {
+    // On Unix a run waiting for the lock sees that the file it locked was removed and locks a new
+    // one.  On Windows the file can't be removed while another run has it open, so it is closed
+    // first and left for the last run to remove.
+    if cfg!(windows) {
+      self.file.take();
+    }
+
+    std::fs::remove_file(&self.path).ok();
+  }
+}
+
+/// Get the path of the lock file for a directory, which is in the directory, or in the nearest
+/// parent directory that exists if it hasn't been created yet.
+#[cfg(feature = "lock")]
+fn lock_path(dir: &Path) -> PathBuf {
+  dir
+    .ancestors()
+    .find(|dir| dir.is_dir())
+    .unwrap_or_else(|| Path::new("."))
+    .join(".whitespace.lock")
+}
+
+/// Open a lock file so that it can only be removed when no other run has it open.
+#[cfg(all(feature = "lock", windows))]
+fn open_lock_file(path: &Path) -> io::Result<File> {
+  use std::os::windows::fs::OpenOptionsExt;
+  use windows_sys::Win32::Storage::FileSystem::{FILE_SHARE_READ, FILE_SHARE_WRITE};
+
+  OpenOptions::new()
+    .create(true)
+    .write(true)
+    .truncate(false)
+    .share_mode(FILE_SHARE_READ | FILE_SHARE_WRITE)
+    .open(path)
+}
+
+/// Open a lock file.
+#[cfg(all(feature = "lock", not(windows)))]
+fn open_lock_file(path: &Path) -> io::Result<File> {
+  OpenOptions::new()
+    .create(true)
+    .write(true)
+    .truncate(false)
+    .open(path)
+}
+
+/// Wait for an exclusive advisory lock on an open file.
+#[cfg(all(feature = "lock", unix))]
+fn lock_file(file: &File) -> io::Result<()> {
+  use std::os::unix::io::AsRawFd;
+
+  loop {
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+      return Ok(());
+    }
+
+    let err = io::Error::last_os_error();
+
+    if err.kind() != io::ErrorKind::Interrupted {
+      return Err(err);
+    }
+  }
+}
+
+/// Wait for an exclusive advisory lock on an open file.
+#[cfg(all(feature = "lock", windows))]
+fn lock_file(file: &File) -> io::Result<()> {
+  use std::os::windows::io::AsRawHandle;
+  use windows_sys::Win32::Storage::FileSystem::{LockFileEx, LOCKFILE_EXCLUSIVE_LOCK};
+  use windows_sys::Win32::System::IO::OVERLAPPED;
+
+  let mut overlapped = OVERLAPPED::default();
+
+  if unsafe {
+    LockFileEx(
+      file.as_raw_handle() as _,
+      LOCKFILE_EXCLUSIVE_LOCK,
+      0,
+      u32::MAX,
+      u32::MAX,
+      &mut overlapped,
+    )
+  } == 0
+  {
+    Err(io::Error::last_os_error())
+  } else {
+    Ok(())
+  }
+}
+
+/// Check if an open lock file is still the one at `path`, which it isn't if the run that held
+/// the lock before removed it.
+#[cfg(all(feature = "lock", unix))]
+fn is_current_lock_file(file: &File, path: &Path) -> io::Result<bool> {
+  use std::os::unix::fs::MetadataExt;
+
+  let metadata = file.metadata()?;
+
+  Ok(file_id(path) == Some((metadata.dev(), metadata.ino())))
+}
+
+/// Check if an open lock file is still the one at `path`, which it always is since it can't be
+/// removed while it is open.
+#[cfg(all(feature = "lock", not(unix)))]
+fn is_current_lock_file(_file: &File, _path: &Path) -> io::Result<bool> {
+  Ok(true)
+}
+
+/// Wait for an advisory lock on a directory, so that other runs that lock it wait until this one
+/// is done with its files.  The lock file, `.whitespace.lock`, is only there while the lock is
+/// held.
+#[cfg(feature = "lock")]
+pub fn lock_directory(dir: &Path) -> io::Result<DirectoryLock> {
+  let path = lock_path(dir);
+
+  loop {
+    let file = open_lock_file(&path)?;
+
+    lock_file(&file)?;
+
+    if is_current_lock_file(&file, &path)? {
+      return Ok(DirectoryLock {
+        path,
+        file: Some(file),
+      });
+    }
+  }
+}
+
+/// Match a file name against a pattern containing `*` and `?` wildcards.
+///
+/// Matching is case-insensitive on Windows.
+fn matches_wildcard(pattern: &str, name: &str) -> bool {
+  let fold = |s: &str| -> Vec<char> {
+    if cfg!(windows) {
+      s.to_lowercase().chars().collect()
+    } else {
+      s.chars().collect()
+    }
+  };
+  let (pattern, name) = (fold(pattern), fold(name));
+  let (mut p, mut n) = (0, 0);
+  let mut star: Option<(usize, usize)> = None;
+
+  while n < name.len() {
+    if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+      p += 1;
+      n += 1;
+    } else if p < pattern.len() && pattern[p] == '*' {
+      star = Some((p, n));
+      p += 1;
+    } else if let Some((star_p, star_n)) = star {
+      p = star_p + 1;
+      n = star_n + 1;
+      star = Some((star_p, star_n + 1));
+    } else {
+      return false;
+    }
+  }
+
+  pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_matches_wildcard() {
+    assert!(matches_wildcard("*.txt", "abc.txt"));
+    assert!(matches_wildcard("a?c.*", "abc.txt"));
+    assert!(matches_wildcard("*", ""));
+    assert!(matches_wildcard("a*b*c", "aXbYbZc"));
+    assert!(!matches_wildcard("*.txt", "abc.rs"));
+    assert!(!matches_wildcard("a?c", "ac"));
+  }
+
+  #[test]
+  fn test_expand_wildcards() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_str().unwrap().to_string();
+
+    std::fs::write(temp_dir.path().join("b.txt"), "").unwrap();
+    std::fs::write(temp_dir.path().join("a.txt"), "").unwrap();
+    std::fs::write(temp_dir.path().join("c.rs"), "").unwrap();
+
+    let txt_pattern = format!("{}/*.txt", dir);
+    let md_pattern = format!("{}/*.md", dir);
+    let files = expand_wildcards(&[&txt_pattern, "other.rs", &md_pattern]);
+
+    assert_eq!(
+      files,
+      vec![
+        Path::new(&dir).join("a.txt").to_string_lossy().to_string(),
+        Path::new(&dir).join("b.txt").to_string_lossy().to_string(),
+        "other.rs".to_string(),
+        md_pattern.clone(),
+      ]
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_walk_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    std::fs::create_dir_all(temp_dir.path().join("b").join("c")).unwrap();
+    std::fs::write(temp_dir.path().join("z.txt"), "").unwrap();
+    std::fs::write(temp_dir.path().join("b").join("c").join("y.txt"), "").unwrap();
+    std::fs::write(temp_dir.path().join("b").join("x.txt"), "").unwrap();
+
+    assert_eq!(
+      walk_files(temp_dir.path()).unwrap(),
+      vec![
+        Path::new("b").join("c").join("y.txt"),
+        Path::new("b").join("x.txt"),
+        PathBuf::from("z.txt"),
+      ]
+    );
+    assert!(walk_files(&temp_dir.path().join("missing")).is_err());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_walk_text_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+
+    std::fs::create_dir_all(dir.join("b")).unwrap();
+    std::fs::create_dir_all(dir.join(".git")).unwrap();
+    std::fs::write(dir.join("z.txt"), "z\n").unwrap();
+    std::fs::write(dir.join("b").join("x.txt"), "x\n").unwrap();
+    std::fs::write(dir.join("b").join("y.bin"), b"y\0").unwrap();
+    std::fs::write(dir.join(".git").join("HEAD"), "ref\n").unwrap();
+
+    assert_eq!(
+      walk_text_files(dir, false).unwrap(),
+      vec![dir.join("b").join("x.txt"), dir.join("z.txt")]
+    );
+    assert!(walk_text_files(&dir.join("missing"), false).is_err());
+
+    std::fs::create_dir_all(dir.join("target")).unwrap();
+    std::fs::write(dir.join("target").join("t.txt"), "t\n").unwrap();
+    std::fs::write(dir.join("b").join(".gitignore"), "x.txt\n").unwrap();
+
+    assert_eq!(
+      walk_text_files(dir, true).unwrap(),
+      vec![dir.join("b").join(".gitignore"), dir.join("z.txt")]
+    );
+    assert_eq!(walk_text_files(dir, false).unwrap().len(), 4);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_extended_length_path() {
+    let path = extended_length_path(Path::new("abc.txt"));
+
+    if cfg!(windows) {
+      assert!(path.to_string_lossy().starts_with(r"\\?\"));
+    } else {
+      assert_eq!(path, Path::new("abc.txt"));
+    }
+  }
+
+  #[test]
+  fn test_mirrored_path() {
+    let output_dir = Path::new("out");
+
+    assert_eq!(
+      mirrored_path(Path::new("src/a.rs"), output_dir),
+      Path::new("out/src/a.rs")
+    );
+    assert_eq!(
+      mirrored_path(Path::new("./src/../b.rs"), output_dir),
+      Path::new("out/src/b.rs")
+    );
+    assert_eq!(
+      mirrored_path(Path::new("/tmp/c.rs"), output_dir),
+      Path::new("out/tmp/c.rs")
+    );
+  }
+
+  #[test]
+  fn test_suffixed_path() {
+    assert_eq!(
+      suffixed_path(Path::new("src/a.txt"), ".fixed"),
+      Path::new("src/a.txt.fixed")
+    );
+  }
+
+  #[test]
+  fn test_patterned_path() {
+    let pattern = "{dir}/{stem}.lf{ext}";
+
+    assert_eq!(
+      patterned_path(Path::new("src/a.txt"), pattern).unwrap(),
+      Path::new("src/a.lf.txt")
+    );
+    assert_eq!(
+      patterned_path(Path::new("Makefile"), pattern).unwrap(),
+      Path::new("./Makefile.lf")
+    );
+    assert_eq!(
+      patterned_path(Path::new("src/a.txt"), "out/{name}").unwrap(),
+      Path::new("out/a.txt")
+    );
+    assert!(patterned_path(Path::new("a.txt"), "{dir}/{base}").is_err());
+    assert!(patterned_path(Path::new("a.txt"), "{dir").is_err());
+  }
+
+  #[test]
+  fn test_open_input() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("a.txt");
+    let mut s = String::new();
+
+    std::fs::write(&path, "abc").unwrap();
+
+    let mut reader = open_input(path.to_str().unwrap()).unwrap();
+
+    reader.read_to_string(&mut s).unwrap();
+    reader.seek(io::SeekFrom::Start(0)).unwrap();
+    reader.read_to_string(&mut s).unwrap();
+    assert_eq!(s, "abcabc");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  #[cfg(all(unix, feature = "gzip"))]
+  fn test_output_writer_finish() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("full.gz");
+
+    // Writing to /dev/full fails, but only once the compressed data is flushed
+    std::os::unix::fs::symlink("/dev/full", &path).unwrap();
+
+    let mut writer = create_output(&path).unwrap();
+
+    writer.write_all(b"abc\n").unwrap();
+    assert!(writer.finish().is_err());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  #[cfg(feature = "gzip")]
+  fn test_gzip_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("dir/a.log.GZ");
+    let mut s = String::new();
+
+    assert!(is_gzip_path(&path));
+    assert!(!is_gzip_path(Path::new("a.log")));
+
+    let mut writer = create_output(&path).unwrap();
+
+    writer.write_all(b"abc\r\n").unwrap();
+    writer.finish().unwrap();
+    assert_ne!(std::fs::read(&path).unwrap(), b"abc\r\n");
+
+    open_input(path.to_str().unwrap())
+      .unwrap()
+      .read_to_string(&mut s)
+      .unwrap();
+    assert_eq!(s, "abc\r\n");
+
+    let mut reader = open_input(path.to_str().unwrap()).unwrap();
+    let mut buf = [0u8; 2];
+
+    assert_eq!(reader.seek(io::SeekFrom::End(-2)).unwrap(), 3);
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"\r\n");
+    assert_eq!(reader.seek(io::SeekFrom::Start(1)).unwrap(), 1);
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"bc");
+    assert_eq!(reader.seek(io::SeekFrom::Current(-3)).unwrap(), 0);
+    assert_eq!(reader.seek(io::SeekFrom::End(0)).unwrap(), 5);
+    assert!(reader.seek(io::SeekFrom::Current(-6)).is_err());
+
+    // Only the start of a large compressed file is decompressed to sniff it
+    let path = temp_dir.path().join("b.bin.gz");
+    let mut writer = create_output(&path).unwrap();
+
+    writer.write_all(b"a\0").unwrap();
+    (0..1000).for_each(|_| writer.write_all(&[b'x'; 1024]).unwrap());
+    writer.finish().unwrap();
+    assert!(is_binary_file(path.to_str().unwrap()).unwrap());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_read_file_list() {
+    assert_eq!(
+      read_file_list(&mut "a.txt\r\nb c.txt\n\nd.txt".as_bytes(), false).unwrap(),
+      vec!["a.txt", "b c.txt", "d.txt"]
+    );
+    assert_eq!(
+      read_file_list(&mut "a\nb.txt\0c.txt\0".as_bytes(), true).unwrap(),
+      vec!["a\nb.txt", "c.txt"]
+    );
+    assert!(read_file_list(&mut &b"a\xff.txt"[..], false).is_err());
+  }
+
+  #[test]
+  fn test_is_same_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("a.txt");
+
+    std::fs::write(&path, "").unwrap();
+
+    assert!(is_same_file(
+      &path,
+      &temp_dir.path().join(".").join("a.txt")
+    ));
+    assert!(!is_same_file(&path, &temp_dir.path().join("b.txt")));
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_is_binary_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let text_path = temp_dir.path().join("a.txt");
+    let binary_path = temp_dir.path().join("a.bin");
+    let copy_path = temp_dir.path().join("dir/b.bin");
+
+    std::fs::write(&text_path, b"a\rb\xff\n").unwrap();
+    std::fs::write(&binary_path, b"a\0b").unwrap();
+
+    assert!(!is_binary_file(text_path.to_str().unwrap()).unwrap());
+    assert!(is_binary_file(binary_path.to_str().unwrap()).unwrap());
+
+    // UTF-16 has NUL bytes, but is text if it can be read
+    std::fs::write(&text_path, b"\xff\xfea\x00\n\x00").unwrap();
+
+    assert_eq!(
+      is_binary_file(text_path.to_str().unwrap()).unwrap(),
+      !cfg!(feature = "encoding")
+    );
+    assert!(is_binary_file("missing.bin").is_err());
+    assert_eq!(copy_file(&binary_path, &copy_path).unwrap(), 3);
+    assert_eq!(std::fs::read(&copy_path).unwrap(), b"a\0b");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_replace_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("a.txt");
+
+    std::fs::write(&path, "abc\n").unwrap();
+
+    let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+
+    permissions.set_readonly(true);
+    std::fs::set_permissions(&path, permissions).unwrap();
+    replace_file(&path, b"xyz\n").unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "xyz\n");
+    assert!(std::fs::metadata(&path).unwrap().permissions().readonly());
+    assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    assert!(replace_file(&temp_dir.path().join("missing.txt"), b"").is_err());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_process_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("a.txt");
+    let file = path.to_str().unwrap();
+    let to_upper = |reader: &mut dyn Read, writer: &mut dyn Write| {
+      let mut s = String::new();
+
+      reader.read_to_string(&mut s)?;
+      writer.write_all(s.to_uppercase().as_bytes())?;
+      Ok(s.len())
+    };
+
+    std::fs::write(&path, "abc\n").unwrap();
+
+    assert_eq!(process_file(file, to_upper).unwrap(), (4, true));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "ABC\n");
+
+    let modified_time = std::fs::metadata(&path).unwrap().modified().unwrap();
+
+    assert_eq!(process_file(file, to_upper).unwrap(), (4, false));
+    assert_eq!(
+      std::fs::metadata(&path).unwrap().modified().unwrap(),
+      modified_time
+    );
+    assert!(process_file(file, |_, _| -> Result<(), Box<dyn Error>> {
+      Err("failed".into())
+    })
+    .is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "ABC\n");
+    assert!(process_file("-", to_upper).is_err());
+    assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_progress_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("progress.txt");
+    let mut progress = ProgressFile::open(&path, true).unwrap();
+
+    assert!(!progress.is_done("a.txt"));
+    progress.mark_done("a.txt").unwrap();
+    assert!(progress.is_done("a.txt"));
+    drop(progress);
+
+    let mut progress = ProgressFile::open(&path, true).unwrap();
+
+    assert!(progress.is_done("a.txt"));
+    progress.mark_done("b.txt").unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "a.txt\nb.txt\n");
+    drop(progress);
+
+    let progress = ProgressFile::open(&path, false).unwrap();
+
+    assert!(!progress.is_done("a.txt"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    progress.remove().unwrap();
+    assert!(!path.exists());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  #[cfg(feature = "lock")]
+  fn test_lock_directory() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_path_buf();
+    let lock_file = dir.join(".whitespace.lock");
+    let lock = lock_directory(&dir).unwrap();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let other_dir = dir.clone();
+    let other = std::thread::spawn(move || {
+      let lock = lock_directory(&other_dir).unwrap();
+
+      sender.send(()).unwrap();
+      lock
+    });
+
+    assert!(lock_file.exists());
+    assert!(receiver
+      .recv_timeout(std::time::Duration::from_millis(100))
+      .is_err());
+    drop(lock);
+    receiver
+      .recv_timeout(std::time::Duration::from_secs(10))
+      .unwrap();
+    assert!(lock_file.exists());
+    drop(other.join().unwrap());
+    assert!(!lock_file.exists());
+
+    // A directory that hasn't been created yet is locked with the nearest one that exists
+    let new_dir = dir.join("new_dir/sub_dir");
+
+    assert_eq!(lock_path(&new_dir), lock_file);
+    drop(lock_directory(&new_dir).unwrap());
+    assert!(!new_dir.exists());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_unique_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path_a = temp_dir.path().join("a.txt");
+    let path_b = temp_dir.path().join("b.txt");
+    let path_c = temp_dir.path().join("c.txt");
+    let file = |path: &Path| path.to_string_lossy().to_string();
+
+    std::fs::write(&path_a, "").unwrap();
+    std::fs::write(&path_b, "").unwrap();
+    std::fs::hard_link(&path_a, &path_c).unwrap();
+
+    let files = vec![
+      file(&path_a),
+      file(&path_b),
+      file(&temp_dir.path().join(".").join("a.txt")),
+      file(&path_c),
+      "-".to_string(),
+      "-".to_string(),
+      "missing.txt".to_string(),
+    ];
+
+    let mut expected = vec![file(&path_a), file(&path_b)];
+
+    if !cfg!(unix) {
+      expected.push(file(&path_c));
+    }
+
+    expected.extend(vec![
+      "-".to_string(),
+      "-".to_string(),
+      "missing.txt".to_string(),
+    ]);
+    assert_eq!(unique_files(&files), expected);
+
+    temp_dir.close().unwrap();
+  }
+}
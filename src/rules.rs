@@ -0,0 +1,517 @@
+//! Check and fix lines with a set of rules.
+//!
+//! Each [`Rule`] looks at one line at a time, finds the [`Issue`]s with it and fixes them.  The
+//! behavior of `ender`, `spacer` and `trimmer` is available as the [`LineEndings`],
+//! [`LineBeginnings`] and [`TrailingWhitespace`] rules, and other rules can be added by
+//! implementing the trait.  Use [`check_lines()`] to find the issues in a [`Read`] trait object
+//! and [`fix_lines()`] to write it out with them fixed:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::ender::EndOfLine;
+//! use whitespace_rs::rules::{self, LineEndings, Rule, TrailingWhitespace};
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let rules: Vec<Box<dyn Rule>> = vec![
+//!     Box::new(LineEndings(EndOfLine::Lf)),
+//!     Box::new(TrailingWhitespace),
+//!   ];
+//!   let issues = rules::check_lines(&mut "abc \r\nxyz\n".as_bytes(), &rules)?;
+//!
+//!   assert_eq!(issues.len(), 2);
+//!
+//!   let mut writer = Vec::new();
+//!
+//!   rules::fix_lines(&mut "abc \r\nxyz\n".as_bytes(), &mut writer, &rules)?;
+//!   assert_eq!(writer, b"abc\nxyz\n");
+//!   Ok(())
+//! }
+//! ```
+//!
+//! Issues are errors unless the rule gives another [`Severity`].  Use [`WithSeverity`] to change
+//! the severity of a rule, or turn it off.  Set [`RuleOptions::directives`] and use the
+//! `_with_options` functions to leave the lines between `ws:disable` and `ws:enable` comments
+//! alone.
+
+use crate::ender::EndOfLine;
+use crate::lines::{self, Directives};
+use crate::spacer::{self, BeginningOfLine};
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+// {grcov-excl-start}
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+/// How serious the issues found by a rule are
+pub enum Severity {
+  /// The rule isn't checked or fixed
+  Off,
+  /// Issues are reported but don't fail a check
+  Warning,
+  /// Issues are reported and fail a check
+  Error,
+}
+// {grcov-excl-end}
+
+/// Parse `off`, `warning` or `error`, ignoring case.
+impl FromStr for Severity {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "off" => Ok(Severity::Off),
+      "warning" => Ok(Severity::Warning),
+      "error" => Ok(Severity::Error),
+      _ => Err(format!("'{}' is not one of off, warning or error", s)),
+    }
+  }
+}
+
+impl fmt::Display for Severity {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(match self {
+      Severity::Off => "off",
+      Severity::Warning => "warning",
+      Severity::Error => "error",
+    })
+  }
+}
+
+impl Severity {
+  /// Names of the severities, as they are parsed
+  pub const NAMES: &'static [&'static str] = &["off", "warning", "error"];
+}
+
+/// A line checked or fixed by a [`Rule`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Line {
+  /// Line number, starting at 1
+  pub number: usize,
+  /// Content of the line without the line ending
+  pub content: String,
+  /// Line ending, or `None` for the last line of a file with no final line ending
+  pub ending: Option<EndOfLine>,
+}
+
+/// A problem that a [`Rule`] found with a line
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Issue {
+  /// Name of the rule
+  pub rule: String,
+  /// Severity of the rule
+  pub severity: Severity,
+  /// Line number, starting at 1
+  pub line: usize,
+  /// Column number in characters, starting at 1
+  pub column: usize,
+  /// Description of the problem
+  pub message: String,
+}
+
+impl Issue {
+  /// Create an issue found by `rule` at `column` of `line`.
+  pub fn new(rule: &dyn Rule, line: &Line, column: usize, message: String) -> Self {
+    Issue {
+      rule: rule.name().to_string(),
+      severity: rule.severity(),
+      line: line.number,
+      column,
+      message,
+    }
+  }
+}
+
+/// A check on each line of a file, and how to fix the problems it finds
+pub trait Rule {
+  /// Name of the rule, used to turn it on and off
+  fn name(&self) -> &str;
+
+  /// Severity of the issues found by the rule
+  fn severity(&self) -> Severity {
+    Severity::Error
+  }
+
+  /// Find the problems with a line.
+  fn check(&self, line: &Line) -> Vec<Issue>;
+
+  /// Fix the problems with a line that [`check()`](Rule::check) finds.
+  fn fix(&self, line: &mut Line);
+}
+
+/// A rule with a different severity
+pub struct WithSeverity {
+  rule: Box<dyn Rule>,
+  severity: Severity,
+}
+
+impl WithSeverity {
+  /// Give the issues found by `rule` a severity of `severity`.
+  pub fn new(rule: Box<dyn Rule>, severity: Severity) -> Self {
+    WithSeverity { rule, severity }
+  }
+}
+
+impl Rule for WithSeverity {
+  fn name(&self) -> &str {
+    self.rule.name()
+  }
+
+  fn severity(&self) -> Severity {
+    self.severity
+  }
+
+  fn check(&self, line: &Line) -> Vec<Issue> {
+    let mut issues = self.rule.check(line);
+
+    issues
+      .iter_mut()
+      .for_each(|issue| issue.severity = self.severity);
+    issues
+  }
+
+  fn fix(&self, line: &mut Line) {
+    self.rule.fix(line)
+  }
+}
+
+/// Names of the built-in rules
+pub const NAMES: &[&str] = &["line-endings", "line-beginnings", "trailing-whitespace"];
+
+/// Lines must end with the given line ending, as `ender` converts them
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LineEndings(pub EndOfLine);
+
+impl Rule for LineEndings {
+  fn name(&self) -> &str {
+    "line-endings"
+  }
+
+  fn check(&self, line: &Line) -> Vec<Issue> {
+    match line.ending {
+      Some(ending) if ending != self.0 => vec![Issue::new(
+        self,
+        line,
+        line.content.chars().count() + 1,
+        format!("{} line ending, expected {}", ending, self.0),
+      )],
+      _ => Vec::new(),
+    }
+  }
+
+  fn fix(&self, line: &mut Line) {
+    if line.ending.is_some() {
+      line.ending = Some(self.0);
+    }
+  }
+}
+
+/// Lines must begin with the given kind of indentation, as `spacer` converts them with the default
+/// options
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LineBeginnings(pub BeginningOfLine);
+
+impl LineBeginnings {
+  /// Split a line into its beginning and the rest of it
+  fn split(content: &str) -> (&str, &str) {
+    content.split_at(content.len() - content.trim_start_matches([' ', '\t']).len())
+  }
+}
+
+impl Rule for LineBeginnings {
+  fn name(&self) -> &str {
+    "line-beginnings"
+  }
+
+  fn check(&self, line: &Line) -> Vec<Issue> {
+    let (bol, _) = LineBeginnings::split(&line.content);
+
+    if spacer::convert_bol(bol, self.0) == bol {
+      return Vec::new();
+    }
+
+    let other = match (bol.contains(' '), bol.contains('\t')) {
+      (true, true) => "mixed tabs and spaces",
+      (true, false) => "spaces",
+      _ => "tabs",
+    };
+
+    vec![Issue::new(
+      self,
+      line,
+      1,
+      format!("line begins with {}, expected {}", other, self.0),
+    )]
+  }
+
+  fn fix(&self, line: &mut Line) {
+    let (bol, rest) = LineBeginnings::split(&line.content);
+
+    line.content = spacer::convert_bol(bol, self.0) + rest;
+  }
+}
+
+/// Lines must not end in spaces or tabs, as `trimmer` removes them
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TrailingWhitespace;
+
+impl Rule for TrailingWhitespace {
+  fn name(&self) -> &str {
+    "trailing-whitespace"
+  }
+
+  fn check(&self, line: &Line) -> Vec<Issue> {
+    let text = line.content.trim_end_matches([' ', '\t']);
+
+    if text.len() < line.content.len() {
+      vec![Issue::new(
+        self,
+        line,
+        text.chars().count() + 1,
+        "trailing whitespace".to_string(),
+      )]
+    } else {
+      Vec::new()
+    }
+  }
+
+  fn fix(&self, line: &mut Line) {
+    let len = line.content.trim_end_matches([' ', '\t']).len();
+
+    line.content.truncate(len);
+  }
+}
+
+/// Options for [`check_lines_with_options()`] and [`fix_lines_with_options()`]
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct RuleOptions {
+  /// Leave the lines after a `ws:disable` comment, up to and including the next `ws:enable`
+  /// comment, unchecked and unchanged
+  pub directives: bool,
+}
+
+impl RuleOptions {
+  /// Set [`directives`](RuleOptions::directives)
+  pub fn directives(mut self, directives: bool) -> Self {
+    self.directives = directives;
+    self
+  }
+}
+
+/// Check each line of the input with each of `rules` that isn't off, and get the issues found in
+/// line order.
+pub fn check_lines(
+  reader: &mut dyn Read,
+  rules: &[Box<dyn Rule>],
+) -> Result<Vec<Issue>, Box<dyn Error>> {
+  check_lines_with_options(reader, rules, &RuleOptions::default())
+}
+
+/// Check each line of the input as [`check_lines()`] does, with options.
+pub fn check_lines_with_options(
+  reader: &mut dyn Read,
+  rules: &[Box<dyn Rule>],
+  options: &RuleOptions,
+) -> Result<Vec<Issue>, Box<dyn Error>> {
+  let mut issues = Vec::new();
+  let mut directives = Directives::new(options.directives);
+
+  for (i, value) in lines::lines_with_endings(reader).enumerate() {
+    let (content, ending) = value?;
+    let line = Line {
+      number: i + 1,
+      content,
+      ending,
+    };
+
+    if !directives.is_disabled() {
+      for rule in rules.iter().filter(|rule| rule.severity() != Severity::Off) {
+        issues.extend(rule.check(&line));
+      }
+    }
+
+    directives.push_line(&line.content);
+  }
+
+  Ok(issues)
+}
+
+/// Write the input out with each line fixed by each of `rules` that isn't off in turn.
+///
+/// Returns the number of lines written.
+pub fn fix_lines(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  rules: &[Box<dyn Rule>],
+) -> Result<usize, Box<dyn Error>> {
+  fix_lines_with_options(reader, writer, rules, &RuleOptions::default())
+}
+
+/// Write the input out with each line fixed as [`fix_lines()`] does, with options.
+pub fn fix_lines_with_options(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  rules: &[Box<dyn Rule>],
+  options: &RuleOptions,
+) -> Result<usize, Box<dyn Error>> {
+  let mut num_lines = 0;
+  let mut directives = Directives::new(options.directives);
+
+  for (i, value) in lines::lines_with_endings(reader).enumerate() {
+    let (content, ending) = value?;
+    let mut line = Line {
+      number: i + 1,
+      content,
+      ending,
+    };
+
+    if !directives.is_disabled() {
+      for rule in rules.iter().filter(|rule| rule.severity() != Severity::Off) {
+        rule.fix(&mut line);
+      }
+    }
+
+    directives.push_line(&line.content);
+
+    writer.write_all(line.content.as_bytes())?;
+    writer.write_all(line.ending.map_or("", EndOfLine::as_str).as_bytes())?;
+    num_lines += 1;
+  }
+
+  writer.flush()?;
+
+  Ok(num_lines)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A rule that lines must not contain tabs after their beginning
+  struct TabsInMiddle;
+
+  impl Rule for TabsInMiddle {
+    fn name(&self) -> &str {
+      "tabs-in-middle"
+    }
+
+    fn check(&self, line: &Line) -> Vec<Issue> {
+      let text = line.content.trim_start_matches([' ', '\t']);
+      let start = line.content.len() - text.len();
+
+      match text.find('\t') {
+        Some(i) => vec![Issue::new(self, line, start + i + 1, "tab".to_string())],
+        None => Vec::new(),
+      }
+    }
+
+    fn fix(&self, line: &mut Line) {
+      line.content = line.content.replace("a\tb", "a b");
+    }
+  }
+
+  #[test]
+  fn test_check_lines() {
+    let rules: Vec<Box<dyn Rule>> = vec![
+      Box::new(LineEndings(EndOfLine::Lf)),
+      Box::new(LineBeginnings(BeginningOfLine::Spaces(2))),
+      Box::new(TrailingWhitespace),
+      Box::new(TabsInMiddle),
+    ];
+    let issues = check_lines(&mut "\tx \r\n  a\tb\n  \t\nend".as_bytes(), &rules).unwrap();
+    let found: Vec<_> = issues
+      .iter()
+      .map(|issue| (issue.rule.as_str(), issue.line, issue.column))
+      .collect();
+
+    assert_eq!(
+      found,
+      vec![
+        ("line-endings", 1, 4),
+        ("line-beginnings", 1, 1),
+        ("trailing-whitespace", 1, 3),
+        ("tabs-in-middle", 2, 4),
+        ("line-beginnings", 3, 1),
+        ("trailing-whitespace", 3, 1),
+      ]
+    );
+    assert_eq!(issues[1].message, "line begins with tabs, expected spaces");
+    assert_eq!(issues[1].severity, Severity::Error);
+  }
+
+  #[test]
+  fn test_severity() {
+    let rules: Vec<Box<dyn Rule>> = vec![
+      Box::new(WithSeverity::new(
+        Box::new(LineEndings(EndOfLine::Lf)),
+        Severity::Off,
+      )),
+      Box::new(WithSeverity::new(
+        Box::new(TrailingWhitespace),
+        Severity::Warning,
+      )),
+    ];
+    let input = "abc \r\n";
+    let issues = check_lines(&mut input.as_bytes(), &rules).unwrap();
+    let mut output = Vec::new();
+
+    assert_eq!(issues.len(), 1);
+    assert_eq!(
+      (issues[0].rule.as_str(), issues[0].severity),
+      ("trailing-whitespace", Severity::Warning)
+    );
+    fix_lines(&mut input.as_bytes(), &mut output, &rules).unwrap();
+    assert_eq!(output, b"abc\r\n");
+    assert_eq!("Warning".parse::<Severity>().unwrap(), Severity::Warning);
+    assert!("fatal".parse::<Severity>().is_err());
+    assert_eq!(Severity::Off.to_string(), "off");
+  }
+
+  #[test]
+  fn test_fix_lines() {
+    let rules: Vec<Box<dyn Rule>> = vec![
+      Box::new(LineEndings(EndOfLine::CrLf)),
+      Box::new(LineBeginnings(BeginningOfLine::Tabs(4, false))),
+      Box::new(TrailingWhitespace),
+      Box::new(TabsInMiddle),
+    ];
+    let input = "      x \n    a\tb\r\n  \t\nend";
+    let mut output = Vec::new();
+
+    assert_eq!(
+      fix_lines(&mut input.as_bytes(), &mut output, &rules).unwrap(),
+      4
+    );
+    assert_eq!(
+      String::from_utf8(output.clone()).unwrap(),
+      "\t  x\r\n\ta b\r\n\r\nend"
+    );
+    assert!(check_lines(&mut output.as_slice(), &rules)
+      .unwrap()
+      .is_empty());
+  }
+
+  #[test]
+  fn test_directives() {
+    let rules: Vec<Box<dyn Rule>> = vec![Box::new(TrailingWhitespace)];
+    let input = "a \n# ws:disable\nb \n# ws:enable \nc \n";
+    let options = RuleOptions::default().directives(true);
+    let issues = check_lines_with_options(&mut input.as_bytes(), &rules, &options).unwrap();
+    let mut output = Vec::new();
+
+    assert_eq!(
+      issues.iter().map(|issue| issue.line).collect::<Vec<_>>(),
+      vec![1, 5]
+    );
+    assert_eq!(check_lines(&mut input.as_bytes(), &rules).unwrap().len(), 4);
+    fix_lines_with_options(&mut input.as_bytes(), &mut output, &rules, &options).unwrap();
+    assert_eq!(
+      String::from_utf8(output).unwrap(),
+      "a\n# ws:disable\nb \n# ws:enable \nc\n"
+    );
+  }
+}
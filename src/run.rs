@@ -0,0 +1,3721 @@
+//! Report on and optionally convert files exactly as the command line tools do.
+//!
+//! [`run_ender()`], [`run_spacer()`] and [`run_trimmer()`] read one input file, work out its line
+//! endings, beginnings or trailing whitespace, optionally write the converted file, modify it in
+//! place or make a fix script, and return a report of what they found and did:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::run::{self, EnderRunOptions, EolTarget};
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let temp_dir = tempfile::tempdir()?;
+//!   let input_path = temp_dir.path().join("a.txt");
+//!   let input_file = input_path.to_str().unwrap();
+//!
+//!   std::fs::write(input_file, "abc\r\nxyz\n")?;
+//!
+//!   let options = EnderRunOptions::default()
+//!     .new_eol(Some(EolTarget::Lf))
+//!     .in_place(true);
+//!   let report = run::run_ender(input_file, None, &options)?;
+//!
+//!   assert!(report.modified);
+//!   assert_eq!(std::fs::read_to_string(input_file)?, "abc\nxyz\n");
+//!   Ok(())
+//! }
+//! ```
+//!
+//! An input file of `-` is read from STDIN, and the converted output goes to STDOUT if there is
+//! no output file.
+
+use crate::audit::{self, AuditLog, Change};
+use crate::decode::DecodePolicy;
+#[cfg(feature = "encoding")]
+use crate::encoding::{self, Encoding};
+use crate::ender::{
+  needs_new_eols, read_eol_info_with_options, verify_new_eols_with_options,
+  write_new_eols_with_options, EndOfLine, EolInfo, EolOptions, TieBreak,
+};
+use crate::error::{self, ErrorCode};
+use crate::filetype::FileType;
+use crate::git::{self, AutoCrlf};
+use crate::journal::Journal;
+use crate::lines::{self, Directives, LineAction};
+use crate::paths::{OutputWriter, ReadSeek};
+use crate::spacer::{
+  alignment_changes, infer_tab_size, needs_new_bols, read_bol_info_with_options,
+  verify_new_bols_with_options, write_new_bols_with_options, BeginningOfLine, BolInfo, BolOptions,
+  BolStrategy,
+};
+use crate::trimmer::{
+  read_trailing_info_with_options, verify_trimmed_with_options, write_trimmed_with_options,
+  TrailingInfo, TrimOptions,
+};
+use crate::{patch, paths};
+use std::borrow::Cow;
+use std::error::Error;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Line endings to convert to
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EolTarget {
+  /// Carriage returns
+  Cr,
+  /// Line feeds
+  Lf,
+  /// Carriage return and line feed pairs
+  CrLf,
+  /// The most common line ending in the file
+  Auto,
+}
+
+/// Line beginnings to convert to
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BolTarget {
+  /// Tabs, with spaces for any columns left over
+  Tabs,
+  /// Spaces
+  Spaces,
+  /// The most common line beginning in the file
+  Auto,
+}
+
+/// Options for [`run_ender()`]
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct EnderRunOptions {
+  /// Line endings to convert to, or `None` to only report
+  pub new_eol: Option<EolTarget>,
+  /// Convert as git would with this `core.autocrlf` setting, instead of to `new_eol`
+  pub autocrlf: Option<AutoCrlf>,
+  /// End the last line with the new line ending if it isn't empty and has no ending
+  pub final_newline: bool,
+  /// Treat the Unicode line separators NEL, LS and PS as line endings
+  pub unicode_separators: bool,
+  /// Leave the line endings between `ws:disable` and `ws:enable` comments unchanged
+  pub directives: bool,
+  /// How `EolTarget::Auto` chooses between line endings that are equally common
+  pub tie_break: TieBreak,
+  /// What to do with bytes that aren't valid UTF-8
+  pub decode_policy: DecodePolicy,
+  /// Check the converted output before writing it
+  pub verify: bool,
+  /// Add the numbers of characters, words, bytes and blank lines to the report text
+  pub counts: bool,
+  /// Modify the input file in place
+  pub in_place: bool,
+  /// Make a shell command that changes the input file instead of changing it
+  pub emit_script: bool,
+  /// Make a unified diff of the changes with the whitespace made visible instead of writing them
+  pub diff: bool,
+  /// Only check if converting would change the input file, without writing anything
+  pub check: bool,
+  /// Encoding to read the input file in, or `None` to detect it
+  #[cfg(feature = "encoding")]
+  pub input_encoding: Option<Encoding>,
+  /// Encoding to write converted output in, or `None` for the encoding of the input file
+  #[cfg(feature = "encoding")]
+  pub output_encoding: Option<Encoding>,
+  /// Make GNU style diagnostics for the line endings that differ
+  pub diagnostics: bool,
+  /// Journal to save the original of each file modified in place to
+  pub journal: Option<Journal>,
+  /// Audit log to record each file modified in place in
+  pub audit_log: Option<AuditLog>,
+  /// Name to report an input file of `-` as
+  pub stdin_filename: Option<String>,
+  /// Fail rather than overwrite an output file that already exists
+  pub no_clobber: bool,
+  /// Fail rather than read an input file larger than this many bytes into memory, which verifying,
+  /// modifying in place and making a script or diff do
+  pub max_memory_size: Option<u64>,
+}
+
+impl EnderRunOptions {
+  /// Set [`new_eol`](EnderRunOptions::new_eol)
+  pub fn new_eol(mut self, new_eol: Option<EolTarget>) -> Self {
+    self.new_eol = new_eol;
+    self
+  }
+
+  /// Set [`autocrlf`](EnderRunOptions::autocrlf)
+  pub fn autocrlf(mut self, autocrlf: Option<AutoCrlf>) -> Self {
+    self.autocrlf = autocrlf;
+    self
+  }
+
+  /// Set [`final_newline`](EnderRunOptions::final_newline)
+  pub fn final_newline(mut self, final_newline: bool) -> Self {
+    self.final_newline = final_newline;
+    self
+  }
+
+  /// Set [`unicode_separators`](EnderRunOptions::unicode_separators)
+  pub fn unicode_separators(mut self, unicode_separators: bool) -> Self {
+    self.unicode_separators = unicode_separators;
+    self
+  }
+
+  /// Set [`directives`](EnderRunOptions::directives)
+  pub fn directives(mut self, directives: bool) -> Self {
+    self.directives = directives;
+    self
+  }
+
+  /// Set [`tie_break`](EnderRunOptions::tie_break)
+  pub fn tie_break(mut self, tie_break: TieBreak) -> Self {
+    self.tie_break = tie_break;
+    self
+  }
+
+  /// Set [`decode_policy`](EnderRunOptions::decode_policy)
+  pub fn decode_policy(mut self, decode_policy: DecodePolicy) -> Self {
+    self.decode_policy = decode_policy;
+    self
+  }
+
+  /// Set [`verify`](EnderRunOptions::verify)
+  pub fn verify(mut self, verify: bool) -> Self {
+    self.verify = verify;
+    self
+  }
+
+  /// Set [`counts`](EnderRunOptions::counts)
+  pub fn counts(mut self, counts: bool) -> Self {
+    self.counts = counts;
+    self
+  }
+
+  /// Set [`in_place`](EnderRunOptions::in_place)
+  pub fn in_place(mut self, in_place: bool) -> Self {
+    self.in_place = in_place;
+    self
+  }
+
+  /// Set [`emit_script`](EnderRunOptions::emit_script)
+  pub fn emit_script(mut self, emit_script: bool) -> Self {
+    self.emit_script = emit_script;
+    self
+  }
+
+  /// Set [`diff`](EnderRunOptions::diff)
+  pub fn diff(mut self, diff: bool) -> Self {
+    self.diff = diff;
+    self
+  }
+
+  /// Set [`check`](EnderRunOptions::check)
+  pub fn check(mut self, check: bool) -> Self {
+    self.check = check;
+    self
+  }
+
+  /// Set [`input_encoding`](EnderRunOptions::input_encoding)
+  #[cfg(feature = "encoding")]
+  pub fn input_encoding(mut self, input_encoding: Option<Encoding>) -> Self {
+    self.input_encoding = input_encoding;
+    self
+  }
+
+  /// Set [`output_encoding`](EnderRunOptions::output_encoding)
+  #[cfg(feature = "encoding")]
+  pub fn output_encoding(mut self, output_encoding: Option<Encoding>) -> Self {
+    self.output_encoding = output_encoding;
+    self
+  }
+
+  /// Set [`diagnostics`](EnderRunOptions::diagnostics)
+  pub fn diagnostics(mut self, diagnostics: bool) -> Self {
+    self.diagnostics = diagnostics;
+    self
+  }
+
+  /// Set [`journal`](EnderRunOptions::journal)
+  pub fn journal(mut self, journal: Option<Journal>) -> Self {
+    self.journal = journal;
+    self
+  }
+
+  /// Set [`audit_log`](EnderRunOptions::audit_log)
+  pub fn audit_log(mut self, audit_log: Option<AuditLog>) -> Self {
+    self.audit_log = audit_log;
+    self
+  }
+
+  /// Set [`stdin_filename`](EnderRunOptions::stdin_filename)
+  pub fn stdin_filename(mut self, stdin_filename: Option<String>) -> Self {
+    self.stdin_filename = stdin_filename;
+    self
+  }
+
+  /// Set [`no_clobber`](EnderRunOptions::no_clobber)
+  pub fn no_clobber(mut self, no_clobber: bool) -> Self {
+    self.no_clobber = no_clobber;
+    self
+  }
+
+  /// Set [`max_memory_size`](EnderRunOptions::max_memory_size)
+  pub fn max_memory_size(mut self, max_memory_size: Option<u64>) -> Self {
+    self.max_memory_size = max_memory_size;
+    self
+  }
+}
+
+/// Options for [`run_spacer()`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct SpacerRunOptions {
+  /// Line beginnings to convert to, or `None` to only report
+  pub new_bol: Option<BolTarget>,
+  /// Number of columns in a tab
+  pub tab_size: usize,
+  /// Use the tab size inferred from files with both tabs and spaces instead of `tab_size`
+  pub infer_tab_size: bool,
+  /// Drop spaces left over when converting to tabs
+  pub round_down: bool,
+  /// How `BolTarget::Auto` decides which line beginning is most common
+  pub bol_strategy: BolStrategy,
+  /// Which lines to change and how
+  pub bol_options: BolOptions,
+  /// Check the converted output before writing it
+  pub verify: bool,
+  /// Add the numbers of characters, words and bytes to the report text
+  pub counts: bool,
+  /// Modify the input file in place
+  pub in_place: bool,
+  /// Make a shell command that changes the input file instead of changing it
+  pub emit_script: bool,
+  /// Make a unified diff of the changes with the whitespace made visible instead of writing them
+  pub diff: bool,
+  /// Only check if converting would change the input file, without writing anything
+  pub check: bool,
+  /// Encoding to read the input file in, or `None` to detect it
+  #[cfg(feature = "encoding")]
+  pub input_encoding: Option<Encoding>,
+  /// Encoding to write converted output in, or `None` for the encoding of the input file
+  #[cfg(feature = "encoding")]
+  pub output_encoding: Option<Encoding>,
+  /// Make GNU style diagnostics for the line beginnings that differ
+  pub diagnostics: bool,
+  /// Journal to save the original of each file modified in place to
+  pub journal: Option<Journal>,
+  /// Audit log to record each file modified in place in
+  pub audit_log: Option<AuditLog>,
+  /// Name to report an input file of `-` as
+  pub stdin_filename: Option<String>,
+  /// Fail rather than overwrite an output file that already exists
+  pub no_clobber: bool,
+  /// Fail rather than read an input file larger than this many bytes into memory, which verifying,
+  /// checking, modifying in place and making a script or diff do
+  pub max_memory_size: Option<u64>,
+}
+
+impl Default for SpacerRunOptions {
+  fn default() -> Self {
+    SpacerRunOptions {
+      new_bol: None,
+      tab_size: 4,
+      infer_tab_size: false,
+      round_down: false,
+      bol_strategy: BolStrategy::default(),
+      bol_options: BolOptions::default(),
+      verify: false,
+      counts: false,
+      in_place: false,
+      emit_script: false,
+      diff: false,
+      check: false,
+      #[cfg(feature = "encoding")]
+      input_encoding: None,
+      #[cfg(feature = "encoding")]
+      output_encoding: None,
+      diagnostics: false,
+      journal: None,
+      audit_log: None,
+      stdin_filename: None,
+      no_clobber: false,
+      max_memory_size: None,
+    }
+  }
+}
+
+impl SpacerRunOptions {
+  /// Set [`new_bol`](SpacerRunOptions::new_bol)
+  pub fn new_bol(mut self, new_bol: Option<BolTarget>) -> Self {
+    self.new_bol = new_bol;
+    self
+  }
+
+  /// Set [`tab_size`](SpacerRunOptions::tab_size)
+  pub fn tab_size(mut self, tab_size: usize) -> Self {
+    self.tab_size = tab_size;
+    self
+  }
+
+  /// Set [`infer_tab_size`](SpacerRunOptions::infer_tab_size)
+  pub fn infer_tab_size(mut self, infer_tab_size: bool) -> Self {
+    self.infer_tab_size = infer_tab_size;
+    self
+  }
+
+  /// Set [`round_down`](SpacerRunOptions::round_down)
+  pub fn round_down(mut self, round_down: bool) -> Self {
+    self.round_down = round_down;
+    self
+  }
+
+  /// Set [`bol_strategy`](SpacerRunOptions::bol_strategy)
+  pub fn bol_strategy(mut self, bol_strategy: BolStrategy) -> Self {
+    self.bol_strategy = bol_strategy;
+    self
+  }
+
+  /// Set [`bol_options`](SpacerRunOptions::bol_options)
+  pub fn bol_options(mut self, bol_options: BolOptions) -> Self {
+    self.bol_options = bol_options;
+    self
+  }
+
+  /// Set [`verify`](SpacerRunOptions::verify)
+  pub fn verify(mut self, verify: bool) -> Self {
+    self.verify = verify;
+    self
+  }
+
+  /// Set [`counts`](SpacerRunOptions::counts)
+  pub fn counts(mut self, counts: bool) -> Self {
+    self.counts = counts;
+    self
+  }
+
+  /// Set [`in_place`](SpacerRunOptions::in_place)
+  pub fn in_place(mut self, in_place: bool) -> Self {
+    self.in_place = in_place;
+    self
+  }
+
+  /// Set [`emit_script`](SpacerRunOptions::emit_script)
+  pub fn emit_script(mut self, emit_script: bool) -> Self {
+    self.emit_script = emit_script;
+    self
+  }
+
+  /// Set [`diff`](SpacerRunOptions::diff)
+  pub fn diff(mut self, diff: bool) -> Self {
+    self.diff = diff;
+    self
+  }
+
+  /// Set [`check`](SpacerRunOptions::check)
+  pub fn check(mut self, check: bool) -> Self {
+    self.check = check;
+    self
+  }
+
+  /// Set [`input_encoding`](SpacerRunOptions::input_encoding)
+  #[cfg(feature = "encoding")]
+  pub fn input_encoding(mut self, input_encoding: Option<Encoding>) -> Self {
+    self.input_encoding = input_encoding;
+    self
+  }
+
+  /// Set [`output_encoding`](SpacerRunOptions::output_encoding)
+  #[cfg(feature = "encoding")]
+  pub fn output_encoding(mut self, output_encoding: Option<Encoding>) -> Self {
+    self.output_encoding = output_encoding;
+    self
+  }
+
+  /// Set [`diagnostics`](SpacerRunOptions::diagnostics)
+  pub fn diagnostics(mut self, diagnostics: bool) -> Self {
+    self.diagnostics = diagnostics;
+    self
+  }
+
+  /// Set [`journal`](SpacerRunOptions::journal)
+  pub fn journal(mut self, journal: Option<Journal>) -> Self {
+    self.journal = journal;
+    self
+  }
+
+  /// Set [`audit_log`](SpacerRunOptions::audit_log)
+  pub fn audit_log(mut self, audit_log: Option<AuditLog>) -> Self {
+    self.audit_log = audit_log;
+    self
+  }
+
+  /// Set [`stdin_filename`](SpacerRunOptions::stdin_filename)
+  pub fn stdin_filename(mut self, stdin_filename: Option<String>) -> Self {
+    self.stdin_filename = stdin_filename;
+    self
+  }
+
+  /// Set [`no_clobber`](SpacerRunOptions::no_clobber)
+  pub fn no_clobber(mut self, no_clobber: bool) -> Self {
+    self.no_clobber = no_clobber;
+    self
+  }
+
+  /// Set [`max_memory_size`](SpacerRunOptions::max_memory_size)
+  pub fn max_memory_size(mut self, max_memory_size: Option<u64>) -> Self {
+    self.max_memory_size = max_memory_size;
+    self
+  }
+}
+
+/// Options for [`run_trimmer()`]
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct TrimmerRunOptions {
+  /// Remove trailing whitespace, or only report it if `false`
+  pub trim: bool,
+  /// Leave the trailing whitespace between `ws:disable` and `ws:enable` comments alone
+  pub directives: bool,
+  /// Check the trimmed output before writing it
+  pub verify: bool,
+  /// Add the numbers of characters, words and bytes to the report text
+  pub counts: bool,
+  /// Modify the input file in place
+  pub in_place: bool,
+  /// Make a shell command that changes the input file instead of changing it
+  pub emit_script: bool,
+  /// Make a unified diff of the changes with the whitespace made visible instead of writing them
+  pub diff: bool,
+  /// Make GNU style diagnostics for the lines with trailing whitespace
+  pub diagnostics: bool,
+  /// Journal to save the original of each file modified in place to
+  pub journal: Option<Journal>,
+  /// Audit log to record each file modified in place in
+  pub audit_log: Option<AuditLog>,
+  /// Name to report an input file of `-` as
+  pub stdin_filename: Option<String>,
+  /// Fail rather than overwrite an output file that already exists
+  pub no_clobber: bool,
+  /// Fail rather than read an input file larger than this many bytes into memory, which verifying,
+  /// modifying in place and making a script or diff do
+  pub max_memory_size: Option<u64>,
+}
+
+impl TrimmerRunOptions {
+  /// Set [`trim`](TrimmerRunOptions::trim)
+  pub fn trim(mut self, trim: bool) -> Self {
+    self.trim = trim;
+    self
+  }
+
+  /// Set [`directives`](TrimmerRunOptions::directives)
+  pub fn directives(mut self, directives: bool) -> Self {
+    self.directives = directives;
+    self
+  }
+
+  /// Set [`verify`](TrimmerRunOptions::verify)
+  pub fn verify(mut self, verify: bool) -> Self {
+    self.verify = verify;
+    self
+  }
+
+  /// Set [`counts`](TrimmerRunOptions::counts)
+  pub fn counts(mut self, counts: bool) -> Self {
+    self.counts = counts;
+    self
+  }
+
+  /// Set [`in_place`](TrimmerRunOptions::in_place)
+  pub fn in_place(mut self, in_place: bool) -> Self {
+    self.in_place = in_place;
+    self
+  }
+
+  /// Set [`emit_script`](TrimmerRunOptions::emit_script)
+  pub fn emit_script(mut self, emit_script: bool) -> Self {
+    self.emit_script = emit_script;
+    self
+  }
+
+  /// Set [`diff`](TrimmerRunOptions::diff)
+  pub fn diff(mut self, diff: bool) -> Self {
+    self.diff = diff;
+    self
+  }
+
+  /// Set [`diagnostics`](TrimmerRunOptions::diagnostics)
+  pub fn diagnostics(mut self, diagnostics: bool) -> Self {
+    self.diagnostics = diagnostics;
+    self
+  }
+
+  /// Set [`journal`](TrimmerRunOptions::journal)
+  pub fn journal(mut self, journal: Option<Journal>) -> Self {
+    self.journal = journal;
+    self
+  }
+
+  /// Set [`audit_log`](TrimmerRunOptions::audit_log)
+  pub fn audit_log(mut self, audit_log: Option<AuditLog>) -> Self {
+    self.audit_log = audit_log;
+    self
+  }
+
+  /// Set [`stdin_filename`](TrimmerRunOptions::stdin_filename)
+  pub fn stdin_filename(mut self, stdin_filename: Option<String>) -> Self {
+    self.stdin_filename = stdin_filename;
+    self
+  }
+
+  /// Set [`no_clobber`](TrimmerRunOptions::no_clobber)
+  pub fn no_clobber(mut self, no_clobber: bool) -> Self {
+    self.no_clobber = no_clobber;
+    self
+  }
+
+  /// Set [`max_memory_size`](TrimmerRunOptions::max_memory_size)
+  pub fn max_memory_size(mut self, max_memory_size: Option<u64>) -> Self {
+    self.max_memory_size = max_memory_size;
+    self
+  }
+}
+
+/// Report on one input file from [`run_ender()`]
+#[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct EnderReport {
+  /// Input file name
+  pub name: String,
+  /// Input file size in bytes
+  pub size: usize,
+  /// Number of line endings that differ from the new or most common line ending
+  pub violations: usize,
+  /// `true` if the input file does not have mixed line endings
+  pub is_consistent: bool,
+  /// Line information for the input file
+  pub eol_info: EolInfo,
+  /// `true` if the input file was modified in place
+  pub modified: bool,
+  /// `true` if converting the input file would change it, when only checking
+  pub needs_change: bool,
+  /// Wall-clock time taken for the input file, for callers that time it
+  #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+  pub seconds: Option<f64>,
+  /// Text of the report line
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub text: String,
+  /// GNU style diagnostics for the lines that differ from the new or most common convention
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub diagnostics: Vec<String>,
+  /// Shell command that makes the changes to the input file, with `emit_script`
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub script: Option<String>,
+  /// Unified diff of the changes to the input file with its whitespace made visible, with `diff`
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub diff: Option<String>,
+}
+
+/// Report on one input file from [`run_spacer()`]
+#[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct SpacerReport {
+  /// Input file name
+  pub name: String,
+  /// Input file size in bytes
+  pub size: usize,
+  /// Number of indented lines that differ from the new or most common line beginning
+  pub violations: usize,
+  /// `true` if the input file does not have mixed line beginnings
+  pub is_consistent: bool,
+  /// Line information for the input file
+  pub bol_info: BolInfo,
+  /// `true` if the input file was modified in place
+  pub modified: bool,
+  /// `true` if converting the input file would change it, when only checking
+  pub needs_change: bool,
+  /// Wall-clock time taken for the input file, for callers that time it
+  #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+  pub seconds: Option<f64>,
+  /// Lines that converting moves relative to the line before them
+  #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+  pub misaligned_lines: Vec<usize>,
+  /// Text of the report line
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub text: String,
+  /// GNU style diagnostics for the lines that differ from the new or most common convention
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub diagnostics: Vec<String>,
+  /// Shell command that makes the changes to the input file, with `emit_script`
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub script: Option<String>,
+  /// Unified diff of the changes to the input file with its whitespace made visible, with `diff`
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub diff: Option<String>,
+}
+
+/// Stand-in for the [`encoding`](crate::encoding) module without the `encoding` feature, when every
+/// input file is read and written as UTF-8
+#[cfg(not(feature = "encoding"))]
+mod encoding {
+  use std::error::Error;
+  use std::fmt;
+
+  #[derive(PartialEq, Eq, Debug, Clone, Copy)]
+  pub enum Encoding {
+    Utf8,
+  }
+
+  impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      f.write_str("utf-8")
+    }
+  }
+
+  pub fn decode(data: &[u8], _encoding: Encoding) -> Result<String, Box<dyn Error>> {
+    Ok(String::from_utf8(data.to_vec())?)
+  }
+
+  pub fn encode(text: &str, _encoding: Encoding) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(text.as_bytes().to_vec())
+  }
+}
+
+#[cfg(not(feature = "encoding"))]
+use encoding::Encoding;
+
+/// Check that an input file of `size` bytes can be read into memory within `max_memory_size`.
+fn check_memory_size(
+  name: &str,
+  size: usize,
+  max_memory_size: Option<u64>,
+) -> Result<(), Box<dyn Error>> {
+  match max_memory_size {
+    Some(max_memory_size) if size as u64 > max_memory_size => Err(
+      error::Error::new(
+        ErrorCode::Limit,
+        format!(
+          "'{}' is larger than the limit of {} bytes for reading into memory, use --max-memory-size to raise it",
+          name, max_memory_size
+        ),
+      )
+      .into(),
+    ),
+    _ => Ok(()),
+  }
+}
+
+/// Detect the encoding of an input file from its first bytes.  Invalid UTF-8 is only taken to mean
+/// Windows-1252 or ISO-8859-1 with the strict decode policy, since the others say how to handle it.
+#[cfg(feature = "encoding")]
+fn detect_encoding(head: &[u8], decode_policy: DecodePolicy) -> Encoding {
+  match Encoding::detect(head) {
+    Encoding::Windows1252 | Encoding::Latin1 if decode_policy != DecodePolicy::Strict => {
+      Encoding::Utf8
+    }
+    encoding => encoding,
+  }
+}
+
+/// Detect the encoding of an input file, which is always UTF-8 without the `encoding` feature.
+#[cfg(not(feature = "encoding"))]
+fn detect_encoding(_head: &[u8], _decode_policy: DecodePolicy) -> Encoding {
+  Encoding::Utf8
+}
+
+/// Decode an input file in `encoding` into memory if it isn't UTF-8, replacing `reader` and `head`
+/// with its UTF-8 text.
+///
+/// Returns the original data of the input file if it was decoded.
+fn decode_input(
+  reader: &mut Box<dyn ReadSeek>,
+  head: &mut Vec<u8>,
+  encoding: Encoding,
+  name: &str,
+  max_memory_size: Option<u64>,
+) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+  if encoding == Encoding::Utf8 {
+    return Ok(None);
+  }
+
+  let size = reader.seek(SeekFrom::End(0))?;
+
+  check_memory_size(name, size as usize, max_memory_size)?;
+
+  let mut data = Vec::new();
+
+  reader.seek(SeekFrom::Start(0))?;
+  reader.read_to_end(&mut data)?;
+
+  let text = encoding::decode(&data, encoding)?.into_bytes();
+
+  *head = text[..text.len().min(1024)].to_vec();
+  *reader = Box::new(io::Cursor::new(text));
+
+  Ok(Some(data))
+}
+
+/// Encode converted UTF-8 output to write it in `encoding`.
+fn encode_output(output: &[u8], encoding: Encoding) -> Result<Cow<'_, [u8]>, Box<dyn Error>> {
+  Ok(if encoding == Encoding::Utf8 {
+    Cow::Borrowed(output)
+  } else {
+    Cow::Owned(encoding::encode(std::str::from_utf8(output)?, encoding)?)
+  })
+}
+
+/// Check that a fix script can change an input file, which it can only do for UTF-8 files that
+/// stay UTF-8.
+fn check_script_encoding(
+  name: &str,
+  encoding: Encoding,
+  output_encoding: Encoding,
+) -> Result<(), Box<dyn Error>> {
+  if encoding != Encoding::Utf8 || output_encoding != Encoding::Utf8 {
+    Err(
+      error::Error::new(
+        ErrorCode::Encoding,
+        format!(
+          "'{}' can't be changed by a script from {} to {}",
+          name, encoding, output_encoding
+        ),
+      )
+      .into(),
+    )
+  } else {
+    Ok(())
+  }
+}
+
+/// Report on one input file from [`run_trimmer()`]
+#[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct TrimmerReport {
+  /// Input file name
+  pub name: String,
+  /// Input file size in bytes
+  pub size: usize,
+  /// Number of lines with trailing whitespace
+  pub violations: usize,
+  /// Trailing whitespace information for the input file
+  pub trailing_info: TrailingInfo,
+  /// `true` if the input file was modified in place
+  pub modified: bool,
+  /// Wall-clock time taken for the input file, for callers that time it
+  #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+  pub seconds: Option<f64>,
+  /// Text of the report line
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub text: String,
+  /// GNU style diagnostics for the lines with trailing whitespace
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub diagnostics: Vec<String>,
+  /// Shell command that makes the changes to the input file, with `emit_script`
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub script: Option<String>,
+  /// Unified diff of the changes to the input file with its whitespace made visible, with `diff`
+  #[cfg_attr(feature = "serde", serde(skip))]
+  pub diff: Option<String>,
+}
+
+/// An input file opened by [`open_run_input()`]
+struct RunInput<'a> {
+  /// Lock on the directory being written to, held until the input file is done
+  #[cfg(feature = "lock")]
+  _lock: Option<paths::DirectoryLock>,
+  reader: Box<dyn ReadSeek>,
+  /// Name of the input file in reports and diagnostics
+  name: &'a str,
+  /// First bytes of the input file
+  head: Vec<u8>,
+}
+
+/// Open `input_file` for [`run_ender()`], [`run_spacer()`] or [`run_trimmer()`] and read its first
+/// bytes.  With the `lock` feature a lock is held on the directory being written to until the file
+/// is done, so that other runs writing there don't interleave with this one.
+#[cfg_attr(not(feature = "lock"), allow(unused_variables))]
+fn open_run_input<'a>(
+  input_file: &'a str,
+  output_file: Option<&str>,
+  in_place: bool,
+  emit_script: bool,
+  stdin_filename: Option<&'a str>,
+) -> Result<RunInput<'a>, Box<dyn Error>> {
+  if in_place && input_file == "-" {
+    return Err(error::Error::new(ErrorCode::Stdin, "STDIN can't be modified in place").into());
+  }
+
+  if emit_script && input_file == "-" {
+    return Err(error::Error::new(ErrorCode::Stdin, "STDIN can't be changed by a script").into());
+  }
+
+  #[cfg(feature = "lock")]
+  let lock = match (in_place, output_file) {
+    (true, _) => Some(input_file),
+    (false, path) => path,
+  }
+  .map(|path| paths::lock_directory(Path::new(path).parent().unwrap_or_else(|| Path::new(""))))
+  .transpose()?;
+  let mut reader = paths::open_input(input_file)?;
+  let name = match stdin_filename {
+    Some(name) if input_file == "-" => name,
+    _ if input_file == "-" => "STDIN",
+    _ => input_file,
+  };
+  let mut head = Vec::new();
+
+  reader.by_ref().take(1024).read_to_end(&mut head)?;
+  reader.seek(SeekFrom::Start(0))?;
+
+  Ok(RunInput {
+    #[cfg(feature = "lock")]
+    _lock: lock,
+    reader,
+    name,
+    head,
+  })
+}
+
+/// How [`run_ender()`], [`run_spacer()`] and [`run_trimmer()`] write a converted input file with
+/// [`Conversion::write()`]
+struct Conversion<'a> {
+  input_file: &'a str,
+  input_name: &'a str,
+  output_file: Option<&'a str>,
+  in_place: bool,
+  verify: bool,
+  emit_script: bool,
+  diff: bool,
+  no_clobber: bool,
+  max_memory_size: Option<u64>,
+  journal: Option<&'a Journal>,
+  audit_log: Option<&'a AuditLog>,
+  /// Encoding of the input file
+  encoding: Encoding,
+  /// Encoding to write the output in
+  output_encoding: Encoding,
+  /// Original data of an input file that was decoded from another encoding
+  original: Option<&'a [u8]>,
+  /// Size in bytes of the decoded input file
+  num_bytes: usize,
+  /// What the conversion does to the output in messages, such as `converted`
+  action: &'a str,
+  /// Rule for the changes in the audit log, such as `line-endings`
+  rule: &'a str,
+  /// Convention of the input file in the audit log
+  before: &'a str,
+}
+
+/// What [`Conversion::write()`] did
+struct Converted<'a, T> {
+  /// Value returned by the conversion, such as the number of lines written
+  value: T,
+  /// Output file written, or `None` for STDOUT
+  output_file: Option<&'a str>,
+  modified: bool,
+  script: Option<String>,
+  diff: Option<String>,
+}
+
+impl<'a> Conversion<'a> {
+  /// Convert the input file from `reader` with `convert` and write it out as the options say,
+  /// checking the output with `verify` first if asked to.  The convention the input file is
+  /// changed to is recorded in the audit log as `after` of the value returned by `convert`.
+  fn write<T, C, V, A>(
+    &self,
+    reader: &mut Box<dyn ReadSeek>,
+    convert: C,
+    verify: V,
+    after: A,
+  ) -> Result<Converted<'a, T>, Box<dyn Error>>
+  where
+    C: FnOnce(&mut dyn Read, &mut dyn Write) -> Result<T, Box<dyn Error>>,
+    V: FnOnce(&mut dyn Read, &mut dyn Read) -> Result<bool, Box<dyn Error>>,
+    A: FnOnce(&T) -> String,
+  {
+    reader.seek(SeekFrom::Start(0))?;
+
+    if let Some(path) = self.output_file {
+      if paths::is_same_file(Path::new(self.input_file), Path::new(path)) {
+        return Err(
+          error::Error::new(
+            ErrorCode::SamePath,
+            format!("output file '{}' is the same as the input file", path),
+          )
+          .into(),
+        );
+      }
+
+      if self.no_clobber && Path::new(path).exists() {
+        return Err(
+          error::Error::new(
+            ErrorCode::OutputExists,
+            format!(
+              "output file '{}' already exists, use --force to overwrite it",
+              path
+            ),
+          )
+          .into(),
+        );
+      }
+    }
+
+    let output_file = if self.in_place {
+      Some(self.input_file)
+    } else {
+      self.output_file
+    };
+    let create_writer = || -> io::Result<OutputWriter> {
+      match output_file {
+        Some(path) => paths::create_output(Path::new(path)),
+        None => Ok(OutputWriter::stdout()),
+      }
+    };
+
+    if self.emit_script {
+      check_script_encoding(self.input_name, self.encoding, self.output_encoding)?;
+    }
+
+    let mut modified = false;
+    let mut lines_changed = 0;
+    let mut script = None;
+    let mut diff = None;
+    let value = if self.verify
+      || self.in_place
+      || self.emit_script
+      || self.diff
+      || self.output_encoding != Encoding::Utf8
+    {
+      check_memory_size(self.input_name, self.num_bytes, self.max_memory_size)?;
+
+      let mut output = Vec::new();
+      let value = convert(reader, &mut output)?;
+
+      reader.seek(SeekFrom::Start(0))?;
+
+      if self.verify && !verify(reader, &mut output.as_slice())? {
+        return Err(
+          error::Error::new(
+            ErrorCode::Verify,
+            format!(
+              "verification of {} output failed, nothing was written",
+              self.action
+            ),
+          )
+          .into(),
+        );
+      }
+
+      if self.in_place || self.emit_script || self.diff {
+        let mut input = Vec::new();
+
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_to_end(&mut input)?;
+
+        if self.emit_script {
+          if input != output {
+            script = Some(patch::shell_command(&patch::diff(
+              self.input_file,
+              &String::from_utf8_lossy(&input),
+              &String::from_utf8_lossy(&output),
+            )));
+          }
+        } else if self.diff {
+          if input != output {
+            diff = Some(
+              patch::diff(
+                self.input_name,
+                &String::from_utf8_lossy(&input),
+                &String::from_utf8_lossy(&output),
+              )
+              .visible()
+              .to_string(),
+            );
+          }
+        } else {
+          modified = input != output || self.encoding != self.output_encoding;
+
+          if let (true, Some(journal)) = (modified, self.journal) {
+            journal.record(
+              Path::new(self.input_file),
+              self.original.unwrap_or(&input),
+              &encode_output(&output, self.output_encoding)?,
+            )?;
+          }
+
+          if modified && self.audit_log.is_some() {
+            lines_changed = audit::count_changed_lines(&input, &output);
+          }
+        }
+      }
+
+      if self.in_place {
+        if modified {
+          paths::replace_file(
+            Path::new(self.input_file),
+            &encode_output(&output, self.output_encoding)?,
+          )?;
+        }
+      } else if !self.emit_script && !self.diff {
+        let mut writer = create_writer()?;
+
+        writer.write_all(&encode_output(&output, self.output_encoding)?)?;
+        writer.finish()?;
+      }
+
+      value
+    } else {
+      let mut writer = create_writer()?;
+      let value = convert(reader, &mut writer)?;
+
+      writer.finish()?;
+      value
+    };
+
+    if let (true, Some(audit_log)) = (modified, self.audit_log) {
+      audit_log.record(&Change {
+        path: Path::new(self.input_file),
+        rule: self.rule,
+        lines_changed,
+        before: self.before,
+        after: &after(&value),
+      })?;
+    }
+
+    Ok(Converted {
+      value,
+      output_file,
+      modified,
+      script,
+      diff,
+    })
+  }
+}
+
+/// Make GNU style diagnostics for the line endings that aren't `eol`, and for a last line with no
+/// ending if [`EolOptions::final_newline`] is set.
+fn eol_diagnostics(
+  reader: &mut dyn Read,
+  name: &str,
+  eol: EndOfLine,
+  options: &EolOptions,
+) -> Result<Vec<String>, Box<dyn Error>> {
+  let mut diagnostics = Vec::new();
+  let mut directives = Directives::new(options.directives);
+
+  lines::process_lines(reader, &mut io::sink(), |line| {
+    let is_disabled = directives.is_disabled();
+
+    directives.push_line(line.content);
+
+    if is_disabled {
+      return LineAction::Keep;
+    }
+
+    if let Some(ending) = line.ending.filter(|&ending| ending != eol) {
+      diagnostics.push(format!(
+        "{}:{}:{}: warning: {} line ending, expected {}",
+        name,
+        line.number,
+        line.content.chars().count() + 1,
+        ending,
+        eol
+      ));
+    } else if options.final_newline && line.ending.is_none() && !line.content.is_empty() {
+      diagnostics.push(format!(
+        "{}:{}:{}: warning: no final newline",
+        name,
+        line.number,
+        line.content.chars().count() + 1
+      ));
+    }
+
+    LineAction::Keep
+  })?;
+
+  Ok(diagnostics)
+}
+
+/// Report on and optionally convert the line endings of one input file, as `ender` does.
+pub fn run_ender(
+  input_file: &str,
+  output_file: Option<&str>,
+  options: &EnderRunOptions,
+) -> Result<EnderReport, Box<dyn Error>> {
+  // The input, and any lock it holds, lives until the input file is done
+  let input = open_run_input(
+    input_file,
+    output_file,
+    options.in_place,
+    options.emit_script,
+    options.stdin_filename.as_deref(),
+  )?;
+  let mut reader = input.reader;
+  let input_name = input.name;
+  let mut head = input.head;
+
+  #[cfg(feature = "encoding")]
+  let (input_encoding, output_encoding) = (options.input_encoding, options.output_encoding);
+  #[cfg(not(feature = "encoding"))]
+  let (input_encoding, output_encoding) = (None, None);
+  let encoding = input_encoding.unwrap_or_else(|| detect_encoding(&head, options.decode_policy));
+  let original = decode_input(
+    &mut reader,
+    &mut head,
+    encoding,
+    input_name,
+    options.max_memory_size,
+  )?;
+  let output_encoding = output_encoding.unwrap_or(encoding);
+  let file_type = FileType::detect(Path::new(input_name), &head);
+  let eol_options = EolOptions::default()
+    .final_newline(options.final_newline)
+    .unicode_separators(options.unicode_separators)
+    .directives(options.directives)
+    .decode_policy(options.decode_policy);
+  let eol_info = read_eol_info_with_options(&mut reader, &eol_options)?;
+  let new_eol = match options.autocrlf {
+    Some(autocrlf) => {
+      check_memory_size(input_name, eol_info.num_bytes, options.max_memory_size)?;
+
+      let mut input = Vec::new();
+
+      reader.seek(SeekFrom::Start(0))?;
+      reader.read_to_end(&mut input)?;
+      git::autocrlf_eol(&input, autocrlf)
+    }
+    None => options
+      .new_eol
+      .map(|target| match target {
+        EolTarget::Auto => eol_info.get_common_eol_with(options.tie_break),
+        EolTarget::Lf => Ok(EndOfLine::Lf),
+        EolTarget::Cr => Ok(EndOfLine::Cr),
+        EolTarget::CrLf => Ok(EndOfLine::CrLf),
+      })
+      .transpose()?,
+  };
+  let eol_type = if eol_info.num_endings() > 1 {
+    "mixed"
+  } else if eol_info.cr > 0 {
+    "cr"
+  } else if eol_info.lf > 0 {
+    "lf"
+  } else if eol_info.nel > 0 {
+    "nel"
+  } else if eol_info.ls > 0 {
+    "ls"
+  } else if eol_info.ps > 0 {
+    "ps"
+  } else {
+    "crlf"
+  };
+  let mut text = format!("'{}', {}", input_name, eol_type);
+
+  if encoding != Encoding::Utf8 {
+    text += &format!(", {}", encoding);
+  }
+
+  text += &format!(", {} lines", eol_info.num_lines);
+
+  if eol_info.trailing_blank_lines > 0 {
+    text += &format!(", {} trailing blank lines", eol_info.trailing_blank_lines);
+  }
+
+  if !eol_info.has_final_newline() {
+    text += ", no final newline";
+  }
+
+  if options.counts {
+    text += &format!(
+      ", {} chars, {} words, {} bytes, {} blank lines, {} whitespace-only lines",
+      eol_info.num_chars,
+      eol_info.num_words,
+      eol_info.num_bytes,
+      eol_info.blank_lines,
+      eol_info.whitespace_lines
+    );
+  }
+
+  if options.autocrlf.is_some() && new_eol.is_none() {
+    text += ", not converted by git";
+  }
+
+  // The line endings of the changed lines of a patch are part of what it changes
+  let exempt = file_type.preserves_whitespace();
+
+  if exempt && new_eol.is_some() {
+    text += ", left unchanged as a patch";
+  }
+
+  let new_eol = new_eol.filter(|_| !exempt);
+
+  let mut modified = false;
+  let mut needs_change = false;
+  let mut script = None;
+  let mut diff = None;
+
+  if let (true, Some(new_eol)) = (options.check, new_eol) {
+    reader.seek(SeekFrom::Start(0))?;
+    needs_change =
+      needs_new_eols(&mut reader, new_eol, &eol_options)? || encoding != output_encoding;
+
+    if needs_change {
+      text += &format!(" -> needs converting to {}", new_eol);
+    }
+  } else if let Some(new_eol) = new_eol {
+    let converted = Conversion {
+      input_file,
+      input_name,
+      output_file,
+      in_place: options.in_place,
+      verify: options.verify,
+      emit_script: options.emit_script,
+      diff: options.diff,
+      no_clobber: options.no_clobber,
+      max_memory_size: options.max_memory_size,
+      journal: options.journal.as_ref(),
+      audit_log: options.audit_log.as_ref(),
+      encoding,
+      output_encoding,
+      original: original.as_deref(),
+      num_bytes: eol_info.num_bytes,
+      action: "converted",
+      rule: "line-endings",
+      before: eol_type,
+    }
+    .write(
+      &mut reader,
+      |reader, writer| write_new_eols_with_options(reader, writer, new_eol, &eol_options),
+      |reader, output| verify_new_eols_with_options(reader, output, new_eol, &eol_options),
+      |_| new_eol.to_string(),
+    )?;
+    let num_lines = converted.value;
+
+    modified = converted.modified;
+    script = converted.script;
+    diff = converted.diff;
+
+    if options.emit_script {
+      if script.is_some() {
+        text += &format!(" -> script, {}, {} lines", new_eol, num_lines);
+      }
+    } else if options.diff {
+      if diff.is_some() {
+        text += &format!(" -> diff, {}, {} lines", new_eol, num_lines);
+      }
+    } else if !options.in_place || modified {
+      text += &format!(
+        " -> '{}', {}",
+        converted.output_file.unwrap_or("STDOUT"),
+        new_eol
+      );
+
+      if encoding != Encoding::Utf8 || output_encoding != Encoding::Utf8 {
+        text += &format!(", {}", output_encoding);
+      }
+
+      text += &format!(", {} lines", num_lines);
+    }
+  }
+
+  let common_eol = new_eol.unwrap_or_else(|| {
+    eol_info
+      .get_common_eol_with(options.tie_break)
+      .unwrap_or_else(|_| eol_info.get_common_eol())
+  });
+  let diagnostics = if options.diagnostics && !exempt {
+    reader.seek(SeekFrom::Start(0))?;
+    eol_diagnostics(&mut reader, input_name, common_eol, &eol_options)?
+  } else {
+    Vec::new()
+  };
+
+  Ok(EnderReport {
+    name: input_name.to_string(),
+    size: original.as_ref().map_or(eol_info.num_bytes, Vec::len),
+    violations: if exempt {
+      0
+    } else if options.final_newline {
+      eol_info.num_other_endings(common_eol) + eol_info.missing_final_newline
+    } else {
+      eol_info.num_other_endings(common_eol)
+    },
+    is_consistent: eol_info.num_endings() <= 1,
+    modified,
+    needs_change,
+    seconds: None,
+    eol_info,
+    text,
+    diagnostics,
+    script,
+    diff,
+  })
+}
+
+/// Make GNU style diagnostics for the indented lines that don't begin with `bol`.
+fn bol_diagnostics(
+  reader: &mut dyn Read,
+  name: &str,
+  bol: BeginningOfLine,
+  options: &BolOptions,
+) -> Result<Vec<String>, Box<dyn Error>> {
+  let mut diagnostics = Vec::new();
+  let mut directives = Directives::new(options.directives);
+
+  lines::process_lines(reader, &mut io::sink(), |line| {
+    let is_disabled = directives.is_disabled();
+
+    directives.push_line(line.content);
+
+    if is_disabled {
+      return LineAction::Keep;
+    }
+
+    let indent_len = line.content.len() - line.content.trim_start_matches([' ', '\t']).len();
+    let indent = &line.content[..indent_len];
+    let other = match (indent.contains(' '), indent.contains('\t'), bol) {
+      (true, true, _) => Some("mixed tabs and spaces"),
+      (true, false, BeginningOfLine::Tabs(_, _)) => Some("spaces"),
+      (false, true, BeginningOfLine::Spaces(_)) => Some("tabs"),
+      _ => None,
+    };
+
+    if let Some(other) = other {
+      diagnostics.push(format!(
+        "{}:{}:1: warning: line begins with {}, expected {}",
+        name, line.number, other, bol
+      ));
+    }
+
+    LineAction::Keep
+  })?;
+
+  Ok(diagnostics)
+}
+
+/// Report on and optionally convert the line beginnings of one input file, as `spacer` does.
+pub fn run_spacer(
+  input_file: &str,
+  output_file: Option<&str>,
+  options: &SpacerRunOptions,
+) -> Result<SpacerReport, Box<dyn Error>> {
+  // The input, and any lock it holds, lives until the input file is done
+  let input = open_run_input(
+    input_file,
+    output_file,
+    options.in_place,
+    options.emit_script,
+    options.stdin_filename.as_deref(),
+  )?;
+  let mut reader = input.reader;
+  let input_name = input.name;
+  let mut head = input.head;
+
+  #[cfg(feature = "encoding")]
+  let (input_encoding, output_encoding) = (options.input_encoding, options.output_encoding);
+  #[cfg(not(feature = "encoding"))]
+  let (input_encoding, output_encoding) = (None, None);
+  let encoding =
+    input_encoding.unwrap_or_else(|| detect_encoding(&head, options.bol_options.decode_policy));
+  let original = decode_input(
+    &mut reader,
+    &mut head,
+    encoding,
+    input_name,
+    options.max_memory_size,
+  )?;
+  let output_encoding = output_encoding.unwrap_or(encoding);
+  let file_type = FileType::detect(Path::new(input_name), &head);
+  let bol_options = options
+    .bol_options
+    .clone()
+    .markdown(options.bol_options.markdown || file_type == FileType::Markdown);
+  let bol_info = read_bol_info_with_options(&mut reader, &bol_options)?;
+  let inferred_tab_size = if options.infer_tab_size
+    && (bol_info.mixed > 0 || (bol_info.tabs > 0 && bol_info.spaces > 0))
+  {
+    reader.seek(SeekFrom::Start(0))?;
+    infer_tab_size(&mut reader)?
+  } else {
+    None
+  };
+  let tab_size = inferred_tab_size.unwrap_or(options.tab_size);
+  let bol_type = |bol_info: &BolInfo| {
+    if bol_info.is_smart_tabs() {
+      "smart tabs"
+    } else if bol_info.tabs > 0 {
+      if bol_info.spaces > 0 {
+        "mixed"
+      } else {
+        "tabs"
+      }
+    } else {
+      "spaces"
+    }
+  };
+
+  let new_bol = match options.new_bol {
+    Some(BolTarget::Tabs) if !file_type.allows_tabs() => {
+      return Err(
+        error::Error::new(
+          ErrorCode::FileType,
+          format!(
+            "'{}' is YAML, which can't be indented with tabs",
+            input_name
+          ),
+        )
+        .into(),
+      );
+    }
+    Some(BolTarget::Auto) if !file_type.allows_tabs() => Some(BeginningOfLine::Spaces(tab_size)),
+    target => target.map(|target| match target {
+      BolTarget::Auto => {
+        bol_info.get_common_bol_with(tab_size, options.round_down, options.bol_strategy)
+      }
+      BolTarget::Tabs => BeginningOfLine::Tabs(tab_size, options.round_down),
+      BolTarget::Spaces => BeginningOfLine::Spaces(tab_size),
+    }),
+  };
+  let mut text = format!("'{}', {}", input_name, bol_type(&bol_info),);
+
+  if encoding != Encoding::Utf8 {
+    text += &format!(", {}", encoding);
+  }
+
+  if bol_info.smart_tabs > 0 {
+    text += &format!(
+      ", {} of {} indented lines follow smart tabs",
+      bol_info.tabs + bol_info.smart_tabs,
+      bol_info.spaces + bol_info.tabs + bol_info.mixed
+    );
+  }
+
+  if bol_info.unusual > 0 {
+    text += &format!(", {} lines begin with unusual spaces", bol_info.unusual);
+  }
+
+  if let Some(tab_size) = inferred_tab_size {
+    text += &format!(", tab size {} inferred", tab_size);
+  }
+
+  if options.counts {
+    text += &format!(
+      ", {} chars, {} words, {} bytes",
+      bol_info.num_chars, bol_info.num_words, bol_info.num_bytes
+    );
+  }
+
+  // The space that begins the context lines of a patch is part of the patch format
+  let exempt = file_type.preserves_whitespace();
+
+  if exempt && new_bol.is_some() {
+    text += ", left unchanged as a patch";
+  }
+
+  let new_bol = new_bol.filter(|_| !exempt);
+
+  let mut modified = false;
+  let mut needs_change = false;
+  let mut script = None;
+  let mut diff = None;
+  let mut misaligned_lines = Vec::new();
+
+  if let (true, Some(new_bol)) = (options.check, new_bol) {
+    check_memory_size(input_name, bol_info.num_bytes, options.max_memory_size)?;
+    reader.seek(SeekFrom::Start(0))?;
+    needs_change =
+      needs_new_bols(&mut reader, new_bol, &bol_options)? || encoding != output_encoding;
+
+    if needs_change {
+      text += &format!(" -> needs converting to {}", new_bol);
+    }
+  } else if let Some(new_bol) = new_bol {
+    // Only rounding down changes the width of line beginnings
+    if let BeginningOfLine::Tabs(_, true) = new_bol {
+      reader.seek(SeekFrom::Start(0))?;
+      misaligned_lines = alignment_changes(&mut reader, new_bol, &bol_options)?;
+
+      if !misaligned_lines.is_empty() {
+        text += &format!(
+          ", {} lines {}",
+          misaligned_lines.len(),
+          if bol_options.safe_only {
+            "left unchanged to keep their alignment"
+          } else {
+            "change alignment"
+          }
+        );
+      }
+    }
+
+    let converted = Conversion {
+      input_file,
+      input_name,
+      output_file,
+      in_place: options.in_place,
+      verify: options.verify,
+      emit_script: options.emit_script,
+      diff: options.diff,
+      no_clobber: options.no_clobber,
+      max_memory_size: options.max_memory_size,
+      journal: options.journal.as_ref(),
+      audit_log: options.audit_log.as_ref(),
+      encoding,
+      output_encoding,
+      original: original.as_deref(),
+      num_bytes: bol_info.num_bytes,
+      action: "converted",
+      rule: "line-beginnings",
+      before: bol_type(&bol_info),
+    }
+    .write(
+      &mut reader,
+      |reader, writer| write_new_bols_with_options(reader, writer, new_bol, &bol_options),
+      |reader, output| verify_new_bols_with_options(reader, output, new_bol, &bol_options),
+      |new_bol_info| bol_type(new_bol_info).to_string(),
+    )?;
+    let new_bol_info = &converted.value;
+
+    modified = converted.modified;
+    script = converted.script;
+    diff = converted.diff;
+
+    if options.emit_script {
+      if script.is_some() {
+        text += &format!(" -> script, {}", bol_type(new_bol_info));
+      }
+    } else if options.diff {
+      if diff.is_some() {
+        text += &format!(" -> diff, {}", bol_type(new_bol_info));
+      }
+    } else if !options.in_place || modified {
+      text += &format!(
+        " -> '{}', {}",
+        converted.output_file.unwrap_or("STDOUT"),
+        bol_type(new_bol_info)
+      );
+
+      if encoding != Encoding::Utf8 || output_encoding != Encoding::Utf8 {
+        text += &format!(", {}", output_encoding);
+      }
+    }
+  }
+
+  let common_bol = new_bol.unwrap_or_else(|| {
+    bol_info.get_common_bol_with(tab_size, options.round_down, options.bol_strategy)
+  });
+  let diagnostics = if options.diagnostics && !exempt {
+    reader.seek(SeekFrom::Start(0))?;
+
+    let mut diagnostics = bol_diagnostics(&mut reader, input_name, common_bol, &bol_options)?;
+
+    if !bol_options.safe_only {
+      diagnostics.extend(misaligned_lines.iter().map(|line| {
+        format!(
+          "{}:{}:1: warning: converting the line beginning changes its alignment with the line before",
+          input_name, line
+        )
+      }));
+    }
+
+    diagnostics
+  } else {
+    Vec::new()
+  };
+
+  Ok(SpacerReport {
+    name: input_name.to_string(),
+    size: original.as_ref().map_or(bol_info.num_bytes, Vec::len),
+    violations: if exempt {
+      0
+    } else {
+      bol_info.num_other_beginnings(common_bol)
+    },
+    is_consistent: bol_info.mixed == 0 && (bol_info.spaces == 0 || bol_info.tabs == 0),
+    modified,
+    needs_change,
+    seconds: None,
+    misaligned_lines,
+    bol_info,
+    text,
+    diagnostics,
+    script,
+    diff,
+  })
+}
+
+/// Make GNU style diagnostics for the lines that end in spaces or tabs.
+fn trailing_diagnostics(
+  reader: &mut dyn Read,
+  name: &str,
+  options: &TrimOptions,
+) -> Result<Vec<String>, Box<dyn Error>> {
+  let mut diagnostics = Vec::new();
+  let mut directives = Directives::new(options.directives);
+
+  lines::process_lines(reader, &mut io::sink(), |line| {
+    let text = line.content.trim_end_matches([' ', '\t']);
+    let is_disabled = directives.is_disabled();
+
+    directives.push_line(line.content);
+
+    if text.len() < line.content.len() && !is_disabled {
+      diagnostics.push(format!(
+        "{}:{}:{}: warning: trailing whitespace",
+        name,
+        line.number,
+        text.chars().count() + 1
+      ));
+    }
+
+    LineAction::Keep
+  })?;
+
+  Ok(diagnostics)
+}
+
+/// Report on and optionally remove the trailing whitespace of one input file, as `trimmer` does.
+pub fn run_trimmer(
+  input_file: &str,
+  output_file: Option<&str>,
+  options: &TrimmerRunOptions,
+) -> Result<TrimmerReport, Box<dyn Error>> {
+  // The input, and any lock it holds, lives until the input file is done
+  let input = open_run_input(
+    input_file,
+    output_file,
+    options.in_place,
+    options.emit_script,
+    options.stdin_filename.as_deref(),
+  )?;
+  let mut reader = input.reader;
+  let input_name = input.name;
+  let head = input.head;
+
+  let file_type = FileType::detect(Path::new(input_name), &head);
+  let trim_options = TrimOptions::default().directives(options.directives);
+  let trailing_info = read_trailing_info_with_options(&mut reader, &trim_options)?;
+  let mut text = format!(
+    "'{}', {} lines, {} with trailing whitespace",
+    input_name, trailing_info.num_lines, trailing_info.lines
+  );
+
+  if options.counts {
+    text += &format!(
+      ", {} chars, {} words, {} bytes",
+      trailing_info.num_chars, trailing_info.num_words, trailing_info.num_bytes
+    );
+  }
+
+  // The trailing whitespace of the lines of a patch is part of what it changes
+  let exempt = file_type.preserves_whitespace();
+
+  if exempt && options.trim {
+    text += ", left unchanged as a patch";
+  }
+
+  let trim = options.trim && !exempt;
+  let mut modified = false;
+  let mut script = None;
+  let mut diff = None;
+
+  if trim {
+    let converted = Conversion {
+      input_file,
+      input_name,
+      output_file,
+      in_place: options.in_place,
+      verify: options.verify,
+      emit_script: options.emit_script,
+      diff: options.diff,
+      no_clobber: options.no_clobber,
+      max_memory_size: options.max_memory_size,
+      journal: options.journal.as_ref(),
+      audit_log: options.audit_log.as_ref(),
+      encoding: Encoding::Utf8,
+      output_encoding: Encoding::Utf8,
+      original: None,
+      num_bytes: trailing_info.num_bytes,
+      action: "trimmed",
+      rule: "trailing-whitespace",
+      before: "trailing",
+    }
+    .write(
+      &mut reader,
+      |reader, writer| write_trimmed_with_options(reader, writer, &trim_options),
+      |reader, output| verify_trimmed_with_options(reader, output, &trim_options),
+      |_| "trimmed".to_string(),
+    )?;
+    let num_trimmed = converted.value;
+
+    modified = converted.modified;
+    script = converted.script;
+    diff = converted.diff;
+
+    if options.emit_script {
+      if script.is_some() {
+        text += &format!(" -> script, {} lines trimmed", num_trimmed);
+      }
+    } else if options.diff {
+      if diff.is_some() {
+        text += &format!(" -> diff, {} lines trimmed", num_trimmed);
+      }
+    } else if !options.in_place || modified {
+      text += &format!(
+        " -> '{}', {} lines trimmed",
+        converted.output_file.unwrap_or("STDOUT"),
+        num_trimmed
+      );
+    }
+  }
+
+  let diagnostics = if options.diagnostics && !exempt {
+    reader.seek(SeekFrom::Start(0))?;
+    trailing_diagnostics(&mut reader, input_name, &trim_options)?
+  } else {
+    Vec::new()
+  };
+
+  Ok(TrimmerReport {
+    name: input_name.to_string(),
+    size: trailing_info.num_bytes,
+    violations: if exempt { 0 } else { trailing_info.lines },
+    modified,
+    seconds: None,
+    trailing_info,
+    text,
+    diagnostics,
+    script,
+    diff,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_run_ender_auto() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir.path().join("output_file.txt");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\nxyz\r\n\r\n123\r\r\r").unwrap();
+
+    run_ender(
+      input_file,
+      Some(output_path.to_str().unwrap()),
+      &EnderRunOptions {
+        new_eol: Some(EolTarget::Auto),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_just_status() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\r\n").unwrap();
+
+    run_ender(input_file, None, &EnderRunOptions::default()).unwrap();
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_crlf() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir.path().join("output_file.txt");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\r\n").unwrap();
+
+    run_ender(
+      input_file,
+      Some(output_path.to_str().unwrap()),
+      &EnderRunOptions {
+        new_eol: Some(EolTarget::Lf),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_cr() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\r").unwrap();
+
+    run_ender(
+      input_file,
+      None,
+      &EnderRunOptions {
+        new_eol: Some(EolTarget::CrLf),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_lf() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\n").unwrap();
+
+    run_ender(
+      input_file,
+      None,
+      &EnderRunOptions {
+        new_eol: Some(EolTarget::CrLf),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_verify() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir.path().join("output_file.txt");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\r\nxyz\r").unwrap();
+
+    run_ender(
+      input_file,
+      Some(output_path.to_str().unwrap()),
+      &EnderRunOptions {
+        new_eol: Some(EolTarget::Lf),
+        verify: true,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    assert_eq!(std::fs::read_to_string(output_path).unwrap(), "abc\nxyz\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_nested_output_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir
+      .path()
+      .join("out")
+      .join("sub")
+      .join("output_file.txt");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\r\n").unwrap();
+
+    run_ender(
+      input_file,
+      Some(output_path.to_str().unwrap()),
+      &EnderRunOptions {
+        new_eol: Some(EolTarget::Lf),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    assert_eq!(std::fs::read_to_string(output_path).unwrap(), "abc\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_same_output_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\r\n").unwrap();
+
+    assert!(run_ender(
+      input_file,
+      Some(input_file),
+      &EnderRunOptions {
+        new_eol: Some(EolTarget::Lf),
+        ..Default::default()
+      }
+    )
+    .is_err());
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), "abc\r\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  #[cfg(feature = "encoding")]
+  fn test_run_ender_not_utf8() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let output_path = temp_dir.path().join("output_file.txt");
+    let options = EnderRunOptions {
+      new_eol: Some(EolTarget::Lf),
+      input_encoding: Some(Encoding::Utf8),
+      ..Default::default()
+    };
+
+    // Latin-1 read as UTF-8 isn't converted, the file is skipped
+    std::fs::write(&input_path, b"caf\xe9\r\n").unwrap();
+
+    assert!(run_ender(input_path.to_str().unwrap(), output_path.to_str(), &options).is_err());
+    assert!(!output_path.exists());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn test_run_ender_write_error() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let output_path = temp_dir.path().join("output_file.txt.gz");
+
+    std::fs::write(&input_path, "abc\r\n").unwrap();
+    std::os::unix::fs::symlink("/dev/full", &output_path).unwrap();
+
+    for verify in [false, true] {
+      assert!(run_ender(
+        input_path.to_str().unwrap(),
+        Some(output_path.to_str().unwrap()),
+        &EnderRunOptions::default()
+          .new_eol(Some(EolTarget::Lf))
+          .verify(verify),
+      )
+      .is_err());
+    }
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  #[cfg(feature = "gzip")]
+  fn test_run_ender_gzip() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.log.gz");
+    let output_path = temp_dir.path().join("output_file.log.gz");
+    let input_file = input_path.to_str().unwrap();
+    let output_file = output_path.to_str().unwrap();
+
+    let mut writer = paths::create_output(&input_path).unwrap();
+
+    writer.write_all(b"abc\r\nxyz\n").unwrap();
+    writer.finish().unwrap();
+
+    let report = run_ender(
+      input_file,
+      Some(output_file),
+      &EnderRunOptions {
+        new_eol: Some(EolTarget::Lf),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+    let mut s = String::new();
+
+    assert_eq!(report.size, 9);
+    paths::open_input(output_file)
+      .unwrap()
+      .read_to_string(&mut s)
+      .unwrap();
+    assert_eq!(s, "abc\nxyz\n");
+
+    // The limit on reading into memory is for the decompressed size
+    let mut writer = paths::create_output(&input_path).unwrap();
+
+    (0..100).for_each(|_| writer.write_all(&[b'x'; 1023]).unwrap());
+    writer.write_all(b"\r\n").unwrap();
+    writer.finish().unwrap();
+    assert!(std::fs::metadata(&input_path).unwrap().len() < 10_000);
+
+    let err = run_ender(
+      input_file,
+      None,
+      &EnderRunOptions::default()
+        .new_eol(Some(EolTarget::Lf))
+        .in_place(true)
+        .max_memory_size(Some(10_000)),
+    )
+    .unwrap_err();
+
+    assert_eq!(error::error_code(&*err), ErrorCode::Limit);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_autocrlf() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir.path().join("output_file.txt");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let output_file = output_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\nxyz\r\n").unwrap();
+
+    run_ender(
+      input_file,
+      Some(output_file),
+      &EnderRunOptions {
+        new_eol: Some(EolTarget::Cr),
+        autocrlf: Some(AutoCrlf::True),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+    assert_eq!(
+      std::fs::read_to_string(output_file).unwrap(),
+      "abc\r\nxyz\r\n"
+    );
+
+    std::fs::write(input_file, "abc\0\n").unwrap();
+
+    let report = run_ender(
+      input_file,
+      None,
+      &EnderRunOptions {
+        autocrlf: Some(AutoCrlf::Input),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    assert_eq!(
+      report.text,
+      format!("'{}', lf, 2 lines, not converted by git", input_file)
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_in_place() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = EnderRunOptions {
+      new_eol: Some(EolTarget::Auto),
+      in_place: true,
+      ..Default::default()
+    };
+
+    std::fs::write(input_file, "abc\r\nxyz\n123\r\n").unwrap();
+
+    let report = run_ender(input_file, None, &options).unwrap();
+
+    assert!(report.modified);
+    assert_eq!(
+      report.text,
+      format!(
+        "'{}', mixed, 4 lines -> '{}', crlf, 4 lines",
+        input_file, input_file
+      )
+    );
+    assert_eq!(
+      std::fs::read_to_string(input_file).unwrap(),
+      "abc\r\nxyz\r\n123\r\n"
+    );
+
+    let report = run_ender(input_file, None, &options).unwrap();
+
+    assert!(!report.modified);
+    assert_eq!(report.text, format!("'{}', crlf, 4 lines", input_file));
+    assert!(run_ender("-", None, &options).is_err());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  #[cfg(feature = "encoding")]
+  fn test_run_ender_utf16() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let output_path = temp_dir.path().join("output_file.txt");
+    let output_file = output_path.to_str().unwrap();
+    let options = EnderRunOptions::default()
+      .new_eol(Some(EolTarget::Lf))
+      .in_place(true);
+
+    std::fs::write(
+      input_file,
+      encoding::encode("abc\r\nxyz\n", Encoding::Utf16Le).unwrap(),
+    )
+    .unwrap();
+
+    let report = run_ender(input_file, None, &options).unwrap();
+
+    assert!(report.modified);
+    assert_eq!(report.size, 20);
+    assert_eq!(
+      report.text,
+      format!(
+        "'{}', mixed, utf-16le, 3 lines -> '{}', lf, utf-16le, 3 lines",
+        input_file, input_file
+      )
+    );
+    assert_eq!(
+      std::fs::read(input_file).unwrap(),
+      encoding::encode("abc\nxyz\n", Encoding::Utf16Le).unwrap()
+    );
+
+    let options = EnderRunOptions::default()
+      .new_eol(Some(EolTarget::Lf))
+      .output_encoding(Some(Encoding::Utf8));
+
+    run_ender(input_file, Some(output_file), &options).unwrap();
+    assert_eq!(std::fs::read_to_string(output_file).unwrap(), "abc\nxyz\n");
+
+    let options = options.emit_script(true);
+
+    assert_eq!(
+      error::error_code(&*run_ender(input_file, None, &options).unwrap_err()),
+      ErrorCode::Encoding
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  #[cfg(feature = "encoding")]
+  fn test_run_ender_windows_1252() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let output_path = temp_dir.path().join("output_file.txt");
+    let output_file = output_path.to_str().unwrap();
+    let options = EnderRunOptions::default()
+      .new_eol(Some(EolTarget::Lf))
+      .in_place(true);
+
+    std::fs::write(input_file, b"caf\xe9\r\n\x80\r\n").unwrap();
+
+    let report = run_ender(input_file, None, &options).unwrap();
+
+    assert!(report.modified);
+    assert_eq!(
+      report.text,
+      format!(
+        "'{}', crlf, windows-1252, 3 lines -> '{}', lf, windows-1252, 3 lines",
+        input_file, input_file
+      )
+    );
+    assert_eq!(std::fs::read(input_file).unwrap(), b"caf\xe9\n\x80\n");
+
+    let options = EnderRunOptions::default()
+      .new_eol(Some(EolTarget::Lf))
+      .input_encoding(Some(Encoding::Latin1))
+      .output_encoding(Some(Encoding::Utf8));
+
+    let report = run_ender(input_file, Some(output_file), &options).unwrap();
+
+    assert_eq!(
+      report.text,
+      format!(
+        "'{}', lf, iso-8859-1, 3 lines -> '{}', lf, utf-8, 3 lines",
+        input_file, output_file
+      )
+    );
+    assert_eq!(
+      std::fs::read_to_string(output_file).unwrap(),
+      "caf\u{e9}\n\u{80}\n"
+    );
+
+    let options = EnderRunOptions::default()
+      .new_eol(Some(EolTarget::Lf))
+      .output_encoding(Some(Encoding::Latin1));
+
+    assert_eq!(
+      error::error_code(&*run_ender(input_file, Some(output_file), &options).unwrap_err()),
+      ErrorCode::Encoding
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_emit_script() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = EnderRunOptions {
+      new_eol: Some(EolTarget::Lf),
+      emit_script: true,
+      ..Default::default()
+    };
+
+    std::fs::write(input_file, "abc\r\nxyz\n").unwrap();
+
+    let report = run_ender(input_file, None, &options).unwrap();
+
+    assert_eq!(
+      report.text,
+      format!("'{}', mixed, 3 lines -> script, lf, 3 lines", input_file)
+    );
+    assert_eq!(
+      report.script.unwrap(),
+      format!(
+        "patch -p0 --forward <<'WHITESPACE_EOF'\n--- {0}\n+++ {0}\n@@ -1,2 +1,2 @@\n-abc\r\n+abc\n xyz\nWHITESPACE_EOF\n",
+        input_file
+      )
+    );
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), "abc\r\nxyz\n");
+    assert!(run_ender(input_file, None, &options)
+      .unwrap()
+      .script
+      .is_some());
+    assert!(run_ender("-", None, &options).is_err());
+
+    std::fs::write(input_file, "abc\nxyz\n").unwrap();
+
+    assert!(run_ender(input_file, None, &options)
+      .unwrap()
+      .script
+      .is_none());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_journal() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let journal_dir = temp_dir.path().join("journal");
+    let options = EnderRunOptions {
+      new_eol: Some(EolTarget::Lf),
+      in_place: true,
+      journal: Some(Journal::create(&journal_dir).unwrap()),
+      ..Default::default()
+    };
+
+    std::fs::write(input_file, "abc\r\nxyz\n").unwrap();
+
+    assert!(run_ender(input_file, None, &options).unwrap().modified);
+    assert!(!run_ender(input_file, None, &options).unwrap().modified);
+    assert_eq!(
+      crate::journal::undo(&journal_dir, false).unwrap(),
+      vec![input_path.clone()]
+    );
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), "abc\r\nxyz\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_audit_log() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let log_path = temp_dir.path().join("audit.log");
+    let options = EnderRunOptions {
+      new_eol: Some(EolTarget::Lf),
+      in_place: true,
+      audit_log: Some(AuditLog::open(&log_path).unwrap()),
+      ..Default::default()
+    };
+
+    std::fs::write(input_file, "abc\r\nxyz\r\nend\n").unwrap();
+
+    assert!(run_ender(input_file, None, &options).unwrap().modified);
+    assert!(!run_ender(input_file, None, &options).unwrap().modified);
+
+    let log = std::fs::read_to_string(&log_path).unwrap();
+
+    assert_eq!(log.lines().count(), 1);
+    assert!(
+      log.contains(r#""rule":"line-endings","lines_changed":2,"before":"mixed","after":"lf"}"#)
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_no_clobber() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let output_path = temp_dir.path().join("output_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let output_file = output_path.to_str().unwrap();
+    let run_options = |no_clobber| EnderRunOptions {
+      new_eol: Some(EolTarget::Lf),
+      no_clobber,
+      ..Default::default()
+    };
+
+    std::fs::write(input_file, "abc\r\n").unwrap();
+    std::fs::write(output_file, "unrelated\n").unwrap();
+
+    let err = run_ender(input_file, Some(output_file), &run_options(true))
+      .err()
+      .unwrap();
+
+    assert_eq!(error::error_code(&*err), ErrorCode::OutputExists);
+    assert_eq!(std::fs::read_to_string(output_file).unwrap(), "unrelated\n");
+
+    run_ender(input_file, Some(output_file), &run_options(false)).unwrap();
+
+    assert_eq!(std::fs::read_to_string(output_file).unwrap(), "abc\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_eol_diagnostics() {
+    assert_eq!(
+      eol_diagnostics(
+        &mut "ab\r\nc\nd\re".as_bytes(),
+        "a.txt",
+        EndOfLine::Lf,
+        &EolOptions::default()
+      )
+      .unwrap(),
+      vec![
+        "a.txt:1:3: warning: crlf line ending, expected lf",
+        "a.txt:3:2: warning: cr line ending, expected lf"
+      ]
+    );
+
+    let options = EolOptions::default().final_newline(true);
+
+    assert_eq!(
+      eol_diagnostics(&mut "ab\nc".as_bytes(), "a.txt", EndOfLine::Lf, &options).unwrap(),
+      vec!["a.txt:2:2: warning: no final newline"]
+    );
+
+    let options = options.directives(true);
+
+    assert_eq!(
+      eol_diagnostics(
+        &mut "a\r\n# ws:disable\nb\r\n# ws:enable\r\nc\r\nd".as_bytes(),
+        "a.txt",
+        EndOfLine::Lf,
+        &options
+      )
+      .unwrap(),
+      vec![
+        "a.txt:1:2: warning: crlf line ending, expected lf",
+        "a.txt:5:2: warning: crlf line ending, expected lf",
+        "a.txt:6:2: warning: no final newline"
+      ]
+    );
+    assert!(eol_diagnostics(
+      &mut "a\n# ws:disable\nb".as_bytes(),
+      "a.txt",
+      EndOfLine::Lf,
+      &options
+    )
+    .unwrap()
+    .is_empty());
+  }
+
+  #[test]
+  fn test_run_ender_counts() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc def\n\txyz\n\n \n").unwrap();
+
+    let report = run_ender(
+      input_file,
+      None,
+      &EnderRunOptions {
+        counts: true,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    assert!(report
+      .text
+      .ends_with("16 chars, 3 words, 16 bytes, 1 blank lines, 1 whitespace-only lines"));
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_trailing_blank_lines() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\n\n\n").unwrap();
+
+    let report = run_ender(input_file, None, &EnderRunOptions::default()).unwrap();
+
+    assert_eq!(
+      report.text,
+      format!("'{}', lf, 4 lines, 2 trailing blank lines", input_file)
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_unicode_separators() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\u{85}xyz\u{85}").unwrap();
+
+    let options = EnderRunOptions::default().unicode_separators(true);
+    let report = run_ender(input_file, None, &options).unwrap();
+
+    assert_eq!(report.text, format!("'{}', nel, 3 lines", input_file));
+
+    let options = options
+      .new_eol(Some(EolTarget::Lf))
+      .in_place(true)
+      .verify(true);
+    let report = run_ender(input_file, None, &options).unwrap();
+
+    assert!(report.modified);
+    assert_eq!(report.violations, 2);
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), "abc\nxyz\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_tie_break() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\nxyz\r\n").unwrap();
+
+    let options = |tie_break| {
+      EnderRunOptions::default()
+        .new_eol(Some(EolTarget::Auto))
+        .check(true)
+        .tie_break(tie_break)
+    };
+
+    assert!(run_ender(input_file, None, &options(TieBreak::Lf))
+      .unwrap()
+      .text
+      .ends_with("needs converting to lf"));
+    assert!(run_ender(input_file, None, &options(TieBreak::CrLf))
+      .unwrap()
+      .text
+      .ends_with("needs converting to crlf"));
+
+    let err = run_ender(input_file, None, &options(TieBreak::Error)).unwrap_err();
+
+    assert_eq!(error::error_code(&*err), ErrorCode::Ambiguous);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_final_newline() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\nxyz").unwrap();
+
+    let report = run_ender(input_file, None, &EnderRunOptions::default()).unwrap();
+
+    assert_eq!(
+      report.text,
+      format!("'{}', lf, 2 lines, no final newline", input_file)
+    );
+    assert_eq!(report.violations, 0);
+
+    let options = EnderRunOptions::default()
+      .new_eol(Some(EolTarget::Auto))
+      .final_newline(true)
+      .in_place(true)
+      .verify(true)
+      .diagnostics(true);
+    let report = run_ender(input_file, None, &options).unwrap();
+
+    assert!(report.modified);
+    assert_eq!(report.violations, 1);
+    assert_eq!(
+      report.diagnostics,
+      vec![format!("{}:2:4: warning: no final newline", input_file)]
+    );
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), "abc\nxyz\n");
+    assert!(!run_ender(input_file, None, &options).unwrap().modified);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_diff() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = EnderRunOptions::default()
+      .new_eol(Some(EolTarget::Lf))
+      .diff(true);
+
+    std::fs::write(input_file, "abc\r\nxyz\n").unwrap();
+
+    let report = run_ender(input_file, None, &options).unwrap();
+
+    assert_eq!(
+      report.text,
+      format!("'{}', mixed, 3 lines -> diff, lf, 3 lines", input_file)
+    );
+    assert_eq!(
+      report.diff.unwrap(),
+      format!(
+        "--- {0}\n+++ {0}\n@@ -1,2 +1,2 @@\n-abc\\r\\n\n+abc\\n\n xyz\\n\n",
+        input_file
+      )
+    );
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), "abc\r\nxyz\n");
+
+    std::fs::write(input_file, "abc\nxyz\n").unwrap();
+
+    let report = run_ender(input_file, None, &options).unwrap();
+
+    assert!(report.diff.is_none());
+    assert_eq!(report.text, format!("'{}', lf, 3 lines", input_file));
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_emit_script_not_utf8() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = EnderRunOptions::default()
+      .new_eol(Some(EolTarget::Lf))
+      .decode_policy(DecodePolicy::PassThroughBytes)
+      .emit_script(true);
+
+    std::fs::write(input_file, b"abc\r\nx\xffz\n").unwrap();
+
+    assert_eq!(
+      run_ender(input_file, None, &options).unwrap().script.unwrap(),
+      format!(
+        "patch -p0 --forward <<'WHITESPACE_EOF'\n--- {0}\n+++ {0}\n@@ -1,2 +1,2 @@\n-abc\r\n+abc\n x\u{fffd}z\nWHITESPACE_EOF\n",
+        input_file
+      )
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_diff_not_utf8() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = EnderRunOptions::default()
+      .new_eol(Some(EolTarget::Lf))
+      .decode_policy(DecodePolicy::PassThroughBytes)
+      .diff(true);
+
+    std::fs::write(input_file, b"abc\r\nx\xffz\n").unwrap();
+
+    assert_eq!(
+      run_ender(input_file, None, &options).unwrap().diff.unwrap(),
+      format!(
+        "--- {0}\n+++ {0}\n@@ -1,2 +1,2 @@\n-abc\\r\\n\n+abc\\n\n x\u{fffd}z\\n\n",
+        input_file
+      )
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_check() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = EnderRunOptions::default()
+      .new_eol(Some(EolTarget::Lf))
+      .check(true);
+
+    std::fs::write(input_file, "abc\r\nxyz\r\n").unwrap();
+
+    let report = run_ender(input_file, None, &options).unwrap();
+
+    assert!(report.needs_change);
+    assert!(!report.modified);
+    assert_eq!(
+      report.text,
+      format!("'{}', crlf, 3 lines -> needs converting to lf", input_file)
+    );
+    assert_eq!(
+      std::fs::read_to_string(input_file).unwrap(),
+      "abc\r\nxyz\r\n"
+    );
+
+    std::fs::write(input_file, "abc\nxyz").unwrap();
+
+    assert!(!run_ender(input_file, None, &options).unwrap().needs_change);
+    assert!(
+      run_ender(input_file, None, &options.final_newline(true))
+        .unwrap()
+        .needs_change
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_mixed() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\r\nxyz\n").unwrap();
+
+    let report = run_ender(input_file, None, &EnderRunOptions::default()).unwrap();
+
+    assert!(!report.is_consistent);
+    assert_eq!(report.violations, 1);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_max_memory_size() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let output_path = temp_dir.path().join("output_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let output_file = output_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\r\nxyz\r\n").unwrap();
+
+    let options = EnderRunOptions::default()
+      .new_eol(Some(EolTarget::Lf))
+      .verify(true)
+      .max_memory_size(Some(8));
+    let err = run_ender(input_file, Some(output_file), &options).unwrap_err();
+
+    assert_eq!(error::error_code(&*err), ErrorCode::Limit);
+    assert!(!output_path.exists());
+
+    // Streaming without reading the whole file into memory isn't limited
+    run_ender(input_file, Some(output_file), &options.verify(false)).unwrap();
+    assert_eq!(std::fs::read_to_string(output_file).unwrap(), "abc\nxyz\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_patch() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("fix.patch");
+    let input_file = input_path.to_str().unwrap();
+    let patch = "--- a/x.txt\n+++ b/x.txt\n@@ -1 +1 @@\n-a\r\n+b\r\n";
+
+    std::fs::write(input_file, patch).unwrap();
+
+    let report = run_ender(
+      input_file,
+      None,
+      &EnderRunOptions {
+        new_eol: Some(EolTarget::Lf),
+        in_place: true,
+        diagnostics: true,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    assert!(!report.modified);
+    assert_eq!(report.violations, 0);
+    assert!(report.diagnostics.is_empty());
+    assert!(report.text.ends_with(", left unchanged as a patch"));
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), patch);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_tabs() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\t\tabc\r").unwrap();
+
+    run_spacer(
+      input_file,
+      None,
+      &SpacerRunOptions {
+        new_bol: Some(BolTarget::Spaces),
+        round_down: true,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_status_only() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\t\tabc\r").unwrap();
+
+    run_spacer(input_file, None, &SpacerRunOptions::default()).unwrap();
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_auto_spaces() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir.path().join("output_file.txt");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\t  abc\r").unwrap();
+
+    run_spacer(
+      input_file,
+      Some(output_path.to_str().unwrap()),
+      &SpacerRunOptions {
+        new_bol: Some(BolTarget::Auto),
+        tab_size: 2,
+        round_down: true,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_auto_tabs() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir.path().join("output_file.txt");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\t\n\t\n\t\t abc\r").unwrap();
+
+    run_spacer(
+      input_file,
+      Some(output_path.to_str().unwrap()),
+      &SpacerRunOptions {
+        new_bol: Some(BolTarget::Auto),
+        tab_size: 2,
+        round_down: true,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_verify() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir.path().join("output_file.txt");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\t\n\t\n\t\t abc\n").unwrap();
+
+    run_spacer(
+      input_file,
+      Some(output_path.to_str().unwrap()),
+      &SpacerRunOptions {
+        new_bol: Some(BolTarget::Spaces),
+        tab_size: 2,
+        verify: true,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    assert_eq!(
+      std::fs::read_to_string(output_path).unwrap(),
+      "  \n  \n     abc\n"
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_nested_output_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir
+      .path()
+      .join("out")
+      .join("sub")
+      .join("output_file.txt");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\tabc\n").unwrap();
+
+    run_spacer(
+      input_file,
+      Some(output_path.to_str().unwrap()),
+      &SpacerRunOptions {
+        new_bol: Some(BolTarget::Spaces),
+        tab_size: 2,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    assert_eq!(std::fs::read_to_string(output_path).unwrap(), "  abc\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_same_output_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\tabc\n").unwrap();
+
+    assert!(run_spacer(
+      input_file,
+      Some(input_file),
+      &SpacerRunOptions {
+        new_bol: Some(BolTarget::Spaces),
+        tab_size: 2,
+        ..Default::default()
+      }
+    )
+    .is_err());
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), "\tabc\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_yaml() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.yml");
+    let output_path = temp_dir.path().join("output_file.yml");
+    let input_file = input_path.to_str().unwrap();
+    let output_file = output_path.to_str().unwrap();
+
+    std::fs::write(input_file, "a:\n\tb: 1\n\tc: 2\n").unwrap();
+
+    assert!(run_spacer(
+      input_file,
+      Some(output_file),
+      &SpacerRunOptions {
+        new_bol: Some(BolTarget::Tabs),
+        ..Default::default()
+      }
+    )
+    .is_err());
+    assert!(!output_path.exists());
+
+    run_spacer(
+      input_file,
+      Some(output_file),
+      &SpacerRunOptions {
+        new_bol: Some(BolTarget::Auto),
+        tab_size: 2,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+    assert_eq!(
+      std::fs::read_to_string(output_file).unwrap(),
+      "a:\n  b: 1\n  c: 2\n"
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_max_memory_size() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\ta\n\tb\n").unwrap();
+
+    let options = SpacerRunOptions::default()
+      .new_bol(Some(BolTarget::Spaces))
+      .in_place(true)
+      .max_memory_size(Some(4));
+    let err = run_spacer(input_file, None, &options).unwrap_err();
+
+    assert_eq!(error::error_code(&*err), ErrorCode::Limit);
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), "\ta\n\tb\n");
+
+    let report = run_spacer(input_file, None, &options.max_memory_size(Some(6))).unwrap();
+
+    assert!(report.modified);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_patch() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("changes");
+    let input_file = input_path.to_str().unwrap();
+    let patch = "diff --git a/x.py b/x.py\n@@ -1,2 +1,2 @@\n     a = 1\n-\tb = 2\n+    b = 2  \n";
+
+    std::fs::write(input_file, patch).unwrap();
+
+    let report = run_spacer(
+      input_file,
+      None,
+      &SpacerRunOptions {
+        new_bol: Some(BolTarget::Tabs),
+        in_place: true,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    assert!(!report.modified);
+    assert_eq!(report.violations, 0);
+    assert!(report.text.ends_with(", left unchanged as a patch"));
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), patch);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_in_place() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = SpacerRunOptions {
+      new_bol: Some(BolTarget::Auto),
+      in_place: true,
+      ..Default::default()
+    };
+
+    std::fs::write(input_file, "    abc\n\txyz\n    123\n").unwrap();
+
+    let report = run_spacer(input_file, None, &options).unwrap();
+
+    assert!(report.modified);
+    assert_eq!(
+      report.text,
+      format!("'{}', mixed -> '{}', spaces", input_file, input_file)
+    );
+    assert_eq!(
+      std::fs::read_to_string(input_file).unwrap(),
+      "    abc\n    xyz\n    123\n"
+    );
+
+    let report = run_spacer(input_file, None, &options).unwrap();
+
+    assert!(!report.modified);
+    assert_eq!(report.text, format!("'{}', spaces", input_file));
+    assert!(run_spacer("-", None, &options).is_err());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  #[cfg(feature = "encoding")]
+  fn test_run_spacer_utf16() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = SpacerRunOptions::default()
+      .new_bol(Some(BolTarget::Spaces))
+      .output_encoding(Some(Encoding::Utf16Be))
+      .in_place(true);
+
+    std::fs::write(input_file, "\tabc\n    xyz\n").unwrap();
+
+    let report = run_spacer(input_file, None, &options).unwrap();
+
+    assert!(report.modified);
+    assert_eq!(
+      report.text,
+      format!(
+        "'{}', mixed -> '{}', spaces, utf-16be",
+        input_file, input_file
+      )
+    );
+    assert_eq!(
+      std::fs::read(input_file).unwrap(),
+      encoding::encode("    abc\n    xyz\n", Encoding::Utf16Be).unwrap()
+    );
+
+    let report = run_spacer(input_file, None, &options).unwrap();
+
+    assert!(!report.modified);
+    assert_eq!(report.text, format!("'{}', spaces, utf-16be", input_file));
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_emit_script() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = SpacerRunOptions {
+      new_bol: Some(BolTarget::Spaces),
+      emit_script: true,
+      ..Default::default()
+    };
+
+    std::fs::write(input_file, "abc\n\txyz\n").unwrap();
+
+    let report = run_spacer(input_file, None, &options).unwrap();
+
+    assert_eq!(
+      report.text,
+      format!("'{}', tabs -> script, spaces", input_file)
+    );
+    assert_eq!(
+      report.script.unwrap(),
+      format!(
+        "patch -p0 --forward <<'WHITESPACE_EOF'\n--- {0}\n+++ {0}\n@@ -1,2 +1,2 @@\n abc\n-\txyz\n+    xyz\nWHITESPACE_EOF\n",
+        input_file
+      )
+    );
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), "abc\n\txyz\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_emit_script_not_utf8() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = SpacerRunOptions::default()
+      .new_bol(Some(BolTarget::Spaces))
+      .bol_options(BolOptions::default().decode_policy(DecodePolicy::Replace))
+      .emit_script(true);
+
+    std::fs::write(input_file, b"a\xffc\n\txyz\n").unwrap();
+
+    assert_eq!(
+      run_spacer(input_file, None, &options).unwrap().script.unwrap(),
+      format!(
+        "patch -p0 --forward <<'WHITESPACE_EOF'\n--- {0}\n+++ {0}\n@@ -1,2 +1,2 @@\n a\u{fffd}c\n-\txyz\n+    xyz\nWHITESPACE_EOF\n",
+        input_file
+      )
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_diff_not_utf8() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = SpacerRunOptions::default()
+      .new_bol(Some(BolTarget::Spaces))
+      .bol_options(BolOptions::default().decode_policy(DecodePolicy::PassThroughBytes))
+      .diff(true);
+
+    std::fs::write(input_file, b"a\xffc\n\txyz\n").unwrap();
+
+    assert_eq!(
+      run_spacer(input_file, None, &options)
+        .unwrap()
+        .diff
+        .unwrap(),
+      format!(
+        "--- {0}\n+++ {0}\n@@ -1,2 +1,2 @@\n a\u{fffd}c\\n\n-\\txyz\\n\n+    xyz\\n\n",
+        input_file
+      )
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_check() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = SpacerRunOptions::default()
+      .new_bol(Some(BolTarget::Spaces))
+      .check(true);
+
+    std::fs::write(input_file, "abc\n\txyz\n").unwrap();
+
+    let report = run_spacer(input_file, None, &options).unwrap();
+
+    assert!(report.needs_change);
+    assert!(!report.modified);
+    assert_eq!(
+      report.text,
+      format!("'{}', tabs -> needs converting to spaces", input_file)
+    );
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), "abc\n\txyz\n");
+
+    std::fs::write(input_file, "abc\n  xyz\n").unwrap();
+
+    let report = run_spacer(input_file, None, &options).unwrap();
+
+    assert!(!report.needs_change);
+    assert_eq!(report.text, format!("'{}', spaces", input_file));
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_journal() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let journal_dir = temp_dir.path().join("journal");
+    let options = SpacerRunOptions {
+      new_bol: Some(BolTarget::Spaces),
+      in_place: true,
+      journal: Some(Journal::create(&journal_dir).unwrap()),
+      ..Default::default()
+    };
+
+    std::fs::write(input_file, "abc\n\txyz\n").unwrap();
+
+    assert!(run_spacer(input_file, None, &options).unwrap().modified);
+    assert_eq!(
+      crate::journal::undo(&journal_dir, false).unwrap(),
+      vec![input_path.clone()]
+    );
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), "abc\n\txyz\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_audit_log() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let log_path = temp_dir.path().join("audit.log");
+    let options = SpacerRunOptions {
+      new_bol: Some(BolTarget::Spaces),
+      in_place: true,
+      audit_log: Some(AuditLog::open(&log_path).unwrap()),
+      ..Default::default()
+    };
+
+    std::fs::write(input_file, "abc\n\txyz\n").unwrap();
+
+    assert!(run_spacer(input_file, None, &options).unwrap().modified);
+    assert!(!run_spacer(input_file, None, &options).unwrap().modified);
+
+    let log = std::fs::read_to_string(&log_path).unwrap();
+
+    assert_eq!(log.lines().count(), 1);
+    assert!(log
+      .contains(r#""rule":"line-beginnings","lines_changed":1,"before":"tabs","after":"spaces"}"#));
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_bol_diagnostics() {
+    assert_eq!(
+      bol_diagnostics(
+        &mut "a\n\tb\n  c\n \td\n".as_bytes(),
+        "a.txt",
+        BeginningOfLine::Spaces(4),
+        &BolOptions::default()
+      )
+      .unwrap(),
+      vec![
+        "a.txt:2:1: warning: line begins with tabs, expected spaces",
+        "a.txt:4:1: warning: line begins with mixed tabs and spaces, expected spaces"
+      ]
+    );
+    assert_eq!(
+      bol_diagnostics(
+        &mut "\ta\n# ws:disable\n\tb\n \tc\n# ws:enable\n\td\n".as_bytes(),
+        "a.txt",
+        BeginningOfLine::Spaces(4),
+        &BolOptions::default().directives(true)
+      )
+      .unwrap(),
+      vec![
+        "a.txt:1:1: warning: line begins with tabs, expected spaces",
+        "a.txt:6:1: warning: line begins with tabs, expected spaces"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_run_spacer_safe_only() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.c");
+    let output_path = temp_dir.path().join("output_file.c");
+    let input_file = input_path.to_str().unwrap();
+    let output_file = output_path.to_str().unwrap();
+    let run_options = |safe_only| SpacerRunOptions {
+      new_bol: Some(BolTarget::Tabs),
+      round_down: true,
+      bol_options: BolOptions::default().safe_only(safe_only),
+      diagnostics: true,
+      ..Default::default()
+    };
+
+    std::fs::write(input_file, "f(a,\n  b);\n    g();\n").unwrap();
+
+    let report = run_spacer(input_file, Some(output_file), &run_options(false)).unwrap();
+
+    assert_eq!(report.misaligned_lines, vec![2, 3]);
+    assert!(report.text.contains("2 lines change alignment"));
+    assert!(report.diagnostics.contains(&format!(
+      "{}:2:1: warning: converting the line beginning changes its alignment with the line before",
+      input_file
+    )));
+    assert_eq!(
+      std::fs::read_to_string(output_file).unwrap(),
+      "f(a,\nb);\n\tg();\n"
+    );
+
+    let report = run_spacer(input_file, Some(output_file), &run_options(true)).unwrap();
+
+    assert!(report
+      .text
+      .contains("2 lines left unchanged to keep their alignment"));
+    assert_eq!(
+      std::fs::read_to_string(output_file).unwrap(),
+      "f(a,\n  b);\n\tg();\n"
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_no_clobber() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let output_path = temp_dir.path().join("output_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let output_file = output_path.to_str().unwrap();
+    let run_options = |no_clobber| SpacerRunOptions {
+      new_bol: Some(BolTarget::Spaces),
+      no_clobber,
+      ..Default::default()
+    };
+
+    std::fs::write(input_file, "\tabc\n").unwrap();
+    std::fs::write(output_file, "unrelated\n").unwrap();
+
+    let err = run_spacer(input_file, Some(output_file), &run_options(true))
+      .err()
+      .unwrap();
+
+    assert_eq!(error::error_code(&*err), ErrorCode::OutputExists);
+    assert_eq!(std::fs::read_to_string(output_file).unwrap(), "unrelated\n");
+
+    run_spacer(input_file, Some(output_file), &run_options(false)).unwrap();
+
+    assert_eq!(std::fs::read_to_string(output_file).unwrap(), "    abc\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_infer_tab_size() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.c");
+    let output_path = temp_dir.path().join("output_file.c");
+    let input_file = input_path.to_str().unwrap();
+    let output_file = output_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\tfoo();\n        bar();\n").unwrap();
+
+    let report = run_spacer(
+      input_file,
+      Some(output_file),
+      &SpacerRunOptions {
+        new_bol: Some(BolTarget::Spaces),
+        infer_tab_size: true,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    assert!(report.text.contains("tab size 8 inferred"));
+    assert_eq!(
+      std::fs::read_to_string(output_file).unwrap(),
+      "        foo();\n        bar();\n"
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_preserve_blank_line_ws() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir.path().join("output_file.txt");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\tabc\n\t\n").unwrap();
+
+    run_spacer(
+      input_file,
+      Some(output_path.to_str().unwrap()),
+      &SpacerRunOptions {
+        new_bol: Some(BolTarget::Spaces),
+        tab_size: 2,
+        bol_options: BolOptions::default().preserve_blank_line_ws(true),
+        verify: true,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    assert_eq!(std::fs::read_to_string(output_path).unwrap(), "  abc\n\t\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_indent_above() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir.path().join("output_file.txt");
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\tabc\n\t\txyz\n").unwrap();
+
+    run_spacer(
+      input_file,
+      Some(output_path.to_str().unwrap()),
+      &SpacerRunOptions {
+        new_bol: Some(BolTarget::Spaces),
+        tab_size: 2,
+        bol_options: BolOptions::default().indent_above(Some(2)),
+        verify: true,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    assert_eq!(
+      std::fs::read_to_string(output_path).unwrap(),
+      "\tabc\n    xyz\n"
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_smart_tabs() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\tabc\n\t  xyz\n  123\n").unwrap();
+
+    let report = run_spacer(input_file, None, &SpacerRunOptions::default()).unwrap();
+
+    assert_eq!(
+      report.text,
+      format!(
+        "'{}', mixed, 2 of 3 indented lines follow smart tabs",
+        input_file
+      )
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_unusual_spaces() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\u{a0}\u{a0}abc\n  xyz\n").unwrap();
+
+    let report = run_spacer(input_file, None, &SpacerRunOptions::default()).unwrap();
+
+    assert_eq!(
+      report.text,
+      format!(
+        "'{}', spaces, 1 lines begin with unusual spaces",
+        input_file
+      )
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_counts() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc def\n\txyz\n").unwrap();
+
+    run_spacer(
+      input_file,
+      None,
+      &SpacerRunOptions {
+        counts: true,
+        ..Default::default()
+      },
+    )
+    .unwrap();
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_mixed() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "\tabc\n  xyz\n").unwrap();
+
+    let report = run_spacer(input_file, None, &SpacerRunOptions::default()).unwrap();
+
+    assert!(!report.is_consistent);
+    assert_eq!(report.violations, 1);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_trimmer_output_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let output_path = temp_dir.path().join("output_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let output_file = output_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc  \r\nxyz\n\t\n").unwrap();
+
+    let report = run_trimmer(input_file, None, &TrimmerRunOptions::default()).unwrap();
+
+    assert_eq!(report.violations, 2);
+    assert_eq!(
+      report.text,
+      format!("'{}', 4 lines, 2 with trailing whitespace", input_file)
+    );
+
+    let options = TrimmerRunOptions::default().trim(true).verify(true);
+    let report = run_trimmer(input_file, Some(output_file), &options).unwrap();
+
+    assert_eq!(
+      report.text,
+      format!(
+        "'{}', 4 lines, 2 with trailing whitespace -> '{}', 2 lines trimmed",
+        input_file, output_file
+      )
+    );
+    assert_eq!(
+      std::fs::read_to_string(output_file).unwrap(),
+      "abc\r\nxyz\n\n"
+    );
+    assert!(run_trimmer(input_file, Some(input_file), &options).is_err());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_trimmer_in_place() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = TrimmerRunOptions::default().trim(true).in_place(true);
+
+    std::fs::write(input_file, "abc \nxyz\n").unwrap();
+
+    let report = run_trimmer(input_file, None, &options).unwrap();
+
+    assert!(report.modified);
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), "abc\nxyz\n");
+
+    let report = run_trimmer(input_file, None, &options).unwrap();
+
+    assert!(!report.modified);
+    assert_eq!(report.violations, 0);
+    assert!(run_trimmer("-", None, &options).is_err());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_trimmer_emit_script() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = TrimmerRunOptions::default().trim(true).emit_script(true);
+
+    std::fs::write(input_file, "abc\t\nxyz\n").unwrap();
+
+    let report = run_trimmer(input_file, None, &options).unwrap();
+
+    assert_eq!(
+      report.script.unwrap(),
+      format!(
+        "patch -p0 --forward <<'WHITESPACE_EOF'\n--- {0}\n+++ {0}\n@@ -1,2 +1,2 @@\n-abc\t\n+abc\n xyz\nWHITESPACE_EOF\n",
+        input_file
+      )
+    );
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), "abc\t\nxyz\n");
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_trimmer_diagnostics() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+
+    std::fs::write(input_file, "abc\nxÿz  \n").unwrap();
+
+    let report = run_trimmer(
+      input_file,
+      None,
+      &TrimmerRunOptions::default().diagnostics(true),
+    )
+    .unwrap();
+
+    assert_eq!(
+      report.diagnostics,
+      vec![format!("{}:2:4: warning: trailing whitespace", input_file)]
+    );
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_ender_directives() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = EnderRunOptions::default()
+      .directives(true)
+      .diagnostics(true);
+
+    std::fs::write(input_file, "a\n# ws:disable\nb\r\n# ws:enable\nc\n").unwrap();
+
+    let report = run_ender(input_file, None, &options).unwrap();
+
+    assert!(report.is_consistent);
+    assert_eq!(report.violations, 0);
+    assert_eq!((report.eol_info.lf, report.eol_info.crlf), (3, 0));
+    assert!(report.diagnostics.is_empty());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_spacer_directives() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = SpacerRunOptions::default()
+      .bol_options(BolOptions::default().directives(true))
+      .diagnostics(true);
+
+    std::fs::write(input_file, "  a\n# ws:disable\n\tb\n# ws:enable\n  c\n").unwrap();
+
+    let report = run_spacer(input_file, None, &options).unwrap();
+
+    assert!(report.is_consistent);
+    assert_eq!(report.violations, 0);
+    assert_eq!((report.bol_info.spaces, report.bol_info.tabs), (2, 0));
+    assert!(report.diagnostics.is_empty());
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_trimmer_directives() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("input_file.txt");
+    let input_file = input_path.to_str().unwrap();
+    let options = TrimmerRunOptions::default()
+      .trim(true)
+      .directives(true)
+      .verify(true)
+      .diagnostics(true)
+      .in_place(true);
+
+    std::fs::write(input_file, "a \n# ws:disable\nb \n# ws:enable\nc \n").unwrap();
+
+    let report = run_trimmer(input_file, None, &options).unwrap();
+
+    assert!(report.modified);
+    assert_eq!(report.violations, 2);
+    assert_eq!(
+      report.diagnostics,
+      vec![
+        format!("{}:1:2: warning: trailing whitespace", input_file),
+        format!("{}:5:2: warning: trailing whitespace", input_file),
+      ]
+    );
+    assert_eq!(
+      std::fs::read_to_string(input_file).unwrap(),
+      "a\n# ws:disable\nb \n# ws:enable\nc\n"
+    );
+    assert!(!run_trimmer(input_file, None, &options).unwrap().modified);
+
+    temp_dir.close().unwrap();
+  }
+
+  #[test]
+  fn test_run_trimmer_patch() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_path = temp_dir.path().join("changes.diff");
+    let input_file = input_path.to_str().unwrap();
+    let patch = "--- a/x.txt\n+++ b/x.txt\n@@ -1 +1 @@\n-a\n+a  \n \n";
+
+    std::fs::write(input_file, patch).unwrap();
+
+    let options = TrimmerRunOptions::default().trim(true).in_place(true);
+    let report = run_trimmer(input_file, None, &options).unwrap();
+
+    assert!(!report.modified);
+    assert_eq!(report.violations, 0);
+    assert!(report.text.ends_with(", left unchanged as a patch"));
+    assert_eq!(std::fs::read_to_string(input_file).unwrap(), patch);
+
+    temp_dir.close().unwrap();
+  }
+}
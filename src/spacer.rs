@@ -26,20 +26,41 @@
 //! fn main() -> Result<(), Box<dyn Error>> {
 //!   let mut reader = "abc\n\r\r\n".as_bytes();
 //!   let mut writer = Vec::new();
-//!   let bol_info = spacer::write_new_bols(&mut reader, &mut writer, spacer::BeginningOfLine::Tabs(2, true))?;
+//!   let new_bol = spacer::BeginningOfLine::tabs(2).round_down(true);
+//!   let bol_info = spacer::write_new_bols(&mut reader, &mut writer, new_bol)?;
 //!
 //!   println!("{:?}", bol_info);
 //!   Ok(())
 //! }
 //! ```
+//!
+//! Before expanding the tabs in a file that mixes tabs and spaces, use [`infer_tab_size()`] to
+//! find the tab size that the author assumed, or [`tab_size_fits()`] to see how well each of
+//! several tab sizes fits.  Use [`needs_new_bols()`] to check if a file would be changed
+//! without writing anything, and [`normalize_str()`] to normalize the line beginnings of text
+//! that is already in memory.  Set [`BolOptions::decode_policy`] to choose what happens to bytes
+//! that aren't valid UTF-8.
+//!
+//! Each function has a `_generic` version that takes concrete reader and writer types instead of
+//! trait objects, so that its decoding loop can be inlined for them.
+//!
+//! With [`BolOptions::directives`] the beginnings of the lines after a `ws:disable` comment, up to
+//! and including the next `ws:enable` comment, are left unchanged.
 
+use crate::decode::{self, DecodePolicy, Decoder};
+use crate::error::{self, ErrorCode};
+use crate::lexer::StringLexer;
+use crate::lines::Directives;
 use std::cmp::max;
 use std::error::Error;
-use std::io::{Read, Write};
-use utf8_decode::UnsafeDecoder;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::str::FromStr;
 
 // {grcov-excl-start}
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 /// Types of line beginnings
 pub enum BeginningOfLine {
   /// Tabs (and spaces if not rounding down extra spaces)
@@ -49,7 +70,92 @@ pub enum BeginningOfLine {
 }
 // {grcov-excl-end}
 
-#[derive(Debug, PartialEq)]
+/// Parse `tabs` or `spaces`, ignoring case.  The tab size defaults to 4 and extra spaces are not
+/// rounded down.
+impl FromStr for BeginningOfLine {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "tabs" => Ok(BeginningOfLine::Tabs(4, false)),
+      "spaces" => Ok(BeginningOfLine::Spaces(4)),
+      _ => Err(format!("'{}' is not one of tabs or spaces", s)),
+    }
+  }
+}
+
+impl BeginningOfLine {
+  /// Tabs `tab_size` columns wide, with spaces for any columns left over
+  pub fn tabs(tab_size: usize) -> Self {
+    BeginningOfLine::Tabs(tab_size, false)
+  }
+
+  /// Spaces, expanding tabs `tab_size` columns wide
+  pub fn spaces(tab_size: usize) -> Self {
+    BeginningOfLine::Spaces(tab_size)
+  }
+
+  /// Drop any spaces left over after tabs if `round_down` is true.  Spaces are left unchanged.
+  pub fn round_down(self, round_down: bool) -> Self {
+    match self {
+      BeginningOfLine::Tabs(tab_size, _) => BeginningOfLine::Tabs(tab_size, round_down),
+      bol => bol,
+    }
+  }
+
+  /// Get the number of columns that tabs are wide
+  pub fn tab_size(self) -> usize {
+    match self {
+      BeginningOfLine::Tabs(tab_size, _) | BeginningOfLine::Spaces(tab_size) => tab_size,
+    }
+  }
+}
+
+impl fmt::Display for BeginningOfLine {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(match self {
+      BeginningOfLine::Tabs(_, _) => "tabs",
+      BeginningOfLine::Spaces(_) => "spaces",
+    })
+  }
+}
+
+// {grcov-excl-start}
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+/// How [`BolInfo::get_common_bol_with()`] decides which line beginning is most common
+pub enum BolStrategy {
+  /// Compare the number of lines indented with only tabs and with only spaces
+  #[default]
+  Lines,
+  /// Compare the number of indented lines that use tabs for indentation, including smart tabs
+  /// lines that align with spaces after the tabs, with the number indented with only spaces
+  Weighted,
+}
+// {grcov-excl-end}
+
+/// Parse `lines` or `weighted`, ignoring case.
+impl FromStr for BolStrategy {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "lines" => Ok(BolStrategy::Lines),
+      "weighted" => Ok(BolStrategy::Weighted),
+      _ => Err(format!("'{}' is not one of lines or weighted", s)),
+    }
+  }
+}
+
+impl BolStrategy {
+  /// Names of the strategies, as they are parsed
+  pub const NAMES: &'static [&'static str] = &["lines", "weighted"];
+}
+
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
 /// Information about line beginnings in the file
 pub struct BolInfo {
   /// Number of lines that have no whitespace at the beginning
@@ -60,62 +166,612 @@ pub struct BolInfo {
   pub tabs: usize,
   /// Number of mixed space/tab line beginnings
   pub mixed: usize,
+  /// Number of mixed line beginnings that are tabs followed by spaces, i.e. smart tabs
+  pub smart_tabs: usize,
+  /// Number of line beginnings that contain unusual space characters, such as non-breaking or
+  /// zero-width spaces.  See [`is_unusual_space()`].
+  pub unusual: usize,
+  /// Numbers of the lines, starting from 1, with mixed space/tab line beginnings.  Only recorded
+  /// by the `read_bol_info` functions with [`BolOptions::line_numbers`].
+  pub mixed_lines: Vec<usize>,
+  /// Numbers of the lines, starting from 1, with all tab line beginnings.  Only recorded by the
+  /// `read_bol_info` functions with [`BolOptions::line_numbers`].
+  pub tab_lines: Vec<usize>,
+  /// Total number of characters in the file
+  pub num_chars: usize,
+  /// Total number of whitespace separated words in the file
+  pub num_words: usize,
+  /// Total number of bytes in the file
+  pub num_bytes: usize,
+}
+
+/// Options for [`write_new_bols_with_options()`] and [`verify_new_bols_with_options()`].  Start
+/// from the default, which changes every line, and use the setters to change it:
+///
+/// ```
+/// use whitespace_rs::spacer::BolOptions;
+///
+/// let options = BolOptions::default().markdown(true).indent_above(Some(4));
+///
+/// assert!(options.markdown);
+/// ```
+#[derive(Debug, Default, PartialEq, Clone)]
+#[non_exhaustive]
+pub struct BolOptions {
+  /// Leave the whitespace on whitespace-only lines unchanged
+  pub preserve_blank_line_ws: bool,
+  /// Only change lines indented by more than this many columns
+  pub indent_above: Option<usize>,
+  /// Only change lines indented by fewer than this many columns
+  pub indent_below: Option<usize>,
+  /// Keep continuation lines that are aligned with an opening delimiter on an earlier line, or
+  /// with a word after it, aligned to the same column using spaces
+  pub align_continuations: bool,
+  /// Leave lines that begin inside string literals or here-docs unchanged, using a lightweight
+  /// lexer for common languages
+  pub protect_strings: bool,
+  /// Leave lines in Markdown fenced and indented code blocks unchanged
+  pub markdown: bool,
+  /// Leave lines unchanged if converting them would move them relative to the lines around them,
+  /// such as alignment spaces dropped by rounding down
+  pub safe_only: bool,
+  /// Fail rather than buffer a line beginning, or a whole line when protecting strings or Markdown
+  /// code blocks, longer than this many bytes
+  pub max_line_length: Option<usize>,
+  /// What to do with bytes that aren't valid UTF-8
+  pub decode_policy: DecodePolicy,
+  /// Treat unusual space characters in line beginnings as part of them, so that they are replaced
+  /// along with the spaces and tabs.  Zero-width spaces are dropped and the others are one column
+  /// wide.
+  pub replace_unusual_spaces: bool,
+  /// Record the numbers of the lines with mixed and all tab line beginnings in
+  /// [`BolInfo::mixed_lines`] and [`BolInfo::tab_lines`] when reading line beginning information
+  pub line_numbers: bool,
+  /// Leave the beginnings of the lines after a `ws:disable` comment, up to and including the next
+  /// `ws:enable` comment, unchanged
+  pub directives: bool,
 }
 
-impl Eq for BolInfo {}
+impl BolOptions {
+  /// Set [`preserve_blank_line_ws`](BolOptions::preserve_blank_line_ws)
+  pub fn preserve_blank_line_ws(mut self, preserve_blank_line_ws: bool) -> Self {
+    self.preserve_blank_line_ws = preserve_blank_line_ws;
+    self
+  }
+
+  /// Set [`indent_above`](BolOptions::indent_above)
+  pub fn indent_above(mut self, indent_above: Option<usize>) -> Self {
+    self.indent_above = indent_above;
+    self
+  }
+
+  /// Set [`indent_below`](BolOptions::indent_below)
+  pub fn indent_below(mut self, indent_below: Option<usize>) -> Self {
+    self.indent_below = indent_below;
+    self
+  }
+
+  /// Set [`align_continuations`](BolOptions::align_continuations)
+  pub fn align_continuations(mut self, align_continuations: bool) -> Self {
+    self.align_continuations = align_continuations;
+    self
+  }
+
+  /// Set [`protect_strings`](BolOptions::protect_strings)
+  pub fn protect_strings(mut self, protect_strings: bool) -> Self {
+    self.protect_strings = protect_strings;
+    self
+  }
+
+  /// Set [`markdown`](BolOptions::markdown)
+  pub fn markdown(mut self, markdown: bool) -> Self {
+    self.markdown = markdown;
+    self
+  }
+
+  /// Set [`safe_only`](BolOptions::safe_only)
+  pub fn safe_only(mut self, safe_only: bool) -> Self {
+    self.safe_only = safe_only;
+    self
+  }
+
+  /// Set [`max_line_length`](BolOptions::max_line_length)
+  pub fn max_line_length(mut self, max_line_length: Option<usize>) -> Self {
+    self.max_line_length = max_line_length;
+    self
+  }
+
+  /// Set [`decode_policy`](BolOptions::decode_policy)
+  pub fn decode_policy(mut self, decode_policy: DecodePolicy) -> Self {
+    self.decode_policy = decode_policy;
+    self
+  }
+
+  /// Set [`replace_unusual_spaces`](BolOptions::replace_unusual_spaces)
+  pub fn replace_unusual_spaces(mut self, replace_unusual_spaces: bool) -> Self {
+    self.replace_unusual_spaces = replace_unusual_spaces;
+    self
+  }
+
+  /// Set [`line_numbers`](BolOptions::line_numbers)
+  pub fn line_numbers(mut self, line_numbers: bool) -> Self {
+    self.line_numbers = line_numbers;
+    self
+  }
+
+  /// Set [`directives`](BolOptions::directives)
+  pub fn directives(mut self, directives: bool) -> Self {
+    self.directives = directives;
+    self
+  }
+
+  /// Check if `c` is part of a line beginning
+  fn is_bol_char(&self, c: char) -> bool {
+    c == ' ' || c == '\t' || (self.replace_unusual_spaces && is_unusual_space(c))
+  }
+
+  /// Check that the buffered part of a line, `len` bytes long, is within
+  /// [`max_line_length`](BolOptions::max_line_length)
+  fn check_line_length(&self, len: usize) -> Result<(), Box<dyn Error>> {
+    match self.max_line_length {
+      Some(max_line_length) if len > max_line_length => Err(
+        error::Error::new(
+          ErrorCode::Limit,
+          format!(
+            "line is longer than the limit of {} bytes, use --max-line-length to raise it",
+            max_line_length
+          ),
+        )
+        .into(),
+      ),
+      _ => Ok(()),
+    }
+  }
+
+  /// Check if a line beginning is within the indentation thresholds
+  fn is_selected(&self, s: &str, tab_size: usize) -> bool {
+    let width = indent_width(s, tab_size);
+
+    self.indent_above.is_none_or(|n| width > n) && self.indent_below.is_none_or(|n| width < n)
+  }
+}
 
 impl BolInfo {
   /// Get the most common beginning of line type in the file
-  pub fn get_common_bol(self: &Self, tab_size: usize, round_down: bool) -> BeginningOfLine {
-    if self.tabs > self.spaces {
+  pub fn get_common_bol(&self, tab_size: usize, round_down: bool) -> BeginningOfLine {
+    self.get_common_bol_with(tab_size, round_down, BolStrategy::Lines)
+  }
+
+  /// Get the most common beginning of line type in the file, deciding as `strategy` says.  Spaces
+  /// are chosen when tabs and spaces are equally common.
+  ///
+  /// With [`BolStrategy::Weighted`], mixed line beginnings that are smart tabs count as tabs and
+  /// other mixed line beginnings, such as spaces followed by tabs, count for neither, so that a
+  /// file indented with tabs and aligned with spaces isn't converted to spaces.
+  pub fn get_common_bol_with(
+    &self,
+    tab_size: usize,
+    round_down: bool,
+    strategy: BolStrategy,
+  ) -> BeginningOfLine {
+    let num_tabs = match strategy {
+      BolStrategy::Lines => self.tabs,
+      BolStrategy::Weighted => self.tabs + self.smart_tabs,
+    };
+
+    if num_tabs > self.spaces {
       BeginningOfLine::Tabs(tab_size, round_down)
     } else {
       BeginningOfLine::Spaces(tab_size)
     }
   }
+
+  /// Check if all indented lines use tabs, or tabs followed by spaces for alignment
+  /// (smart tabs), and at least one line uses smart tabs.
+  pub fn is_smart_tabs(&self) -> bool {
+    self.smart_tabs > 0 && self.spaces == 0 && self.mixed == self.smart_tabs
+  }
+
+  /// Get the number of indented lines that don't begin with `bol`.
+  pub fn num_other_beginnings(&self, bol: BeginningOfLine) -> usize {
+    match bol {
+      BeginningOfLine::Tabs(_, _) => self.spaces + self.mixed,
+      BeginningOfLine::Spaces(_) => self.tabs + self.mixed,
+    }
+  }
+}
+
+/// An opening delimiter that hasn't been closed yet
+struct Opening {
+  /// Line number of the delimiter
+  line: usize,
+  /// Indentation width of the line in the input
+  indent: usize,
+  /// Beginning of the line in the output
+  bol: String,
+  /// Columns that continuation lines can be aligned with
+  columns: Vec<usize>,
+}
+
+/// Tracks unclosed delimiters to find continuation lines aligned with them
+#[derive(Default)]
+struct Continuations {
+  openings: Vec<Opening>,
+  line: usize,
+  indent: usize,
+  bol: String,
+  column: usize,
+  in_word: bool,
+  in_string: bool,
+}
+
+impl Continuations {
+  /// Get a beginning of line that keeps a line aligned with the innermost unclosed delimiter, if
+  /// it is aligned with it
+  fn aligned_bol(&self, s: &str, tab_size: usize) -> Option<String> {
+    let opening = self.openings.last()?;
+    let width = indent_width(s, tab_size);
+
+    if width > opening.indent && opening.columns.contains(&width) {
+      Some(opening.bol.clone() + &" ".repeat(width - opening.indent))
+    } else {
+      None
+    }
+  }
+
+  /// Start a line that has the beginning `s` in the input and `bol` in the output
+  fn start_line(&mut self, s: &str, bol: &str, tab_size: usize) {
+    self.line += 1;
+    self.indent = indent_width(s, tab_size);
+    self.bol.clear();
+    self.bol.push_str(bol);
+    self.column = self.indent;
+    self.in_word = false;
+    self.in_string = false;
+  }
+
+  /// Track a character after the beginning of a line
+  fn push(&mut self, c: char, tab_size: usize) {
+    if !c.is_whitespace() && !self.in_word && !self.in_string {
+      if let Some(opening) = self.openings.last_mut() {
+        if opening.line == self.line {
+          opening.columns.push(self.column);
+        }
+      }
+    }
+
+    if self.in_string {
+      self.in_string = c != '"';
+    } else {
+      match c {
+        '(' | '[' | '{' => self.openings.push(Opening {
+          line: self.line,
+          indent: self.indent,
+          bol: self.bol.clone(),
+          columns: vec![self.column + 1],
+        }),
+        ')' | ']' | '}' => {
+          self.openings.pop();
+        }
+        '"' => self.in_string = true,
+        _ => (),
+      }
+    }
+
+    self.in_word = !c.is_whitespace();
+    self.column += if c == '\t' {
+      tab_size - (self.column % tab_size)
+    } else {
+      1
+    };
+  }
+}
+
+/// Tracks the code blocks in a Markdown file
+struct MarkdownBlocks {
+  /// Fence that opened the current fenced code block
+  fence: Option<String>,
+  prev_blank: bool,
+  prev_code: bool,
+  in_list: bool,
+}
+
+impl Default for MarkdownBlocks {
+  fn default() -> Self {
+    MarkdownBlocks {
+      fence: None,
+      prev_blank: true,
+      prev_code: false,
+      in_list: false,
+    }
+  }
+}
+
+impl MarkdownBlocks {
+  /// Check if a line with the beginning `s` is in a fenced or indented code block.  Markdown
+  /// always uses a tab size of 4.
+  fn is_code(&self, s: &str) -> bool {
+    self.fence.is_some()
+      || (indent_width(s, 4) >= 4 && (self.prev_code || (self.prev_blank && !self.in_list)))
+  }
+
+  /// Track a line without its line ending
+  fn push_line(&mut self, line: &str) {
+    let text = line.trim_start_matches([' ', '\t']);
+    let indent = &line[..line.len() - text.len()];
+    let text = text.trim_end();
+
+    if let Some(fence) = &self.fence {
+      let fence_char = fence.as_bytes()[0] as char;
+
+      if text.len() >= fence.len() && text.chars().all(|c| c == fence_char) {
+        self.fence = None;
+      }
+
+      self.prev_blank = false;
+      return;
+    }
+
+    if text.is_empty() {
+      self.prev_blank = true;
+      return;
+    }
+
+    self.prev_code = self.is_code(indent);
+    self.prev_blank = false;
+
+    if self.prev_code {
+      return;
+    }
+
+    if text.starts_with("```") || text.starts_with("~~~") {
+      let fence_char = text.as_bytes()[0] as char;
+
+      self.fence = Some(text.chars().take_while(|c| *c == fence_char).collect());
+    }
+
+    let is_list_item = text
+      .trim_start_matches(|c: char| c.is_ascii_digit())
+      .strip_prefix(['-', '*', '+', '.', ')'])
+      .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t']));
+
+    self.in_list = is_list_item || (self.in_list && !indent.is_empty());
+  }
+}
+
+/// Tracks the lines that are left unchanged because they begin inside a string literal or a
+/// Markdown code block
+#[derive(Default)]
+struct ProtectedLines {
+  protect_strings: bool,
+  markdown: bool,
+  lexer: StringLexer,
+  blocks: MarkdownBlocks,
+  directives: Directives,
+  line: String,
+}
+
+impl ProtectedLines {
+  fn new(options: &BolOptions) -> Self {
+    ProtectedLines {
+      protect_strings: options.protect_strings,
+      markdown: options.markdown,
+      directives: Directives::new(options.directives),
+      ..Default::default()
+    }
+  }
+
+  /// Check if the current line, with the beginning `s`, is left unchanged
+  fn is_protected(&self, s: &str) -> bool {
+    self.directives.is_disabled()
+      || (self.protect_strings && self.lexer.in_string())
+      || (self.markdown && self.blocks.is_code(s))
+  }
+
+  /// Track a character, passing the line on when it ends
+  fn push(&mut self, c: char) {
+    if c == '\n' {
+      self.directives.end_line();
+    } else if c != '\r' {
+      self.directives.push(c);
+    }
+
+    if !self.protect_strings && !self.markdown {
+      return;
+    }
+
+    if c == '\n' {
+      let line = self.line.trim_end_matches('\r');
+
+      self.lexer.push_line(line);
+      self.blocks.push_line(line);
+      self.line.clear();
+    } else {
+      self.line.push(c);
+    }
+  }
+
+  /// Get the length in bytes of the part of the current line buffered so far
+  fn buffered_len(&self) -> usize {
+    self.line.len()
+  }
+}
+
+/// Check if `c` is a space character other than a space or tab that can hide in a line beginning:
+/// a non-breaking space, one of the fixed width spaces such as U+2003 EM SPACE, an ideographic
+/// space or a zero-width space.  The byte order mark U+FEFF isn't included.
+pub fn is_unusual_space(c: char) -> bool {
+  matches!(
+    c,
+    '\u{a0}' | '\u{1680}' | '\u{2000}'
+      ..='\u{200b}' | '\u{202f}' | '\u{205f}' | '\u{2060}' | '\u{3000}'
+  )
+}
+
+/// Check if `c` is an unusual space that takes up no columns
+fn is_zero_width_space(c: char) -> bool {
+  c == '\u{200b}' || c == '\u{2060}'
+}
+
+/// Get the number of columns taken up by a line beginning of spaces and tabs
+pub(crate) fn indent_width(s: &str, tab_size: usize) -> usize {
+  let tab_size = max(1, tab_size);
+
+  s.chars().fold(0, |width, c| {
+    if c == '\t' {
+      width + tab_size - (width % tab_size)
+    } else if is_zero_width_space(c) {
+      width
+    } else {
+      width + 1
+    }
+  })
+}
+
+/// Get the line beginning `s` of spaces and tabs converted to `new_bol`
+pub(crate) fn convert_bol(s: &str, new_bol: BeginningOfLine) -> String {
+  let tab_size = max(1, new_bol.tab_size());
+  let width = indent_width(s, tab_size);
+  let (num_tabs, num_spaces) = match new_bol {
+    BeginningOfLine::Tabs(_, round_down) => (
+      width / tab_size,
+      if round_down { 0 } else { width % tab_size },
+    ),
+    BeginningOfLine::Spaces(_) => (0, width),
+  };
+
+  "\t".repeat(num_tabs) + &" ".repeat(num_spaces)
+}
+
+/// Check if a line beginning is tabs followed by spaces
+fn is_smart_tab_bol(s: &str) -> bool {
+  let spaces = s.trim_start_matches('\t');
+
+  spaces.len() < s.len() && !spaces.is_empty() && !spaces.contains('\t')
 }
 
 /// Read beginning of line information
 pub fn read_bol_info(reader: &mut dyn Read) -> Result<BolInfo, Box<dyn Error>> {
-  let mut bol_info = BolInfo {
-    none: 0,
-    spaces: 0,
-    tabs: 0,
-    mixed: 0,
-  };
-  let mut decoder = UnsafeDecoder::new(reader.bytes()).peekable();
+  read_bol_info_generic(reader, &BolOptions::default())
+}
+
+/// Read beginning of line information, decoding the input as `options` says.  To find the lines
+/// that don't begin with spaces, set [`BolOptions::line_numbers`]:
+///
+/// ```
+/// use std::error::Error;
+/// use whitespace_rs::spacer::{self, BolOptions};
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///   let options = BolOptions::default().line_numbers(true);
+///   let bol_info = spacer::read_bol_info_with_options(&mut "a\n \tb\n\tc\n".as_bytes(), &options)?;
+///
+///   for line in &bol_info.mixed_lines {
+///     println!("file:{}: mixed tabs and spaces", line);
+///   }
+///
+///   assert_eq!(bol_info.mixed_lines, vec![2]);
+///   assert_eq!(bol_info.tab_lines, vec![3]);
+///   Ok(())
+/// }
+/// ```
+pub fn read_bol_info_with_options(
+  reader: &mut dyn Read,
+  options: &BolOptions,
+) -> Result<BolInfo, Box<dyn Error>> {
+  read_bol_info_generic(reader, options)
+}
+
+/// Generic version of [`read_bol_info_with_options()`] that can be inlined for a concrete reader
+/// type
+pub fn read_bol_info_generic<R: Read + ?Sized>(
+  reader: &mut R,
+  options: &BolOptions,
+) -> Result<BolInfo, Box<dyn Error>> {
+  let mut bol_info = BolInfo::default();
+  let decoder = Decoder::new(BufReader::new(reader).bytes(), options.decode_policy);
   let mut at_bol = true;
+  let mut in_word = false;
   let (mut num_spaces, mut num_tabs) = (0, 0);
+  let mut is_smart_tab = true;
+  let mut is_unusual = false;
+  let mut directives = Directives::new(options.directives);
+  let mut line = 1;
 
-  loop {
-    let c;
-    match decoder.next() {
-      Some(value) => c = value?,
-      None => break,
-    };
+  for value in decoder {
+    let c = value?;
+
+    bol_info.num_chars += 1;
+    bol_info.num_bytes += options.decode_policy.byte_len(c);
+
+    if c.is_whitespace() {
+      in_word = false;
+    } else if !in_word {
+      bol_info.num_words += 1;
+      in_word = true;
+    }
 
     if at_bol {
       if c == ' ' {
         num_spaces += 1;
       } else if c == '\t' {
         num_tabs += 1;
+        is_smart_tab = is_smart_tab && num_spaces == 0;
+      } else if is_unusual_space(c) {
+        is_unusual = true;
       } else {
-        if num_spaces == 0 && num_tabs == 0 {
+        if is_unusual && !directives.is_disabled() {
+          bol_info.unusual += 1;
+        }
+        is_unusual = false;
+
+        if directives.is_disabled() {
+          // Lines turned off with a directive aren't counted
+        } else if num_spaces == 0 && num_tabs == 0 {
           bol_info.none += 1;
         } else if num_spaces > 0 && num_tabs > 0 {
           bol_info.mixed += 1;
+          if is_smart_tab {
+            bol_info.smart_tabs += 1;
+          }
+          if options.line_numbers {
+            bol_info.mixed_lines.push(line);
+          }
         } else if num_spaces > 0 {
           bol_info.spaces += 1;
         } else {
           bol_info.tabs += 1;
+          if options.line_numbers {
+            bol_info.tab_lines.push(line);
+          }
+        }
+
+        if c == '\n' {
+          num_spaces = 0;
+          num_tabs = 0;
+          is_smart_tab = true;
+          line += 1;
+        } else {
+          at_bol = false;
         }
-        at_bol = false;
       }
     } else if c == '\n' {
       num_spaces = 0;
       num_tabs = 0;
+      is_smart_tab = true;
+      line += 1;
       at_bol = true;
     }
+
+    if c == '\n' {
+      directives.end_line();
+    } else if c != '\r' {
+      directives.push(c);
+    }
+  }
+
+  if is_unusual && !directives.is_disabled() {
+    bol_info.unusual += 1;
   }
 
   Ok(bol_info)
@@ -126,146 +782,800 @@ pub fn write_new_bols(
   reader: &mut dyn Read,
   writer: &mut dyn Write,
   new_bol: BeginningOfLine,
+) -> Result<BolInfo, Box<dyn Error>> {
+  write_new_bols_with_options(reader, writer, new_bol, &BolOptions::default())
+}
+
+/// Write input file out with new beginning-of-lines, using `options` to control which lines are
+/// changed
+pub fn write_new_bols_with_options(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  new_bol: BeginningOfLine,
+  options: &BolOptions,
+) -> Result<BolInfo, Box<dyn Error>> {
+  write_new_bols_generic(reader, writer, new_bol, options)
+}
+
+/// Generic version of [`write_new_bols_with_options()`] that can be inlined for concrete reader
+/// and writer types
+pub fn write_new_bols_generic<R: Read + ?Sized, W: Write + ?Sized>(
+  reader: &mut R,
+  writer: &mut W,
+  new_bol: BeginningOfLine,
+  options: &BolOptions,
 ) -> Result<BolInfo, Box<dyn Error>> {
   let (tab_size, round_down) = match new_bol {
     BeginningOfLine::Spaces(tab_size) => (max(1, tab_size), false),
     BeginningOfLine::Tabs(tab_size, round_down) => (max(1, tab_size), round_down),
   };
-  let mut bol_info = BolInfo {
-    none: 0,
-    spaces: 0,
-    tabs: 0,
-    mixed: 0,
-  };
-  let mut decoder = UnsafeDecoder::new(reader.bytes()).peekable();
-  let mut buf = [0u8; 4];
+  let mut bol_info = BolInfo::default();
+  let decoder = Decoder::new(BufReader::new(reader).bytes(), options.decode_policy);
   let mut s = String::new();
+  let mut new_s = String::new();
   let mut at_bol = true;
-  let untabify = |s: &str| -> String {
-    let mut t = String::new();
-
-    for c in s.chars() {
-      if c == '\t' {
-        t.push_str(&" ".repeat(tab_size - (t.len() % tab_size)));
-      } else {
-        t.push(c);
-      }
+  let mut in_word = false;
+  let mut continuations = Continuations::default();
+  let mut protected = ProtectedLines::new(options);
+  // Only rounding down changes the width of line beginnings
+  let width_change = |s: &str| -> isize {
+    match new_bol {
+      BeginningOfLine::Tabs(_, true) => -((indent_width(s, tab_size) % tab_size) as isize),
+      _ => 0,
     }
-
-    t
   };
-  let tabify = |s: &str| -> (_, _) {
-    let mut num_spaces = 0;
-    let mut t = String::new();
+  // Write the new beginning for the line beginning `s`, building it in `new_s` so that it doesn't
+  // need a new allocation for every line
+  let write_bol = |s: &str,
+                   new_s: &mut String,
+                   bol_info: &mut BolInfo,
+                   writer: &mut W|
+   -> Result<(), Box<dyn Error>> {
+    let width = indent_width(s, tab_size);
 
-    for c in s.chars() {
-      if c == ' ' {
-        num_spaces += 1;
-      }
+    new_s.clear();
 
-      if num_spaces % tab_size == 0 {
-        t.push('\t');
-        num_spaces = 0
-      }
-    }
+    if let BeginningOfLine::Tabs(_, _) = new_bol {
+      let num_spaces = if round_down { 0 } else { width % tab_size };
 
-    if num_spaces > 0 {
-      if !round_down {
-        t.push_str(&" ".repeat(num_spaces));
+      new_s.extend(std::iter::repeat_n('\t', width / tab_size));
+      new_s.extend(std::iter::repeat_n(' ', num_spaces));
+
+      if num_spaces > 0 {
+        bol_info.mixed += 1;
+        if width >= tab_size {
+          bol_info.smart_tabs += 1;
+        }
       } else {
-        num_spaces = 0;
+        bol_info.tabs += 1;
       }
+    } else {
+      new_s.extend(std::iter::repeat_n(' ', width));
+      bol_info.spaces += 1;
     }
 
-    (t, num_spaces)
+    writer.write_all(new_s.as_bytes())?;
+    bol_info.num_chars += new_s.len();
+    bol_info.num_bytes += new_s.len();
+    Ok(())
   };
 
-  loop {
-    let c;
+  let write_unchanged_bol =
+    |s: &str, bol_info: &mut BolInfo, writer: &mut W| -> Result<(), Box<dyn Error>> {
+      if !s.contains('\t') {
+        bol_info.spaces += 1;
+      } else if !s.contains(' ') {
+        bol_info.tabs += 1;
+      } else {
+        bol_info.mixed += 1;
+        if is_smart_tab_bol(s) {
+          bol_info.smart_tabs += 1;
+        }
+      }
 
-    match decoder.next() {
-      Some(value) => c = value?,
-      None => break,
+      writer.write_all(s.as_bytes())?;
+      bol_info.num_chars += s.chars().count();
+      bol_info.num_bytes += s.len();
+      Ok(())
     };
+
+  for value in decoder {
+    let c = value?;
+
+    if c.is_whitespace() {
+      in_word = false;
+    } else if !in_word {
+      bol_info.num_words += 1;
+      in_word = true;
+    }
+
     if at_bol {
-      if c == ' ' || c == '\t' {
+      if options.is_bol_char(c) {
         s.push(c);
       } else {
-        if s.len() == 0 {
-          bol_info.none += 1
+        let aligned_bol = if options.align_continuations && c != '\n' && c != '\r' {
+          continuations.aligned_bol(&s, tab_size)
         } else {
-          s = untabify(&s);
-
-          if let BeginningOfLine::Tabs(_, _) = new_bol {
-            let (t, num_spaces) = tabify(&s);
-
-            s = t;
-            if num_spaces > 0 {
-              bol_info.mixed += 1;
-            } else {
-              bol_info.tabs += 1;
-            }
-          } else {
-            bol_info.spaces += 1;
-          }
+          None
+        };
+        let bol: &str = if s.is_empty() {
+          bol_info.none += 1;
+          ""
+        } else if protected.is_protected(&s) {
+          write_unchanged_bol(&s, &mut bol_info, writer)?;
+          &s
+        } else if options.preserve_blank_line_ws && (c == '\n' || c == '\r') {
+          writer.write_all(s.as_bytes())?;
+          bol_info.num_chars += s.chars().count();
+          bol_info.num_bytes += s.len();
+          &s
+        } else if !options.is_selected(&s, tab_size) {
+          write_unchanged_bol(&s, &mut bol_info, writer)?;
+          &s
+        } else if let Some(aligned_bol) = &aligned_bol {
+          write_unchanged_bol(aligned_bol, &mut bol_info, writer)?;
+          aligned_bol
+        } else if options.safe_only && c != '\n' && c != '\r' && width_change(&s) != 0 {
+          write_unchanged_bol(&s, &mut bol_info, writer)?;
+          &s
+        } else {
+          write_bol(&s, &mut new_s, &mut bol_info, writer)?;
+          &new_s
+        };
 
-          writer.write(s.as_bytes())?;
-        }
+        decode::write_char(writer, c, options.decode_policy)?;
+        bol_info.num_chars += 1;
+        bol_info.num_bytes += options.decode_policy.byte_len(c);
 
-        writer.write(c.encode_utf8(&mut buf).as_bytes())?;
+        s.chars().for_each(|c| protected.push(c));
+        protected.push(c);
 
         if c == '\n' {
           s.clear();
         } else {
           at_bol = false;
+
+          if options.align_continuations {
+            continuations.start_line(&s, bol, tab_size);
+            continuations.push(c, tab_size);
+          }
         }
       }
     } else {
-      writer.write(c.encode_utf8(&mut buf).as_bytes())?;
+      decode::write_char(writer, c, options.decode_policy)?;
+      bol_info.num_chars += 1;
+      bol_info.num_bytes += options.decode_policy.byte_len(c);
+
+      protected.push(c);
 
       if c == '\n' {
         s.clear();
         at_bol = true;
+      } else if options.align_continuations {
+        continuations.push(c, tab_size);
       }
     }
+
+    options.check_line_length(max(s.len(), protected.buffered_len()))?;
+  }
+
+  if at_bol && !s.is_empty() {
+    if options.preserve_blank_line_ws || protected.is_protected(&s) {
+      writer.write_all(s.as_bytes())?;
+      bol_info.num_chars += s.chars().count();
+      bol_info.num_bytes += s.len();
+    } else if !options.is_selected(&s, tab_size) {
+      write_unchanged_bol(&s, &mut bol_info, writer)?;
+    } else {
+      write_bol(&s, &mut new_s, &mut bol_info, writer)?;
+    }
   }
+
   writer.flush()?;
 
   Ok(bol_info)
 }
 
-#[cfg(test)]
-mod tests {
-  use super::*;
+/// Get `s` with the beginning of every line changed to `new_bol`.
+pub fn normalize_str(s: &str, new_bol: BeginningOfLine) -> String {
+  let mut output = Vec::with_capacity(s.len());
 
-  #[test]
-  fn test_read_bol_info() {
-    let bol_info = read_bol_info(&mut "a\n\tb\n  c\n \td\n".as_bytes()).unwrap();
+  // A string is always valid UTF-8, writing to a vector can't fail and the default options have no
+  // line length limit
+  write_new_bols_generic(
+    &mut s.as_bytes(),
+    &mut output,
+    new_bol,
+    &BolOptions::default(),
+  )
+  .unwrap();
+  String::from_utf8(output).unwrap()
+}
 
-    assert_eq!(
-      bol_info,
-      BolInfo {
-        none: 1,
-        spaces: 1,
-        tabs: 1,
-        mixed: 1,
-      }
-    );
-  }
+/// Check if [`write_new_bols_with_options()`] would change the input file, without writing
+/// anything.
+///
+/// The input file is read into memory so that the converted output can be compared with it.
+pub fn needs_new_bols(
+  reader: &mut dyn Read,
+  new_bol: BeginningOfLine,
+  options: &BolOptions,
+) -> Result<bool, Box<dyn Error>> {
+  let mut input = Vec::new();
 
-  #[test]
-  fn test_write_new_file_tabs_round_down() {
-    let mut input = "\na\n  b\n     c\n".as_bytes();
-    let mut output = Vec::new();
-    let bol_info = write_new_bols(&mut input, &mut output, BeginningOfLine::Tabs(2, true)).unwrap();
+  reader.read_to_end(&mut input)?;
 
-    assert_eq!(
+  let mut writer = CompareWriter {
+    remaining: &input,
+    differs: false,
+  };
+
+  write_new_bols_generic(&mut input.as_slice(), &mut writer, new_bol, options)?;
+
+  Ok(writer.differs || !writer.remaining.is_empty())
+}
+
+/// A [`Write`] that checks that what is written matches some bytes instead of keeping it
+struct CompareWriter<'a> {
+  /// The bytes that haven't been matched yet
+  remaining: &'a [u8],
+  /// Something written didn't match
+  differs: bool,
+}
+
+impl Write for CompareWriter<'_> {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    if !self.differs {
+      match self.remaining.strip_prefix(buf) {
+        Some(rest) => self.remaining = rest,
+        None => self.differs = true,
+      }
+    }
+
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+/// Verify converted output against the original input.
+///
+/// Returns `true` if the sequence of non-indentation characters is identical in both and the
+/// beginning of every line in the converted output follows `new_bol` and has the same width as the
+/// original, at the tab size of `new_bol`.
+pub fn verify_new_bols(
+  original: &mut dyn Read,
+  converted: &mut dyn Read,
+  new_bol: BeginningOfLine,
+) -> Result<bool, Box<dyn Error>> {
+  verify_new_bols_with_options(original, converted, new_bol, &BolOptions::default())
+}
+
+/// Verify output converted with [`write_new_bols_with_options()`] against the original input.
+pub fn verify_new_bols_with_options(
+  original: &mut dyn Read,
+  converted: &mut dyn Read,
+  new_bol: BeginningOfLine,
+  options: &BolOptions,
+) -> Result<bool, Box<dyn Error>> {
+  verify_new_bols_generic(original, converted, new_bol, options)
+}
+
+/// Generic version of [`verify_new_bols_with_options()`] that can be inlined for concrete reader
+/// types
+pub fn verify_new_bols_generic<R1: Read + ?Sized, R2: Read + ?Sized>(
+  original: &mut R1,
+  converted: &mut R2,
+  new_bol: BeginningOfLine,
+  options: &BolOptions,
+) -> Result<bool, Box<dyn Error>> {
+  let tab_size = match new_bol {
+    BeginningOfLine::Spaces(tab_size) | BeginningOfLine::Tabs(tab_size, _) => tab_size,
+  };
+  let is_valid_bol = |s: &str| match new_bol {
+    BeginningOfLine::Spaces(_) => !s.contains('\t'),
+    BeginningOfLine::Tabs(_, _)
+      if options.align_continuations && !s.trim_start_matches('\t').contains('\t') =>
+    {
+      true
+    }
+    BeginningOfLine::Tabs(tab_size, true) if options.safe_only => {
+      !s.contains(' ') || !indent_width(s, tab_size).is_multiple_of(max(1, tab_size))
+    }
+    BeginningOfLine::Tabs(_, true) => !s.contains(' '),
+    BeginningOfLine::Tabs(tab_size, false) => {
+      let spaces = s.trim_start_matches('\t');
+
+      !spaces.contains('\t') && spaces.len() < max(1, tab_size)
+    }
+  } && !s.chars().any(is_unusual_space);
+  // Only rounding down and aligning continuations change the width of line beginnings
+  let is_same_width = |original_bol: &str, s: &str| {
+    let width = indent_width(original_bol, tab_size);
+    let new_width = indent_width(s, tab_size);
+
+    new_width == width
+      || (matches!(new_bol, BeginningOfLine::Tabs(_, true))
+        && new_width == width - width % max(1, tab_size))
+      || options.align_continuations
+  };
+  let mut original_chars = Decoder::new(BufReader::new(original).bytes(), options.decode_policy);
+  let mut original_at_bol = true;
+  // Get the next character of the original that isn't a line beginning character, with the line
+  // beginning before it in `original_bol`
+  let mut next_original = |original_bol: &mut String| -> Result<Option<char>, Box<dyn Error>> {
+    original_bol.clear();
+
+    while let Some(c) = original_chars.next().transpose()? {
+      if !options.is_bol_char(c) {
+        original_at_bol = c == '\n';
+        return Ok(Some(c));
+      } else if original_at_bol {
+        original_bol.push(c);
+      }
+    }
+
+    Ok(None)
+  };
+  let mut original_bol = String::new();
+  let decoder = Decoder::new(BufReader::new(converted).bytes(), options.decode_policy);
+  let mut s = String::new();
+  let mut at_bol = true;
+  let mut protected = ProtectedLines::new(options);
+
+  for value in decoder {
+    let c = value?;
+    let is_protected = protected.is_protected(&s);
+
+    protected.push(c);
+    options.check_line_length(max(s.len(), protected.buffered_len()))?;
+
+    if options.is_bol_char(c) {
+      if at_bol {
+        s.push(c);
+      }
+      continue;
+    }
+
+    if next_original(&mut original_bol)? != Some(c) {
+      return Ok(false);
+    }
+
+    if at_bol {
+      let is_preserved = (options.preserve_blank_line_ws && (c == '\n' || c == '\r'))
+        || is_protected
+        || !options.is_selected(&s, tab_size);
+
+      if (!is_preserved && !is_valid_bol(&s)) || !is_same_width(&original_bol, &s) {
+        return Ok(false);
+      }
+
+      s.clear();
+      at_bol = c == '\n';
+    } else if c == '\n' {
+      at_bol = true;
+    }
+  }
+
+  Ok(
+    (options.preserve_blank_line_ws
+      || protected.is_protected(&s)
+      || !options.is_selected(&s, tab_size)
+      || is_valid_bol(&s))
+      && next_original(&mut original_bol)?.is_none()
+      && (!at_bol || is_same_width(&original_bol, &s)),
+  )
+}
+
+/// Find the lines that converting to `new_bol` with [`write_new_bols_with_options()`] would move
+/// relative to the line before them, at the tab size of `new_bol`.  Blank lines are ignored.
+///
+/// Returns the line numbers, starting from 1.  Converting only changes the width of a line
+/// beginning when alignment spaces are dropped by rounding down to tabs.
+pub fn alignment_changes(
+  reader: &mut dyn Read,
+  new_bol: BeginningOfLine,
+  options: &BolOptions,
+) -> Result<Vec<usize>, Box<dyn Error>> {
+  let tab_size = match new_bol {
+    BeginningOfLine::Spaces(tab_size) | BeginningOfLine::Tabs(tab_size, _) => tab_size,
+  };
+  let input = Decoder::new(BufReader::new(reader).bytes(), options.decode_policy)
+    .collect::<Result<String, _>>()?;
+  let mut output = Vec::new();
+
+  // The input is decoded already, so characters that stand for passed through bytes are written
+  // as they are rather than as the bytes
+  write_new_bols_generic(
+    &mut input.as_bytes(),
+    &mut output,
+    new_bol,
+    &BolOptions {
+      safe_only: false,
+      decode_policy: DecodePolicy::Strict,
+      ..options.clone()
+    },
+  )?;
+
+  let output = String::from_utf8(output)?;
+  let width = |line: &str| {
+    indent_width(
+      &line[..line.len() - line.trim_start_matches([' ', '\t']).len()],
+      tab_size,
+    ) as isize
+  };
+  let mut prev_change = 0;
+  let mut lines = Vec::new();
+
+  for (i, (old_line, new_line)) in input.split('\n').zip(output.split('\n')).enumerate() {
+    if old_line.trim().is_empty() {
+      continue;
+    }
+
+    let change = width(new_line) - width(old_line);
+
+    if change != prev_change {
+      lines.push(i + 1);
+    }
+
+    prev_change = change;
+  }
+
+  Ok(lines)
+}
+
+/// Tab sizes that [`infer_tab_size()`] chooses between
+const TAB_SIZES: [usize; 4] = [2, 3, 4, 8];
+
+/// Get the columns that the line after `line` could be aligned with: the indentation of `line`,
+/// and the column after each delimiter left open on it and of the word that follows it.
+fn alignment_columns(line: &str, tab_size: usize) -> Vec<usize> {
+  let text = line.trim_start_matches([' ', '\t']);
+  let mut column = indent_width(&line[..line.len() - text.len()], tab_size);
+  let mut columns = vec![column];
+  let mut openings: Vec<Vec<usize>> = Vec::new();
+  let mut after_opening = false;
+
+  for c in text.chars() {
+    match c {
+      '(' | '[' | '{' => {
+        openings.push(vec![column + 1]);
+        after_opening = true;
+      }
+      ')' | ']' | '}' => {
+        openings.pop();
+        after_opening = false;
+      }
+      ' ' | '\t' => (),
+      _ if after_opening => {
+        if let Some(opening) = openings.last_mut() {
+          opening.push(column);
+        }
+
+        after_opening = false;
+      }
+      _ => (),
+    }
+
+    column += if c == '\t' {
+      tab_size - (column % tab_size)
+    } else {
+      1
+    };
+  }
+
+  columns.extend(openings.into_iter().flatten());
+  columns
+}
+
+/// Infer the tab size that the author of a file indented with both tabs and spaces assumed.
+///
+/// Each indented line counts for the tab sizes that put it at the same column as the line before
+/// it, or aligned with an opening delimiter on that line or the word after it.  Returns the tab
+/// size that the most lines count for, or `None` if no single tab size is best.
+pub fn infer_tab_size(reader: &mut dyn Read) -> Result<Option<usize>, Box<dyn Error>> {
+  let mut scores = [0; TAB_SIZES.len()];
+  let mut prev_line: Option<String> = None;
+
+  for line in BufReader::new(reader).lines() {
+    let line = line?;
+    let text = line.trim_start_matches([' ', '\t']);
+
+    if text.trim().is_empty() {
+      continue;
+    }
+
+    if let (Some(prev_line), false) = (&prev_line, text.len() == line.len()) {
+      let indent = &line[..line.len() - text.len()];
+
+      for (score, &tab_size) in scores.iter_mut().zip(TAB_SIZES.iter()) {
+        if alignment_columns(prev_line, tab_size).contains(&indent_width(indent, tab_size)) {
+          *score += 1;
+        }
+      }
+    }
+
+    prev_line = Some(line);
+  }
+
+  let best = scores.iter().copied().max().unwrap_or(0);
+
+  Ok(
+    if scores.iter().filter(|&&score| score == best).count() == 1 {
+      scores
+        .iter()
+        .position(|&score| score == best)
+        .map(|i| TAB_SIZES[i])
+    } else {
+      None
+    },
+  )
+}
+
+/// How well a tab size fits the indentation of a file, from [`tab_size_fits()`]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TabSizeFit {
+  /// Number of columns in a tab
+  pub tab_size: usize,
+  /// Number of lines indented further than the line before them that are neither one tab further
+  /// in nor aligned with an opening delimiter on that line or the word after it
+  pub misaligned_lines: usize,
+  /// Number of indented lines at a column that is neither a multiple of the tab size nor aligned
+  /// with the line before them
+  pub odd_columns: usize,
+}
+
+impl TabSizeFit {
+  /// Get the number of lines that the tab size doesn't suit
+  pub fn num_breaks(&self) -> usize {
+    self.misaligned_lines + self.odd_columns
+  }
+}
+
+/// Expand the line beginnings of a file at each of `tab_sizes` and count the lines that each one
+/// leaves misaligned with the line before them or at odd columns, to help choose the tab size to
+/// convert a file with.  Blank lines are ignored.
+pub fn tab_size_fits(
+  reader: &mut dyn Read,
+  tab_sizes: &[usize],
+) -> Result<Vec<TabSizeFit>, Box<dyn Error>> {
+  let mut fits: Vec<TabSizeFit> = tab_sizes
+    .iter()
+    .map(|&tab_size| TabSizeFit {
+      tab_size: max(1, tab_size),
+      ..Default::default()
+    })
+    .collect();
+  let mut prev_line: Option<String> = None;
+
+  for line in BufReader::new(reader).lines() {
+    let line = line?;
+    let text = line.trim_start_matches([' ', '\t']);
+
+    if text.trim().is_empty() {
+      continue;
+    }
+
+    if text.len() < line.len() {
+      let indent = &line[..line.len() - text.len()];
+
+      for fit in fits.iter_mut() {
+        let width = indent_width(indent, fit.tab_size);
+        let columns = prev_line
+          .as_deref()
+          .map(|prev_line| alignment_columns(prev_line, fit.tab_size))
+          .unwrap_or_default();
+        let is_aligned = columns.contains(&width);
+
+        if columns.first().is_some_and(|&prev_width| {
+          width > prev_width && width != prev_width + fit.tab_size && !is_aligned
+        }) {
+          fit.misaligned_lines += 1;
+        }
+
+        if !width.is_multiple_of(fit.tab_size) && !is_aligned {
+          fit.odd_columns += 1;
+        }
+      }
+    }
+
+    prev_line = Some(line);
+  }
+
+  Ok(fits)
+}
+
+/// Choose the tab size from [`tab_size_fits()`] that the fewest lines don't suit, or `None` if no
+/// single tab size is best
+pub fn best_tab_size(fits: &[TabSizeFit]) -> Option<usize> {
+  let fewest = fits.iter().map(TabSizeFit::num_breaks).min()?;
+  let mut best = fits.iter().filter(|fit| fit.num_breaks() == fewest);
+
+  match (best.next(), best.next()) {
+    (Some(fit), None) => Some(fit.tab_size),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_beginning_of_line() {
+    assert_eq!(
+      "Tabs".parse::<BeginningOfLine>().unwrap(),
+      BeginningOfLine::Tabs(4, false)
+    );
+    assert_eq!(
+      "spaces".parse::<BeginningOfLine>().unwrap(),
+      BeginningOfLine::Spaces(4)
+    );
+    assert!("mixed".parse::<BeginningOfLine>().is_err());
+    assert_eq!(BeginningOfLine::Tabs(2, true).to_string(), "tabs");
+  }
+
+  #[test]
+  fn test_read_bol_info() {
+    let bol_info = read_bol_info(&mut "a\n\tb\n  c\n \td\n".as_bytes()).unwrap();
+
+    assert_eq!(
+      bol_info,
+      BolInfo {
+        none: 1,
+        spaces: 1,
+        tabs: 1,
+        mixed: 1,
+        smart_tabs: 0,
+        unusual: 0,
+        mixed_lines: vec![],
+        tab_lines: vec![],
+        num_chars: 13,
+        num_words: 4,
+        num_bytes: 13,
+      }
+    );
+  }
+
+  #[test]
+  fn test_beginning_of_line_keys() {
+    let bols: std::collections::HashSet<BeginningOfLine> = [
+      BeginningOfLine::Spaces(4),
+      BeginningOfLine::Tabs(4, false),
+      BeginningOfLine::Spaces(4),
+    ]
+    .iter()
+    .copied()
+    .collect();
+
+    assert_eq!(bols.len(), 2);
+    assert!(BeginningOfLine::Tabs(8, true) < BeginningOfLine::Spaces(2));
+    assert!(BeginningOfLine::Spaces(2) < BeginningOfLine::Spaces(4));
+  }
+
+  #[test]
+  fn test_bol_options_setters() {
+    assert_eq!(
+      BolOptions::default().indent_above(Some(2)).safe_only(true),
+      BolOptions {
+        indent_above: Some(2),
+        safe_only: true,
+        ..Default::default()
+      }
+    );
+    assert_eq!(
+      BolInfo::default(),
+      read_bol_info(&mut "".as_bytes()).unwrap()
+    );
+  }
+
+  #[test]
+  fn test_read_bol_info_smart_tabs() {
+    let bol_info = read_bol_info(&mut "\ta\n\t  b\n\n  \tc\n\t\td\n".as_bytes()).unwrap();
+
+    assert_eq!(
+      (bol_info.tabs, bol_info.mixed, bol_info.smart_tabs),
+      (2, 2, 1)
+    );
+    assert!(!bol_info.is_smart_tabs());
+
+    let bol_info = read_bol_info(&mut "\ta\n\t  b\n\n\t\td\n".as_bytes()).unwrap();
+
+    assert_eq!(bol_info.none, 1);
+    assert!(bol_info.is_smart_tabs());
+  }
+
+  #[test]
+  fn test_read_bol_info_line_numbers() {
+    let input = "\ta\n  b\n\t  c\n\n \td\n\te";
+    let options = BolOptions::default().line_numbers(true);
+    let bol_info = read_bol_info_with_options(&mut input.as_bytes(), &options).unwrap();
+
+    assert_eq!(bol_info.mixed_lines, vec![3, 5]);
+    assert_eq!(bol_info.tab_lines, vec![1, 6]);
+    assert!(read_bol_info(&mut input.as_bytes())
+      .unwrap()
+      .tab_lines
+      .is_empty());
+  }
+
+  #[test]
+  fn test_unusual_spaces_counts() {
+    let input = "\u{2003}\u{a0}x\n\u{a0} \n\u{a0}";
+
+    for options in [
+      BolOptions::default().indent_above(Some(8)),
+      BolOptions::default().preserve_blank_line_ws(true),
+      BolOptions::default(),
+    ] {
+      let mut output = Vec::new();
+      let bol_info = write_new_bols_with_options(
+        &mut input.as_bytes(),
+        &mut output,
+        BeginningOfLine::Spaces(2),
+        &options.replace_unusual_spaces(true),
+      )
+      .unwrap();
+      let output = String::from_utf8(output).unwrap();
+
+      assert_eq!(bol_info.num_chars, output.chars().count(), "{:?}", output);
+      assert_eq!(bol_info.num_bytes, output.len(), "{:?}", output);
+    }
+  }
+
+  #[test]
+  fn test_get_common_bol_with() {
+    let bol_info = read_bol_info(&mut "\ta\n\t  b\n\t  c\n  d\n  e\n".as_bytes()).unwrap();
+
+    assert_eq!(
+      bol_info.get_common_bol(4, false),
+      BeginningOfLine::Spaces(4)
+    );
+    assert_eq!(
+      bol_info.get_common_bol_with(4, true, BolStrategy::Weighted),
+      BeginningOfLine::Tabs(4, true)
+    );
+    assert_eq!(
+      "Weighted".parse::<BolStrategy>().unwrap(),
+      BolStrategy::Weighted
+    );
+    assert!("columns".parse::<BolStrategy>().is_err());
+  }
+
+  #[test]
+  fn test_num_other_beginnings() {
+    let bol_info = read_bol_info(&mut "a\n\tb\n  c\n \td\n\te\n".as_bytes()).unwrap();
+
+    assert_eq!(bol_info.num_other_beginnings(BeginningOfLine::Spaces(4)), 3);
+    assert_eq!(
+      bol_info.num_other_beginnings(BeginningOfLine::Tabs(4, false)),
+      2
+    );
+  }
+
+  #[test]
+  fn test_write_new_file_tabs_round_down() {
+    let mut input = "\na\n  b\n     c\n".as_bytes();
+    let mut output = Vec::new();
+    let bol_info = write_new_bols(&mut input, &mut output, BeginningOfLine::Tabs(2, true)).unwrap();
+
+    assert_eq!(
       bol_info,
       BolInfo {
         none: 2,
         spaces: 0,
         tabs: 2,
-        mixed: 0
+        mixed: 0,
+        smart_tabs: 0,
+        unusual: 0,
+        mixed_lines: vec![],
+        tab_lines: vec![],
+        num_chars: 10,
+        num_words: 3,
+        num_bytes: 10,
       }
     );
     assert_eq!(String::from_utf8(output).unwrap(), "\na\n\tb\n\t\tc\n");
@@ -284,12 +1594,30 @@ mod tests {
         none: 2,
         spaces: 0,
         tabs: 1,
-        mixed: 1
+        mixed: 1,
+        smart_tabs: 1,
+        unusual: 0,
+        mixed_lines: vec![],
+        tab_lines: vec![],
+        num_chars: 11,
+        num_words: 3,
+        num_bytes: 11,
       }
     );
     assert_eq!(String::from_utf8(output).unwrap(), "\na\n\tb\n\t\t c\n");
   }
 
+  #[test]
+  fn test_write_new_file_tabs_narrow_indent() {
+    let mut input = "  a\n \t  b\n".as_bytes();
+    let mut output = Vec::new();
+    let bol_info =
+      write_new_bols(&mut input, &mut output, BeginningOfLine::Tabs(4, false)).unwrap();
+
+    assert_eq!((bol_info.mixed, bol_info.smart_tabs), (2, 1));
+    assert_eq!(String::from_utf8(output).unwrap(), "  a\n\t  b\n");
+  }
+
   #[test]
   fn test_write_new_file_spaces() {
     let mut input = "\ta\n \t x\n\t\t\n".as_bytes();
@@ -302,9 +1630,601 @@ mod tests {
         none: 0,
         spaces: 3,
         tabs: 0,
-        mixed: 0
+        mixed: 0,
+        smart_tabs: 0,
+        unusual: 0,
+        mixed_lines: vec![],
+        tab_lines: vec![],
+        num_chars: 14,
+        num_words: 2,
+        num_bytes: 14,
       }
     );
     assert_eq!(String::from_utf8(output).unwrap(), "  a\n   x\n    \n");
   }
+
+  #[test]
+  fn test_write_new_file_preserve_blank_line_ws() {
+    let input = "\ta\n\t\n  \r\n\t\tb\n \t";
+    let options = BolOptions {
+      preserve_blank_line_ws: true,
+      ..Default::default()
+    };
+    let mut output = Vec::new();
+    let bol_info = write_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut output,
+      BeginningOfLine::Spaces(2),
+      &options,
+    )
+    .unwrap();
+
+    assert_eq!(bol_info.spaces, 2);
+    assert_eq!(
+      String::from_utf8(output.clone()).unwrap(),
+      "  a\n\t\n  \r\n    b\n \t"
+    );
+    assert!(verify_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut output.as_slice(),
+      BeginningOfLine::Spaces(2),
+      &options
+    )
+    .unwrap());
+
+    let mut output = Vec::new();
+
+    write_new_bols(
+      &mut input.as_bytes(),
+      &mut output,
+      BeginningOfLine::Spaces(2),
+    )
+    .unwrap();
+    assert_eq!(
+      String::from_utf8(output).unwrap(),
+      "  a\n  \n  \r\n    b\n  "
+    );
+  }
+
+  #[test]
+  fn test_write_new_file_indent_thresholds() {
+    let input = "a\n  b\n    c\n      d\n";
+    let options = BolOptions {
+      indent_above: Some(2),
+      indent_below: Some(6),
+      ..Default::default()
+    };
+    let mut output = Vec::new();
+    let bol_info = write_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut output,
+      BeginningOfLine::Tabs(2, false),
+      &options,
+    )
+    .unwrap();
+
+    assert_eq!((bol_info.spaces, bol_info.tabs), (2, 1));
+    assert_eq!(
+      String::from_utf8(output.clone()).unwrap(),
+      "a\n  b\n\t\tc\n      d\n"
+    );
+    assert!(verify_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut output.as_slice(),
+      BeginningOfLine::Tabs(2, false),
+      &options
+    )
+    .unwrap());
+  }
+
+  #[test]
+  fn test_write_new_file_align_continuations() {
+    let input = "foo(a,\n    b)\n\tif (bar(x,\n\t        y) &&\n\t    z)\n(defun (x)\n       y)\n";
+    let options = BolOptions {
+      align_continuations: true,
+      ..Default::default()
+    };
+    let mut output = Vec::new();
+
+    write_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut output,
+      BeginningOfLine::Tabs(4, true),
+      &options,
+    )
+    .unwrap();
+    assert_eq!(
+      String::from_utf8(output.clone()).unwrap(),
+      "foo(a,\n    b)\n\tif (bar(x,\n\t        y) &&\n\t    z)\n(defun (x)\n       y)\n"
+    );
+    assert!(verify_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut output.as_slice(),
+      BeginningOfLine::Tabs(4, true),
+      &options
+    )
+    .unwrap());
+
+    let mut output = Vec::new();
+
+    write_new_bols_with_options(
+      &mut "\tfoo(a,\n\t    b)\n\t\tc\n".as_bytes(),
+      &mut output,
+      BeginningOfLine::Spaces(2),
+      &options,
+    )
+    .unwrap();
+    assert_eq!(
+      String::from_utf8(output).unwrap(),
+      "  foo(a,\n      b)\n    c\n"
+    );
+  }
+
+  #[test]
+  fn test_write_new_file_protect_strings() {
+    let input = "\ts = \"\"\"\n    a\n\t\"\"\"\n    b\ncat <<EOF\n    c\nEOF\n";
+    let options = BolOptions {
+      protect_strings: true,
+      ..Default::default()
+    };
+    let mut output = Vec::new();
+
+    write_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut output,
+      BeginningOfLine::Tabs(4, true),
+      &options,
+    )
+    .unwrap();
+    assert_eq!(
+      String::from_utf8(output.clone()).unwrap(),
+      "\ts = \"\"\"\n    a\n\t\"\"\"\n\tb\ncat <<EOF\n    c\nEOF\n"
+    );
+    assert!(verify_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut output.as_slice(),
+      BeginningOfLine::Tabs(4, true),
+      &options
+    )
+    .unwrap());
+    assert!(!verify_new_bols(
+      &mut input.as_bytes(),
+      &mut output.as_slice(),
+      BeginningOfLine::Tabs(4, true)
+    )
+    .unwrap());
+  }
+
+  #[test]
+  fn test_write_new_file_markdown() {
+    let input = "# Title\n\n    code\n\n    more code\n\n- item\n\n    list text\n\n```\n    fenced\n```\n  text\n";
+    let options = BolOptions {
+      markdown: true,
+      ..Default::default()
+    };
+    let mut output = Vec::new();
+
+    write_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut output,
+      BeginningOfLine::Tabs(2, true),
+      &options,
+    )
+    .unwrap();
+    assert_eq!(
+      String::from_utf8(output.clone()).unwrap(),
+      "# Title\n\n    code\n\n    more code\n\n- item\n\n\t\tlist text\n\n```\n    fenced\n```\n\ttext\n"
+    );
+    assert!(verify_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut output.as_slice(),
+      BeginningOfLine::Tabs(2, true),
+      &options
+    )
+    .unwrap());
+  }
+
+  #[test]
+  fn test_indent_width() {
+    assert_eq!(indent_width(" \t  ", 4), 6);
+    assert_eq!(indent_width("\t\t", 2), 4);
+    assert_eq!(indent_width("", 4), 0);
+  }
+
+  #[test]
+  fn test_alignment_changes() {
+    let input = "f(a,\n  b)\n\n\tc\n  d\n";
+    let changes = |new_bol, safe_only| {
+      alignment_changes(
+        &mut input.as_bytes(),
+        new_bol,
+        &BolOptions {
+          safe_only,
+          ..Default::default()
+        },
+      )
+      .unwrap()
+    };
+
+    assert_eq!(
+      changes(BeginningOfLine::Tabs(4, true), false),
+      vec![2, 4, 5]
+    );
+    assert_eq!(changes(BeginningOfLine::Tabs(4, true), true), vec![2, 4, 5]);
+    assert_eq!(
+      changes(BeginningOfLine::Tabs(4, false), false),
+      Vec::<usize>::new()
+    );
+    assert_eq!(
+      changes(BeginningOfLine::Spaces(4), false),
+      Vec::<usize>::new()
+    );
+
+    let input = b"f(\xff,\n  b)\n";
+
+    assert!(alignment_changes(
+      &mut &input[..],
+      BeginningOfLine::Tabs(4, true),
+      &BolOptions::default()
+    )
+    .is_err());
+
+    for decode_policy in [DecodePolicy::Replace, DecodePolicy::PassThroughBytes] {
+      assert_eq!(
+        alignment_changes(
+          &mut &input[..],
+          BeginningOfLine::Tabs(4, true),
+          &BolOptions::default().decode_policy(decode_policy)
+        )
+        .unwrap(),
+        vec![2]
+      );
+    }
+  }
+
+  #[test]
+  fn test_safe_only() {
+    let input = "f(a,\n  b)\n    c\n";
+    let new_bol = BeginningOfLine::Tabs(4, true);
+    let options = BolOptions {
+      safe_only: true,
+      ..Default::default()
+    };
+    let mut output = Vec::new();
+
+    write_new_bols_with_options(&mut input.as_bytes(), &mut output, new_bol, &options).unwrap();
+
+    assert_eq!(
+      String::from_utf8(output.clone()).unwrap(),
+      "f(a,\n  b)\n\tc\n"
+    );
+    assert!(verify_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut &output[..],
+      new_bol,
+      &options
+    )
+    .unwrap());
+    assert!(!verify_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut &output[..],
+      new_bol,
+      &BolOptions::default()
+    )
+    .unwrap());
+  }
+
+  #[test]
+  fn test_max_line_length() {
+    let new_bol = BeginningOfLine::Tabs(4, false);
+    let options = BolOptions::default().max_line_length(Some(8));
+    let convert = |input: &str, options: &BolOptions| {
+      write_new_bols_with_options(&mut input.as_bytes(), &mut Vec::new(), new_bol, options)
+    };
+
+    assert!(convert("    a long line after a short beginning\n", &options).is_ok());
+    assert_eq!(
+      error::error_code(&*convert("            a\n", &options).unwrap_err()),
+      ErrorCode::Limit
+    );
+    assert!(convert(&" ".repeat(100), &options).is_err());
+    assert!(convert("    a long line\n", &options.clone().protect_strings(true)).is_err());
+    assert!(verify_new_bols_with_options(
+      &mut "\ta\n".as_bytes(),
+      &mut "\ta\n".as_bytes(),
+      new_bol,
+      &options
+    )
+    .is_ok());
+    assert!(verify_new_bols_with_options(
+      &mut "a long line\n".as_bytes(),
+      &mut "a long line\n".as_bytes(),
+      new_bol,
+      &options.clone().markdown(true)
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn test_alignment_columns() {
+    assert_eq!(alignment_columns("\tf(a, b)", 4), vec![4]);
+    assert_eq!(alignment_columns("\tf(a, g(b", 4), vec![4, 6, 6, 11, 11]);
+    assert_eq!(alignment_columns("  x = [  1,", 8), vec![2, 7, 9]);
+    assert_eq!(alignment_columns("", 4), vec![0]);
+  }
+
+  #[test]
+  fn test_infer_tab_size() {
+    let infer = |s: &str| infer_tab_size(&mut s.as_bytes()).unwrap();
+
+    assert_eq!(infer("\tfoo();\n        bar();\n"), Some(8));
+    assert_eq!(infer("    foo();\n\n\tbar();\n"), Some(4));
+    assert_eq!(infer("\tx = f(a,\n\t\t  b);\n"), Some(4));
+    assert_eq!(infer("{\n  \tfoo(\n   bar);\n"), Some(3));
+    assert_eq!(infer("\tfoo();\n\tbar();\n"), None);
+    assert_eq!(infer("foo();\n"), None);
+    assert!(infer_tab_size(&mut &[0xffu8, b'\n'][..]).is_err());
+  }
+
+  #[test]
+  fn test_tab_size_fits() {
+    let fits = |s: &str| tab_size_fits(&mut s.as_bytes(), &[2, 4, 8]).unwrap();
+    let spaces = fits("if a {\n    if b {\n        c();\n    }\n}\n");
+
+    assert_eq!(
+      spaces,
+      vec![
+        TabSizeFit {
+          tab_size: 2,
+          misaligned_lines: 2,
+          odd_columns: 0,
+        },
+        TabSizeFit {
+          tab_size: 4,
+          misaligned_lines: 0,
+          odd_columns: 0,
+        },
+        TabSizeFit {
+          tab_size: 8,
+          misaligned_lines: 2,
+          odd_columns: 2,
+        },
+      ]
+    );
+    assert_eq!(best_tab_size(&spaces), Some(4));
+
+    let narrow = fits("a\n  b\n    c(d,\n      e)\n");
+
+    assert_eq!(best_tab_size(&narrow), Some(2));
+    assert_eq!(narrow[1].num_breaks(), 3);
+    assert_eq!(best_tab_size(&fits("a\n\tb\n\t\tc\n")), None);
+    assert_eq!(best_tab_size(&[]), None);
+  }
+
+  #[test]
+  fn test_generic_versions() {
+    let input = "\ta\n  b\n";
+    let new_bol = BeginningOfLine::Spaces(2);
+    let options = BolOptions::default();
+    let mut output = Vec::new();
+
+    assert_eq!(
+      read_bol_info_generic(&mut input.as_bytes(), &options).unwrap(),
+      read_bol_info(&mut input.as_bytes()).unwrap()
+    );
+    write_new_bols_generic(&mut input.as_bytes(), &mut output, new_bol, &options).unwrap();
+    assert_eq!(output, b"  a\n  b\n");
+    assert!(
+      verify_new_bols_generic(&mut input.as_bytes(), &mut &output[..], new_bol, &options).unwrap()
+    );
+  }
+
+  #[test]
+  fn test_decode_policy() {
+    let input = b"\tcaf\xe9\n  \xff\n";
+    let new_bol = BeginningOfLine::Spaces(2);
+    let options = BolOptions::default().decode_policy(DecodePolicy::PassThroughBytes);
+    let mut output = Vec::new();
+
+    assert!(write_new_bols(&mut &input[..], &mut Vec::new(), new_bol).is_err());
+
+    let bol_info =
+      write_new_bols_with_options(&mut &input[..], &mut output, new_bol, &options).unwrap();
+
+    assert_eq!(output, b"  caf\xe9\n  \xff\n");
+    assert_eq!(bol_info.num_bytes, output.len());
+    assert_eq!(
+      read_bol_info_with_options(&mut &input[..], &options)
+        .unwrap()
+        .num_bytes,
+      input.len()
+    );
+    assert!(
+      verify_new_bols_with_options(&mut &input[..], &mut &output[..], new_bol, &options).unwrap()
+    );
+  }
+
+  #[test]
+  fn test_verify_new_bols() {
+    let input = "\na\n  b\n     c\n";
+
+    assert!(verify_new_bols(
+      &mut input.as_bytes(),
+      &mut "\na\n\tb\n\t\tc\n".as_bytes(),
+      BeginningOfLine::Tabs(2, true)
+    )
+    .unwrap());
+    assert!(verify_new_bols(
+      &mut input.as_bytes(),
+      &mut "\na\n\tb\n\t\t c\n".as_bytes(),
+      BeginningOfLine::Tabs(2, false)
+    )
+    .unwrap());
+    assert!(!verify_new_bols(
+      &mut input.as_bytes(),
+      &mut "\na\n\tb\n\t\t c\n".as_bytes(),
+      BeginningOfLine::Tabs(2, true)
+    )
+    .unwrap());
+    assert!(!verify_new_bols(
+      &mut input.as_bytes(),
+      &mut "\na\n\tb\n\t\tc\n".as_bytes(),
+      BeginningOfLine::Spaces(2)
+    )
+    .unwrap());
+    assert!(!verify_new_bols(
+      &mut input.as_bytes(),
+      &mut "\na\n  b\n     d\n".as_bytes(),
+      BeginningOfLine::Spaces(2)
+    )
+    .unwrap());
+    assert!(!verify_new_bols(
+      &mut "\tx\n".as_bytes(),
+      &mut "  x\n".as_bytes(),
+      BeginningOfLine::Spaces(4)
+    )
+    .unwrap());
+    assert!(!verify_new_bols(
+      &mut "x\n  ".as_bytes(),
+      &mut "x\n ".as_bytes(),
+      BeginningOfLine::Spaces(4)
+    )
+    .unwrap());
+    assert!(verify_new_bols(
+      &mut "\tx\n".as_bytes(),
+      &mut "    x\n".as_bytes(),
+      BeginningOfLine::Spaces(4)
+    )
+    .unwrap());
+  }
+
+  #[test]
+  fn test_beginning_of_line_builders() {
+    assert_eq!(BeginningOfLine::tabs(2), BeginningOfLine::Tabs(2, false));
+    assert_eq!(
+      BeginningOfLine::tabs(8).round_down(true),
+      BeginningOfLine::Tabs(8, true)
+    );
+    assert_eq!(
+      BeginningOfLine::spaces(4).round_down(true),
+      BeginningOfLine::Spaces(4)
+    );
+    assert_eq!(BeginningOfLine::tabs(3).tab_size(), 3);
+  }
+
+  #[test]
+  fn test_normalize_str() {
+    assert_eq!(
+      normalize_str("\tü\n  \tx", BeginningOfLine::Spaces(2)),
+      "  ü\n    x"
+    );
+    assert_eq!(
+      normalize_str("    a\n", BeginningOfLine::Tabs(4, false)),
+      "\ta\n"
+    );
+  }
+
+  #[test]
+  fn test_directives() {
+    let input = "\ta\n\tb // ws:disable\n\tc\n\td // ws:enable\n\te\n";
+    let mut output = Vec::new();
+
+    write_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut output,
+      BeginningOfLine::Spaces(2),
+      &BolOptions::default().directives(true),
+    )
+    .unwrap();
+    assert_eq!(
+      String::from_utf8(output).unwrap(),
+      "  a\n  b // ws:disable\n\tc\n\td // ws:enable\n  e\n"
+    );
+    assert_eq!(
+      normalize_str(input, BeginningOfLine::Spaces(2)),
+      "  a\n  b // ws:disable\n  c\n  d // ws:enable\n  e\n"
+    );
+
+    let input = "  a\n# ws:disable\n\tb\n \tc\n# ws:enable\n  d\n";
+    let options = BolOptions::default().directives(true).line_numbers(true);
+    let bol_info = read_bol_info_with_options(&mut input.as_bytes(), &options).unwrap();
+
+    assert_eq!((bol_info.spaces, bol_info.tabs, bol_info.mixed), (2, 0, 0));
+    assert!(bol_info.tab_lines.is_empty());
+    assert!(bol_info.mixed_lines.is_empty());
+  }
+
+  #[test]
+  fn test_unusual_spaces() {
+    let input = "\u{a0}\u{a0}a\n \u{200b} b\u{a0}c\n\t\u{3000}\n\u{2003}";
+
+    assert_eq!(read_bol_info(&mut input.as_bytes()).unwrap().unusual, 4);
+    assert_eq!(
+      normalize_str(input, BeginningOfLine::Spaces(2)),
+      "\u{a0}\u{a0}a\n \u{200b} b\u{a0}c\n  \u{3000}\n\u{2003}"
+    );
+
+    let options = BolOptions::default().replace_unusual_spaces(true);
+    let mut output = Vec::new();
+    let bol_info = write_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut output,
+      BeginningOfLine::Spaces(2),
+      &options,
+    )
+    .unwrap();
+
+    assert_eq!(bol_info.unusual, 0);
+    assert_eq!(
+      String::from_utf8(output.clone()).unwrap(),
+      "  a\n  b\u{a0}c\n   \n "
+    );
+    assert!(verify_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut output.as_slice(),
+      BeginningOfLine::Spaces(2),
+      &options
+    )
+    .unwrap());
+    assert!(!verify_new_bols_with_options(
+      &mut input.as_bytes(),
+      &mut input.as_bytes(),
+      BeginningOfLine::Spaces(2),
+      &options
+    )
+    .unwrap());
+  }
+
+  #[test]
+  fn test_needs_new_bols() {
+    let needs = |input: &str, new_bol, options: &BolOptions| {
+      needs_new_bols(&mut input.as_bytes(), new_bol, options).unwrap()
+    };
+
+    assert!(needs(
+      "a\n  b\n",
+      BeginningOfLine::Tabs(2, true),
+      &BolOptions::default()
+    ));
+    assert!(!needs(
+      "a\n\tb\n",
+      BeginningOfLine::Tabs(2, true),
+      &BolOptions::default()
+    ));
+    assert!(!needs(
+      "a\n  b\n",
+      BeginningOfLine::Spaces(2),
+      &BolOptions::default()
+    ));
+    assert!(needs(
+      "a\n\t\n",
+      BeginningOfLine::Spaces(2),
+      &BolOptions::default()
+    ));
+    assert!(!needs(
+      "a\n\t\n",
+      BeginningOfLine::Spaces(2),
+      &BolOptions::default().preserve_blank_line_ws(true)
+    ));
+  }
 }
@@ -0,0 +1,450 @@
+//! Report on or remove trailing whitespace.
+//!
+//! To find out how much trailing whitespace there is given a [`Read`] trait object use
+//! [`read_trailing_info()`]:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::trimmer;
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let mut reader = "abc  \nxyz\t\r\n".as_bytes();
+//!   let trailing_info = trimmer::read_trailing_info(&mut reader)?;
+//!
+//!   assert_eq!(trailing_info.lines, 2);
+//!   Ok(())
+//! }
+//! ```
+//!
+//! To remove the spaces and tabs before the end of each line given a [`Read`] trait object,
+//! create a [`Write`] trait object and use [`write_trimmed()`]:
+//!
+//! ```
+//! use std::error::Error;
+//! use whitespace_rs::trimmer;
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!   let mut reader = "abc  \nxyz\t\r\n".as_bytes();
+//!   let mut writer = Vec::new();
+//!   let num_trimmed = trimmer::write_trimmed(&mut reader, &mut writer)?;
+//!
+//!   assert_eq!(num_trimmed, 2);
+//!   assert_eq!(writer, b"abc\nxyz\r\n");
+//!   Ok(())
+//! }
+//! ```
+//!
+//! With [`TrimOptions::directives`] and the `_with_options` functions the trailing whitespace of
+//! the lines after a `ws:disable` comment, up to and including the next `ws:enable` comment, is
+//! left alone.
+//!
+//! Each function has a `_generic` version that takes concrete reader and writer types instead of
+//! trait objects, so that its decoding loop can be inlined for them.
+
+use crate::decode::{DecodePolicy, Decoder};
+use crate::lines::Directives;
+use std::error::Error;
+use std::io::{BufReader, Read, Write};
+
+/// File trailing whitespace information
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct TrailingInfo {
+  /// Number of lines that end in spaces or tabs
+  pub lines: usize,
+  /// Number of trailing spaces
+  pub spaces: usize,
+  /// Number of trailing tabs
+  pub tabs: usize,
+  /// Total number of lines in the file (includes lines with no ending)
+  pub num_lines: usize,
+  /// Total number of characters in the file
+  pub num_chars: usize,
+  /// Total number of whitespace separated words in the file
+  pub num_words: usize,
+  /// Total number of bytes in the file
+  pub num_bytes: usize,
+}
+
+impl TrailingInfo {
+  /// Create the information for an empty file, which has one line with no ending.
+  pub fn new() -> Self {
+    TrailingInfo {
+      num_lines: 1,
+      ..Default::default()
+    }
+  }
+
+  /// Count the trailing spaces and tabs in `s` for the line that it ends
+  fn count_trailing(&mut self, s: &str) {
+    if !s.is_empty() {
+      let tabs = s.matches('\t').count();
+
+      self.lines += 1;
+      self.tabs += tabs;
+      self.spaces += s.len() - tabs;
+    }
+  }
+}
+
+/// Options for the `_with_options` functions
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct TrimOptions {
+  /// Leave the trailing whitespace of the lines after a `ws:disable` comment, up to and including
+  /// the next `ws:enable` comment, uncounted and unchanged
+  pub directives: bool,
+}
+
+impl TrimOptions {
+  /// Set [`directives`](TrimOptions::directives)
+  pub fn directives(mut self, directives: bool) -> Self {
+    self.directives = directives;
+    self
+  }
+}
+
+/// Read trailing whitespace information for a file.
+pub fn read_trailing_info(reader: &mut dyn Read) -> Result<TrailingInfo, Box<dyn Error>> {
+  read_trailing_info_generic(reader, &TrimOptions::default())
+}
+
+/// Read trailing whitespace information for a file, with options.
+pub fn read_trailing_info_with_options(
+  reader: &mut dyn Read,
+  options: &TrimOptions,
+) -> Result<TrailingInfo, Box<dyn Error>> {
+  read_trailing_info_generic(reader, options)
+}
+
+/// Generic version of [`read_trailing_info_with_options()`] that can be inlined for a concrete
+/// reader type.
+pub fn read_trailing_info_generic<R: Read + ?Sized>(
+  reader: &mut R,
+  options: &TrimOptions,
+) -> Result<TrailingInfo, Box<dyn Error>> {
+  let mut trailing_info = TrailingInfo::new();
+  let mut decoder = Decoder::new(BufReader::new(reader).bytes(), DecodePolicy::Strict).peekable();
+  let mut directives = Directives::new(options.directives);
+  let mut s = String::new();
+  let mut in_word = false;
+
+  while let Some(value) = decoder.next() {
+    let c = value?;
+
+    trailing_info.num_chars += 1;
+    trailing_info.num_bytes += c.len_utf8();
+
+    if c.is_whitespace() {
+      in_word = false;
+    } else if !in_word {
+      trailing_info.num_words += 1;
+      in_word = true;
+    }
+
+    if c == ' ' || c == '\t' {
+      s.push(c);
+      directives.push(c);
+    } else if c == '\r' || c == '\n' {
+      if c == '\r' && matches!(decoder.peek(), Some(Ok(c)) if *c == '\n') {
+        trailing_info.num_chars += 1;
+        trailing_info.num_bytes += 1;
+        decoder.next();
+      }
+
+      trailing_info.num_lines += 1;
+
+      if !directives.is_disabled() {
+        trailing_info.count_trailing(&s);
+      }
+
+      s.clear();
+      directives.end_line();
+    } else {
+      s.clear();
+      directives.push(c);
+    }
+  }
+
+  if !directives.is_disabled() {
+    trailing_info.count_trailing(&s);
+  }
+
+  Ok(trailing_info)
+}
+
+/// Write input file out without the spaces and tabs at the end of each line.
+///
+/// Returns the number of lines that were trimmed.
+pub fn write_trimmed(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+) -> Result<usize, Box<dyn Error>> {
+  write_trimmed_generic(reader, writer, &TrimOptions::default())
+}
+
+/// Write input file out without the spaces and tabs at the end of each line, with options.
+pub fn write_trimmed_with_options(
+  reader: &mut dyn Read,
+  writer: &mut dyn Write,
+  options: &TrimOptions,
+) -> Result<usize, Box<dyn Error>> {
+  write_trimmed_generic(reader, writer, options)
+}
+
+/// Generic version of [`write_trimmed_with_options()`] that can be inlined for concrete reader and
+/// writer types.
+pub fn write_trimmed_generic<R: Read + ?Sized, W: Write + ?Sized>(
+  reader: &mut R,
+  writer: &mut W,
+  options: &TrimOptions,
+) -> Result<usize, Box<dyn Error>> {
+  let mut num_trimmed = 0;
+  let decoder = Decoder::new(BufReader::new(reader).bytes(), DecodePolicy::Strict);
+  let mut directives = Directives::new(options.directives);
+  let mut buf = [0u8; 4];
+  // Spaces and tabs are held back until it's clear that they don't end the line
+  let mut s = String::new();
+
+  for value in decoder {
+    let c = value?;
+
+    if c == ' ' || c == '\t' {
+      s.push(c);
+      directives.push(c);
+      continue;
+    }
+
+    if c == '\r' || c == '\n' {
+      if directives.is_disabled() {
+        writer.write_all(s.as_bytes())?;
+      } else if !s.is_empty() {
+        num_trimmed += 1;
+      }
+
+      directives.end_line();
+    } else {
+      writer.write_all(s.as_bytes())?;
+      directives.push(c);
+    }
+
+    s.clear();
+    writer.write_all(c.encode_utf8(&mut buf).as_bytes())?;
+  }
+
+  if directives.is_disabled() {
+    writer.write_all(s.as_bytes())?;
+  } else if !s.is_empty() {
+    num_trimmed += 1;
+  }
+
+  writer.flush()?;
+
+  Ok(num_trimmed)
+}
+
+/// Verify trimmed output against the original input.
+///
+/// Returns `true` if the sequence of characters other than trailing spaces and tabs is identical
+/// in both and no line in the trimmed output ends in a space or tab.
+pub fn verify_trimmed(
+  original: &mut dyn Read,
+  converted: &mut dyn Read,
+) -> Result<bool, Box<dyn Error>> {
+  verify_trimmed_generic(original, converted, &TrimOptions::default())
+}
+
+/// Verify output trimmed with [`write_trimmed_with_options()`] against the original input.
+pub fn verify_trimmed_with_options(
+  original: &mut dyn Read,
+  converted: &mut dyn Read,
+  options: &TrimOptions,
+) -> Result<bool, Box<dyn Error>> {
+  verify_trimmed_generic(original, converted, options)
+}
+
+/// Generic version of [`verify_trimmed_with_options()`] that can be inlined for concrete reader
+/// types.
+pub fn verify_trimmed_generic<R1: Read + ?Sized, R2: Read + ?Sized>(
+  original: &mut R1,
+  converted: &mut R2,
+  options: &TrimOptions,
+) -> Result<bool, Box<dyn Error>> {
+  let mut original_chars = Decoder::new(BufReader::new(original).bytes(), DecodePolicy::Strict);
+  let decoder = Decoder::new(BufReader::new(converted).bytes(), DecodePolicy::Strict);
+  let mut directives = Directives::new(options.directives);
+  let mut prev_c = None;
+
+  for value in decoder {
+    let c = value?;
+    let is_eol = c == '\r' || c == '\n';
+    let is_trimmed = !directives.is_disabled();
+
+    if is_eol && is_trimmed && matches!(prev_c, Some(' ') | Some('\t')) {
+      return Ok(false);
+    }
+
+    // Skip the original's trailing whitespace, which the trimmed output doesn't have
+    let original_c = loop {
+      match original_chars.next().transpose()? {
+        Some(' ') | Some('\t') if is_eol && is_trimmed => continue,
+        original_c => break original_c,
+      }
+    };
+
+    if original_c != Some(c) {
+      return Ok(false);
+    }
+
+    if is_eol {
+      directives.end_line();
+    } else {
+      directives.push(c);
+    }
+
+    prev_c = Some(c);
+  }
+
+  let is_trimmed = !directives.is_disabled();
+
+  if is_trimmed && matches!(prev_c, Some(' ') | Some('\t')) {
+    return Ok(false);
+  }
+
+  for value in original_chars {
+    if !is_trimmed || !matches!(value?, ' ' | '\t') {
+      return Ok(false);
+    }
+  }
+
+  Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_read_trailing_info() {
+    let trailing_info = read_trailing_info(&mut "a  \n\tb\t\r\n \r\nc d".as_bytes()).unwrap();
+
+    assert_eq!(
+      trailing_info,
+      TrailingInfo {
+        lines: 3,
+        spaces: 3,
+        tabs: 1,
+        num_lines: 4,
+        num_chars: 15,
+        num_words: 4,
+        num_bytes: 15,
+      }
+    );
+    assert_eq!(
+      read_trailing_info(&mut "".as_bytes()).unwrap(),
+      TrailingInfo::new()
+    );
+    assert_eq!(read_trailing_info(&mut "a\t ".as_bytes()).unwrap().lines, 1);
+    assert!(read_trailing_info(&mut &[0xffu8][..]).is_err());
+  }
+
+  #[test]
+  fn test_write_trimmed() {
+    let mut output = Vec::new();
+    let num_trimmed = write_trimmed(
+      &mut "a  \n\tb\t\r\n \r\nc d\n  \ne \t".as_bytes(),
+      &mut output,
+    )
+    .unwrap();
+
+    assert_eq!(num_trimmed, 5);
+    assert_eq!(String::from_utf8(output).unwrap(), "a\n\tb\r\n\r\nc d\n\ne");
+
+    let mut output = Vec::new();
+
+    assert_eq!(
+      write_trimmed(&mut "\ta b\n".as_bytes(), &mut output).unwrap(),
+      0
+    );
+    assert_eq!(output, b"\ta b\n");
+  }
+
+  #[test]
+  fn test_verify_trimmed() {
+    let verify = |original: &str, converted: &str| {
+      verify_trimmed(&mut original.as_bytes(), &mut converted.as_bytes()).unwrap()
+    };
+
+    assert!(verify("a  \n\tb\t\r\n ", "a\n\tb\r\n"));
+    assert!(verify("a\n", "a\n"));
+    assert!(!verify("a  \n", "a \n"));
+    assert!(!verify("a  ", "a "));
+    assert!(!verify("a b\n", "ab\n"));
+    assert!(!verify("a\n", "a\nb"));
+    assert!(!verify("a\nb", "a\n"));
+  }
+
+  #[test]
+  fn test_generic_versions() {
+    let input = "a \nb\t";
+    let mut output = Vec::new();
+
+    let options = TrimOptions::default();
+
+    assert_eq!(
+      read_trailing_info_generic(&mut input.as_bytes(), &options).unwrap(),
+      read_trailing_info(&mut input.as_bytes()).unwrap()
+    );
+    assert_eq!(
+      write_trimmed_generic(&mut input.as_bytes(), &mut output, &options).unwrap(),
+      2
+    );
+    assert!(verify_trimmed_generic(&mut input.as_bytes(), &mut &output[..], &options).unwrap());
+  }
+
+  #[test]
+  fn test_directives() {
+    let input = "a \n# ws:disable\nb \r\n# ws:enable \nc \n# ws:disable\nd\t";
+    let trimmed = "a\n# ws:disable\nb \r\n# ws:enable \nc\n# ws:disable\nd\t";
+    let options = TrimOptions::default().directives(true);
+    let mut output = Vec::new();
+
+    assert_eq!(
+      read_trailing_info_with_options(&mut input.as_bytes(), &options)
+        .unwrap()
+        .lines,
+      2
+    );
+    assert_eq!(read_trailing_info(&mut input.as_bytes()).unwrap().lines, 5);
+    assert_eq!(
+      write_trimmed_with_options(&mut input.as_bytes(), &mut output, &options).unwrap(),
+      2
+    );
+    assert_eq!(String::from_utf8(output).unwrap(), trimmed);
+
+    let verify = |original: &str, converted: &str| {
+      verify_trimmed_with_options(
+        &mut original.as_bytes(),
+        &mut converted.as_bytes(),
+        &options,
+      )
+      .unwrap()
+    };
+
+    assert!(verify(input, trimmed));
+    assert!(!verify(
+      input,
+      "a\n# ws:disable\nb\r\n# ws:enable \nc\n# ws:disable\nd\t"
+    ));
+    assert!(!verify(
+      input,
+      "a\n# ws:disable\nb \r\n# ws:enable \nc\n# ws:disable\nd"
+    ));
+    assert!(!verify(
+      input,
+      "a \n# ws:disable\nb \r\n# ws:enable \nc\n# ws:disable\nd\t"
+    ));
+    assert!(!verify_trimmed(&mut input.as_bytes(), &mut trimmed.as_bytes()).unwrap());
+  }
+}
@@ -0,0 +1,17 @@
+//! Raising SIGINT sets the process-wide interrupted flag for good, so this runs in its own test
+//! binary where it can't stop the batch tests in the library.
+
+#[cfg(unix)]
+#[test]
+fn test_install_handler() {
+  use whitespace_rs::interrupt::{install_handler, is_interrupted};
+
+  install_handler().unwrap();
+  assert!(!is_interrupted());
+
+  unsafe {
+    libc::raise(libc::SIGINT);
+  }
+
+  assert!(is_interrupted());
+}